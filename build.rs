@@ -1,81 +1,189 @@
-use std::{env, fs, ptr, slice, str};
+//! Provides the compiled shaders in `OUT_DIR`, read from the directory named by the
+//! `D3D11_GLYPH_SHADER_DIR` environment variable if it is set, or compiled with `D3DCompile`
+//! when building on a Windows host.
+//!
+//! Setting the variable is the only way to embed compiled shaders when cross-compiling from
+//! Linux or macOS, e.g. to a directory of blobs built on Windows with the same names as in
+//! `OUT_DIR`. Otherwise an empty blob is written and the shader is compiled at runtime using
+//! `d3dcompiler_47.dll` instead.
+use std::path::{Path, PathBuf};
+use std::{env, fs};
 
-use winapi::{shared::winerror::S_OK, um::d3dcommon::ID3DBlob, um::d3dcompiler::D3DCompile};
+/// The shader models the shaders are compiled for, paired with whether the
+/// `LEVEL_9_3` define is set for them.
+const SHADER_MODELS: [(&str, bool); 3] = [("4_0_level_9_3", true), ("4_0", false), ("5_0", false)];
 
-fn main() {
-    static VERTEX_SHADER: &str = include_str!("src/shader/vertex.hlsl");
-    static PIXEL_SHADER: &str = include_str!("src/shader/pixel.hlsl");
+/// The shaders as `(name, stage, source, level_9_3)`, where `level_9_3` is whether the shader
+/// is also compiled for feature level 9.3.
+const SHADERS: [(&str, &str, &str, bool); 4] = [
+    (
+        "vertex_shader",
+        "vs",
+        include_str!("src/shader/vertex.hlsl"),
+        true,
+    ),
+    (
+        "pixel_shader",
+        "ps",
+        include_str!("src/shader/pixel.hlsl"),
+        true,
+    ),
+    (
+        "fullscreen_shader",
+        "vs",
+        include_str!("src/shader/fullscreen.hlsl"),
+        false,
+    ),
+    (
+        "blur_shader",
+        "ps",
+        include_str!("src/shader/blur.hlsl"),
+        false,
+    ),
+];
 
-    let mut err = ptr::null_mut();
+/// Variants of the shaders only compiled for shader model 5.0, as `(name, stage, source, define)`.
+const SHADER_MODEL_5_0_VARIANTS: [(&str, &str, &str, &str); 1] = [(
+    "vertex_shader_raw",
+    "vs",
+    include_str!("src/shader/vertex.hlsl"),
+    "RAW_INSTANCES",
+)];
 
-    unsafe {
-        let mut vs_blob = ptr::null_mut();
-        if D3DCompile(
-            VERTEX_SHADER.as_ptr().cast(),
-            VERTEX_SHADER.len(),
-            ptr::null_mut(),
-            ptr::null_mut(),
-            ptr::null_mut(),
-            "main\0".as_ptr().cast(),
-            "vs_4_0\0".as_ptr().cast(),
-            0,
-            0,
-            &mut vs_blob,
-            &mut err,
-        ) != S_OK
-        {
-            report_err(err)
+const SHADER_DIR_VAR: &str = "D3D11_GLYPH_SHADER_DIR";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed={}", SHADER_DIR_VAR);
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let shader_dir = env::var_os(SHADER_DIR_VAR).map(PathBuf::from);
+
+    let mut runtime_compiled = false;
+    for &(model, level_9_3) in SHADER_MODELS.iter() {
+        for &(name, stage, source, _) in SHADERS.iter().filter(|shader| shader.3 || !level_9_3) {
+            let define = if level_9_3 { Some("LEVEL_9_3") } else { None };
+            runtime_compiled |=
+                !write_shader(&out_dir, &shader_dir, name, stage, model, source, define);
         }
-        if let Some(vs_blob) = vs_blob.as_ref() {
-            write_blob("vertex_shader.vs_4_0", vs_blob);
+    }
+    for &(name, stage, source, define) in SHADER_MODEL_5_0_VARIANTS.iter() {
+        runtime_compiled |= !write_shader(
+            &out_dir,
+            &shader_dir,
+            name,
+            stage,
+            "5_0",
+            source,
+            Some(define),
+        );
+    }
+
+    if runtime_compiled {
+        println!(
+            "cargo:warning=No shader compiler available, shaders will be compiled at runtime. \
+             Set {} to a directory of pre-compiled shaders to avoid this.",
+            SHADER_DIR_VAR
+        );
+    }
+}
+
+/// Writes the blob of a shader to `OUT_DIR`. Returns `false` if no blob was available and an
+/// empty one was written instead.
+fn write_shader(
+    out_dir: &Path,
+    shader_dir: &Option<PathBuf>,
+    name: &str,
+    stage: &str,
+    model: &str,
+    source: &str,
+    define: Option<&str>,
+) -> bool {
+    let shader_name = format!("{}.{}_{}", name, stage, model);
+    let blob = match shader_dir {
+        Some(dir) => {
+            let path = dir.join(&shader_name);
+            println!("cargo:rerun-if-changed={}", path.display());
+            Some(fs::read(&path).unwrap_or_else(|e| {
+                panic!(
+                    "Unable to read {} shader from {}: {:?}",
+                    shader_name,
+                    dir.display(),
+                    e
+                )
+            }))
         }
+        None => compile(source, stage, model, define),
+    };
+    let compiled = blob.is_some();
+    fs::write(out_dir.join(&shader_name), blob.unwrap_or_default())
+        .unwrap_or_else(|e| panic!("Unable to write {} shader to out dir: {:?}", shader_name, e));
+    compiled
+}
+
+#[cfg(not(windows))]
+fn compile(_: &str, _: &str, _: &str, _: Option<&str>) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(windows)]
+fn compile(source: &str, stage: &str, model: &str, define: Option<&str>) -> Option<Vec<u8>> {
+    use std::{ptr, slice, str};
+
+    use winapi::{
+        shared::winerror::S_OK,
+        um::d3dcommon::{ID3DBlob, D3D_SHADER_MACRO},
+        um::d3dcompiler::D3DCompile,
+    };
+
+    unsafe fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+        slice::from_raw_parts(blob.GetBufferPointer().cast::<u8>(), blob.GetBufferSize())
     }
+
+    let target = format!("{}_{}\0", stage, model);
+    let define = define.map(|define| format!("{}\0", define));
+    let defines = [
+        D3D_SHADER_MACRO {
+            Name: define
+                .as_ref()
+                .map_or(ptr::null(), |define| define.as_ptr().cast()),
+            Definition: "1\0".as_ptr().cast(),
+        },
+        D3D_SHADER_MACRO {
+            Name: ptr::null(),
+            Definition: ptr::null(),
+        },
+    ];
+
     unsafe {
-        let mut ps_blob = ptr::null_mut();
+        let mut blob = ptr::null_mut();
+        let mut err = ptr::null_mut();
         if D3DCompile(
-            PIXEL_SHADER.as_ptr().cast(),
-            PIXEL_SHADER.len(),
-            ptr::null_mut(),
+            source.as_ptr().cast(),
+            source.len(),
             ptr::null_mut(),
+            defines.as_ptr(),
             ptr::null_mut(),
             "main\0".as_ptr().cast(),
-            "ps_4_0\0".as_ptr().cast(),
+            target.as_ptr().cast(),
             0,
             0,
-            &mut ps_blob,
+            &mut blob,
             &mut err,
         ) != S_OK
         {
-            report_err(err)
-        }
-        if let Some(ps_blob) = ps_blob.as_ref() {
-            write_blob("pixel_shader.ps_4_0", ps_blob);
+            let err_msg = err
+                .as_ref()
+                .and_then(|err| str::from_utf8(blob_bytes(err)).ok())
+                .map(ToOwned::to_owned);
+            err.as_ref().map(|err| err.Release());
+            panic!(
+                "Failed to compile shader: {}",
+                err_msg.unwrap_or_else(|| String::from("Unknown error"))
+            )
         }
-    }
-}
-
-unsafe fn write_blob(shader_name: &str, blob: &ID3DBlob) {
-    let out_dir = env::var("OUT_DIR").unwrap();
-    let data = slice::from_raw_parts(blob.GetBufferPointer().cast::<u8>(), blob.GetBufferSize());
-    let _ = fs::write(&format!("{}/{}", out_dir, shader_name), data)
-        .map_err(|e| panic!("Unable to write {} shader to out dir: {:?}", shader_name, e));
-    blob.Release();
-}
-
-unsafe fn report_err(err: *const ID3DBlob) -> ! {
-    let err_msg = err
-        .as_ref()
-        .and_then(|err| {
-            str::from_utf8(slice::from_raw_parts(
-                err.GetBufferPointer().cast::<u8>(),
-                err.GetBufferSize(),
-            ))
-            .ok()
+        blob.as_ref().map(|blob| {
+            let data = blob_bytes(blob).to_vec();
+            blob.Release();
+            data
         })
-        .map(ToOwned::to_owned);
-    err.as_ref().map(|err| err.Release());
-    panic!(
-        "Failed to compile shader: {}",
-        err_msg.unwrap_or_else(|| String::from("Unknown error"))
-    )
+    }
 }
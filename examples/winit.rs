@@ -233,7 +233,7 @@ fn main() {
             unsafe { context.RSSetViewports(1, &vp) };
             // Draw the text!
             glyph_brush
-                .draw_queued(&main_rtv, &depth_stencil, size.width, size.height)
+                .draw_queued_with_depth_view(&main_rtv, &depth_stencil, size.width, size.height)
                 .expect("Draw queued");
 
             unsafe {
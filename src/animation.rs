@@ -0,0 +1,140 @@
+use std::f32::consts::PI;
+
+use glyph_brush::SectionGlyph;
+
+use crate::GlyphExtra;
+
+/// A per-glyph animation of a section, see
+/// [`GlyphBrush::queue_animated`](struct.GlyphBrush.html#method.queue_animated).
+///
+/// Glyphs are numbered in the order they are laid out in, which is the order of their
+/// characters in the section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnimation {
+    /// Reveals the glyphs one after another, like dialog text being typed.
+    Typewriter { glyphs_per_second: f32 },
+    /// Fades the glyphs in one after another, each over `duration` seconds. Requires an extra
+    /// type supporting [`GlyphExtra::faded`](trait.GlyphExtra.html#method.faded).
+    FadeIn {
+        glyphs_per_second: f32,
+        duration: f32,
+    },
+    /// Moves the glyphs up and down along a sine wave travelling through the text.
+    Wave {
+        /// The height of the wave in pixels.
+        amplitude: f32,
+        /// The length of the wave in glyphs.
+        wavelength: f32,
+        /// The number of waves passing a glyph per second.
+        frequency: f32,
+    },
+    /// Moves every glyph to a random offset of up to `amplitude` pixels, `frequency` times
+    /// per second.
+    Shake { amplitude: f32, frequency: f32 },
+}
+
+impl TextAnimation {
+    /// Returns `true` if all glyphs have been revealed at `time`, so an animated dialog can
+    /// continue. Animations which don't reveal glyphs are always finished.
+    pub fn is_finished(&self, glyph_count: usize, time: f32) -> bool {
+        match *self {
+            TextAnimation::Typewriter { glyphs_per_second } => {
+                time * glyphs_per_second >= glyph_count as f32
+            }
+            TextAnimation::FadeIn {
+                glyphs_per_second,
+                duration,
+            } => time - duration >= glyph_count.saturating_sub(1) as f32 / glyphs_per_second,
+            TextAnimation::Wave { .. } | TextAnimation::Shake { .. } => true,
+        }
+    }
+}
+
+/// Applies the animations at `time` to the laid out glyphs and returns how far they can be
+/// moved out of their layout bounds. The extra data is split into one entry per glyph if the
+/// glyphs are faded.
+pub(crate) fn animate<X: GlyphExtra>(
+    animations: &[TextAnimation],
+    time: f32,
+    glyphs: &mut Vec<SectionGlyph>,
+    extra: &mut Vec<X>,
+) -> f32 {
+    let mut margin = 0.0f32;
+    let mut alpha = vec![1.0f32; glyphs.len()];
+    for animation in animations {
+        match *animation {
+            TextAnimation::Typewriter { glyphs_per_second } => {
+                let revealed = (time * glyphs_per_second).max(0.0) as usize;
+                for alpha in alpha.iter_mut().skip(revealed) {
+                    *alpha = 0.0;
+                }
+            }
+            TextAnimation::FadeIn {
+                glyphs_per_second,
+                duration,
+            } => {
+                for (index, alpha) in alpha.iter_mut().enumerate() {
+                    let start = index as f32 / glyphs_per_second;
+                    let faded = if duration > 0.0 {
+                        ((time - start) / duration).clamp(0.0, 1.0)
+                    } else if time >= start {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                    *alpha *= faded;
+                }
+            }
+            TextAnimation::Wave {
+                amplitude,
+                wavelength,
+                frequency,
+            } => {
+                margin += amplitude.abs();
+                for (index, glyph) in glyphs.iter_mut().enumerate() {
+                    let phase = frequency * time - index as f32 / wavelength;
+                    glyph.glyph.position.y += amplitude * (2.0 * PI * phase).sin();
+                }
+            }
+            TextAnimation::Shake {
+                amplitude,
+                frequency,
+            } => {
+                margin += amplitude.abs();
+                let step = (time * frequency).floor() as i64 as u64;
+                for (index, glyph) in glyphs.iter_mut().enumerate() {
+                    let position = &mut glyph.glyph.position;
+                    position.x += amplitude * noise(index as u64, step, 0);
+                    position.y += amplitude * noise(index as u64, step, 1);
+                }
+            }
+        }
+    }
+
+    if alpha.iter().all(|&alpha| alpha >= 1.0) {
+        return margin;
+    }
+    let mut faded = Vec::with_capacity(glyphs.len());
+    let mut visible = alpha.iter().map(|&alpha| alpha > 0.0);
+    glyphs.retain(|_| visible.next().unwrap_or(true));
+    for (glyph, &alpha) in glyphs
+        .iter_mut()
+        .zip(alpha.iter().filter(|&&alpha| alpha > 0.0))
+    {
+        faded.push(extra[glyph.section_index].faded(alpha));
+        glyph.section_index = faded.len() - 1;
+    }
+    *extra = faded;
+    margin
+}
+
+/// Returns a pseudo-random value in `-1.0..=1.0` for a glyph at an animation step.
+fn noise(index: u64, step: u64, axis: u64) -> f32 {
+    let mut hash = (index.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        ^ step.wrapping_mul(0xBF58_476D_1CE4_E5B9)
+        ^ axis.wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+    hash = hash.wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    hash ^= hash >> 32;
+    (hash & 0xFFFF) as f32 / 0x7FFF as f32 - 1.0
+}
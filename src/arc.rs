@@ -0,0 +1,138 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glyph_brush::ab_glyph::{point, Font, Rect, ScaleFont};
+use glyph_brush::{HorizontalAlign, SectionGlyph};
+
+/// A circular arc text is laid out along, see
+/// [`GlyphBrush::queue_on_arc`](struct.GlyphBrush.html#method.queue_on_arc).
+///
+/// Angles are in radians, clockwise from the positive x axis as y points down, so
+/// `-PI / 2.0` is the top of the circle. The text runs from `start_angle` towards
+/// `end_angle`, so text reading left to right runs clockwise along the top of a circle and
+/// counter-clockwise along its bottom.
+///
+/// Glyphs are rotated to stand on the arc if the extra type of the text supports
+/// [rotation](trait.GlyphExtra.html#method.rotated). Otherwise they keep standing upright,
+/// with their centers beside the arc.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextArc {
+    pub center: (f32, f32),
+    /// The radius of the baseline of the text.
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub orientation: ArcOrientation,
+}
+
+/// Which side of its arc text is placed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArcOrientation {
+    /// Outside of the circle, e.g. along the top of a badge.
+    Outward,
+    /// Inside of the circle, e.g. along the bottom of a badge.
+    Inward,
+}
+
+impl TextArc {
+    /// The length of the arc in pixels.
+    #[inline]
+    pub fn length(&self) -> f32 {
+        (self.end_angle - self.start_angle).abs() * self.radius
+    }
+
+    /// The point at `distance` pixels along the arc and the angle of the circle there.
+    fn point_at(&self, distance: f32) -> ((f32, f32), f32) {
+        let direction = if self.end_angle < self.start_angle {
+            -1.0
+        } else {
+            1.0
+        };
+        let angle = self.start_angle + direction * distance / self.radius.max(f32::EPSILON);
+        let (sin, cos) = angle.sin_cos();
+        let point = (
+            self.center.0 + self.radius * cos,
+            self.center.1 + self.radius * sin,
+        );
+        (point, angle)
+    }
+}
+
+/// Moves the glyphs of a single line laid out at the origin onto `arc`, aligned within its
+/// length by `h_align`, and returns the bounds of the bent text.
+///
+/// With `rotate` the glyphs are placed to be rotated around the centers of their quads by the
+/// angles written into `rotations`, otherwise they stay upright.
+pub(crate) fn bend<F: Font>(
+    arc: &TextArc,
+    h_align: HorizontalAlign,
+    fonts: &[F],
+    glyphs: &mut [SectionGlyph],
+    rotate: bool,
+    rotations: &mut Vec<f32>,
+) -> Rect {
+    let advance = |glyph: &SectionGlyph| {
+        let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+        font.h_advance(glyph.glyph.id)
+    };
+    let width = glyphs
+        .iter()
+        .map(|glyph| glyph.glyph.position.x + advance(glyph))
+        .fold(0.0f32, f32::max);
+    let start = match h_align {
+        HorizontalAlign::Left => 0.0,
+        HorizontalAlign::Center => (arc.length() - width) / 2.0,
+        HorizontalAlign::Right => arc.length() - width,
+    };
+
+    let mut height = 0.0f32;
+    for glyph in glyphs.iter_mut() {
+        let font = &fonts[glyph.font_id.0];
+        let advance = advance(glyph);
+        let ascent = font.as_scaled(glyph.glyph.scale).ascent();
+        height = height.max(glyph.glyph.scale.y);
+        let ((x, y), angle) = arc.point_at(start + glyph.glyph.position.x + advance / 2.0);
+        if rotate {
+            let rotation = match arc.orientation {
+                ArcOrientation::Outward => angle + FRAC_PI_2,
+                ArcOrientation::Inward => angle - FRAC_PI_2,
+            };
+            // the center of the quad relative to the origin of the glyph
+            let mut origin = glyph.glyph.clone();
+            origin.position = point(0.0, 0.0);
+            let center =
+                font.outline_glyph(origin)
+                    .map_or(point(advance / 2.0, -ascent / 2.0), |outline| {
+                        let bounds = outline.px_bounds();
+                        point(
+                            (bounds.min.x + bounds.max.x) / 2.0,
+                            (bounds.min.y + bounds.max.y) / 2.0,
+                        )
+                    });
+            // rotating the quad around its center moves the middle of the baseline onto the arc
+            let (dx, dy) = (center.x - advance / 2.0, center.y);
+            let (sin, cos) = rotation.sin_cos();
+            glyph.glyph.position = point(
+                x + cos * dx - sin * dy - center.x,
+                y + sin * dx + cos * dy - center.y,
+            );
+            rotations.push(rotation);
+        } else {
+            // glyphs stay upright, centered half their ascent beside the arc
+            let offset = match arc.orientation {
+                ArcOrientation::Outward => ascent / 2.0,
+                ArcOrientation::Inward => -ascent / 2.0,
+            };
+            let (sin, cos) = angle.sin_cos();
+            glyph.glyph.position = point(
+                x + offset * cos - advance / 2.0,
+                y + offset * sin + ascent / 2.0,
+            );
+        }
+    }
+
+    let extent = arc.radius.abs() + height;
+    Rect {
+        min: point(arc.center.0 - extent, arc.center.1 - extent),
+        max: point(arc.center.0 + extent, arc.center.1 + extent),
+    }
+}
@@ -0,0 +1,282 @@
+use std::num::NonZeroI32;
+use std::{mem, ptr};
+
+use glyph_brush::Rectangle;
+use winapi::shared::dxgiformat::{
+    DXGI_FORMAT_A8_UNORM, DXGI_FORMAT_R8_TYPELESS, DXGI_FORMAT_R8_UNORM,
+};
+use winapi::shared::winerror::E_INVALIDARG;
+use winapi::um::d3d11::{
+    ID3D11DeviceContext, ID3D11Texture2D, D3D11_BOX, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+};
+use wio::com::ComPtr;
+
+use crate::util::{com_ref_cast, HResult};
+use crate::{DrawTarget, Error, TextRenderBackend, Vertex};
+
+/// What is written into an [`AtlasBackend`](struct.AtlasBackend.html) texture or the
+/// [glyph cache](struct.GlyphBrushBuilder.html#method.cache_format) of a brush for each glyph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AtlasFormat {
+    /// The coverage of each texel, as used by the built-in shaders.
+    Coverage,
+    /// A signed distance field where `0.5` is the glyph edge, `1.0` lies `spread` texels inside
+    /// and `0.0` `spread` texels outside of the glyph.
+    ///
+    /// Glyphs are only padded by a single texel in the atlas, so distances outside of the glyph
+    /// are cut off at the edge of its rectangle.
+    Sdf { spread: f32 },
+}
+
+impl AtlasFormat {
+    /// The change of the distance field across one pixel of the render target, for glyphs
+    /// drawn with `texels_per_pixel` atlas texels per pixel, e.g. `0.5` at twice their
+    /// rasterized size. Returns `None` for coverage atlases.
+    ///
+    /// Shaders drawing the atlas antialias the glyph edge by smoothing over this width, e.g.
+    /// with `smoothstep(0.5 - width / 2, 0.5 + width / 2, distance)`, which keeps edges one
+    /// pixel wide both when magnified and at small sizes. The built-in shader derives it per
+    /// pixel unless a width is set with
+    /// [`GlyphBrush::set_sdf_smoothing`](struct.GlyphBrush.html#method.set_sdf_smoothing).
+    #[inline]
+    pub fn sdf_smoothing_width(&self, texels_per_pixel: f32) -> Option<f32> {
+        match *self {
+            AtlasFormat::Coverage => None,
+            AtlasFormat::Sdf { spread } => Some(0.5 * texels_per_pixel / spread.max(1.0)),
+        }
+    }
+}
+
+/// How the built-in pixel shader turns the signed distance field of a
+/// [`AtlasFormat::Sdf`](enum.AtlasFormat.html#variant.Sdf) glyph cache into coverage, see
+/// [`GlyphBrush::set_sdf_smoothing`](struct.GlyphBrush.html#method.set_sdf_smoothing).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfSmoothing {
+    /// The distance of the glyph edge, `0.5` by default. Lower values thicken the glyphs,
+    /// higher values thin them.
+    pub edge: f32,
+    /// The width of distances the edge is smoothed over, or `None` to derive it from the
+    /// texels drawn per pixel like
+    /// [`AtlasFormat::sdf_smoothing_width`](enum.AtlasFormat.html#method.sdf_smoothing_width),
+    /// keeping edges one pixel wide at any scale.
+    ///
+    /// Wider edges look softer, e.g. for magnified text seen from afar in 3D, narrower ones
+    /// sharper.
+    pub width: Option<f32>,
+}
+
+impl Default for SdfSmoothing {
+    #[inline]
+    fn default() -> Self {
+        SdfSmoothing {
+            edge: 0.5,
+            width: None,
+        }
+    }
+}
+
+/// A [`TextRenderBackend`](trait.TextRenderBackend.html) rasterizing glyphs into a texture owned
+/// by the user instead of drawing them.
+///
+/// Driven by a [`PassthroughBrush`](struct.PassthroughBrush.html), this allows engines with
+/// their own text shaders to reuse the layout, caching and packing of glyphs. The texture
+/// coordinates of the processed glyphs are returned by [`glyphs`](#method.glyphs). Use
+/// [`GlyphBrushBuilder::build_atlas`](struct.GlyphBrushBuilder.html#method.build_atlas) to
+/// create a brush with a cache matching the texture and drive it with
+/// [`PassthroughBrush::process_queued_with`](struct.PassthroughBrush.html#method.process_queued_with).
+pub struct AtlasBackend<V = Vertex> {
+    ctx: ComPtr<ID3D11DeviceContext>,
+    texture: ComPtr<ID3D11Texture2D>,
+    width: u32,
+    height: u32,
+    format: AtlasFormat,
+    glyphs: Vec<V>,
+}
+
+impl<V> AtlasBackend<V> {
+    /// Creates a backend writing into `texture`, which has to be a default usage texture with a
+    /// single 8 bit channel.
+    ///
+    /// The glyph cache of the brush driving the backend has to have the size of the texture.
+    pub fn new(texture: ComPtr<ID3D11Texture2D>, format: AtlasFormat) -> HResult<Self> {
+        let (desc, ctx) = unsafe {
+            let mut desc = mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+            texture.GetDesc(&mut desc);
+            let mut device = ptr::null_mut();
+            texture.GetDevice(&mut device);
+            let device = ComPtr::from_raw(device);
+            let mut ctx = ptr::null_mut();
+            device.GetImmediateContext(&mut ctx);
+            (desc, ComPtr::from_raw(ctx))
+        };
+        let single_channel = [
+            DXGI_FORMAT_R8_UNORM,
+            DXGI_FORMAT_R8_TYPELESS,
+            DXGI_FORMAT_A8_UNORM,
+        ]
+        .contains(&desc.Format);
+        if !single_channel || desc.Usage != D3D11_USAGE_DEFAULT {
+            return Err(Error::HResult {
+                call: "AtlasBackend::new",
+                code: NonZeroI32::new(E_INVALIDARG).unwrap(),
+            });
+        }
+        Ok(AtlasBackend {
+            ctx,
+            texture,
+            width: desc.Width,
+            height: desc.Height,
+            format,
+            glyphs: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    #[inline]
+    pub fn texture(&self) -> &ComPtr<ID3D11Texture2D> {
+        &self.texture
+    }
+
+    /// Returns the glyph quads of the last processed batch, containing the texture coordinates
+    /// of their glyphs in the atlas.
+    #[inline]
+    pub fn glyphs(&self) -> &[V] {
+        &self.glyphs
+    }
+}
+
+impl<V: Copy> TextRenderBackend<V> for AtlasBackend<V> {
+    type Error = Error;
+
+    #[inline]
+    fn max_cache_dimension(&self) -> u32 {
+        self.width.max(self.height)
+    }
+
+    fn update_cache(&mut self, rect: Rectangle<u32>, data: &[u8]) {
+        let width = rect.width();
+        if width == 0 || rect.height() == 0 {
+            return;
+        }
+        let sdf;
+        let data = match self.format {
+            AtlasFormat::Coverage => data,
+            AtlasFormat::Sdf { spread } => {
+                sdf = signed_distance_field(width as usize, data, spread);
+                &sdf
+            }
+        };
+        unsafe {
+            self.ctx.UpdateSubresource(
+                com_ref_cast(&self.texture).as_raw(),
+                0,
+                &D3D11_BOX {
+                    left: rect.min[0],
+                    right: rect.max[0],
+                    top: rect.min[1],
+                    bottom: rect.max[1],
+                    front: 0,
+                    back: 1,
+                },
+                data.as_ptr().cast(),
+                width,
+                width * rect.height(),
+            );
+        }
+    }
+
+    /// Fails as the texture of the user can't be resized, which means the atlas is full.
+    fn resize_cache(&mut self, width: u32, height: u32) -> HResult<()> {
+        if (width, height) == (self.width, self.height) {
+            Ok(())
+        } else {
+            Err(Error::CacheTooLarge {
+                requested: (width, height),
+                max_dimension: self.max_cache_dimension(),
+            })
+        }
+    }
+
+    fn upload_vertices(&mut self, vertices: &[V]) -> HResult<()> {
+        self.glyphs.clear();
+        self.glyphs.extend_from_slice(vertices);
+        Ok(())
+    }
+
+    /// Does nothing, the glyphs are drawn by the user.
+    #[inline]
+    fn draw(&mut self, _: &DrawTarget<'_>) -> HResult<()> {
+        Ok(())
+    }
+}
+
+/// Converts the coverage of a glyph into a signed distance field with the given spread.
+pub(crate) fn signed_distance_field(width: usize, coverage: &[u8], spread: f32) -> Vec<u8> {
+    let spread = spread.max(1.0);
+    let height = coverage.len() / width;
+    let inside = |x: isize, y: isize| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && coverage[y as usize * width + x as usize] >= 128
+    };
+    let radius = spread.ceil() as isize;
+    (0..coverage.len())
+        .map(|i| {
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            let is_inside = inside(x, y);
+            let mut nearest = spread + 0.5;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if inside(x + dx, y + dy) != is_inside {
+                        nearest = nearest.min(((dx * dx + dy * dy) as f32).sqrt());
+                    }
+                }
+            }
+            // the edge lies halfway between the texel centers
+            let distance = if is_inside {
+                nearest - 0.5
+            } else {
+                0.5 - nearest
+            };
+            ((0.5 + distance / (2.0 * spread)).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_field_across_a_stroke() {
+        // a vertical stroke three texels wide
+        let coverage: Vec<u8> = (0..9 * 9)
+            .map(|i| if (3..6).contains(&(i % 9)) { 255 } else { 0 })
+            .collect();
+        let sdf = signed_distance_field(9, &coverage, 2.0);
+        // the edges lie halfway between the texels, distances beyond the spread are clamped
+        assert_eq!(sdf[4 * 9..5 * 9], [0, 32, 96, 159, 223, 159, 96, 32, 0]);
+    }
+
+    #[test]
+    fn distance_field_of_a_square() {
+        let mut coverage = [0; 7 * 7];
+        for y in 2..5 {
+            coverage[y * 7 + 2..y * 7 + 5].fill(200);
+        }
+        let sdf = signed_distance_field(7, &coverage, 2.0);
+        // texels are inside where they were covered
+        for (coverage, distance) in coverage.iter().zip(&sdf) {
+            assert_eq!(*coverage >= 128, *distance >= 128);
+        }
+        assert_eq!(sdf[3 * 7 + 3], 223);
+        // the corner is further away diagonally
+        assert!(sdf[7 + 1] < sdf[7 + 2]);
+    }
+}
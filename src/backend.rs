@@ -0,0 +1,139 @@
+use std::hash::BuildHasher;
+
+use glyph_brush::ab_glyph::Font;
+use glyph_brush::{BrushAction, BrushError, GlyphVertex, Rectangle};
+use winapi::um::d3d11::D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
+
+use crate::pipeline::Vertex;
+use crate::{grown_cache_size, DrawTarget, GlyphExtra, Metrics};
+
+/// The GPU side of a glyph brush, receiving glyph cache updates and glyph quads.
+///
+/// The built-in D3D11 pipeline of [`GlyphBrush`](struct.GlyphBrush.html) implements this trait.
+/// Custom implementations can be driven by a
+/// [`PassthroughBrush`](struct.PassthroughBrush.html), reusing its layout, caching and cache
+/// resizing logic, e.g. to write glyph quads into a larger UI vertex buffer.
+pub trait TextRenderBackend<V = Vertex> {
+    type Error;
+
+    /// The maximum width and height the glyph cache may be grown to.
+    fn max_cache_dimension(&self) -> u32 {
+        D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    }
+
+    /// Writes the single channel coverage `data` of a newly rasterized glyph into the cache
+    /// texture at `rect`.
+    fn update_cache(&mut self, rect: Rectangle<u32>, data: &[u8]);
+
+    /// Resizes the cache texture, discarding its content.
+    ///
+    /// glyph_brush clears its draw cache when resizing, so every glyph of the frame being
+    /// processed is rasterized again before it can be drawn. The built-in pipeline avoids
+    /// these resizes while its texture grows into a
+    /// [reserved height](struct.GlyphBrushBuilder.html#method.reserve_cache_height), keeping
+    /// the layout and copying the texture instead, which
+    /// [`cache_migration_rows`](struct.GlyphBrushBuilder.html#method.cache_migration_rows)
+    /// spreads over several frames. Otherwise avoid resizes in animated scenes with
+    /// [`initial_cache_size`](struct.GlyphBrushBuilder.html#method.initial_cache_size) or by
+    /// [precaching](struct.GlyphBrush.html#method.precache) during a loading screen.
+    fn resize_cache(&mut self, width: u32, height: u32) -> Result<(), Self::Error>;
+
+    /// Returns `true` if `error`, returned by [`resize_cache`](#tymethod.resize_cache), means
+    /// the texture couldn't be allocated. Growing the cache is then retried with smaller sizes.
+    ///
+    /// Defaults to `false`.
+    #[inline]
+    fn is_out_of_memory(&self, _error: &Self::Error) -> bool {
+        false
+    }
+
+    /// Replaces the glyph quads to draw.
+    fn upload_vertices(&mut self, vertices: &[V]) -> Result<(), Self::Error>;
+
+    /// Draws the last uploaded glyph quads into `target`.
+    fn draw(&mut self, target: &DrawTarget<'_>) -> Result<(), Self::Error>;
+}
+
+/// Processes the queue of `glyph_brush`, growing the cache of `backend` when required, and
+/// returns the instances created by `to_instance` if they changed.
+pub(crate) fn process_queued<B, F, H, X, V, I>(
+    glyph_brush: &mut glyph_brush::GlyphBrush<I, X, F, H>,
+    backend: &mut B,
+    metrics: &mut Metrics,
+    to_instance: impl Fn(GlyphVertex<'_, X>) -> I + Copy,
+) -> Result<Option<Vec<I>>, B::Error>
+where
+    B: TextRenderBackend<V>,
+    F: Font + Sync,
+    H: BuildHasher,
+    X: GlyphExtra,
+    I: Clone + 'static,
+{
+    trace_span!(
+        "process_queued",
+        cache_width = glyph_brush.texture_dimensions().0,
+        cache_height = glyph_brush.texture_dimensions().1,
+    );
+    let brush_action = loop {
+        let brush_action = glyph_brush.process_queued(
+            |rect, tex_data| {
+                metrics.rasterized_glyphs += 1;
+                backend.update_cache(rect, tex_data)
+            },
+            to_instance,
+        );
+
+        match brush_action {
+            Ok(action) => break action,
+            Err(BrushError::TextureTooSmall { suggested }) => {
+                let current = glyph_brush.texture_dimensions();
+                let (new_width, new_height) =
+                    grow_cache(backend, current, suggested, &mut *metrics)?;
+                glyph_brush.resize_texture(new_width, new_height);
+            }
+        }
+    };
+
+    match brush_action {
+        BrushAction::Draw(instances) => Ok(Some(instances)),
+        BrushAction::ReDraw => Ok(None),
+    }
+}
+
+/// Resizes the cache of `backend` to the size suggested by glyph_brush, halving the growth
+/// while the texture can't be allocated. Fails once the cache can't grow any further.
+///
+/// Returns the new dimensions of the cache.
+fn grow_cache<B: TextRenderBackend<V>, V>(
+    backend: &mut B,
+    current: (u32, u32),
+    suggested: (u32, u32),
+    metrics: &mut Metrics,
+) -> Result<(u32, u32), B::Error> {
+    let (mut width, mut height) =
+        grown_cache_size(current, suggested, backend.max_cache_dimension());
+    loop {
+        trace_span!("resize_cache", width = width, height = height);
+        match backend.resize_cache(width, height) {
+            Ok(()) => {
+                metrics.cache_resizes += 1;
+                return Ok((width, height));
+            }
+            Err(error) if backend.is_out_of_memory(&error) => {
+                let smaller = ((current.0 + width) / 2, (current.1 + height) / 2);
+                if smaller == (width, height) || (smaller.0 <= current.0 && smaller.1 <= current.1)
+                {
+                    return Err(error);
+                }
+                log::warn!(
+                    "Out of memory growing the glyph cache to {:?}, retrying with {:?}",
+                    (width, height),
+                    smaller,
+                );
+                width = smaller.0;
+                height = smaller.1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
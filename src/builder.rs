@@ -1,74 +1,318 @@
 use core::hash::BuildHasher;
+use core::marker::PhantomData;
 
 use glyph_brush::ab_glyph::Font;
 use glyph_brush::delegate_glyph_brush_builder_fns;
-use glyph_brush::DefaultSectionHasher;
+use glyph_brush::{DefaultSectionHasher, Extra, FontId};
+use winapi::shared::dxgi::IDXGISwapChain;
 use winapi::um::d3d11::{
-    ID3D11Device, D3D11_DEPTH_STENCIL_DESC, D3D11_FILTER, D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+    ID3D11Device, D3D11_DEPTH_STENCIL_DESC, D3D11_FILTER, D3D11_FILTER_MIN_MAG_MIP_POINT,
+    D3D11_RASTERIZER_DESC,
 };
+use winapi::Interface;
 use wio::com::ComPtr;
 
-use crate::util::HResult;
+use crate::layout::LayoutOptions;
+use crate::offscreen::create_warp_device;
+use crate::pipeline::PipelineOptions;
+use crate::util::{com_ptr_from_fn, HResult};
 
-use super::GlyphBrush;
+use super::{
+    AtlasBackend, AtlasFormat, BoundsClipping, BrushVertex, CacheUpdateMethod, DepthMode,
+    GlyphBrush, GlyphExtra, Outline, PassthroughBrush, Script, SdfSmoothing, Vertex,
+};
 
 /// Builder for a [`GlyphBrush`](struct.GlyphBrush.html).
-pub struct GlyphBrushBuilder<D, F, H = DefaultSectionHasher> {
+pub struct GlyphBrushBuilder<F, H = DefaultSectionHasher, X = Extra, V = Vertex> {
     inner: glyph_brush::GlyphBrushBuilder<F, H>,
-    texture_filter_method: D3D11_FILTER,
-    depth: D,
+    pipeline_options: PipelineOptions,
+    bounds_clipping: BoundsClipping,
+    layout_options: LayoutOptions,
+    depth_mode: DepthMode,
+    vertex: PhantomData<(X, V)>,
 }
 
-impl<F, H> From<glyph_brush::GlyphBrushBuilder<F, H>> for GlyphBrushBuilder<(), F, H> {
+impl<F, H> From<glyph_brush::GlyphBrushBuilder<F, H>> for GlyphBrushBuilder<F, H> {
     fn from(inner: glyph_brush::GlyphBrushBuilder<F, H>) -> Self {
         GlyphBrushBuilder {
             inner,
-            texture_filter_method: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            depth: (),
+            pipeline_options: PipelineOptions::default(),
+            bounds_clipping: BoundsClipping::default(),
+            layout_options: LayoutOptions::default(),
+            depth_mode: DepthMode::default(),
+            vertex: PhantomData,
         }
     }
 }
 
-impl GlyphBrushBuilder<(), ()> {
+impl GlyphBrushBuilder<()> {
     /// Specifies the default font used to render glyphs.
     /// Referenced with `FontId(0)`, which is default.
     #[inline]
-    pub fn using_font<F: Font>(font: F) -> GlyphBrushBuilder<(), F> {
+    pub fn using_font<F: Font>(font: F) -> GlyphBrushBuilder<F> {
         GlyphBrushBuilder {
             inner: glyph_brush::GlyphBrushBuilder::using_font(font),
-            texture_filter_method: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            depth: (),
+            pipeline_options: PipelineOptions::default(),
+            bounds_clipping: BoundsClipping::default(),
+            layout_options: LayoutOptions::default(),
+            depth_mode: DepthMode::default(),
+            vertex: PhantomData,
         }
     }
 
     /// Create a new builder with multiple fonts.
-    pub fn using_fonts<F: Font>(fonts: Vec<F>) -> GlyphBrushBuilder<(), F> {
+    pub fn using_fonts<F: Font>(fonts: Vec<F>) -> GlyphBrushBuilder<F> {
         GlyphBrushBuilder {
             inner: glyph_brush::GlyphBrushBuilder::using_fonts(fonts),
-            texture_filter_method: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            depth: (),
+            pipeline_options: PipelineOptions::default(),
+            bounds_clipping: BoundsClipping::default(),
+            layout_options: LayoutOptions::default(),
+            depth_mode: DepthMode::default(),
+            vertex: PhantomData,
         }
     }
 
     /// Create a new builder without any fonts.
-    pub fn without_fonts() -> GlyphBrushBuilder<(), ()> {
+    pub fn without_fonts() -> GlyphBrushBuilder<()> {
         GlyphBrushBuilder {
             inner: glyph_brush::GlyphBrushBuilder::without_fonts(),
-            texture_filter_method: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            depth: (),
+            pipeline_options: PipelineOptions::default(),
+            bounds_clipping: BoundsClipping::default(),
+            layout_options: LayoutOptions::default(),
+            depth_mode: DepthMode::default(),
+            vertex: PhantomData,
         }
     }
 }
 
-impl<F: Font, D, H: BuildHasher> GlyphBrushBuilder<D, F, H> {
+impl<F, H, X, V> GlyphBrushBuilder<F, H, X, V>
+where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
     delegate_glyph_brush_builder_fns!(inner);
 
     /// Sets the texture filtering method.
     pub fn texture_filter_method(mut self, filter_method: D3D11_FILTER) -> Self {
-        self.texture_filter_method = filter_method;
+        self.pipeline_options.filter_mode = filter_method;
+        self
+    }
+
+    /// Sets the maximum anisotropy of the glyph cache sampler, clamped to `1..=16`.
+    ///
+    /// Only has an effect with an anisotropic
+    /// [`texture_filter_method`](#method.texture_filter_method), e.g. when drawing text onto
+    /// slanted surfaces in 3D. Defaults to `1`.
+    pub fn max_anisotropy(mut self, max_anisotropy: u32) -> Self {
+        self.pipeline_options.max_anisotropy = max_anisotropy.clamp(1, 16);
+        self
+    }
+
+    /// Sets the range of mipmap levels the glyph cache sampler may access.
+    ///
+    /// Defaults to `0.0..=0.0`.
+    pub fn lod_range(mut self, min_lod: f32, max_lod: f32) -> Self {
+        self.pipeline_options.min_lod = min_lod;
+        self.pipeline_options.max_lod = max_lod;
+        self
+    }
+
+    /// Sets the rasterizer state used for drawing.
+    ///
+    /// Defaults to solid filling without culling or depth clipping, with scissoring enabled.
+    /// Disabling `ScissorEnable` makes the brush ignore the scissor rectangle passed to the
+    /// `*_with_scissoring` functions and `DrawTarget::scissor`.
+    pub fn rasterizer_state(mut self, rasterizer_desc: D3D11_RASTERIZER_DESC) -> Self {
+        self.pipeline_options.rasterizer = rasterizer_desc;
+        self
+    }
+
+    /// Sets how newly rasterized glyphs are uploaded into the glyph cache texture.
+    ///
+    /// Defaults to
+    /// [`CacheUpdateMethod::UpdateSubresource`](enum.CacheUpdateMethod.html#variant.UpdateSubresource).
+    pub fn cache_update_method(mut self, method: CacheUpdateMethod) -> Self {
+        self.pipeline_options.cache_update_method = method;
+        self
+    }
+
+    /// Keeps a CPU copy of the glyph cache, which can be inspected with
+    /// [`GlyphBrush::cache_contents`](struct.GlyphBrush.html#method.cache_contents) and lets
+    /// [`GlyphBrush::reupload_cache`](struct.GlyphBrush.html#method.reupload_cache) restore the
+    /// cache without rasterizing the glyphs again, at the cost of one byte of memory per texel.
+    ///
+    /// Defaults to `false`.
+    pub fn cache_shadow_copy(mut self, enabled: bool) -> Self {
+        self.pipeline_options.cache_shadow_copy = enabled;
+        self
+    }
+
+    /// Lays out the glyph cache for `height` rows while its texture starts at the height of
+    /// the [`initial_cache_size`](#method.initial_cache_size) and grows as glyphs are packed
+    /// below it, doubling its height each time.
+    ///
+    /// Growing within the reserved height keeps glyph_brush's layout, so the texture is copied
+    /// into the larger one with `CopySubresourceRegion` and none of the cached glyphs are
+    /// rasterized again, unlike the resizes of a full cache. The texture coordinates of the
+    /// glyphs are relative to the reserved height, which the built-in vertex shader scales by
+    /// `cache_height_scale` of the `pixelBuffer` constants to address the texture. Ignored
+    /// unless larger than the initial height.
+    pub fn reserve_cache_height(mut self, height: u32) -> Self {
+        self.pipeline_options.reserved_cache_height = height;
+        self
+    }
+
+    /// Copies the glyph cache into a texture grown into the
+    /// [reserved height](#method.reserve_cache_height) over several frames, `rows` rows per
+    /// draw, instead of all at once.
+    ///
+    /// The old texture is kept until all of its rows are copied and the glyphs in them are
+    /// drawn from it meanwhile, while new glyphs are written into both textures. This spreads
+    /// the cost of growing large caches at the expense of keeping both textures for a few
+    /// frames. Defaults to `0`, copying the texture at once. Ignored on feature level 9.3.
+    pub fn cache_migration_rows(mut self, rows: u32) -> Self {
+        self.pipeline_options.cache_migration_rows = rows;
+        self
+    }
+
+    /// Sets the radius in pixels the glow of texts is blurred by, see
+    /// [`GlyphExtra::glow`](trait.GlyphExtra.html#method.glow).
+    ///
+    /// Defaults to `8.0`.
+    pub fn glow_radius(mut self, radius: f32) -> Self {
+        self.pipeline_options.glow_radius = radius.max(0.0);
+        self
+    }
+
+    /// Makes the brush enter the critical section of the device through `ID3D11Multithread`
+    /// whenever it uses the immediate context, e.g. to upload vertices and glyphs or to draw.
+    ///
+    /// Enable this when the host uses the immediate context from other threads and relies on
+    /// `ID3D11Multithread::SetMultithreadProtected`, which is common for overlays hooked into
+    /// games. Building fails with `E_NOINTERFACE` if the runtime doesn't support the interface.
+    /// Defaults to `false`.
+    pub fn multithread_protection(mut self, enabled: bool) -> Self {
+        self.pipeline_options.multithread_protection = enabled;
+        self
+    }
+
+    /// Draws outlines and halos under their glyphs in the same pass, halving the draw calls
+    /// of outlined text.
+    ///
+    /// The glyph cache then stores the distance to each glyph up to the
+    /// [maximum outline width](#method.max_outline_width) in a second channel next to its
+    /// coverage, which the pixel shader reads the outline from with a single sample.
+    ///
+    /// The outline of a glyph covers the neighbouring glyphs drawn before it where they
+    /// overlap, e.g. with thick outlines or tight letter spacing. Defaults to `false`, drawing
+    /// all outlines in a pass of their own before the glyphs.
+    pub fn single_pass_outlines(mut self, enabled: bool) -> Self {
+        self.pipeline_options.single_pass_outlines = enabled;
+        self
+    }
+
+    /// Sets the width in cache texels up to which
+    /// [single pass outlines](#method.single_pass_outlines) are drawn, wider outlines are cut
+    /// off at it.
+    ///
+    /// Larger widths make rasterizing new glyphs slower. Defaults to `4.0`.
+    pub fn max_outline_width(mut self, width: f32) -> Self {
+        self.pipeline_options.max_outline_width = width.max(1.0);
+        self
+    }
+
+    /// Sets the gamma glyph coverage is corrected with, see
+    /// [`GlyphBrush::set_coverage_gamma`](struct.GlyphBrush.html#method.set_coverage_gamma).
+    ///
+    /// Defaults to `1.0`.
+    pub fn coverage_gamma(mut self, gamma: f32) -> Self {
+        self.pipeline_options.coverage_gamma = gamma.max(f32::MIN_POSITIVE);
+        self
+    }
+
+    /// Sets what the glyph cache holds for each glyph. An
+    /// [`AtlasFormat::Sdf`](enum.AtlasFormat.html#variant.Sdf) cache stores signed distance
+    /// fields instead of coverage, which the built-in pixel shader smooths as set with
+    /// [`sdf_smoothing`](#method.sdf_smoothing), keeping the edges of magnified glyphs sharp.
+    ///
+    /// Custom pixel shaders read whatever the cache holds. Defaults to
+    /// `AtlasFormat::Coverage`.
+    pub fn cache_format(mut self, format: AtlasFormat) -> Self {
+        self.pipeline_options.cache_format = format;
+        self
+    }
+
+    /// Sets how the signed distance fields of an SDF [cache](#method.cache_format) are turned
+    /// into coverage, see
+    /// [`GlyphBrush::set_sdf_smoothing`](struct.GlyphBrush.html#method.set_sdf_smoothing).
+    pub fn sdf_smoothing(mut self, smoothing: SdfSmoothing) -> Self {
+        self.pipeline_options.sdf_smoothing = smoothing;
+        self
+    }
+
+    /// Draws a halo around all glyphs without an outline of their own, see
+    /// [`GlyphBrush::set_halo`](struct.GlyphBrush.html#method.set_halo).
+    pub fn halo(mut self, halo: Outline) -> Self {
+        self.pipeline_options.halo = Some(halo);
         self
     }
 
+    /// Sets how glyphs overlapping the bounds of their section are clipped.
+    ///
+    /// Defaults to [`BoundsClipping::Vertex`](enum.BoundsClipping.html#variant.Vertex).
+    pub fn bounds_clipping(mut self, bounds_clipping: BoundsClipping) -> Self {
+        self.bounds_clipping = bounds_clipping;
+        self
+    }
+
+    /// Sets whether glyph positions are rounded to whole pixels. Defaults to `false`.
+    ///
+    /// Snapping keeps glyphs from being blurred across pixel boundaries, at the cost of
+    /// slightly uneven spacing and jittering when text moves by fractions of a pixel.
+    pub fn pixel_snapping(mut self, pixel_snapping: bool) -> Self {
+        self.layout_options.subpixel_variants = if pixel_snapping { Some(1) } else { None };
+        self
+    }
+
+    /// Snaps glyphs to `variants` horizontal positions per pixel and vertically to whole
+    /// pixels, so the glyph cache stores at most `variants` rasterizations of each glyph at a
+    /// scale. `1` is the same as [`pixel_snapping`](#method.pixel_snapping).
+    ///
+    /// More variants keep slowly scrolling text moving smoothly, fewer keep the cache small,
+    /// e.g. `1` for static HUD text. This also sets the
+    /// [`draw_cache_position_tolerance`](#method.draw_cache_position_tolerance) to tell the
+    /// variants apart. By default glyphs aren't snapped and the cache tolerates differences of
+    /// `0.1` pixels in both directions.
+    pub fn subpixel_variants(mut self, variants: u32) -> Self {
+        let variants = variants.max(1);
+        self.layout_options.subpixel_variants = Some(variants);
+        // slightly below the distance of the variants, so rounding never merges two of them
+        self.draw_cache_position_tolerance(0.99 / variants as f32)
+    }
+
+    /// Draws the characters of `script` with the font `font_id`, see
+    /// [`GlyphBrush::set_script_font`](struct.GlyphBrush.html#method.set_script_font).
+    pub fn script_font(mut self, script: Script, font_id: FontId) -> Self {
+        let script_fonts = &mut self.layout_options.script_fonts;
+        script_fonts.retain(|&(mapped, _)| mapped != script);
+        script_fonts.push((script, font_id));
+        self
+    }
+
+    /// Configures the brush for crisp pixel-art text: point sampling of the glyph cache,
+    /// [`pixel_snapping`](#method.pixel_snapping) and no subpixel positioning in the draw
+    /// cache.
+    ///
+    /// Fonts designed on a pixel grid look like bitmap fonts when drawn at multiples of their
+    /// design size.
+    pub fn pixel_art(self) -> Self {
+        self.texture_filter_method(D3D11_FILTER_MIN_MAG_MIP_POINT)
+            .pixel_snapping(true)
+            .draw_cache_position_tolerance(1.0)
+    }
+
     /// Sets the section hasher. `GlyphBrush` cannot handle absolute section
     /// hash collisions so use a good hash algorithm.
     ///
@@ -76,44 +320,177 @@ impl<F: Font, D, H: BuildHasher> GlyphBrushBuilder<D, F, H> {
     /// internal use.
     ///
     /// Defaults to [seahash](https://docs.rs/seahash).
-    pub fn section_hasher<T: BuildHasher>(self, section_hasher: T) -> GlyphBrushBuilder<D, F, T> {
+    pub fn section_hasher<T: BuildHasher>(
+        self,
+        section_hasher: T,
+    ) -> GlyphBrushBuilder<F, T, X, V> {
         GlyphBrushBuilder {
             inner: self.inner.section_hasher(section_hasher),
-            texture_filter_method: self.texture_filter_method,
-            depth: self.depth,
+            pipeline_options: self.pipeline_options,
+            bounds_clipping: self.bounds_clipping,
+            layout_options: self.layout_options,
+            depth_mode: self.depth_mode,
+            vertex: PhantomData,
         }
     }
 
-    pub fn depth_stencil_state(
-        self,
-        depth_stencil: D3D11_DEPTH_STENCIL_DESC,
-    ) -> GlyphBrushBuilder<D3D11_DEPTH_STENCIL_DESC, F, H> {
+    /// Sets the type of the extra data attached to texts, see
+    /// [`GlyphExtra`](trait.GlyphExtra.html).
+    ///
+    /// Defaults to [`Extra`](struct.Extra.html).
+    pub fn extra_type<X2: GlyphExtra>(self) -> GlyphBrushBuilder<F, H, X2, V>
+    where
+        V: BrushVertex<X2>,
+    {
         GlyphBrushBuilder {
             inner: self.inner,
-            texture_filter_method: self.texture_filter_method,
-            depth: depth_stencil,
+            pipeline_options: self.pipeline_options,
+            bounds_clipping: self.bounds_clipping,
+            layout_options: self.layout_options,
+            depth_mode: self.depth_mode,
+            vertex: PhantomData,
         }
     }
-}
 
-impl<F: Font, H: BuildHasher> GlyphBrushBuilder<(), F, H> {
-    /// Builds a `GlyphBrush` using the given `ID3D11Device`.
-    pub fn build(self, device: ComPtr<ID3D11Device>) -> HResult<GlyphBrush<(), F, H>> {
-        GlyphBrush::<(), F, H>::new(device, self.texture_filter_method, self.inner)
+    /// Sets the type of the per-instance vertices generated for every glyph, see
+    /// [`BrushVertex`](trait.BrushVertex.html).
+    ///
+    /// Defaults to [`Vertex`](struct.Vertex.html).
+    pub fn vertex_type<V2: BrushVertex<X>>(self) -> GlyphBrushBuilder<F, H, X, V2> {
+        GlyphBrushBuilder {
+            inner: self.inner,
+            pipeline_options: self.pipeline_options,
+            bounds_clipping: self.bounds_clipping,
+            layout_options: self.layout_options,
+            depth_mode: self.depth_mode,
+            vertex: PhantomData,
+        }
+    }
+
+    /// Replaces the built-in vertex shader with the given compiled shader bytecode.
+    ///
+    /// The shader receives the per-instance data described by the
+    /// [`input_layout`](trait.BrushVertex.html#tymethod.input_layout) of the vertex type and
+    /// its output has to match the input of the pixel shader. With a
+    /// [reserved cache height](#method.reserve_cache_height) it has to scale the vertical
+    /// texture coordinates like the built-in shader.
+    pub fn vertex_shader(mut self, bytecode: impl Into<Vec<u8>>) -> Self {
+        self.pipeline_options.vertex_shader = Some(bytecode.into());
+        self
+    }
+
+    /// Replaces the built-in pixel shader with the given compiled shader bytecode.
+    ///
+    /// The glyph cache is bound as `Texture2D` to slot `t0` and its sampler to `s0`. While the
+    /// cache [migrates](#method.cache_migration_rows) into a grown texture, the rows above
+    /// `migration_height` of the `pixelBuffer` constants have to be read from the old texture
+    /// bound to `t3`.
+    pub fn pixel_shader(mut self, bytecode: impl Into<Vec<u8>>) -> Self {
+        self.pipeline_options.pixel_shader = Some(bytecode.into());
+        self
+    }
+
+    /// Builds a [`PassthroughBrush`](struct.PassthroughBrush.html) which lays out and caches
+    /// glyphs without creating any GPU resources.
+    pub fn build_passthrough(self) -> PassthroughBrush<F, H, X, V> {
+        PassthroughBrush::new(self.bounds_clipping, self.layout_options, self.inner)
+    }
+
+    /// Builds a [`PassthroughBrush`](struct.PassthroughBrush.html) packing its glyphs into the
+    /// texture of `atlas`.
+    ///
+    /// The glyph cache is sized to the texture, so the texture coordinates of the glyphs address
+    /// the texture directly. Processing fails with
+    /// [`Error::CacheTooLarge`](enum.Error.html#variant.CacheTooLarge) once the atlas is full.
+    pub fn build_atlas(mut self, atlas: &AtlasBackend<V>) -> PassthroughBrush<F, H, X, V> {
+        self.inner = self.inner.initial_cache_size(atlas.dimensions());
+        self.build_passthrough()
+    }
+
+    /// Sets whether glyphs are depth tested, which can be changed later with
+    /// [`GlyphBrush::set_depth_mode`](struct.GlyphBrush.html#method.set_depth_mode).
+    ///
+    /// Defaults to [`DepthMode::Disabled`](enum.DepthMode.html#variant.Disabled).
+    pub fn depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Shorthand for `depth_mode(DepthMode::Enabled(depth_stencil))`.
+    pub fn depth_stencil_state(self, depth_stencil: D3D11_DEPTH_STENCIL_DESC) -> Self {
+        self.depth_mode(DepthMode::Enabled(depth_stencil))
     }
-}
 
-impl<F: Font, H: BuildHasher> GlyphBrushBuilder<D3D11_DEPTH_STENCIL_DESC, F, H> {
     /// Builds a `GlyphBrush` using the given `ID3D11Device`.
-    pub fn build(
-        self,
-        device: ComPtr<ID3D11Device>,
-    ) -> HResult<GlyphBrush<D3D11_DEPTH_STENCIL_DESC, F, H>> {
-        GlyphBrush::<D3D11_DEPTH_STENCIL_DESC, F, H>::new(
+    pub fn build(self, device: ComPtr<ID3D11Device>) -> HResult<GlyphBrush<F, H, X, V>> {
+        GlyphBrush::new(
             device,
-            self.texture_filter_method,
-            self.depth,
+            self.pipeline_options,
+            self.bounds_clipping,
+            self.layout_options,
+            self.depth_mode,
             self.inner,
         )
     }
+
+    /// Builds a `GlyphBrush` on a new WARP device, see
+    /// [`create_warp_device`](fn.create_warp_device.html).
+    ///
+    /// Without a window to present to, text is drawn with
+    /// [`GlyphBrush::render_to_texture`](struct.GlyphBrush.html#method.render_to_texture). To draw
+    /// into render targets of your own, create the device with `create_warp_device` and pass it
+    /// to [`build`](#method.build) instead.
+    pub fn build_warp(self) -> HResult<GlyphBrush<F, H, X, V>> {
+        let (device, _) = create_warp_device()?;
+        self.build(device)
+    }
+
+    /// Builds a `GlyphBrush` on the device `swapchain` has been created with, for overlays
+    /// which only have access to the swapchain.
+    ///
+    /// The brush keeps the swapchain, so
+    /// [`GlyphBrush::draw_queued_to_back_buffer`](struct.GlyphBrush.html#method.draw_queued_to_back_buffer)
+    /// projects onto its back buffer, also after it has been resized.
+    pub fn build_for_swapchain(
+        self,
+        swapchain: &ComPtr<IDXGISwapChain>,
+    ) -> HResult<GlyphBrush<F, H, X, V>> {
+        let device = unsafe {
+            com_ptr_from_fn("GetDevice", |device: &mut *mut ID3D11Device| {
+                swapchain.GetDevice(
+                    &ID3D11Device::uuidof(),
+                    (device as *mut *mut ID3D11Device).cast(),
+                )
+            })?
+        };
+        let mut brush = self.build(device)?;
+        brush.swapchain = Some(swapchain.clone());
+        Ok(brush)
+    }
+}
+
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
+where
+    F: Font + Clone,
+    H: BuildHasher + Clone,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
+    /// Returns a builder with the fonts, cache size, hasher and pipeline configuration of this
+    /// brush, e.g. to rebuild it on a new device after the adapter changed.
+    ///
+    /// Shaders, halo, depth mode and layout options set since building are included. Font
+    /// names, fill textures and the swapchain of
+    /// [`build_for_swapchain`](struct.GlyphBrushBuilder.html#method.build_for_swapchain) belong
+    /// to the old device or are registered by the application and aren't.
+    pub fn to_builder(&self) -> GlyphBrushBuilder<F, H, X, V> {
+        GlyphBrushBuilder {
+            inner: self.glyph_brush.to_builder(),
+            pipeline_options: self.pipeline.options(),
+            bounds_clipping: self.bounds_clipping,
+            layout_options: self.layout_options.clone(),
+            depth_mode: self.pipeline.depth_mode(),
+            vertex: PhantomData,
+        }
+    }
 }
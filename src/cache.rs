@@ -1,87 +1,783 @@
 use std::{mem, ptr};
 
 use glyph_brush::Rectangle;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R8_UNORM;
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R8G8_UNORM, DXGI_FORMAT_R8_UNORM};
 use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
 use winapi::um::d3d11::{
     ID3D11Device, ID3D11DeviceContext, ID3D11ShaderResourceView, ID3D11Texture2D,
-    D3D11_BIND_SHADER_RESOURCE, D3D11_BOX, D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_TEX2D_SRV,
-    D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+    D3D11_BIND_SHADER_RESOURCE, D3D11_BOX, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE,
+    D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_SUBRESOURCE_DATA, D3D11_TEX2D_SRV, D3D11_TEXTURE2D_DESC,
+    D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
 };
 use winapi::um::d3dcommon::D3D11_SRV_DIMENSION_TEXTURE2D;
 use wio::com::ComPtr;
 
-use crate::util::{com_ptr_from_fn, com_ref_cast, HResult};
+use crate::atlas::signed_distance_field;
+use crate::util::{com_ptr_from_fn, com_ref_cast, hresult, HResult};
+use crate::AtlasFormat;
 
+/// How glyphs are uploaded into the glyph cache texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheUpdateMethod {
+    /// Uploads the glyphs rasterized since the last draw with `UpdateSubresource`, a single
+    /// upload of the region covering them from the
+    /// [CPU copy](struct.GlyphBrushBuilder.html#method.cache_shadow_copy) if kept, otherwise one
+    /// upload per row of the cache they were packed into.
+    UpdateSubresource,
+    /// Writes the glyphs into a staging texture through `Map` and copies the region they cover
+    /// into the cache with a single `CopySubresourceRegion`, avoiding the extra internal staging
+    /// copy some drivers perform for `UpdateSubresource`.
+    Map,
+}
+
+impl Default for CacheUpdateMethod {
+    #[inline]
+    fn default() -> Self {
+        CacheUpdateMethod::UpdateSubresource
+    }
+}
+
+/// The glyph cache texture.
+///
+/// Glyph updates are staged on the CPU and uploaded together on [`flush`](#method.flush),
+/// instead of one upload per glyph as they are rasterized. A CPU copy of the whole texture is
+/// only kept if requested.
+///
+/// For single pass outlines the texture has a second channel holding the distance of each
+/// texel to its glyph, see [`outline_distance`](fn.outline_distance.html), so outlines of any
+/// width up to the radius of the cache are drawn with a single sample.
+///
+/// The texture may be shorter than the draw cache glyph_brush lays out, growing as glyphs are
+/// written below it while keeping the glyphs it holds, see
+/// [`reserve_height`](#method.reserve_height).
 pub struct Cache {
     texture: ComPtr<ID3D11Texture2D>,
     view: ComPtr<ID3D11ShaderResourceView>,
+    update_method: CacheUpdateMethod,
+    /// The texture the glyphs are written into with the `Map` update method, holding the
+    /// contents of the whole cache.
+    staging: Option<ComPtr<ID3D11Texture2D>>,
+    width: u32,
+    height: u32,
+    /// The height glyph_brush lays out the cache for, which the texture grows to as glyphs are
+    /// written below it.
+    layout_height: u32,
+    /// What the first channel holds.
+    format: AtlasFormat,
+    /// The distance in texels up to which the second channel holds the distance to the glyph,
+    /// if the texture has one.
+    outline_radius: Option<f32>,
+    /// The CPU copy of the texture, if kept.
+    data: Option<Vec<u8>>,
+    /// The glyphs written since the last flush with their texels.
+    pending: Vec<(Rectangle<u32>, Vec<u8>)>,
+    /// The number of rows copied into a grown texture per flush, or `0` to copy all at once.
+    migration_rows: u32,
+    /// The old texture while it's copied into the grown one.
+    migration: Option<Migration>,
+}
+
+/// The texture the cache grew from, which glyphs are drawn from until all of its rows have been
+/// copied into the grown texture.
+struct Migration {
+    texture: ComPtr<ID3D11Texture2D>,
+    view: ComPtr<ID3D11ShaderResourceView>,
+    height: u32,
+    /// The number of rows copied so far, from the top.
+    copied: u32,
 }
 
 impl Cache {
-    pub fn new(device: &ID3D11Device, width: u32, height: u32) -> HResult<Cache> {
-        let desc = D3D11_TEXTURE2D_DESC {
-            Width: width,
-            Height: height,
-            MipLevels: 1,
-            ArraySize: 1,
-            Format: DXGI_FORMAT_R8_UNORM,
-            SampleDesc: DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Usage: D3D11_USAGE_DEFAULT,
-            BindFlags: D3D11_BIND_SHADER_RESOURCE,
-            CPUAccessFlags: 0,
-            MiscFlags: 0,
+    pub fn new(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        update_method: CacheUpdateMethod,
+        shadow_copy: bool,
+        format: AtlasFormat,
+        outline_radius: Option<f32>,
+    ) -> HResult<Cache> {
+        let (texture, view) = create_texture(device, width, height, outline_radius)?;
+        let staging = match update_method {
+            CacheUpdateMethod::UpdateSubresource => None,
+            CacheUpdateMethod::Map => Some(create_staging(device, width, height, outline_radius)?),
+        };
+        let texel_size = texel_size(outline_radius);
+        Ok(Cache {
+            texture,
+            view,
+            update_method,
+            staging,
+            width,
+            height,
+            layout_height: height,
+            format,
+            outline_radius,
+            data: Some(vec![0; width as usize * height as usize * texel_size])
+                .filter(|_| shadow_copy),
+            pending: Vec::new(),
+            migration_rows: 0,
+            migration: None,
+        })
+    }
+
+    /// Lets glyphs be written down to `layout_height`, the height glyph_brush lays out its draw
+    /// cache for, while the texture keeps its height until glyphs are written below it.
+    pub fn reserve_height(&mut self, layout_height: u32) {
+        self.layout_height = layout_height.max(self.height);
+    }
+
+    /// Writes the coverage of a glyph in the format of the cache, adding its distance to the
+    /// texels of the second channel if there is one.
+    pub fn update(&mut self, rect: Rectangle<u32>, data: &[u8]) {
+        let width = rect.width() as usize;
+        if width == 0 || rect.height() == 0 {
+            return;
+        }
+        let sdf;
+        let first = match self.format {
+            AtlasFormat::Coverage => data,
+            AtlasFormat::Sdf { spread } => {
+                sdf = signed_distance_field(width, data, spread);
+                &sdf
+            }
         };
-        let texture = unsafe {
-            com_ptr_from_fn(|texture| device.CreateTexture2D(&desc, ptr::null(), texture))?
+        let texels = match self.outline_radius {
+            Some(radius) => (first.iter())
+                .zip(outline_distance(width, data, radius))
+                .flat_map(|(&coverage, distance)| [coverage, distance])
+                .collect(),
+            None => first.to_vec(),
         };
+        let texel_size = self.texel_size();
+        let row_len = width * texel_size;
+        if let Some(copy) = &mut self.data {
+            // the texture only grows on the next flush
+            let len = rect.max[1] as usize * self.width as usize * texel_size;
+            if copy.len() < len {
+                copy.resize(len, 0);
+            }
+            for (y, row) in (rect.min[1]..rect.max[1]).zip(texels.chunks_exact(row_len)) {
+                let start = (y as usize * self.width as usize + rect.min[0] as usize) * texel_size;
+                copy[start..start + row_len].copy_from_slice(row);
+            }
+        }
+        self.pending.push((rect, texels));
+    }
+
+    #[inline]
+    pub fn update_method(&self) -> CacheUpdateMethod {
+        self.update_method
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The height glyph_brush lays out the cache for, larger than the texture while it grows
+    /// into a [reserved height](#method.reserve_height).
+    #[inline]
+    pub fn layout_height(&self) -> u32 {
+        self.layout_height
+    }
+
+    #[inline]
+    pub fn format(&self) -> AtlasFormat {
+        self.format
+    }
+
+    /// The distance in texels up to which the second channel of the texture holds the distance
+    /// of the texels to their glyph, or `None` if it only has the first channel.
+    #[inline]
+    pub fn outline_radius(&self) -> Option<f32> {
+        self.outline_radius
+    }
+
+    /// The number of bytes per texel, one per channel.
+    #[inline]
+    pub fn texel_size(&self) -> usize {
+        texel_size(self.outline_radius)
+    }
 
-        let view = unsafe {
-            com_ptr_from_fn(|font_texture_view| {
-                let mut desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
-                    Format: DXGI_FORMAT_R8_UNORM,
-                    ViewDimension: D3D11_SRV_DIMENSION_TEXTURE2D,
-                    u: mem::zeroed(),
-                };
-                *desc.u.Texture2D_mut() = D3D11_TEX2D_SRV {
-                    MostDetailedMip: 0,
-                    MipLevels: 1,
-                };
-                device.CreateShaderResourceView(
-                    com_ref_cast(&texture).as_raw(),
-                    &desc,
-                    font_texture_view,
-                )
-            })?
+    /// The CPU copy of the texture in row-major order, if kept, with the coverage or signed
+    /// distance of each texel followed by its distance to the glyph if the texture has a second
+    /// channel.
+    ///
+    /// Glyphs written below the texture aren't included until the next flush grows it.
+    #[inline]
+    pub fn data(&self) -> Option<&[u8]> {
+        let len = self.width as usize * self.height as usize * self.texel_size();
+        self.data.as_deref().map(|data| &data[..len])
+    }
+
+    /// Stages the whole texture so the next flush re-uploads it from the CPU copy. Returns
+    /// `false` if there is no CPU copy.
+    pub fn invalidate(&mut self) -> bool {
+        let data = match &self.data {
+            Some(data) => data.clone(),
+            None => return false,
         };
+        let rows = data.len() / (self.width as usize * self.texel_size());
+        let rect = Rectangle {
+            min: [0, 0],
+            max: [self.width, rows as u32],
+        };
+        self.pending.clear();
+        self.pending.push((rect, data));
+        true
+    }
 
-        Ok(Cache { texture, view })
+    /// Uploads all glyphs written since the last flush, first growing the texture if they
+    /// were written below it, and copies the next rows of a [migration](#method.set_migration_rows)
+    /// in progress. The glyphs stay staged if uploading them fails.
+    pub fn flush(&mut self, device: &ID3D11Device, ctx: &ID3D11DeviceContext) -> HResult<()> {
+        if !self.pending.is_empty() {
+            self.upload(device, ctx)?;
+        }
+        self.migrate(ctx);
+        Ok(())
     }
 
-    pub fn update(&mut self, ctx: &ID3D11DeviceContext, rect: Rectangle<u32>, data: &[u8]) {
-        unsafe {
-            ctx.UpdateSubresource(
-                com_ref_cast(&self.texture).as_raw(),
-                0,
-                &D3D11_BOX {
-                    left: rect.min[0],
-                    right: rect.max[0],
-                    top: rect.min[1],
-                    bottom: rect.max[1],
-                    front: 0,
-                    back: 1,
-                },
-                data.as_ptr().cast(),
-                rect.width(),
-                rect.width() * rect.height(),
-            );
+    fn upload(&mut self, device: &ID3D11Device, ctx: &ID3D11DeviceContext) -> HResult<()> {
+        let rows = union(&self.pending).max[1];
+        if rows > self.height {
+            self.grow(
+                device,
+                ctx,
+                grown_height(self.height, rows, self.layout_height),
+            )?;
+        }
+        let texture = self.texture.as_raw().cast();
+        let texel_size = self.texel_size();
+        let uploaded = if let Some(staging) = &self.staging {
+            unsafe { flush_mapped(ctx, staging, &self.texture, &self.pending, texel_size)? };
+            vec![union(&self.pending)]
+        } else if let Some(data) = &self.data {
+            // the CPU copy holds the texels between the glyphs as well
+            let dirty = union(&self.pending);
+            let start =
+                (dirty.min[1] as usize * self.width as usize + dirty.min[0] as usize) * texel_size;
+            let pitch = self.width * texel_size as u32;
+            unsafe {
+                ctx.UpdateSubresource(
+                    texture,
+                    0,
+                    &texture_box(dirty),
+                    data[start..].as_ptr().cast(),
+                    pitch,
+                    pitch * self.height,
+                );
+            }
+            vec![dirty]
+        } else {
+            let bands = row_bands(&self.pending, texel_size);
+            for (band, data) in &bands {
+                let pitch = band.width() * texel_size as u32;
+                unsafe {
+                    ctx.UpdateSubresource(
+                        texture,
+                        0,
+                        &texture_box(*band),
+                        data.as_ptr().cast(),
+                        pitch,
+                        pitch * band.height(),
+                    );
+                }
+            }
+            bands.into_iter().map(|(band, _)| band).collect()
+        };
+        if let Some(migration) = &self.migration {
+            // the old texture is drawn from until the migration completes, so it receives the
+            // glyphs written into its rows as well
+            for rect in uploaded {
+                let rows = rect.min[1]..rect.max[1].min(migration.height);
+                if !rows.is_empty() {
+                    let rect = Rectangle {
+                        min: [rect.min[0], rows.start],
+                        max: [rect.max[0], rows.end],
+                    };
+                    unsafe { copy_region(ctx, &migration.texture, &self.texture, rect) };
+                }
+            }
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Copies glyphs from the old texture into the new one over several flushes when the
+    /// texture grows, `rows` rows per flush, drawing from the old texture meanwhile. Copies the
+    /// whole texture at once if `0`.
+    #[inline]
+    pub fn set_migration_rows(&mut self, rows: u32) {
+        self.migration_rows = rows;
+    }
+
+    #[inline]
+    pub fn migration_rows(&self) -> u32 {
+        self.migration_rows
+    }
+
+    /// The height of the old texture while it's copied into the grown one, whose rows are
+    /// drawn from it until the copy completes, or `0` if the cache isn't migrating.
+    #[inline]
+    pub fn migration_height(&self) -> u32 {
+        self.migration
+            .as_ref()
+            .map_or(0, |migration| migration.height)
+    }
+
+    /// The view of the old texture while the cache is migrating, otherwise null.
+    #[inline]
+    pub fn migration_view(&self) -> *mut ID3D11ShaderResourceView {
+        self.migration
+            .as_ref()
+            .map_or(ptr::null_mut(), |migration| migration.view.as_raw())
+    }
+
+    /// Copies the next rows of the old texture into the grown one, dropping the old texture
+    /// once all of them are copied.
+    fn migrate(&mut self, ctx: &ID3D11DeviceContext) {
+        let migration = match &mut self.migration {
+            Some(migration) => migration,
+            None => return,
+        };
+        let end = migration
+            .copied
+            .saturating_add(self.migration_rows)
+            .min(migration.height);
+        let rect = Rectangle {
+            min: [0, migration.copied],
+            max: [self.width, end],
+        };
+        unsafe { copy_region(ctx, &self.texture, &migration.texture, rect) };
+        migration.copied = end;
+        if end == migration.height {
+            self.migration = None;
+        }
+    }
+
+    /// Replaces the texture with one of the given height, copying the old texture into its top
+    /// rows with `CopySubresourceRegion`. glyph_brush keeps its layout while the texture grows
+    /// within the layout height, so the cached glyphs stay where they are.
+    ///
+    /// With [migration rows](#method.set_migration_rows) set, only the last row is copied at
+    /// once and the old texture is kept to draw from until `flush` has copied the others.
+    fn grow(
+        &mut self,
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        height: u32,
+    ) -> HResult<()> {
+        trace_span!("grow_cache", height = height);
+        let (texture, view) = create_texture(device, self.width, height, self.outline_radius)?;
+        let staging = match self.staging {
+            Some(_) => Some(create_staging(
+                device,
+                self.width,
+                height,
+                self.outline_radius,
+            )?),
+            None => None,
+        };
+        // a migration in progress is completed first, as the grown texture is copied from
+        // the current one
+        if let Some(migration) = self.migration.take() {
+            let rest = Rectangle {
+                min: [0, migration.copied],
+                max: [self.width, migration.height],
+            };
+            unsafe { copy_region(ctx, &self.texture, &migration.texture, rest) };
+        }
+        let old = Rectangle {
+            min: [0, 0],
+            max: [self.width, self.height],
+        };
+        if let (Some(target), Some(source)) = (&staging, &self.staging) {
+            // the staging texture is only written on the CPU, so it's copied at once
+            unsafe { copy_region(ctx, target, source, old) };
+        }
+        let texture = mem::replace(&mut self.texture, texture);
+        let view = mem::replace(&mut self.view, view);
+        if self.migration_rows == 0 {
+            unsafe { copy_region(ctx, &self.texture, &texture, old) };
+        } else {
+            // the row above the new rows is sampled from the new texture when filtering
+            // between them
+            let last_row = Rectangle {
+                min: [0, self.height - 1],
+                max: old.max,
+            };
+            unsafe { copy_region(ctx, &self.texture, &texture, last_row) };
+            self.migration = Some(Migration {
+                texture,
+                view,
+                height: self.height,
+                copied: 0,
+            });
         }
+        let len = self.width as usize * height as usize * self.texel_size();
+        if let Some(data) = &mut self.data {
+            data.resize(len.max(data.len()), 0);
+        }
+        self.staging = staging;
+        self.height = height;
+        Ok(())
     }
 
     pub fn view(&self) -> *mut ID3D11ShaderResourceView {
         self.view.as_raw()
     }
+
+    #[inline]
+    pub fn texture(&self) -> &ComPtr<ID3D11Texture2D> {
+        &self.texture
+    }
+
+    #[inline]
+    pub fn shader_resource_view(&self) -> &ComPtr<ID3D11ShaderResourceView> {
+        &self.view
+    }
+}
+
+/// Creates the cache texture with its view.
+fn create_texture(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+    outline_radius: Option<f32>,
+) -> HResult<(ComPtr<ID3D11Texture2D>, ComPtr<ID3D11ShaderResourceView>)> {
+    let desc = texture_desc(width, height, outline_radius);
+    let texture = unsafe {
+        com_ptr_from_fn("CreateTexture2D", |texture| {
+            device.CreateTexture2D(&desc, ptr::null(), texture)
+        })?
+    };
+    let view = unsafe {
+        com_ptr_from_fn("CreateShaderResourceView", |font_texture_view| {
+            let mut view_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+                Format: desc.Format,
+                ViewDimension: D3D11_SRV_DIMENSION_TEXTURE2D,
+                u: mem::zeroed(),
+            };
+            *view_desc.u.Texture2D_mut() = D3D11_TEX2D_SRV {
+                MostDetailedMip: 0,
+                MipLevels: 1,
+            };
+            device.CreateShaderResourceView(
+                com_ref_cast(&texture).as_raw(),
+                &view_desc,
+                font_texture_view,
+            )
+        })?
+    };
+    Ok((texture, view))
+}
+
+/// Creates the zeroed staging texture glyphs are written into with the `Map` update method.
+fn create_staging(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+    outline_radius: Option<f32>,
+) -> HResult<ComPtr<ID3D11Texture2D>> {
+    let desc = D3D11_TEXTURE2D_DESC {
+        Usage: D3D11_USAGE_STAGING,
+        BindFlags: 0,
+        CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+        ..texture_desc(width, height, outline_radius)
+    };
+    let texel_size = texel_size(outline_radius);
+    // staging textures start out uninitialized, while the cache is zeroed
+    let zeroed = vec![0u8; width as usize * height as usize * texel_size];
+    let initial_data = D3D11_SUBRESOURCE_DATA {
+        pSysMem: zeroed.as_ptr().cast(),
+        SysMemPitch: width * texel_size as u32,
+        SysMemSlicePitch: 0,
+    };
+    unsafe {
+        com_ptr_from_fn("CreateTexture2D", |staging| {
+            device.CreateTexture2D(&desc, &initial_data, staging)
+        })
+    }
+}
+
+fn texture_desc(width: u32, height: u32, outline_radius: Option<f32>) -> D3D11_TEXTURE2D_DESC {
+    D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: match outline_radius {
+            Some(_) => DXGI_FORMAT_R8G8_UNORM,
+            None => DXGI_FORMAT_R8_UNORM,
+        },
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_DEFAULT,
+        BindFlags: D3D11_BIND_SHADER_RESOURCE,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+    }
+}
+
+/// Returns the height a texture of `height` rows grows to so `rows` fit, doubling it to grow
+/// less often but staying within the `layout_height` of the draw cache.
+fn grown_height(height: u32, rows: u32, layout_height: u32) -> u32 {
+    height.saturating_mul(2).min(layout_height).max(rows)
+}
+
+/// Writes the rows of the `pending` glyphs with `texel_size` bytes per texel into `staging` and
+/// copies the region covering them into `texture`.
+unsafe fn flush_mapped(
+    ctx: &ID3D11DeviceContext,
+    staging: &ComPtr<ID3D11Texture2D>,
+    texture: &ComPtr<ID3D11Texture2D>,
+    pending: &[(Rectangle<u32>, Vec<u8>)],
+    texel_size: usize,
+) -> HResult<()> {
+    let mapped = {
+        let mut mapped = mem::MaybeUninit::zeroed();
+        hresult(
+            "Map",
+            ctx.Map(
+                com_ref_cast(staging).as_raw(),
+                0,
+                D3D11_MAP_WRITE,
+                0,
+                mapped.as_mut_ptr(),
+            ),
+        )?;
+        mapped.assume_init()
+    };
+    for (rect, data) in pending {
+        let row_len = rect.width() as usize * texel_size;
+        // the mapped rows may be padded, so copy them one by one
+        for (y, row) in (rect.min[1]..rect.max[1]).zip(data.chunks_exact(row_len)) {
+            let offset = y as usize * mapped.RowPitch as usize + rect.min[0] as usize * texel_size;
+            let target = mapped.pData.cast::<u8>().add(offset);
+            ptr::copy_nonoverlapping(row.as_ptr(), target, row_len);
+        }
+    }
+    ctx.Unmap(com_ref_cast(staging).as_raw(), 0);
+    copy_region(ctx, texture, staging, union(pending));
+    Ok(())
+}
+
+/// Copies `rect` of `source` to the same position in `target`.
+unsafe fn copy_region(
+    ctx: &ID3D11DeviceContext,
+    target: &ComPtr<ID3D11Texture2D>,
+    source: &ComPtr<ID3D11Texture2D>,
+    rect: Rectangle<u32>,
+) {
+    ctx.CopySubresourceRegion(
+        com_ref_cast(target).as_raw(),
+        0,
+        rect.min[0],
+        rect.min[1],
+        0,
+        com_ref_cast(source).as_raw(),
+        0,
+        &texture_box(rect),
+    );
+}
+
+/// Returns the rect covering all `pending` glyphs.
+fn union(pending: &[(Rectangle<u32>, Vec<u8>)]) -> Rectangle<u32> {
+    let mut union = pending[0].0;
+    for (rect, _) in &pending[1..] {
+        union = Rectangle {
+            min: [union.min[0].min(rect.min[0]), union.min[1].min(rect.min[1])],
+            max: [union.max[0].max(rect.max[0]), union.max[1].max(rect.max[1])],
+        };
+    }
+    union
+}
+
+/// Merges the `pending` glyphs written next to each other into the same row of the cache into
+/// bands covering them, returning the bands with their texels of `texel_size` bytes.
+///
+/// glyph_brush packs glyphs into rows, appending new glyphs to the right of the glyphs in use,
+/// so the texels between the glyphs of a band don't belong to any other glyph and are cleared.
+fn row_bands(
+    pending: &[(Rectangle<u32>, Vec<u8>)],
+    texel_size: usize,
+) -> Vec<(Rectangle<u32>, Vec<u8>)> {
+    let mut rects: Vec<_> = pending.iter().map(|(rect, _)| *rect).collect();
+    rects.sort_unstable_by_key(|rect| (rect.min[1], rect.min[0]));
+    let mut bands: Vec<Rectangle<u32>> = Vec::new();
+    for rect in rects {
+        match bands.last_mut() {
+            Some(band) if band.min[1] == rect.min[1] && rect.min[0] <= band.max[0] => {
+                band.max = [band.max[0].max(rect.max[0]), band.max[1].max(rect.max[1])];
+            }
+            _ => bands.push(rect),
+        }
+    }
+    bands
+        .into_iter()
+        .map(|band| {
+            let width = band.width() as usize * texel_size;
+            let mut data = vec![0; width * band.height() as usize];
+            // glyphs written later in the frame overwrite the earlier ones in every band
+            for (rect, texels) in pending {
+                let (left, right) = (rect.min[0].max(band.min[0]), rect.max[0].min(band.max[0]));
+                let (top, bottom) = (rect.min[1].max(band.min[1]), rect.max[1].min(band.max[1]));
+                if left >= right {
+                    continue;
+                }
+                let len = (right - left) as usize * texel_size;
+                let rect_width = rect.width() as usize * texel_size;
+                for y in top..bottom {
+                    let source = (y - rect.min[1]) as usize * rect_width
+                        + (left - rect.min[0]) as usize * texel_size;
+                    let target = (y - band.min[1]) as usize * width
+                        + (left - band.min[0]) as usize * texel_size;
+                    data[target..target + len].copy_from_slice(&texels[source..source + len]);
+                }
+            }
+            (band, data)
+        })
+        .collect()
+}
+
+#[inline]
+fn texel_size(outline_radius: Option<f32>) -> usize {
+    1 + outline_radius.is_some() as usize
+}
+
+/// Returns the distance of each texel of a glyph to the glyph, from `255` on the glyph down to
+/// `0` at least `radius` texels away from it.
+///
+/// A partially covered texel counts as lying further away by its uncovered share, so outlines
+/// drawn up to a distance of this stay antialiased and include hairlines too thin to cover a
+/// texel. Distances outside of the glyph's rectangle are left to the shader, as they would
+/// overlap the neighbouring glyphs of the cache.
+fn outline_distance(width: usize, coverage: &[u8], radius: f32) -> Vec<u8> {
+    let radius = radius.max(1.0);
+    let height = coverage.len() / width;
+    let reach = radius.ceil() as isize;
+    (0..coverage.len())
+        .map(|i| {
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            let mut nearest = radius;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx as usize >= width || ny as usize >= height {
+                        continue;
+                    }
+                    let covered = coverage[ny as usize * width + nx as usize];
+                    if covered > 0 {
+                        let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                        nearest = nearest.min(distance + 1.0 - covered as f32 / 255.0);
+                    }
+                }
+            }
+            ((1.0 - nearest / radius) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+fn texture_box(rect: Rectangle<u32>) -> D3D11_BOX {
+    D3D11_BOX {
+        left: rect.min[0],
+        right: rect.max[0],
+        top: rect.min[1],
+        bottom: rect.max[1],
+        front: 0,
+        back: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: u32, top: u32, right: u32, bottom: u32) -> Rectangle<u32> {
+        Rectangle {
+            min: [left, top],
+            max: [right, bottom],
+        }
+    }
+
+    fn glyph(rect: Rectangle<u32>, coverage: u8) -> (Rectangle<u32>, Vec<u8>) {
+        (
+            rect,
+            vec![coverage; (rect.width() * rect.height()) as usize],
+        )
+    }
+
+    #[test]
+    fn bands_per_row() {
+        let pending = [
+            glyph(rect(2, 0, 4, 2), 1),
+            glyph(rect(0, 4, 2, 5), 2),
+            glyph(rect(4, 0, 5, 1), 3),
+        ];
+        let bands = row_bands(&pending, 1);
+        assert_eq!(
+            bands,
+            [
+                // the texel below the shorter glyph is cleared
+                (rect(2, 0, 5, 2), vec![1, 1, 3, 1, 1, 0]),
+                (rect(0, 4, 2, 5), vec![2, 2]),
+            ]
+        );
+        assert_eq!(union(&pending), rect(0, 0, 5, 5));
+    }
+
+    #[test]
+    fn bands_keep_separate_glyphs_of_a_row_apart() {
+        // glyphs in use may lie between glyphs written to the same row, e.g. after a resize
+        let pending = [glyph(rect(0, 0, 1, 1), 1), glyph(rect(3, 0, 4, 1), 2)];
+        assert_eq!(row_bands(&pending, 1), pending);
+    }
+
+    #[test]
+    fn bands_of_two_channel_texels() {
+        let pending = [
+            (rect(0, 0, 1, 2), vec![1, 2, 3, 4]),
+            (rect(1, 0, 2, 1), vec![5, 6]),
+        ];
+        assert_eq!(
+            row_bands(&pending, 2),
+            [(rect(0, 0, 2, 2), vec![1, 2, 5, 6, 3, 4, 0, 0])]
+        );
+    }
+
+    #[test]
+    fn outline_distance_around_a_texel() {
+        let mut coverage = [0; 5 * 5];
+        coverage[2 * 5 + 2] = 255;
+        coverage[2 * 5 + 3] = 51;
+        let distance = outline_distance(5, &coverage, 2.0);
+        assert_eq!(distance[2 * 5..3 * 5], [0, 128, 255, 153, 26]);
+        // diagonally next to the covered texel
+        assert_eq!(distance[5 + 1], 75);
+        assert_eq!(distance[0], 0);
+    }
+
+    #[test]
+    fn growth_within_the_layout() {
+        assert_eq!(grown_height(64, 65, 256), 128);
+        // rows far below the texture are reached at once
+        assert_eq!(grown_height(64, 200, 256), 200);
+        assert_eq!(grown_height(192, 193, 256), 256);
+    }
+
+    #[test]
+    fn bands_take_the_last_write() {
+        let pending = [glyph(rect(0, 0, 2, 2), 1), glyph(rect(1, 1, 3, 2), 2)];
+        assert_eq!(
+            row_bands(&pending, 1),
+            [
+                (rect(0, 0, 2, 2), vec![1, 1, 1, 2]),
+                (rect(1, 1, 3, 2), vec![2, 2]),
+            ]
+        );
+    }
 }
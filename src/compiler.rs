@@ -0,0 +1,176 @@
+//! Runtime compilation of HLSL shaders, e.g. for iterating on custom text effects without
+//! rebuilding.
+//!
+//! The compiled bytecode can be passed to
+//! [`GlyphBrushBuilder::vertex_shader`](../struct.GlyphBrushBuilder.html#method.vertex_shader)
+//! and [`GlyphBrushBuilder::pixel_shader`](../struct.GlyphBrushBuilder.html#method.pixel_shader)
+//! or swapped into an existing brush with
+//! [`GlyphBrush::set_vertex_shader`](../struct.GlyphBrush.html#method.set_vertex_shader) and
+//! [`GlyphBrush::set_pixel_shader`](../struct.GlyphBrush.html#method.set_pixel_shader).
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{fmt, fs, io, ptr, slice};
+
+use winapi::um::d3dcommon::ID3DBlob;
+use winapi::um::d3dcompiler::D3DCompile;
+use wio::com::ComPtr;
+
+/// The HLSL source of a shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderSource {
+    /// Reads the source from a file, which allows watching it with a
+    /// [`ShaderWatcher`](struct.ShaderWatcher.html).
+    File(PathBuf),
+    Hlsl(String),
+}
+
+/// An error that occurred while compiling a shader.
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The source file couldn't be read.
+    Io(io::Error),
+    /// The compiler rejected the source, containing the compiler output.
+    Compile(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io(err) => write!(f, "failed to read shader source: {}", err),
+            ShaderError::Compile(msg) => write!(f, "failed to compile shader: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::Io(err) => Some(err),
+            ShaderError::Compile(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ShaderError {
+    #[inline]
+    fn from(err: io::Error) -> Self {
+        ShaderError::Io(err)
+    }
+}
+
+/// Compiles `source` with the given entry point and target profile, e.g. `"main"` and
+/// `"ps_4_0"`, returning the shader bytecode.
+pub fn compile(
+    source: &ShaderSource,
+    entry_point: &str,
+    target: &str,
+) -> Result<Vec<u8>, ShaderError> {
+    match source {
+        ShaderSource::File(path) => {
+            let hlsl = fs::read_to_string(path)?;
+            compile_hlsl(&hlsl, Some(path), entry_point, target)
+        }
+        ShaderSource::Hlsl(hlsl) => compile_hlsl(hlsl, None, entry_point, target),
+    }
+}
+
+fn compile_hlsl(
+    hlsl: &str,
+    path: Option<&Path>,
+    entry_point: &str,
+    target: &str,
+) -> Result<Vec<u8>, ShaderError> {
+    let c_string = |s: &str| {
+        CString::new(s).map_err(|_| ShaderError::Compile(format!("invalid name {:?}", s)))
+    };
+    let source_name = path
+        .map(|path| c_string(&path.to_string_lossy()))
+        .transpose()?;
+    let entry_point = c_string(entry_point)?;
+    let target = c_string(target)?;
+
+    unsafe {
+        let mut code = ptr::null_mut();
+        let mut err = ptr::null_mut();
+        let hr = D3DCompile(
+            hlsl.as_ptr().cast(),
+            hlsl.len(),
+            source_name
+                .as_ref()
+                .map_or(ptr::null(), |name| name.as_ptr()),
+            ptr::null(),
+            ptr::null_mut(),
+            entry_point.as_ptr(),
+            target.as_ptr(),
+            0,
+            0,
+            &mut code,
+            &mut err,
+        );
+        let code = (!code.is_null()).then(|| ComPtr::from_raw(code));
+        let err = (!err.is_null()).then(|| ComPtr::from_raw(err));
+        match code {
+            Some(code) if hr >= 0 => Ok(blob_bytes(&code).to_vec()),
+            _ => Err(ShaderError::Compile(err.map_or_else(
+                || format!("HRESULT {:#010X}", hr),
+                |err| String::from_utf8_lossy(blob_bytes(&err)).into_owned(),
+            ))),
+        }
+    }
+}
+
+unsafe fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    slice::from_raw_parts(blob.GetBufferPointer().cast::<u8>(), blob.GetBufferSize())
+}
+
+/// Watches a shader source file and recompiles it whenever it changes, allowing shaders to be
+/// hot-reloaded during development.
+///
+/// ```no_run
+/// # use d3d11_glyph::compiler::ShaderWatcher;
+/// # fn frame(brush: &mut d3d11_glyph::GlyphBrush) {
+/// # let mut watcher = ShaderWatcher::new("text.hlsl", "main", "ps_4_0");
+/// match watcher.poll() {
+///     Some(Ok(bytecode)) => brush.set_pixel_shader(&bytecode).unwrap(),
+///     Some(Err(err)) => eprintln!("{}", err),
+///     None => {}
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    path: PathBuf,
+    entry_point: String,
+    target: String,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderWatcher {
+    pub fn new(path: impl Into<PathBuf>, entry_point: &str, target: &str) -> Self {
+        ShaderWatcher {
+            path: path.into(),
+            entry_point: entry_point.to_owned(),
+            target: target.to_owned(),
+            modified: None,
+        }
+    }
+
+    /// Checks the modification time of the watched file, returning the recompiled bytecode if
+    /// it changed since the last call. The first call always compiles the file.
+    pub fn poll(&mut self) -> Option<Result<Vec<u8>, ShaderError>> {
+        let modified = match fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(err) => return Some(Err(err.into())),
+        };
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(compile(
+            &ShaderSource::File(self.path.clone()),
+            &self.entry_point,
+            &self.target,
+        ))
+    }
+}
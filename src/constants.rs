@@ -0,0 +1,98 @@
+use std::{mem, ptr};
+
+use winapi::um::d3d11::{
+    ID3D11Buffer, ID3D11Device, ID3D11DeviceContext, D3D11_BIND_CONSTANT_BUFFER, D3D11_BUFFER_DESC,
+    D3D11_CPU_ACCESS_WRITE, D3D11_SUBRESOURCE_DATA, D3D11_USAGE_DYNAMIC,
+};
+use wio::com::ComPtr;
+
+use crate::pipeline::write_constants;
+use crate::util::{com_ptr_from_fn, HResult};
+
+/// The number of buffers of a `ConstantBufferPool`.
+const POOL_SIZE: usize = 4;
+
+/// A few constant buffers of the same type, each holding one of the values written last.
+///
+/// Drawing with a value held by one of the buffers binds that buffer instead of writing it
+/// again, and new values are written into the buffers round-robin. Alternating between a few
+/// values, e.g. the transforms of several independently transformed batches, then doesn't
+/// rename a single buffer with `D3D11_MAP_WRITE_DISCARD` for every draw.
+pub struct ConstantBufferPool<T> {
+    buffers: Vec<(ComPtr<ID3D11Buffer>, T)>,
+    /// The buffer holding the value selected last.
+    current: usize,
+    /// The buffer the next value missing from the pool is written into.
+    next: usize,
+}
+
+impl<T: Copy + PartialEq> ConstantBufferPool<T> {
+    /// Creates the buffers, all holding `initial`.
+    pub unsafe fn new(device: &ID3D11Device, initial: T) -> HResult<Self> {
+        let desc = D3D11_BUFFER_DESC {
+            // constant buffers are sized in multiples of 16 bytes
+            ByteWidth: ((mem::size_of::<T>() + 15) & !15) as u32,
+            Usage: D3D11_USAGE_DYNAMIC,
+            BindFlags: D3D11_BIND_CONSTANT_BUFFER,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+        let mut data = vec![0u8; desc.ByteWidth as usize];
+        ptr::copy_nonoverlapping(
+            (&initial as *const T).cast::<u8>(),
+            data.as_mut_ptr(),
+            mem::size_of::<T>(),
+        );
+        let subresource = D3D11_SUBRESOURCE_DATA {
+            pSysMem: data.as_ptr().cast(),
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+        let buffers = (0..POOL_SIZE)
+            .map(|_| {
+                let buffer = com_ptr_from_fn("CreateBuffer", |buffer| {
+                    device.CreateBuffer(&desc, &subresource, buffer)
+                })?;
+                Ok((buffer, initial))
+            })
+            .collect::<HResult<_>>()?;
+        Ok(ConstantBufferPool {
+            buffers,
+            current: 0,
+            next: 0,
+        })
+    }
+
+    /// The buffer holding the value selected last.
+    #[inline]
+    pub fn current(&self) -> &ComPtr<ID3D11Buffer> {
+        &self.buffers[self.current].0
+    }
+
+    /// Returns a buffer holding `value`, writing it into the least recently written buffer if
+    /// no buffer holds it yet.
+    pub unsafe fn select(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        value: T,
+    ) -> HResult<&ComPtr<ID3D11Buffer>> {
+        // the current buffer is checked first, as it is the most likely to match
+        let held = if self.buffers[self.current].1 == value {
+            Some(self.current)
+        } else {
+            self.buffers.iter().position(|(_, held)| *held == value)
+        };
+        self.current = match held {
+            Some(index) => index,
+            None => {
+                let index = self.next;
+                write_constants(ctx, &self.buffers[index].0, value)?;
+                self.buffers[index].1 = value;
+                self.next = (index + 1) % self.buffers.len();
+                index
+            }
+        };
+        Ok(self.current())
+    }
+}
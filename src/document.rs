@@ -0,0 +1,128 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use glyph_brush::ab_glyph::PxScale;
+use glyph_brush::{Extra, FontId};
+
+use crate::Region;
+
+/// A large text, e.g. a log, drawn line by line with
+/// [`GlyphBrush::queue_document`](struct.GlyphBrush.html#method.queue_document).
+///
+/// The start of every line is indexed, so the visible lines can be found without scanning the
+/// text before them.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    /// The byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document {
+            text: String::new(),
+            line_starts: vec![0],
+        }
+    }
+}
+
+impl From<String> for Document {
+    fn from(text: String) -> Self {
+        let mut document = Document {
+            text,
+            line_starts: vec![0],
+        };
+        document.index_lines(0);
+        document
+    }
+}
+
+impl From<&str> for Document {
+    #[inline]
+    fn from(text: &str) -> Self {
+        Document::from(text.to_owned())
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document::default()
+    }
+
+    /// Appends text to the end of the document, only indexing the appended lines.
+    pub fn push_str(&mut self, text: &str) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.index_lines(start);
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.line_starts.truncate(1);
+    }
+
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the number of lines, which is one more than the number of line breaks.
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the line at `index` without its line break, or `None` if it is out of range.
+    pub fn line(&self, index: usize) -> Option<&str> {
+        let start = *self.line_starts.get(index)?;
+        let end = match self.line_starts.get(index + 1) {
+            Some(next) => next - 1,
+            None => self.text.len(),
+        };
+        let line = &self.text[start..end];
+        Some(line.strip_suffix('\r').unwrap_or(line))
+    }
+
+    /// Returns the lines overlapping a viewport `height` pixels high, scrolled `scroll` pixels
+    /// down from the first line.
+    pub(crate) fn visible_lines(&self, height: f32, scroll: f32, line_height: f32) -> Range<usize> {
+        if line_height <= 0.0 {
+            return 0..0;
+        }
+        let first = (scroll / line_height).floor().max(0.0) as usize;
+        let last = ((scroll + height) / line_height).ceil().max(0.0) as usize;
+        first.min(self.line_count())..last.min(self.line_count())
+    }
+
+    /// Indexes the line breaks from byte offset `start` on.
+    fn index_lines(&mut self, start: usize) {
+        let breaks = self.text[start..].match_indices('\n');
+        self.line_starts
+            .extend(breaks.map(|(offset, _)| start + offset + 1));
+    }
+}
+
+/// How a [`Document`](struct.Document.html) is drawn, see
+/// [`GlyphBrush::queue_document`](struct.GlyphBrush.html#method.queue_document).
+#[derive(Debug, Clone)]
+pub struct DocumentView<X = Extra> {
+    /// The viewport the lines are drawn and clipped to.
+    pub region: Region,
+    /// The number of pixels the document is scrolled down by.
+    pub scroll: f32,
+    pub font_id: FontId,
+    pub scale: PxScale,
+    pub extra: X,
+}
+
+/// Returns the key under which the layout of a line is cached.
+pub(crate) fn line_key(line: &str, font_id: FontId, scale: PxScale) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.hash(&mut hasher);
+    font_id.hash(&mut hasher);
+    scale.x.to_bits().hash(&mut hasher);
+    scale.y.to_bits().hash(&mut hasher);
+    hasher.finish()
+}
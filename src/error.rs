@@ -0,0 +1,117 @@
+use std::num::NonZeroI32;
+use std::{fmt, ptr};
+
+use winapi::shared::winerror::{
+    DXGI_ERROR_DEVICE_HUNG, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
+    DXGI_ERROR_DRIVER_INTERNAL_ERROR, DXGI_ERROR_INVALID_CALL, DXGI_ERROR_UNSUPPORTED,
+    DXGI_ERROR_WAS_STILL_DRAWING, E_ACCESSDENIED, E_FAIL, E_INVALIDARG, E_NOINTERFACE, E_NOTIMPL,
+    E_OUTOFMEMORY, E_POINTER,
+};
+use winapi::um::winbase::{
+    FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+
+/// Errors returned by the brush.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A D3D11 call failed with the contained `HRESULT`.
+    HResult {
+        /// The name of the failed call, e.g. `CreateTexture2D`.
+        call: &'static str,
+        code: NonZeroI32,
+    },
+    /// The requested glyph cache size exceeds the maximum texture dimension supported by the
+    /// device.
+    CacheTooLarge {
+        requested: (u32, u32),
+        max_dimension: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::HResult { call, code } => {
+                let code = code.get();
+                write!(f, "{} failed: ", call)?;
+                match hresult_name(code) {
+                    Some(name) => write!(f, "{} ({:#010X})", name, code)?,
+                    None => write!(f, "{:#010X}", code)?,
+                }
+                match system_message(code) {
+                    Some(message) => write!(f, ": {}", message),
+                    None => Ok(()),
+                }
+            }
+            Error::CacheTooLarge {
+                requested: (width, height),
+                max_dimension,
+            } => write!(
+                f,
+                "glyph cache size {}x{} exceeds the maximum texture dimension {} of the device",
+                width, height, max_dimension
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// Returns `true` if a D3D11 call failed because memory couldn't be allocated.
+    #[inline]
+    pub fn is_out_of_memory(&self) -> bool {
+        matches!(self, Error::HResult { code, .. } if code.get() == E_OUTOFMEMORY)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<NonZeroI32> for Error {
+    #[inline]
+    fn from(code: NonZeroI32) -> Self {
+        Error::HResult {
+            call: "D3D11 call",
+            code,
+        }
+    }
+}
+
+/// Returns the name of the common `HRESULT`s returned by D3D11 and DXGI.
+fn hresult_name(code: i32) -> Option<&'static str> {
+    let name = match code {
+        E_FAIL => "E_FAIL",
+        E_INVALIDARG => "E_INVALIDARG",
+        E_OUTOFMEMORY => "E_OUTOFMEMORY",
+        E_NOTIMPL => "E_NOTIMPL",
+        E_NOINTERFACE => "E_NOINTERFACE",
+        E_POINTER => "E_POINTER",
+        E_ACCESSDENIED => "E_ACCESSDENIED",
+        DXGI_ERROR_INVALID_CALL => "DXGI_ERROR_INVALID_CALL",
+        DXGI_ERROR_UNSUPPORTED => "DXGI_ERROR_UNSUPPORTED",
+        DXGI_ERROR_WAS_STILL_DRAWING => "DXGI_ERROR_WAS_STILL_DRAWING",
+        DXGI_ERROR_DEVICE_REMOVED => "DXGI_ERROR_DEVICE_REMOVED",
+        DXGI_ERROR_DEVICE_HUNG => "DXGI_ERROR_DEVICE_HUNG",
+        DXGI_ERROR_DEVICE_RESET => "DXGI_ERROR_DEVICE_RESET",
+        DXGI_ERROR_DRIVER_INTERNAL_ERROR => "DXGI_ERROR_DRIVER_INTERNAL_ERROR",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Looks up the message text the system provides for an `HRESULT`.
+fn system_message(code: i32) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            ptr::null(),
+            code as u32,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            ptr::null_mut(),
+        )
+    } as usize;
+    let message = String::from_utf16_lossy(&buffer[..len]);
+    let message = message.trim_end();
+    (!message.is_empty()).then(|| message.to_owned())
+}
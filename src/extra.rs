@@ -0,0 +1,239 @@
+use std::hash::Hash;
+
+use glyph_brush::Extra;
+use winapi::um::d3d11::D3D11_FILTER;
+
+use crate::LineHeight;
+
+/// Extra data attached to every text of a section, mapped into the glyph quads drawn for it.
+///
+/// Implemented by [`Extra`](struct.Extra.html), the default color and z value. Custom types
+/// can carry additional data, e.g. for use with glyph positioners or
+/// [`PassthroughBrush`](struct.PassthroughBrush.html) backends, as long as they provide the
+/// color and depth of their glyphs.
+///
+/// Optional styling like [`letter_spacing`](#method.letter_spacing) is applied by overriding
+/// the provided methods in a custom type, see
+/// [`GlyphBrushBuilder::extra_type`](struct.GlyphBrushBuilder.html#method.extra_type).
+pub trait GlyphExtra: Clone + Hash + PartialEq {
+    /// The RGBA color of the glyphs.
+    fn color(&self) -> [f32; 4];
+
+    /// The z value of the glyphs.
+    fn z(&self) -> f32;
+
+    /// Additional advance in pixels between the glyphs of the text, like CSS `letter-spacing`.
+    /// Negative values move the glyphs closer together. Defaults to `0.0`.
+    ///
+    /// The spacing counts towards the width lines are wrapped at, except after the last glyph of
+    /// a line, which doesn't affect its alignment.
+    #[inline]
+    fn letter_spacing(&self) -> f32 {
+        0.0
+    }
+
+    /// Overrides the height of the lines containing the text instead of using the font
+    /// metrics. Defaults to `None`.
+    ///
+    /// A line is as high as the largest line height of the texts on it, so returning the same
+    /// value for all texts of a section overrides the line height of the whole section.
+    #[inline]
+    fn line_height(&self) -> Option<LineHeight> {
+        None
+    }
+
+    /// A drop shadow drawn behind the glyphs. Defaults to `None`.
+    ///
+    /// The built-in shaders draw the shadow within the quad of its glyph, so it follows the
+    /// glyph wherever it is moved to, e.g. by a custom glyph positioner.
+    #[inline]
+    fn shadow(&self) -> Option<Shadow> {
+        None
+    }
+
+    /// An outline drawn around the glyphs. Defaults to `None`.
+    ///
+    /// Outlines of all glyphs are drawn in a separate pass before the glyphs themselves, so
+    /// the outline of a glyph never covers its neighbours.
+    #[inline]
+    fn outline(&self) -> Option<Outline> {
+        None
+    }
+
+    /// The RGBA color of a soft glow drawn behind the glyphs. Defaults to `None`.
+    ///
+    /// The glow is blurred in a post pass over the whole render target with the radius set by
+    /// [`GlyphBrushBuilder::glow_radius`](struct.GlyphBrushBuilder.html#method.glow_radius).
+    /// It is ignored on devices below feature level 10.0.
+    #[inline]
+    fn glow(&self) -> Option<[f32; 4]> {
+        None
+    }
+
+    /// How the glyphs are blended with the render target. Defaults to
+    /// [`BlendMode::Alpha`](enum.BlendMode.html#variant.Alpha).
+    ///
+    /// Glyphs are drawn in the order they were queued in, with one draw call per run of
+    /// consecutive glyphs sharing a blend mode.
+    #[inline]
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Alpha
+    }
+
+    /// A texture the glyphs are filled with instead of their flat color, e.g. a metal or
+    /// gradient texture. Defaults to `None`.
+    ///
+    /// The texel of each pixel is multiplied with the [color](#tymethod.color) of the glyphs,
+    /// so a white color draws the texture as is. Like blend modes, consecutive glyphs sharing a
+    /// fill texture are drawn in a single draw call.
+    #[inline]
+    fn fill(&self) -> Option<TextureFill> {
+        None
+    }
+
+    /// Overrides the filter sampling the glyph cache for the glyphs, e.g. point sampling for
+    /// pixel fonts. Defaults to `None`, which uses the filter set by
+    /// [`GlyphBrushBuilder::texture_filter_method`](struct.GlyphBrushBuilder.html#method.texture_filter_method).
+    ///
+    /// Like blend modes, consecutive glyphs sharing a filter are drawn in a single draw call.
+    #[inline]
+    fn texture_filter(&self) -> Option<D3D11_FILTER> {
+        None
+    }
+
+    /// Returns a copy of the extra data with its color multiplied by `alpha`, used to fade in
+    /// glyphs by [`TextAnimation::FadeIn`](enum.TextAnimation.html#variant.FadeIn). Defaults
+    /// to an unchanged copy, so the glyphs only pop in.
+    #[inline]
+    fn faded(&self, alpha: f32) -> Self {
+        let _ = alpha;
+        self.clone()
+    }
+
+    /// The angle in radians the glyphs are rotated by clockwise around the centers of their
+    /// quads. Defaults to `0.0`.
+    ///
+    /// Rotated glyphs aren't clipped to their bounds by
+    /// [`BoundsClipping::Vertex`](enum.BoundsClipping.html#variant.Vertex). Glyphs of a section
+    /// can be rotated individually by queueing them
+    /// [pre-positioned](struct.GlyphBrush.html#method.queue_pre_positioned) with extra data
+    /// per glyph, e.g. for dice rolls or upright tate-chu-yoko in vertical text.
+    #[inline]
+    fn rotation(&self) -> f32 {
+        0.0
+    }
+
+    /// Returns a copy of the extra data with its [rotation](#method.rotation) set to `angle`,
+    /// used to rotate glyphs onto the arc of
+    /// [`GlyphBrush::queue_on_arc`](struct.GlyphBrush.html#method.queue_on_arc). Defaults to
+    /// an unchanged copy, which keeps the glyphs upright.
+    #[inline]
+    fn rotated(&self, angle: f32) -> Self {
+        let _ = angle;
+        self.clone()
+    }
+
+    /// Whether [`rotated`](#method.rotated) sets the rotation of the copy. If it does,
+    /// [`GlyphBrush::queue_on_arc`](struct.GlyphBrush.html#method.queue_on_arc) gives every
+    /// glyph extra data of its own, rotated onto the arc. Defaults to `false`, types overriding
+    /// `rotated` should return `true`.
+    #[inline]
+    fn supports_rotation() -> bool {
+        false
+    }
+}
+
+/// How the glyphs of a text are blended with the render target, see
+/// [`GlyphExtra::blend_mode`](trait.GlyphExtra.html#method.blend_mode).
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The glyphs are drawn over the render target.
+    Alpha,
+    /// The color of the glyphs is added to the render target, e.g. for glowing text.
+    Additive,
+    /// The render target is multiplied with the color of the glyphs, darkening it.
+    ///
+    /// Requires the built-in pixel shader or one handling the `blend_mode` constant.
+    Multiply,
+}
+
+impl Default for BlendMode {
+    #[inline]
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+/// An outline around the glyphs of a text, see
+/// [`GlyphExtra::outline`](trait.GlyphExtra.html#method.outline).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    /// The width of the outline in pixels.
+    pub width: f32,
+    /// The RGBA color of the outline.
+    pub color: [f32; 4],
+}
+
+/// A texture registered with
+/// [`GlyphBrush::add_fill_texture`](struct.GlyphBrush.html#method.add_fill_texture).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FillTexture(pub(crate) usize);
+
+/// The texture filling the glyphs of a text, see
+/// [`GlyphExtra::fill`](trait.GlyphExtra.html#method.fill).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureFill {
+    pub texture: FillTexture,
+    /// The texture coordinates per pixel, e.g. `[1.0 / 256.0; 2]` to repeat a 256x256 texture
+    /// with a wrapping sampler, or across the quad of each glyph if `per_glyph` is set.
+    pub uv_scale: [f32; 2],
+    /// The texture coordinates at the pixel position `(0, 0)` of the section, or at the top
+    /// left corner of each glyph if `per_glyph` is set.
+    pub uv_offset: [f32; 2],
+    /// Maps the texture onto the quad of each glyph instead of the pixels of the section, e.g.
+    /// to draw [inline objects](struct.ImageFont.html#method.add_inline_object) from a user
+    /// texture.
+    pub per_glyph: bool,
+}
+
+impl TextureFill {
+    /// Stretches the whole texture over the quad of each glyph.
+    #[inline]
+    pub fn per_glyph(texture: FillTexture) -> Self {
+        TextureFill {
+            texture,
+            uv_scale: [1.0; 2],
+            uv_offset: [0.0; 2],
+            per_glyph: true,
+        }
+    }
+}
+
+/// A drop shadow of a text, see [`GlyphExtra::shadow`](trait.GlyphExtra.html#method.shadow).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    /// The offset of the shadow to the glyphs in pixels.
+    pub offset: [f32; 2],
+    /// The RGBA color of the shadow.
+    pub color: [f32; 4],
+}
+
+impl GlyphExtra for Extra {
+    #[inline]
+    fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    #[inline]
+    fn z(&self) -> f32 {
+        self.z
+    }
+
+    #[inline]
+    fn faded(&self, alpha: f32) -> Self {
+        let mut color = self.color;
+        color[3] *= alpha;
+        Extra { color, ..*self }
+    }
+}
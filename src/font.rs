@@ -0,0 +1,166 @@
+//! Loading fonts from files, including single faces of font collections, and replacing the
+//! fonts of a brush.
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::{fs, io, mem, ptr, slice};
+
+use glyph_brush::ab_glyph::{Font, FontRef, FontVec, InvalidFont, PxScale, ScaleFont};
+use glyph_brush::FontId;
+use winapi::um::fileapi::{CreateFileW, GetFileSizeEx, OPEN_EXISTING};
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::memoryapi::{CreateFileMappingW, MapViewOfFile, FILE_MAP_READ};
+use winapi::um::winnt::{
+    FILE_ATTRIBUTE_NORMAL, FILE_SHARE_READ, GENERIC_READ, LARGE_INTEGER, PAGE_READONLY,
+};
+
+/// Reads the face at `index` of a font file into memory. `index` is `0` for fonts that aren't
+/// part of a `.ttc` collection.
+pub fn load_font(path: impl AsRef<Path>, index: u32) -> io::Result<FontVec> {
+    FontVec::try_from_vec_and_index(fs::read(path)?, index).map_err(invalid_font)
+}
+
+/// Maps a font file into memory and parses the face at `index`, without copying the file. `index`
+/// is `0` for fonts that aren't part of a `.ttc` collection.
+///
+/// Pages of the file are only loaded when accessed, which keeps large CJK fonts and collections
+/// from occupying memory for glyphs that are never drawn. The file stays mapped, and can't be
+/// modified, until the process exits.
+pub fn map_font(path: impl AsRef<Path>, index: u32) -> io::Result<FontRef<'static>> {
+    let data = unsafe { map_file(path.as_ref())? };
+    FontRef::try_from_slice_and_index(data, index).map_err(invalid_font)
+}
+
+/// Rebuilds `glyph_brush` with its fonts modified by `modify`, discarding all cached layouts
+/// and glyphs as well as the queue.
+pub(crate) fn rebuild_fonts<F, H, X, V, R>(
+    glyph_brush: &mut glyph_brush::GlyphBrush<V, X, F, H>,
+    modify: impl FnOnce(&mut Vec<F>) -> R,
+) -> R
+where
+    F: Font + Clone,
+    H: BuildHasher + Clone,
+    X: Clone + Hash,
+    V: Clone + 'static,
+{
+    let mut result = None;
+    *glyph_brush = glyph_brush
+        .to_builder()
+        .replace_fonts(|mut fonts| {
+            result = Some(modify(&mut fonts));
+            fonts
+        })
+        .build();
+    result.unwrap()
+}
+
+/// The vertical metrics of a font at a scale, in pixels, see
+/// [`GlyphBrush::font_metrics`](struct.GlyphBrush.html#method.font_metrics).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    /// The distance from the baseline to the top of the highest glyphs.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest glyphs, usually negative.
+    pub descent: f32,
+    /// The gap between the descent of a line and the ascent of the next one.
+    pub line_gap: f32,
+    /// The height of capital letters above the baseline, measured from the `H` glyph. Falls
+    /// back to the ascent if the font has no `H`.
+    pub cap_height: f32,
+}
+
+impl FontMetrics {
+    pub(crate) fn new<F: Font>(font: &F, scale: PxScale) -> FontMetrics {
+        let font = font.as_scaled(scale);
+        let ascent = font.ascent();
+        let cap_height = font
+            .font()
+            .outline(font.glyph_id('H'))
+            .map_or(ascent, |outline| {
+                outline.bounds.max.y * font.scale_factor().vertical
+            });
+        FontMetrics {
+            ascent,
+            descent: font.descent(),
+            line_gap: font.line_gap(),
+            cap_height,
+        }
+    }
+
+    /// Returns the distance between the baselines of two lines.
+    #[inline]
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+/// The names fonts were registered under.
+#[derive(Debug, Default)]
+pub(crate) struct FontNames(HashMap<String, FontId>);
+
+impl FontNames {
+    #[inline]
+    pub fn insert(&mut self, name: String, font_id: FontId) {
+        self.0.insert(name, font_id);
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<FontId> {
+        self.0.get(name).copied()
+    }
+
+    /// Updates the names after the font `font_id` was removed.
+    pub fn remove_font(&mut self, font_id: FontId) {
+        self.0.retain(|_, id| *id != font_id);
+        for id in self.0.values_mut() {
+            if id.0 > font_id.0 {
+                id.0 -= 1;
+            }
+        }
+    }
+}
+
+fn invalid_font(error: InvalidFont) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Maps the file read-only for the rest of the lifetime of the process.
+unsafe fn map_file(path: &Path) -> io::Result<&'static [u8]> {
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+    let file = CreateFileW(
+        path.as_ptr(),
+        GENERIC_READ,
+        FILE_SHARE_READ,
+        ptr::null_mut(),
+        OPEN_EXISTING,
+        FILE_ATTRIBUTE_NORMAL,
+        ptr::null_mut(),
+    );
+    if file == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    // the size of the opened file, which the path may no longer refer to
+    let mut size = mem::zeroed::<LARGE_INTEGER>();
+    if GetFileSizeEx(file, &mut size) == 0 {
+        let error = io::Error::last_os_error();
+        CloseHandle(file);
+        return Err(error);
+    }
+    let len = *size.QuadPart() as usize;
+    let mapping = CreateFileMappingW(file, ptr::null_mut(), PAGE_READONLY, 0, 0, ptr::null());
+    let error = io::Error::last_os_error();
+    // the mapping and its views keep the file and the mapping open respectively
+    CloseHandle(file);
+    if mapping.is_null() {
+        return Err(error);
+    }
+    let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+    let error = io::Error::last_os_error();
+    CloseHandle(mapping);
+    if view.is_null() {
+        return Err(error);
+    }
+    Ok(slice::from_raw_parts(view.cast::<u8>(), len))
+}
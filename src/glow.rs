@@ -0,0 +1,249 @@
+//! The glow post pass, blurring the glow mask of the glyphs and compositing it below them.
+use std::{mem, ptr};
+
+use winapi::shared::minwindef::{FALSE, TRUE};
+use winapi::um::d3d11::{
+    ID3D11BlendState, ID3D11Buffer, ID3D11Device, ID3D11DeviceContext, ID3D11PixelShader,
+    ID3D11RenderTargetView, ID3D11Resource, ID3D11ShaderResourceView, ID3D11Texture2D,
+    ID3D11VertexShader, D3D11_BIND_CONSTANT_BUFFER, D3D11_BLEND_DESC, D3D11_BLEND_INV_SRC_ALPHA,
+    D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BUFFER_DESC, D3D11_COLOR_WRITE_ENABLE_ALL,
+    D3D11_CPU_ACCESS_WRITE, D3D11_RENDER_TARGET_BLEND_DESC, D3D11_TEXTURE2D_DESC,
+    D3D11_USAGE_DYNAMIC,
+};
+use winapi::um::d3dcommon::{
+    D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST, D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_10_0,
+    D3D_FEATURE_LEVEL_11_0,
+};
+use wio::com::ComPtr;
+
+use crate::offscreen;
+use crate::pipeline::write_constants;
+use crate::runtime_compiler;
+use crate::util::{com_ptr_from_fn, com_ref_cast, hresult, HResult};
+
+/// The parameters of the blur shader, matching `blurBuffer` in `blur.hlsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BlurConstants {
+    tap_step: [i32; 2],
+    sigma: f32,
+    radius: u32,
+}
+
+/// A render target that can be sampled from.
+struct Target {
+    view: ComPtr<ID3D11RenderTargetView>,
+    resource_view: ComPtr<ID3D11ShaderResourceView>,
+}
+
+impl Target {
+    unsafe fn new(device: &ID3D11Device, width: u32, height: u32) -> HResult<Target> {
+        let (texture, view) = offscreen::create_render_target(device, width, height)?;
+        let resource_view = com_ptr_from_fn("CreateShaderResourceView", |view| {
+            device.CreateShaderResourceView(com_ref_cast(&texture).as_raw(), ptr::null(), view)
+        })?;
+        Ok(Target {
+            view,
+            resource_view,
+        })
+    }
+}
+
+pub(crate) struct GlowPass {
+    radius: f32,
+    vertex_shader: ComPtr<ID3D11VertexShader>,
+    pixel_shader: ComPtr<ID3D11PixelShader>,
+    constants: ComPtr<ID3D11Buffer>,
+    blend_state: ComPtr<ID3D11BlendState>,
+    /// The glow mask and the horizontally blurred mask, recreated when the size changes.
+    targets: Option<((u32, u32), [Target; 2])>,
+}
+
+impl GlowPass {
+    /// Returns `None` below feature level 10.0, which doesn't support the glow shaders.
+    pub unsafe fn new(device: &ID3D11Device, radius: f32) -> HResult<Option<GlowPass>> {
+        let (model, vertex_shader_code, pixel_shader_code) =
+            match builtin_shaders(device.GetFeatureLevel()) {
+                Some(shaders) => shaders,
+                None => return Ok(None),
+            };
+        let vertex_shader_code = runtime_compiler::or_compile(
+            vertex_shader_code,
+            include_str!("shader/fullscreen.hlsl"),
+            "vs",
+            model,
+            None,
+        )?;
+        let vertex_shader = com_ptr_from_fn("CreateVertexShader", |shader| {
+            device.CreateVertexShader(
+                vertex_shader_code.as_ptr().cast(),
+                vertex_shader_code.len(),
+                ptr::null_mut(),
+                shader,
+            )
+        })?;
+        let pixel_shader_code = runtime_compiler::or_compile(
+            pixel_shader_code,
+            include_str!("shader/blur.hlsl"),
+            "ps",
+            model,
+            None,
+        )?;
+        let pixel_shader = com_ptr_from_fn("CreatePixelShader", |shader| {
+            device.CreatePixelShader(
+                pixel_shader_code.as_ptr().cast(),
+                pixel_shader_code.len(),
+                ptr::null_mut(),
+                shader,
+            )
+        })?;
+
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: mem::size_of::<BlurConstants>() as _,
+            Usage: D3D11_USAGE_DYNAMIC,
+            BindFlags: D3D11_BIND_CONSTANT_BUFFER,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+        let constants = com_ptr_from_fn("CreateBuffer", |buffer| {
+            device.CreateBuffer(&desc, ptr::null(), buffer)
+        })?;
+
+        // the glow mask is premultiplied by the glyph blend state
+        let mut desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: FALSE,
+            IndependentBlendEnable: FALSE,
+            RenderTarget: mem::zeroed(),
+        };
+        desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+            BlendEnable: TRUE,
+            SrcBlend: D3D11_BLEND_ONE,
+            DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
+            BlendOp: D3D11_BLEND_OP_ADD,
+            SrcBlendAlpha: D3D11_BLEND_ONE,
+            DestBlendAlpha: D3D11_BLEND_INV_SRC_ALPHA,
+            BlendOpAlpha: D3D11_BLEND_OP_ADD,
+            RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL as u8,
+        };
+        let blend_state = com_ptr_from_fn("CreateBlendState", |blend_state| {
+            device.CreateBlendState(&desc, blend_state)
+        })?;
+
+        Ok(Some(GlowPass {
+            radius,
+            vertex_shader,
+            pixel_shader,
+            constants,
+            blend_state,
+            targets: None,
+        }))
+    }
+
+    /// Returns the render target to draw the glow mask into, sized to match `target`.
+    pub unsafe fn mask_target(
+        &mut self,
+        device: &ID3D11Device,
+        target: &ID3D11RenderTargetView,
+    ) -> HResult<&ComPtr<ID3D11RenderTargetView>> {
+        let size = target_size(target)?;
+        match &self.targets {
+            Some((targets_size, _)) if *targets_size == size => {}
+            _ => {
+                let targets = [
+                    Target::new(device, size.0, size.1)?,
+                    Target::new(device, size.0, size.1)?,
+                ];
+                self.targets = Some((size, targets));
+            }
+        }
+        Ok(&self.targets.as_ref().unwrap().1[0].view)
+    }
+
+    /// Blurs the glow mask drawn into the [`mask_target`](#method.mask_target) and composites
+    /// it into `target`.
+    ///
+    /// Expects the rasterizer state and viewport of the glyph draw to still be bound. The mask
+    /// is read at the pixels drawn, so the glow lines up with the glyphs within any viewport.
+    pub unsafe fn composite(
+        &self,
+        ctx: &ID3D11DeviceContext,
+        target: &ComPtr<ID3D11RenderTargetView>,
+    ) -> HResult<()> {
+        let (_, [mask, blurred]) = match &self.targets {
+            Some(targets) => targets,
+            None => return Ok(()),
+        };
+        // the vertical pass reads the horizontally blurred mask outside of the viewport as well
+        ctx.ClearRenderTargetView(blurred.view.as_raw(), &[0.0; 4]);
+
+        ctx.IASetInputLayout(ptr::null_mut());
+        ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        ctx.VSSetShader(self.vertex_shader.as_raw(), ptr::null(), 0);
+        ctx.PSSetShader(self.pixel_shader.as_raw(), ptr::null(), 0);
+        ctx.PSSetConstantBuffers(0, 1, &self.constants.as_raw());
+
+        let constants = BlurConstants {
+            tap_step: [0; 2],
+            sigma: (self.radius / 3.0).max(0.5),
+            radius: self.radius.ceil() as u32,
+        };
+        let passes = [
+            (mask, blurred.view.as_raw(), ptr::null_mut(), [1, 0]),
+            (blurred, target.as_raw(), self.blend_state.as_raw(), [0, 1]),
+        ];
+        for &(source, target, blend_state, tap_step) in &passes {
+            write_constants(
+                ctx,
+                &self.constants,
+                BlurConstants {
+                    tap_step,
+                    ..constants
+                },
+            )?;
+            // unbind the source of the previous pass before rendering into it
+            ctx.PSSetShaderResources(0, 1, &ptr::null_mut());
+            ctx.OMSetRenderTargets(1, &target, ptr::null_mut());
+            ctx.OMSetBlendState(blend_state, &[0.0; 4], 0xFFFFFFFF);
+            ctx.PSSetShaderResources(0, 1, &source.resource_view.as_raw());
+            ctx.Draw(3, 0);
+        }
+        ctx.PSSetShaderResources(0, 1, &ptr::null_mut());
+        Ok(())
+    }
+}
+
+/// Returns the shader model and the fullscreen vertex and blur pixel shaders compiled for the
+/// highest shader model supported by the feature level.
+fn builtin_shaders(
+    feature_level: D3D_FEATURE_LEVEL,
+) -> Option<(&'static str, &'static [u8], &'static [u8])> {
+    if feature_level >= D3D_FEATURE_LEVEL_11_0 {
+        Some((
+            "5_0",
+            include_bytes!(concat!(env!("OUT_DIR"), "/fullscreen_shader.vs_5_0")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/blur_shader.ps_5_0")),
+        ))
+    } else if feature_level >= D3D_FEATURE_LEVEL_10_0 {
+        Some((
+            "4_0",
+            include_bytes!(concat!(env!("OUT_DIR"), "/fullscreen_shader.vs_4_0")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/blur_shader.ps_4_0")),
+        ))
+    } else {
+        None
+    }
+}
+
+unsafe fn target_size(target: &ID3D11RenderTargetView) -> HResult<(u32, u32)> {
+    let mut resource: *mut ID3D11Resource = ptr::null_mut();
+    target.GetResource(&mut resource);
+    let resource = ComPtr::from_raw(resource);
+    let texture = match resource.cast::<ID3D11Texture2D>() {
+        Ok(texture) => texture,
+        Err(hr) => return hresult("QueryInterface", hr).map(|()| (0, 0)),
+    };
+    let mut desc = mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+    texture.GetDesc(&mut desc);
+    Ok((desc.Width, desc.Height))
+}
@@ -0,0 +1,317 @@
+//! Custom coverage images rasterized into the glyph cache like the glyphs of a font.
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use glyph_brush::ab_glyph::{
+    point, v2, Font, Glyph, GlyphId, GlyphSvg, Outline, OutlineCurve, Point, PxScale, Rect,
+};
+use glyph_brush::{FontId, SectionGlyph};
+
+/// A font extended by custom coverage images, e.g. icons or symbols missing from the font.
+///
+/// The images are drawn from the glyph cache like any other glyph, so text and icons queued
+/// together share their draw calls. Each image gets a glyph id past the glyphs of the wrapped
+/// font, which is drawn by queuing it with
+/// [`queue_pre_positioned`](struct.GlyphBrush.html#method.queue_pre_positioned), see
+/// [`section_glyph`](#method.section_glyph).
+///
+/// Images can also be mapped to characters, which are then laid out like the other characters
+/// of a section, e.g. for icons in tooltips, see [`map_char`](#method.map_char) and
+/// [`add_inline_object`](#method.add_inline_object).
+///
+/// Clones share their images, so images can still be added after the font has been added to a
+/// brush. All other glyphs and metrics are those of the wrapped font.
+#[derive(Debug)]
+pub struct ImageFont<F> {
+    font: F,
+    images: Arc<RwLock<Images>>,
+}
+
+/// A custom image added to an [`ImageFont`](struct.ImageFont.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomGlyph {
+    pub id: GlyphId,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Default)]
+struct Images {
+    images: Vec<Image>,
+    /// The images the characters mapped with `map_char` are laid out as.
+    chars: HashMap<char, GlyphId>,
+}
+
+#[derive(Debug)]
+struct Image {
+    width: u32,
+    height: u32,
+    coverage: Vec<u8>,
+}
+
+impl<F: Font> ImageFont<F> {
+    #[inline]
+    pub fn new(font: F) -> Self {
+        ImageFont {
+            font,
+            images: Arc::default(),
+        }
+    }
+
+    /// The wrapped font.
+    #[inline]
+    pub fn font(&self) -> &F {
+        &self.font
+    }
+
+    /// Adds an image of one coverage byte per pixel in row-major order, returning its glyph.
+    ///
+    /// Images are drawn in the color of their text, like glyphs. They are never removed, as
+    /// the glyph cache may still contain them.
+    ///
+    /// Returns `None` if the glyph ids are used up, glyph ids are 16 bits wide and the images
+    /// get the ids following the glyphs of the font, so fonts with close to 65536 glyphs, e.g.
+    /// many CJK fonts, leave room for few images or none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `coverage` doesn't contain `width * height` bytes.
+    pub fn add_image(&self, width: u32, height: u32, coverage: &[u8]) -> Option<CustomGlyph> {
+        assert_eq!(
+            coverage.len(),
+            width as usize * height as usize,
+            "the coverage doesn't match the size of the image"
+        );
+        let mut images = self.images.write().unwrap_or_else(PoisonError::into_inner);
+        let id = self.font.glyph_count() + images.images.len();
+        if id > u16::MAX as usize {
+            return None;
+        }
+        images.images.push(Image {
+            width,
+            height,
+            coverage: coverage.to_vec(),
+        });
+        Some(CustomGlyph {
+            id: GlyphId(id as u16),
+            width,
+            height,
+        })
+    }
+
+    /// Lays out `c` as `glyph` in texts using this font, e.g. a character of the private use
+    /// area `U+E000..=U+F8FF` as an inline icon.
+    ///
+    /// Like other glyphs, the image is as high as the scale of its text and sized to span from
+    /// the descent to the ascent of the font, so it is drawn unscaled with a scale matching its
+    /// height. Changing the glyph of a character affects sections laid out afterwards.
+    pub fn map_char(&self, c: char, glyph: CustomGlyph) {
+        let mut images = self.images.write().unwrap_or_else(PoisonError::into_inner);
+        images.chars.insert(c, glyph.id);
+    }
+
+    /// Adds a fully covered image of `width` x `height` pixels laid out as `c`, reserving space
+    /// for an inline object like an emote in chat text.
+    ///
+    /// The object is drawn in the color of its text. Draw it from a user texture by giving the
+    /// text a [per glyph fill](struct.TextureFill.html#method.per_glyph), or leave it
+    /// transparent and draw over the [glyph quad](struct.GlyphBrush.html#method.glyph_quads)
+    /// reserved for it.
+    ///
+    /// Returns `None` if the glyph ids are used up, see [`add_image`](#method.add_image).
+    pub fn add_inline_object(&self, c: char, width: u32, height: u32) -> Option<CustomGlyph> {
+        let glyph = self.add_image(width, height, &vec![255; width as usize * height as usize])?;
+        self.map_char(c, glyph);
+        Some(glyph)
+    }
+
+    /// Returns a glyph drawing `glyph` unscaled with its top left corner at `position`, rounded
+    /// to whole pixels, for a brush which has this font added as `font_id`.
+    ///
+    /// `section_index` selects the extra data of the glyph when it is queued with
+    /// [`queue_pre_positioned`](struct.GlyphBrush.html#method.queue_pre_positioned).
+    pub fn section_glyph(
+        &self,
+        glyph: CustomGlyph,
+        font_id: FontId,
+        position: Point,
+        section_index: usize,
+    ) -> SectionGlyph {
+        let height = glyph.height as f32;
+        // images span from the descent to the ascent of the font
+        let ascent = height * self.font.ascent_unscaled() / self.font.height_unscaled();
+        SectionGlyph {
+            section_index,
+            byte_index: 0,
+            glyph: Glyph {
+                id: glyph.id,
+                scale: PxScale::from(height),
+                position: point(position.x.round(), position.y.round() + ascent),
+            },
+            font_id,
+        }
+    }
+
+    /// Calls `f` with the image of `id`, if it is a custom glyph.
+    fn with_image<R>(&self, id: GlyphId, f: impl FnOnce(&Image) -> R) -> Option<R> {
+        let index = (id.0 as usize).checked_sub(self.font.glyph_count())?;
+        let images = self.images.read().unwrap_or_else(PoisonError::into_inner);
+        images.images.get(index).map(f)
+    }
+
+    /// The size of a pixel of `image` in font units.
+    #[inline]
+    fn pixel_size(&self, image: &Image) -> f32 {
+        self.font.height_unscaled() / image.height.max(1) as f32
+    }
+
+    /// Outlines the pixels of `image` as rectangles covering the same fraction of their pixel
+    /// as their coverage, which the rasterizer turns back into the exact coverage.
+    fn image_outline(&self, image: &Image) -> Option<Outline> {
+        if image.width == 0 {
+            return None;
+        }
+        let size = self.pixel_size(image);
+        let top = self.font.ascent_unscaled();
+        let mut curves = Vec::new();
+        let mut rectangle = |x0: f32, x1: f32, y0: f32, y1: f32| {
+            curves.push(OutlineCurve::Line(point(x0, y0), point(x1, y0)));
+            curves.push(OutlineCurve::Line(point(x1, y0), point(x1, y1)));
+            curves.push(OutlineCurve::Line(point(x1, y1), point(x0, y1)));
+            curves.push(OutlineCurve::Line(point(x0, y1), point(x0, y0)));
+        };
+        for (y, row) in image.coverage.chunks(image.width as usize).enumerate() {
+            let (y0, y1) = (top - y as f32 * size, top - (y + 1) as f32 * size);
+            let mut x = 0;
+            while x < row.len() {
+                // runs of fully covered pixels are merged into a single rectangle
+                let run = row[x..].iter().take_while(|&&texel| texel == 255).count();
+                if run > 0 {
+                    rectangle(x as f32 * size, (x + run) as f32 * size, y0, y1);
+                    x += run;
+                    continue;
+                }
+                if row[x] > 0 {
+                    let width = row[x] as f32 / 255.0;
+                    rectangle(x as f32 * size, (x as f32 + width) * size, y0, y1);
+                }
+                x += 1;
+            }
+        }
+        if curves.is_empty() {
+            return None;
+        }
+        Some(Outline {
+            bounds: Rect {
+                min: point(0.0, top),
+                max: point(image.width as f32 * size, self.font.descent_unscaled()),
+            },
+            curves,
+        })
+    }
+}
+
+impl<F: Clone> Clone for ImageFont<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        ImageFont {
+            font: self.font.clone(),
+            images: Arc::clone(&self.images),
+        }
+    }
+}
+
+impl<F: Font> Font for ImageFont<F> {
+    #[inline]
+    fn units_per_em(&self) -> Option<f32> {
+        self.font.units_per_em()
+    }
+
+    #[inline]
+    fn ascent_unscaled(&self) -> f32 {
+        self.font.ascent_unscaled()
+    }
+
+    #[inline]
+    fn descent_unscaled(&self) -> f32 {
+        self.font.descent_unscaled()
+    }
+
+    #[inline]
+    fn line_gap_unscaled(&self) -> f32 {
+        self.font.line_gap_unscaled()
+    }
+
+    #[inline]
+    fn italic_angle(&self) -> f32 {
+        self.font.italic_angle()
+    }
+
+    fn glyph_id(&self, c: char) -> GlyphId {
+        let images = self.images.read().unwrap_or_else(PoisonError::into_inner);
+        match images.chars.get(&c) {
+            Some(&id) => id,
+            None => self.font.glyph_id(c),
+        }
+    }
+
+    fn h_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.with_image(id, |image| image.width as f32 * self.pixel_size(image))
+            .unwrap_or_else(|| self.font.h_advance_unscaled(id))
+    }
+
+    fn h_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.with_image(id, |_| 0.0)
+            .unwrap_or_else(|| self.font.h_side_bearing_unscaled(id))
+    }
+
+    fn v_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.with_image(id, |_| self.font.height_unscaled())
+            .unwrap_or_else(|| self.font.v_advance_unscaled(id))
+    }
+
+    fn v_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.with_image(id, |_| 0.0)
+            .unwrap_or_else(|| self.font.v_side_bearing_unscaled(id))
+    }
+
+    fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> f32 {
+        let count = self.font.glyph_count();
+        if first.0 as usize >= count || second.0 as usize >= count {
+            return 0.0;
+        }
+        self.font.kern_unscaled(first, second)
+    }
+
+    fn outline(&self, id: GlyphId) -> Option<Outline> {
+        match self.with_image(id, |image| self.image_outline(image)) {
+            Some(outline) => outline,
+            None => self.font.outline(id),
+        }
+    }
+
+    fn glyph_count(&self) -> usize {
+        let images = self.images.read().unwrap_or_else(PoisonError::into_inner);
+        self.font.glyph_count() + images.images.len()
+    }
+
+    #[inline]
+    fn codepoint_ids(&self) -> glyph_brush::ab_glyph::CodepointIdIter<'_> {
+        self.font.codepoint_ids()
+    }
+
+    #[inline]
+    fn glyph_raster_image2(&self, id: GlyphId, pixel_size: u16) -> Option<v2::GlyphImage<'_>> {
+        self.font.glyph_raster_image2(id, pixel_size)
+    }
+
+    #[inline]
+    fn glyph_svg_image(&self, id: GlyphId) -> Option<GlyphSvg<'_>> {
+        self.font.glyph_svg_image(id)
+    }
+
+    #[inline]
+    fn font_data(&self) -> &[u8] {
+        self.font.font_data()
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+use glyph_brush::ab_glyph::Rect;
+use glyph_brush::SectionGlyph;
+
+use crate::Region;
+
+/// A laid out section queued with an id.
+pub(crate) struct KeyedSection<X> {
+    /// Identifies the glyphs in the layout cache of glyph_brush, see `CachedLayout`. Assigned
+    /// anew whenever the section is laid out.
+    pub key: u64,
+    pub glyphs: Arc<[SectionGlyph]>,
+    /// The extra data of each text of the section.
+    pub extra: Vec<X>,
+    pub bounds: Rect,
+    /// The clip region the section has been laid out in.
+    clip: Option<Region>,
+    dirty: bool,
+    /// Whether the section has been queued since the last processed frame.
+    used: bool,
+}
+
+impl<X> KeyedSection<X> {
+    pub fn new(
+        glyphs: impl Into<Arc<[SectionGlyph]>>,
+        extra: Vec<X>,
+        bounds: Rect,
+        clip: Option<Region>,
+    ) -> Self {
+        KeyedSection {
+            key: 0,
+            glyphs: glyphs.into(),
+            extra,
+            bounds,
+            clip,
+            dirty: false,
+            used: true,
+        }
+    }
+}
+
+/// The layouts of the sections queued with an id, which are reused until the section is marked
+/// dirty or isn't queued for a frame.
+pub(crate) struct KeyedSections<X> {
+    sections: HashMap<u64, KeyedSection<X>>,
+    next_key: u64,
+}
+
+impl<X> Default for KeyedSections<X> {
+    fn default() -> Self {
+        KeyedSections {
+            sections: HashMap::new(),
+            next_key: 0,
+        }
+    }
+}
+
+impl<X> KeyedSections<X> {
+    /// Returns the layout of the section `id` if it is still valid in `clip`, keeping it for the
+    /// next frame.
+    pub fn get(&mut self, id: u64, clip: Option<Region>) -> Option<&KeyedSection<X>> {
+        let section = self.sections.get_mut(&id)?;
+        if section.dirty || section.clip != clip {
+            return None;
+        }
+        section.used = true;
+        Some(section)
+    }
+
+    /// Inserts the layout of the section `id`, returning the key it is given, which no layout
+    /// inserted before had.
+    pub fn insert(&mut self, id: u64, mut section: KeyedSection<X>) -> u64 {
+        section.key = self.next_key;
+        self.next_key += 1;
+        self.sections.insert(id, section);
+        self.next_key - 1
+    }
+
+    pub fn mark_dirty(&mut self, id: u64) {
+        if let Some(section) = self.sections.get_mut(&id) {
+            section.dirty = true;
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.sections.clear();
+    }
+
+    /// Forgets the sections which haven't been queued since the last call.
+    pub fn finish_frame(&mut self) {
+        self.sections
+            .retain(|_, section| mem::replace(&mut section.used, false));
+    }
+}
@@ -0,0 +1,1222 @@
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use glyph_brush::ab_glyph::{
+    point, v2, CodepointIdIter, Font, GlyphId, GlyphSvg, Outline, PxScale, Rect, ScaleFont,
+};
+use glyph_brush::{
+    BuiltInLineBreaker, FontId, GlyphChange, GlyphCruncher, GlyphPositioner, HorizontalAlign,
+    Layout, LineBreak, LineBreaker, Section, SectionGeometry, SectionGlyph, SectionText, Text,
+    ToSectionText, VerticalAlign,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[cfg(feature = "shaping")]
+use crate::shaping;
+use crate::GlyphExtra;
+
+/// The height of a line of text, overriding the height given by the font metrics, see
+/// [`GlyphExtra::line_height`](trait.GlyphExtra.html#method.line_height).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineHeight {
+    /// A line height in pixels.
+    Pixels(f32),
+    /// A multiple of the line height of the font, `1.0` being the default.
+    Multiplier(f32),
+}
+
+impl Hash for LineHeight {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            LineHeight::Pixels(height) => (0u8, height.to_bits()).hash(state),
+            LineHeight::Multiplier(factor) => (1u8, factor.to_bits()).hash(state),
+        }
+    }
+}
+
+/// What is drawn for characters missing from the font of their text, see
+/// [`GlyphBrush::set_missing_glyph`](struct.GlyphBrush.html#method.set_missing_glyph).
+///
+/// Applied when a section is laid out, which is cached, so a callback is only invoked once
+/// for an unchanged section.
+#[derive(Clone)]
+pub enum MissingGlyph {
+    /// Draws the `.notdef` glyph of the font, usually a box.
+    Notdef,
+    /// Leaves the character out.
+    Skip,
+    /// Draws the given character of the same font instead, drawing `.notdef` if it's missing
+    /// as well.
+    Replace(char),
+    /// Draws `.notdef` and calls the function with the missing character, e.g. to log gaps in
+    /// the coverage of a localization.
+    Callback(Arc<dyn Fn(char) + Send + Sync>),
+}
+
+impl Default for MissingGlyph {
+    #[inline]
+    fn default() -> Self {
+        MissingGlyph::Notdef
+    }
+}
+
+impl fmt::Debug for MissingGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingGlyph::Notdef => f.write_str("Notdef"),
+            MissingGlyph::Skip => f.write_str("Skip"),
+            MissingGlyph::Replace(character) => f.debug_tuple("Replace").field(character).finish(),
+            MissingGlyph::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+impl Hash for MissingGlyph {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            MissingGlyph::Notdef => 0u8.hash(state),
+            MissingGlyph::Skip => 1u8.hash(state),
+            MissingGlyph::Replace(character) => (2u8, character).hash(state),
+            MissingGlyph::Callback(_) => 3u8.hash(state),
+        }
+    }
+}
+
+/// A line breaker only breaking lines at the boundaries of grapheme clusters, so combining
+/// sequences and emoji ZWJ sequences are never split across lines.
+///
+/// Wraps one of the built-in line breakers, whose soft breaks within a cluster are dropped.
+/// Sections queued with their built-in [`Layout`](enum.Layout.html) use it automatically.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GraphemeLineBreaker(pub BuiltInLineBreaker);
+
+impl LineBreaker for GraphemeLineBreaker {
+    fn line_breaks<'a>(&self, text: &'a str) -> Box<dyn Iterator<Item = LineBreak> + 'a> {
+        let mut boundaries = (text.grapheme_indices(true))
+            .map(|(offset, grapheme)| offset + grapheme.len())
+            .peekable();
+        Box::new(
+            (self.0.line_breaks(text)).filter(move |line_break| match *line_break {
+                LineBreak::Hard(_) => true,
+                LineBreak::Soft(offset) => {
+                    while boundaries.next_if(|&boundary| boundary < offset).is_some() {}
+                    boundaries.peek() == Some(&offset)
+                }
+            }),
+        )
+    }
+}
+
+/// Returns the built-in layout breaking lines at grapheme cluster boundaries only.
+pub(crate) fn grapheme_layout(layout: Layout<BuiltInLineBreaker>) -> Layout<GraphemeLineBreaker> {
+    let line_breaker = match layout {
+        Layout::SingleLine { line_breaker, .. } | Layout::Wrap { line_breaker, .. } => line_breaker,
+    };
+    layout.line_breaker(GraphemeLineBreaker(line_breaker))
+}
+
+/// A range of characters drawn with a font of its own, see
+/// [`GlyphBrush::set_script_font`](struct.GlyphBrush.html#method.set_script_font).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    /// Basic Latin letters and digits and the Latin supplements and extensions.
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Hangul,
+    /// Hiragana and katakana.
+    Kana,
+    /// CJK ideographs and punctuation, including the halfwidth and fullwidth forms.
+    Han,
+    /// The emoji blocks and the joiners and modifiers of emoji sequences.
+    Emoji,
+    /// An inclusive range of characters.
+    Range(char, char),
+}
+
+impl Script {
+    /// Returns `true` if the character belongs to the script.
+    pub fn contains(self, character: char) -> bool {
+        let code = character as u32;
+        match self {
+            Script::Latin => matches!(
+                code,
+                0x0030..=0x0039 | 0x0041..=0x005A | 0x0061..=0x007A | 0x00C0..=0x024F | 0x1E00..=0x1EFF
+            ),
+            Script::Greek => matches!(code, 0x0370..=0x03FF | 0x1F00..=0x1FFF),
+            Script::Cyrillic => matches!(code, 0x0400..=0x052F),
+            Script::Hebrew => matches!(code, 0x0590..=0x05FF),
+            Script::Arabic => matches!(code, 0x0600..=0x06FF | 0x0750..=0x077F),
+            Script::Devanagari => matches!(code, 0x0900..=0x097F),
+            Script::Thai => matches!(code, 0x0E00..=0x0E7F),
+            Script::Hangul => matches!(code, 0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF),
+            Script::Kana => matches!(code, 0x3040..=0x30FF | 0x31F0..=0x31FF),
+            Script::Han => matches!(
+                code,
+                0x2E80..=0x2FDF
+                    | 0x3000..=0x303F
+                    | 0x3400..=0x4DBF
+                    | 0x4E00..=0x9FFF
+                    | 0xF900..=0xFAFF
+                    | 0xFF00..=0xFFEF
+                    | 0x20000..=0x3134F
+            ),
+            Script::Emoji => is_emoji(character),
+            Script::Range(first, last) => (first..=last).contains(&character),
+        }
+    }
+}
+
+/// The layout options of a brush applying to all of its sections.
+#[derive(Debug, Clone, Default, Hash)]
+pub(crate) struct LayoutOptions {
+    pub emoji_font: Option<FontId>,
+    /// The fonts of scripts, the first matching script of a character applies.
+    pub script_fonts: Vec<(Script, FontId)>,
+    /// How glyphs missing from the font of their text are handled.
+    pub missing_glyph: MissingGlyph,
+    /// The number of horizontal positions per pixel glyphs are snapped to, if any. Vertical
+    /// positions are then rounded to whole pixels.
+    pub subpixel_variants: Option<u32>,
+}
+
+impl LayoutOptions {
+    /// Updates the emoji font after the font `font_id` was removed.
+    pub fn remove_font(&mut self, font_id: FontId) {
+        self.emoji_font = match self.emoji_font {
+            Some(emoji_font) if emoji_font == font_id => None,
+            Some(emoji_font) if emoji_font.0 > font_id.0 => Some(FontId(emoji_font.0 - 1)),
+            emoji_font => emoji_font,
+        };
+        self.script_fonts.retain(|&(_, id)| id != font_id);
+        for (_, id) in &mut self.script_fonts {
+            if id.0 > font_id.0 {
+                id.0 -= 1;
+            }
+        }
+    }
+
+    /// Returns the font mapped to the script of `character` if it contains the character.
+    fn script_font<F: Font>(&self, fonts: &[F], character: char) -> Option<(FontId, GlyphId)> {
+        let &(_, font_id) =
+            (self.script_fonts.iter()).find(|(script, _)| script.contains(character))?;
+        let glyph_id = fonts.get(font_id.0)?.glyph_id(character);
+        Some((font_id, glyph_id)).filter(|_| glyph_id.0 != 0)
+    }
+
+    #[inline]
+    fn has_fallback(&self) -> bool {
+        self.emoji_font.is_some() || !matches!(self.missing_glyph, MissingGlyph::Notdef)
+    }
+
+    /// Returns the glyph drawn for a character missing from the font `font_id`, if any.
+    fn substitute<F: Font>(
+        &self,
+        fonts: &[F],
+        font_id: FontId,
+        character: char,
+    ) -> Option<(FontId, GlyphId)> {
+        if let Some(emoji_font) = self.emoji_font {
+            if emoji_font != font_id && emoji_font.0 < fonts.len() && is_emoji(character) {
+                let glyph_id = fonts[emoji_font.0].glyph_id(character);
+                if glyph_id.0 != 0 {
+                    return Some((emoji_font, glyph_id));
+                }
+            }
+        }
+        match &self.missing_glyph {
+            MissingGlyph::Replace(replacement) => {
+                let glyph_id = fonts[font_id.0].glyph_id(*replacement);
+                Some((font_id, glyph_id)).filter(|_| glyph_id.0 != 0)
+            }
+            MissingGlyph::Callback(callback) => {
+                callback(character);
+                None
+            }
+            MissingGlyph::Notdef | MissingGlyph::Skip => None,
+        }
+    }
+}
+
+/// Wraps a layout, applying the per-text styling of the section's
+/// [`GlyphExtra`](trait.GlyphExtra.html) data to the laid out glyphs.
+pub(crate) struct StyledLayout<'a, L> {
+    layout: &'a L,
+    h_align: HorizontalAlign,
+    v_align: VerticalAlign,
+    letter_spacing: Vec<f32>,
+    line_height: Vec<Option<LineHeight>>,
+    /// Whether the section contains Arabic or Indic text to be shaped.
+    #[cfg_attr(not(feature = "shaping"), allow(dead_code))]
+    shape: bool,
+    options: LayoutOptions,
+}
+
+impl<'a, L> StyledLayout<'a, L> {
+    /// Returns `None` if the section doesn't use any styling, missing glyphs aren't replaced,
+    /// glyphs aren't snapped to pixels and there is no text to shape, so the layout can be used
+    /// as is.
+    pub fn new<X: GlyphExtra>(
+        layout: &'a L,
+        section: &Section<'_, X>,
+        options: &LayoutOptions,
+    ) -> Option<Self> {
+        let letter_spacing: Vec<_> = section
+            .text
+            .iter()
+            .map(|text| text.extra.letter_spacing())
+            .collect();
+        let line_height: Vec<_> = section
+            .text
+            .iter()
+            .map(|text| text.extra.line_height())
+            .collect();
+        #[cfg(feature = "shaping")]
+        let shape = (section.text.iter()).any(|text| shaping::needs_shaping(text.text));
+        #[cfg(not(feature = "shaping"))]
+        let shape = false;
+        if letter_spacing.iter().all(|&spacing| spacing == 0.0)
+            && line_height.iter().all(Option::is_none)
+            && !options.has_fallback()
+            && options.subpixel_variants.is_none()
+            && options.script_fonts.is_empty()
+            && !shape
+        {
+            return None;
+        }
+        let (h_align, v_align) = match section.layout {
+            Layout::SingleLine {
+                h_align, v_align, ..
+            }
+            | Layout::Wrap {
+                h_align, v_align, ..
+            } => (h_align, v_align),
+        };
+        Some(StyledLayout {
+            layout,
+            h_align,
+            v_align,
+            letter_spacing,
+            line_height,
+            shape,
+            options: options.clone(),
+        })
+    }
+
+    /// Draws the characters of scripts mapped to a font with that font, moving the following
+    /// glyphs of the line by the difference in advance.
+    ///
+    /// Texts using the default font are always remapped, texts using another font keep it for
+    /// the characters it contains.
+    fn apply_script_fonts<F: Font, S: ToSectionText>(
+        &self,
+        fonts: &[F],
+        sections: &[S],
+        glyphs: &mut [SectionGlyph],
+    ) {
+        if self.options.script_fonts.is_empty() {
+            return;
+        }
+        for line in lines(glyphs) {
+            let mut offset = 0.0;
+            for glyph in line.iter_mut() {
+                glyph.glyph.position.x += offset;
+                if glyph.font_id != FontId::default() && glyph.glyph.id.0 != 0 {
+                    continue;
+                }
+                let character = sections[glyph.section_index].to_section_text().text
+                    [glyph.byte_index..]
+                    .chars()
+                    .next()
+                    .unwrap_or_default();
+                let (font_id, glyph_id) = match self.options.script_font(fonts, character) {
+                    Some(mapped) if mapped.0 != glyph.font_id => mapped,
+                    _ => continue,
+                };
+                let scale = glyph.glyph.scale;
+                offset += fonts[font_id.0].as_scaled(scale).h_advance(glyph_id)
+                    - fonts[glyph.font_id.0]
+                        .as_scaled(scale)
+                        .h_advance(glyph.glyph.id);
+                glyph.font_id = font_id;
+                glyph.glyph.id = glyph_id;
+            }
+            self.realign(line, offset);
+        }
+    }
+
+    /// Replaces or removes the glyphs missing from the font of their text, moving the following
+    /// glyphs of the line by the difference in advance.
+    fn apply_fallback<F: Font, S: ToSectionText>(
+        &self,
+        fonts: &[F],
+        sections: &[S],
+        glyphs: &mut Vec<SectionGlyph>,
+    ) {
+        if !self.options.has_fallback() {
+            return;
+        }
+        // control characters like line breaks are never drawn
+        let missing = |glyph: &SectionGlyph| {
+            if glyph.glyph.id.0 != 0 {
+                return None;
+            }
+            sections[glyph.section_index].to_section_text().text[glyph.byte_index..]
+                .chars()
+                .next()
+                .filter(|character| !character.is_control())
+        };
+        let skip = matches!(self.options.missing_glyph, MissingGlyph::Skip);
+        for line in lines(glyphs) {
+            let mut offset = 0.0;
+            for glyph in line.iter_mut() {
+                glyph.glyph.position.x += offset;
+                let character = match missing(glyph) {
+                    Some(character) => character,
+                    None => continue,
+                };
+                let scale = glyph.glyph.scale;
+                let advance = fonts[glyph.font_id.0]
+                    .as_scaled(scale)
+                    .h_advance(glyph.glyph.id);
+                match self.options.substitute(fonts, glyph.font_id, character) {
+                    Some((font_id, glyph_id)) => {
+                        offset += fonts[font_id.0].as_scaled(scale).h_advance(glyph_id) - advance;
+                        glyph.font_id = font_id;
+                        glyph.glyph.id = glyph_id;
+                    }
+                    None if skip => offset -= advance,
+                    None => {}
+                }
+            }
+            self.realign(line, offset);
+        }
+        if skip {
+            glyphs.retain(|glyph| missing(glyph).is_none());
+        }
+    }
+
+    /// Lays out the sections with the letter spacing of their texts added to the advances of
+    /// their glyphs, so the wrapped layout breaks lines with the spacing taken into account.
+    fn calculate_spaced<F: Font, S: ToSectionText>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        L: GlyphPositioner,
+    {
+        // a font for every combination of font and spacing, with the id of the spaced font
+        let mut spaced_fonts: Vec<SpacedFont<'_, F>> = Vec::new();
+        let mut font_ids = Vec::new();
+        let spaced_sections: Vec<SectionText<'_>> = (sections.iter().enumerate())
+            .map(|(index, section)| {
+                let text = section.to_section_text();
+                let font = &fonts[text.font_id.0];
+                let spacing = match self.letter_spacing[index] {
+                    spacing if spacing != 0.0 && text.scale.x > 0.0 => {
+                        spacing * font.height_unscaled() / text.scale.x
+                    }
+                    _ => 0.0,
+                };
+                let spaced = (spaced_fonts.iter().zip(&font_ids))
+                    .position(|(spaced, &id)| id == text.font_id && spaced.spacing == spacing)
+                    .unwrap_or_else(|| {
+                        spaced_fonts.push(SpacedFont { font, spacing });
+                        font_ids.push(text.font_id);
+                        font_ids.len() - 1
+                    });
+                SectionText {
+                    font_id: FontId(spaced),
+                    ..text
+                }
+            })
+            .collect();
+        let mut glyphs = (self.layout).calculate_glyphs(&spaced_fonts, geometry, &spaced_sections);
+        for glyph in &mut glyphs {
+            glyph.font_id = font_ids[glyph.font_id.0];
+        }
+        glyphs
+    }
+
+    /// Realigns every line without the letter spacing after its last glyph.
+    fn trim_letter_spacing(&self, glyphs: &mut [SectionGlyph]) {
+        for line in lines(glyphs) {
+            let spacing = self.letter_spacing[line[line.len() - 1].section_index];
+            self.realign(line, -spacing);
+        }
+    }
+
+    /// Moves a line that grew by `width` to the left to keep it aligned.
+    fn realign(&self, line: &mut [SectionGlyph], width: f32) {
+        let shift = match self.h_align {
+            HorizontalAlign::Left => 0.0,
+            HorizontalAlign::Center => -width / 2.0,
+            HorizontalAlign::Right => -width,
+        };
+        if shift != 0.0 {
+            line.iter_mut()
+                .for_each(|glyph| glyph.glyph.position.x += shift);
+        }
+    }
+
+    /// Snaps the glyph positions to the subpixel variants if enabled, which are whole pixels
+    /// with pixel snapping.
+    fn apply_pixel_snapping(&self, glyphs: &mut [SectionGlyph]) {
+        if let Some(variants) = self.options.subpixel_variants {
+            let variants = variants as f32;
+            for glyph in glyphs {
+                let position = &mut glyph.glyph.position;
+                *position = point(
+                    (position.x * variants).round() / variants,
+                    position.y.round(),
+                );
+            }
+        }
+    }
+
+    /// Moves the lines apart to match the line heights of their texts, keeping the section
+    /// aligned. The difference to the default line height is added below each line.
+    fn apply_line_height<F: Font>(&self, fonts: &[F], glyphs: &mut [SectionGlyph]) {
+        if self.line_height.iter().all(Option::is_none) {
+            return;
+        }
+        let mut offset = 0.0;
+        for line in lines(glyphs) {
+            let (mut default_height, mut height) = (0.0f32, 0.0f32);
+            for glyph in line.iter() {
+                let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+                let font_height = font.height() + font.line_gap();
+                default_height = default_height.max(font_height);
+                height = height.max(match self.line_height[glyph.section_index] {
+                    Some(LineHeight::Pixels(height)) => height,
+                    Some(LineHeight::Multiplier(factor)) => font_height * factor,
+                    None => font_height,
+                });
+            }
+            line.iter_mut()
+                .for_each(|glyph| glyph.glyph.position.y += offset);
+            offset += height - default_height;
+        }
+        let shift = match self.v_align {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Center => -offset / 2.0,
+            VerticalAlign::Bottom => -offset,
+        };
+        if shift != 0.0 {
+            glyphs
+                .iter_mut()
+                .for_each(|glyph| glyph.glyph.position.y += shift);
+        }
+    }
+}
+
+/// A layout returning already positioned glyphs, used to retain pre-positioned glyph batches in
+/// the glyph cache of `glyph_brush`, which only keeps laid out sections cached.
+pub(crate) struct PrePositioned<'a> {
+    pub glyphs: &'a [SectionGlyph],
+}
+
+impl Hash for PrePositioned<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for glyph in self.glyphs {
+            glyph.section_index.hash(state);
+            glyph.byte_index.hash(state);
+            glyph.font_id.hash(state);
+            glyph.glyph.id.hash(state);
+            [
+                glyph.glyph.scale.x,
+                glyph.glyph.scale.y,
+                glyph.glyph.position.x,
+                glyph.glyph.position.y,
+            ]
+            .iter()
+            .for_each(|value| value.to_bits().hash(state));
+        }
+    }
+}
+
+impl GlyphPositioner for PrePositioned<'_> {
+    fn calculate_glyphs<F, S>(&self, _: &[F], _: &SectionGeometry, _: &[S]) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        self.glyphs.to_vec()
+    }
+
+    fn bounds_rect(&self, _: &SectionGeometry) -> Rect {
+        Rect {
+            min: point(f32::NEG_INFINITY, f32::NEG_INFINITY),
+            max: point(f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    fn recalculate_glyphs<F, S, P>(
+        &self,
+        _: P,
+        _: GlyphChange,
+        _: &[F],
+        _: &SectionGeometry,
+        _: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+        P: IntoIterator<Item = SectionGlyph>,
+    {
+        // the previous glyphs may belong to an unrelated section of the last frame
+        self.glyphs.to_vec()
+    }
+}
+
+/// A layout returning the glyphs of a section laid out before, identified by a key instead of
+/// the text of the section.
+///
+/// glyph_brush caches the glyphs and vertices of the section by the hash of its layout like
+/// those of any other section, so queueing it again neither hashes its text nor copies its
+/// glyphs. The glyphs are only copied when the key isn't cached yet, the key has to change
+/// whenever they do.
+pub(crate) struct CachedLayout<'a> {
+    pub key: u64,
+    pub glyphs: &'a [SectionGlyph],
+    pub bounds: Rect,
+}
+
+impl Hash for CachedLayout<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl GlyphPositioner for CachedLayout<'_> {
+    fn calculate_glyphs<F, S>(&self, _: &[F], _: &SectionGeometry, _: &[S]) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        self.glyphs.to_vec()
+    }
+
+    fn bounds_rect(&self, _: &SectionGeometry) -> Rect {
+        self.bounds
+    }
+
+    fn recalculate_glyphs<F, S, P>(
+        &self,
+        _: P,
+        _: GlyphChange,
+        _: &[F],
+        _: &SectionGeometry,
+        _: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+        P: IntoIterator<Item = SectionGlyph>,
+    {
+        self.glyphs.to_vec()
+    }
+}
+
+/// Queues the glyphs of `layout` into `glyph_brush`, drawn with the extra data of the texts
+/// they were laid out from.
+pub(crate) fn queue_cached<F, H, X, V>(
+    glyph_brush: &mut glyph_brush::GlyphBrush<V, X, F, H>,
+    layout: &CachedLayout<'_>,
+    extra: &[X],
+) where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: Clone + 'static,
+{
+    glyph_brush.queue_custom_layout(extra_section(extra), layout);
+}
+
+/// Retains the glyphs of `layout` in the caches of `glyph_brush` as if they had been queued
+/// in the last frame.
+pub(crate) fn keep_cached<F, H, X, V>(
+    glyph_brush: &mut glyph_brush::GlyphBrush<V, X, F, H>,
+    layout: &CachedLayout<'_>,
+    extra: &[X],
+) where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: Clone + 'static,
+{
+    glyph_brush.keep_cached_custom_layout(extra_section(extra), layout);
+}
+
+/// Returns a section for a `CachedLayout`, whose texts only carry the extra data of the texts
+/// the glyphs were laid out from.
+fn extra_section<X: Clone>(extra: &[X]) -> Section<'static, X> {
+    let text = (extra.iter())
+        .map(|extra| Text {
+            text: "",
+            scale: PxScale::from(1.0),
+            font_id: FontId(0),
+            extra: extra.clone(),
+        })
+        .collect();
+    Section {
+        screen_position: (0.0, 0.0),
+        bounds: (f32::INFINITY, f32::INFINITY),
+        layout: Layout::default(),
+        text,
+    }
+}
+
+/// A section queued since the last `process_queued` call, which is passed on to glyph_brush
+/// when the queue is processed.
+pub(crate) enum QueuedSection<X> {
+    /// Glyphs laid out by the brush, identified by the key of their `CachedLayout`.
+    Cached {
+        key: u64,
+        glyphs: Arc<[SectionGlyph]>,
+        extra: Vec<X>,
+        bounds: Rect,
+    },
+    PrePositioned {
+        glyphs: Vec<SectionGlyph>,
+        extra: Vec<X>,
+        bounds: Rect,
+    },
+}
+
+impl<X: GlyphExtra> QueuedSection<X> {
+    #[inline]
+    pub fn glyphs(&self) -> &[SectionGlyph] {
+        match self {
+            QueuedSection::Cached { glyphs, .. } => glyphs,
+            QueuedSection::PrePositioned { glyphs, .. } => glyphs,
+        }
+    }
+
+    #[inline]
+    pub fn extra(&self) -> &[X] {
+        match self {
+            QueuedSection::Cached { extra, .. } | QueuedSection::PrePositioned { extra, .. } => {
+                extra
+            }
+        }
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> Rect {
+        match *self {
+            QueuedSection::Cached { bounds, .. } | QueuedSection::PrePositioned { bounds, .. } => {
+                bounds
+            }
+        }
+    }
+
+    /// Passes the section on to `glyph_brush`.
+    pub fn queue<F, H, V>(self, glyph_brush: &mut glyph_brush::GlyphBrush<V, X, F, H>)
+    where
+        F: Font,
+        H: BuildHasher,
+        V: Clone + 'static,
+    {
+        match self {
+            QueuedSection::Cached {
+                key,
+                glyphs,
+                extra,
+                bounds,
+            } => {
+                let layout = CachedLayout {
+                    key,
+                    glyphs: &glyphs,
+                    bounds,
+                };
+                queue_cached(glyph_brush, &layout, &extra);
+            }
+            QueuedSection::PrePositioned {
+                glyphs,
+                extra,
+                bounds,
+            } => glyph_brush.queue_pre_positioned(glyphs, extra, bounds),
+        }
+    }
+}
+
+/// Retains the pre-positioned `glyphs` in the caches of `glyph_brush` as if they had been
+/// queued in the last frame.
+pub(crate) fn keep_cached_pre_positioned<F, H, X, V>(
+    glyph_brush: &mut glyph_brush::GlyphBrush<V, X, F, H>,
+    glyphs: &[SectionGlyph],
+) where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: Clone + 'static,
+{
+    let section = Section {
+        screen_position: (0.0, 0.0),
+        bounds: (f32::INFINITY, f32::INFINITY),
+        layout: Layout::default(),
+        text: Vec::new(),
+    };
+    // queries the glyphs of the section, which caches them and keeps them cached
+    let _ = glyph_brush.glyphs_custom_layout(section, &PrePositioned { glyphs });
+}
+
+/// Returns `true` for characters of the emoji blocks and the joiners and modifiers of emoji
+/// sequences.
+fn is_emoji(character: char) -> bool {
+    matches!(
+        character as u32,
+        0x00A9
+            | 0x00AE
+            | 0x200D
+            | 0x203C
+            | 0x2049
+            | 0x20E3
+            | 0x2122
+            | 0x2139
+            | 0x2194..=0x21AA
+            | 0x2300..=0x23FF
+            | 0x24C2
+            | 0x25AA..=0x25FE
+            | 0x2600..=0x27BF
+            | 0x2934..=0x2935
+            | 0x2B05..=0x2B55
+            | 0x3030
+            | 0x303D
+            | 0x3297
+            | 0x3299
+            | 0xFE0F
+            | 0x1F000..=0x1FAFF
+            | 0xE0020..=0xE007F
+    )
+}
+
+/// Splits the laid out glyphs into lines, i.e. runs of glyphs sharing a baseline.
+pub(crate) fn lines(glyphs: &mut [SectionGlyph]) -> impl Iterator<Item = &mut [SectionGlyph]> {
+    let mut rest = glyphs;
+    std::iter::from_fn(move || {
+        let baseline = rest.first()?.glyph.position.y;
+        let len = rest
+            .iter()
+            .position(|glyph| glyph.glyph.position.y != baseline)
+            .unwrap_or(rest.len());
+        let (line, tail) = std::mem::take(&mut rest).split_at_mut(len);
+        rest = tail;
+        Some(line)
+    })
+}
+
+/// A font whose horizontal advances include a letter spacing.
+struct SpacedFont<'a, F> {
+    font: &'a F,
+    /// The letter spacing in font units.
+    spacing: f32,
+}
+
+impl<F: Font> Font for SpacedFont<'_, F> {
+    #[inline]
+    fn units_per_em(&self) -> Option<f32> {
+        self.font.units_per_em()
+    }
+
+    #[inline]
+    fn ascent_unscaled(&self) -> f32 {
+        self.font.ascent_unscaled()
+    }
+
+    #[inline]
+    fn descent_unscaled(&self) -> f32 {
+        self.font.descent_unscaled()
+    }
+
+    #[inline]
+    fn line_gap_unscaled(&self) -> f32 {
+        self.font.line_gap_unscaled()
+    }
+
+    #[inline]
+    fn italic_angle(&self) -> f32 {
+        self.font.italic_angle()
+    }
+
+    #[inline]
+    fn glyph_id(&self, c: char) -> GlyphId {
+        self.font.glyph_id(c)
+    }
+
+    #[inline]
+    fn h_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.h_advance_unscaled(id) + self.spacing
+    }
+
+    #[inline]
+    fn h_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.h_side_bearing_unscaled(id)
+    }
+
+    #[inline]
+    fn v_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.v_advance_unscaled(id)
+    }
+
+    #[inline]
+    fn v_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.v_side_bearing_unscaled(id)
+    }
+
+    #[inline]
+    fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> f32 {
+        self.font.kern_unscaled(first, second)
+    }
+
+    #[inline]
+    fn outline(&self, id: GlyphId) -> Option<Outline> {
+        self.font.outline(id)
+    }
+
+    #[inline]
+    fn glyph_count(&self) -> usize {
+        self.font.glyph_count()
+    }
+
+    #[inline]
+    fn codepoint_ids(&self) -> CodepointIdIter<'_> {
+        self.font.codepoint_ids()
+    }
+
+    #[inline]
+    fn glyph_raster_image2(&self, id: GlyphId, pixel_size: u16) -> Option<v2::GlyphImage<'_>> {
+        self.font.glyph_raster_image2(id, pixel_size)
+    }
+
+    #[inline]
+    fn glyph_svg_image(&self, id: GlyphId) -> Option<GlyphSvg<'_>> {
+        self.font.glyph_svg_image(id)
+    }
+
+    #[inline]
+    fn font_data(&self) -> &[u8] {
+        self.font.font_data()
+    }
+}
+
+impl<L: Hash> Hash for StyledLayout<'_, L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.layout.hash(state);
+        self.h_align.hash(state);
+        self.v_align.hash(state);
+        for spacing in &self.letter_spacing {
+            spacing.to_bits().hash(state);
+        }
+        self.line_height.hash(state);
+        self.options.hash(state);
+    }
+}
+
+impl<L: GlyphPositioner> GlyphPositioner for StyledLayout<'_, L> {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let spaced = self.letter_spacing.iter().any(|&spacing| spacing != 0.0);
+        let mut glyphs = if spaced {
+            self.calculate_spaced(fonts, geometry, sections)
+        } else {
+            self.layout.calculate_glyphs(fonts, geometry, sections)
+        };
+        self.apply_script_fonts(fonts, sections, &mut glyphs);
+        self.apply_fallback(fonts, sections, &mut glyphs);
+        // shaped after the fonts are mapped, so the presentation forms are looked up in the font
+        // drawing each glyph and aren't replaced by the base letters again
+        #[cfg(feature = "shaping")]
+        if self.shape {
+            shaping::shape(fonts, sections, &mut glyphs, |line, width| {
+                self.realign(line, width)
+            });
+        }
+        if spaced {
+            self.trim_letter_spacing(&mut glyphs);
+        }
+        self.apply_line_height(fonts, &mut glyphs);
+        self.apply_pixel_snapping(&mut glyphs);
+        glyphs
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect {
+        self.layout.bounds_rect(geometry)
+    }
+
+    fn recalculate_glyphs<F, S, P>(
+        &self,
+        previous: P,
+        change: GlyphChange,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+        P: IntoIterator<Item = SectionGlyph>,
+    {
+        match change {
+            // the wrapped layout would lose the styling when recalculating, so only reuse the
+            // previous glyphs if they just moved
+            GlyphChange::Geometry(old) if old.bounds == geometry.bounds => {
+                let adjustment = point(
+                    geometry.screen_position.0 - old.screen_position.0,
+                    geometry.screen_position.1 - old.screen_position.1,
+                );
+                let mut glyphs: Vec<_> = previous
+                    .into_iter()
+                    .map(|mut glyph| {
+                        glyph.glyph.position += adjustment;
+                        glyph
+                    })
+                    .collect();
+                self.apply_pixel_snapping(&mut glyphs);
+                glyphs
+            }
+            _ => self.calculate_glyphs(fonts, geometry, sections),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glyph_brush::ab_glyph::FontRef;
+
+    use super::*;
+
+    /// A glyph of the text `section_index` at `(x, y)`.
+    fn glyph(section_index: usize, x: f32, y: f32) -> SectionGlyph {
+        SectionGlyph {
+            section_index,
+            byte_index: 0,
+            glyph: GlyphId(1).with_scale_and_position(16.0, point(x, y)),
+            font_id: FontId(0),
+        }
+    }
+
+    fn positions(glyphs: &[SectionGlyph]) -> Vec<(f32, f32)> {
+        (glyphs.iter())
+            .map(|glyph| (glyph.glyph.position.x, glyph.glyph.position.y))
+            .collect()
+    }
+
+    fn styled<L>(
+        layout: &L,
+        h_align: HorizontalAlign,
+        v_align: VerticalAlign,
+    ) -> StyledLayout<'_, L> {
+        StyledLayout {
+            layout,
+            h_align,
+            v_align,
+            letter_spacing: Vec::new(),
+            line_height: Vec::new(),
+            shape: false,
+            options: LayoutOptions::default(),
+        }
+    }
+
+    fn line_breaks(line_breaker: impl LineBreaker, text: &str) -> Vec<LineBreak> {
+        line_breaker.line_breaks(text).collect()
+    }
+
+    #[test]
+    fn grapheme_line_breaks() {
+        // a combining mark forms a cluster with the space before it, which Unicode allows to
+        // break after
+        let text = "a \u{301}b";
+        let built_in = BuiltInLineBreaker::default();
+        assert_eq!(
+            line_breaks(built_in, text),
+            [LineBreak::Soft(2), LineBreak::Soft(5)]
+        );
+        assert_eq!(
+            line_breaks(GraphemeLineBreaker(built_in), text),
+            [LineBreak::Soft(5)]
+        );
+        // the breaks at cluster boundaries are kept
+        assert_eq!(
+            line_breaks(GraphemeLineBreaker(built_in), "e\u{301} x\ny"),
+            [LineBreak::Soft(4), LineBreak::Hard(6), LineBreak::Soft(7)]
+        );
+    }
+
+    #[test]
+    fn lines_share_a_baseline() {
+        let mut glyphs = vec![
+            glyph(0, 0.0, 10.0),
+            glyph(0, 8.0, 10.0),
+            glyph(1, 0.0, 30.0),
+            glyph(1, 0.0, 50.0),
+            glyph(1, 8.0, 50.0),
+        ];
+        let split: Vec<_> = lines(&mut glyphs).map(|line| positions(line)).collect();
+        assert_eq!(
+            split,
+            [
+                vec![(0.0, 10.0), (8.0, 10.0)],
+                vec![(0.0, 30.0)],
+                vec![(0.0, 50.0), (8.0, 50.0)],
+            ]
+        );
+        assert_eq!(lines(&mut []).count(), 0);
+    }
+
+    fn inconsolata() -> FontRef<'static> {
+        FontRef::try_from_slice(include_bytes!("../examples/Inconsolata-Regular.ttf")).unwrap()
+    }
+
+    /// The advance of the monospaced glyphs of Inconsolata at a scale of 16 pixels.
+    fn advance() -> f32 {
+        let font = inconsolata();
+        font.as_scaled(16.0).h_advance(font.glyph_id('a'))
+    }
+
+    /// Lays out `text` with a letter spacing of 2 pixels, returning the horizontal positions of
+    /// the glyphs and the number of lines.
+    fn lay_out_spaced(h_align: HorizontalAlign, text: &str, width: f32) -> (Vec<f32>, usize) {
+        let layout = Layout::default_wrap().h_align(h_align);
+        let styled = StyledLayout {
+            letter_spacing: vec![2.0],
+            ..styled(&layout, h_align, VerticalAlign::Top)
+        };
+        let sections = [SectionText {
+            text,
+            scale: 16.0.into(),
+            font_id: FontId(0),
+        }];
+        let geometry = SectionGeometry {
+            screen_position: (0.0, 0.0),
+            bounds: (width, f32::INFINITY),
+        };
+        let mut glyphs = styled.calculate_glyphs(&[inconsolata()], &geometry, &sections);
+        let positions = (glyphs.iter())
+            .map(|glyph| glyph.glyph.position.x)
+            .collect();
+        (positions, lines(&mut glyphs).count())
+    }
+
+    fn assert_close(values: &[f32], expected: &[f32]) {
+        let close = values.len() == expected.len()
+            && (values.iter().zip(expected)).all(|(a, b)| (a - b).abs() < 1e-3);
+        assert!(close, "{:?} isn't {:?}", values, expected);
+    }
+
+    #[test]
+    fn letter_spacing() {
+        let spaced = advance() + 2.0;
+        let (positions, _) = lay_out_spaced(HorizontalAlign::Left, "aaa", f32::INFINITY);
+        assert_close(&positions, &[0.0, spaced, 2.0 * spaced]);
+        // the spacing after the last glyph of a line doesn't count towards its width
+        let (positions, _) = lay_out_spaced(HorizontalAlign::Right, "aaa", f32::INFINITY);
+        assert_close(
+            &positions,
+            &[-2.0 * spaced - advance(), -spaced - advance(), -advance()],
+        );
+    }
+
+    #[test]
+    fn letter_spacing_wraps() {
+        // the text would fit on a single line without the spacing
+        let width = 9.0 * advance() + 1.0;
+        let (positions, lines) = lay_out_spaced(HorizontalAlign::Left, "aaaa aaaa", width);
+        assert_eq!(lines, 2);
+        for x in positions {
+            assert!(x + advance() <= width);
+        }
+    }
+
+    #[test]
+    fn line_height() {
+        let font = inconsolata();
+        let scaled = font.as_scaled(16.0);
+        let height = scaled.height() + scaled.line_gap();
+        let heightened = |v_align, line_height| {
+            let layout = StyledLayout {
+                line_height: vec![line_height, None],
+                ..styled(&(), HorizontalAlign::Left, v_align)
+            };
+            let mut glyphs = vec![glyph(0, 0.0, 0.0), glyph(1, 0.0, height)];
+            layout.apply_line_height(std::slice::from_ref(&font), &mut glyphs);
+            (glyphs.iter())
+                .map(|glyph| glyph.glyph.position.y / height)
+                .collect::<Vec<_>>()
+        };
+        let assert_lines = |lines: Vec<f32>, expected: [f32; 2]| {
+            let close = (lines.iter().zip(&expected)).all(|(a, b)| (a - b).abs() < 1e-4);
+            assert!(close, "lines at {:?}, expected {:?}", lines, expected);
+        };
+
+        assert_lines(heightened(VerticalAlign::Top, None), [0.0, 1.0]);
+        assert_lines(
+            heightened(VerticalAlign::Top, Some(LineHeight::Multiplier(2.0))),
+            [0.0, 2.0],
+        );
+        assert_lines(
+            heightened(VerticalAlign::Top, Some(LineHeight::Pixels(height / 2.0))),
+            [0.0, 0.5],
+        );
+        // the difference of the whole section is distributed by the vertical alignment
+        assert_lines(
+            heightened(VerticalAlign::Center, Some(LineHeight::Multiplier(2.0))),
+            [-0.5, 1.5],
+        );
+        assert_lines(
+            heightened(VerticalAlign::Bottom, Some(LineHeight::Multiplier(2.0))),
+            [-1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn cached_layouts_are_identified_by_their_key() {
+        let mut glyph_brush = glyph_brush::GlyphBrushBuilder::using_font(inconsolata())
+            .build::<(), glyph_brush::Extra>();
+        let id = inconsolata().glyph_id('a');
+        let glyphs: Vec<_> = (0..3)
+            .map(|index| SectionGlyph {
+                section_index: 0,
+                byte_index: index,
+                glyph: id.with_scale_and_position(16.0, point(index as f32 * 10.0, 20.0)),
+                font_id: FontId(0),
+            })
+            .collect();
+        let extra = [glyph_brush::Extra::default()];
+        let mut queue = |key: u64, glyphs: &[SectionGlyph]| {
+            let layout = CachedLayout {
+                key,
+                glyphs,
+                bounds: Rect {
+                    min: point(0.0, 0.0),
+                    max: point(100.0, 100.0),
+                },
+            };
+            queue_cached(&mut glyph_brush, &layout, &extra);
+            match glyph_brush.process_queued(|_, _| {}, |_| ()).unwrap() {
+                glyph_brush::BrushAction::Draw(vertices) => Some(vertices.len()),
+                glyph_brush::BrushAction::ReDraw => None,
+            }
+        };
+        assert_eq!(queue(1, &glyphs[..2]), Some(2));
+        // the glyphs cached under the key are reused
+        assert_eq!(queue(1, &glyphs), None);
+        assert_eq!(queue(2, &glyphs), Some(3));
+    }
+}
@@ -1,35 +1,230 @@
+pub use animation::TextAnimation;
+pub use arc::{ArcOrientation, TextArc};
+pub use atlas::{AtlasBackend, AtlasFormat, SdfSmoothing};
+pub use backend::TextRenderBackend;
 pub use builder::GlyphBrushBuilder;
+pub use cache::CacheUpdateMethod;
+pub use document::{Document, DocumentView};
+pub use error::Error;
+pub use extra::{BlendMode, FillTexture, GlyphExtra, Outline, Shadow, TextureFill};
+pub use font::{load_font, map_font, FontMetrics};
 pub use glyph_brush::ab_glyph;
 pub use glyph_brush::{
     BuiltInLineBreaker, Extra, FontId, GlyphCruncher, GlyphPositioner, HorizontalAlign, Layout,
-    LineBreak, LineBreaker, Section, SectionGeometry, SectionGlyph, SectionGlyphIter, SectionText,
-    Text, VerticalAlign,
+    LineBreak, LineBreaker, OwnedSection, OwnedText, Rectangle, Section, SectionGeometry,
+    SectionGlyph, SectionGlyphIter, SectionText, Text, VerticalAlign,
 };
+pub use image_font::{CustomGlyph, ImageFont};
+pub use layout::{GraphemeLineBreaker, LineHeight, MissingGlyph, Script};
+pub use lines::{Line, TextPosition, TextRange};
+pub use metrics::Metrics;
+pub use offscreen::create_warp_device;
+pub use passthrough::PassthroughBrush;
+pub use pick::PickResult;
+pub use pipeline::{BoundsClipping, Vertex, USER_CONSTANT_BUFFER_SLOT};
+pub use queue::{TextBatch, TextGroup, TextQueue};
+pub use rasterizer::{GlyphBitmap, GlyphRasterizer, RasterizedFont};
+pub use region::{ClipShape, Region};
+pub use shared_layout::SharedLayoutCache;
+pub use transform::{ScreenPosition, Transform};
+pub use vertex::{BrushVertex, GlyphQuad};
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::hash::BuildHasher;
+use std::num::NonZeroI32;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::{io, iter, mem};
 
-use ab_glyph::{Font, Rect};
-use glyph_brush::{BrushAction, BrushError, DefaultSectionHasher};
-use pipeline::{Pipeline, Vertex};
-use util::HResult;
+use ab_glyph::{point, Font, FontRef, PxScale, Rect};
+use font::FontNames;
+use glyph_brush::DefaultSectionHasher;
+use keyed::{KeyedSection, KeyedSections};
+use layout::{CachedLayout, LayoutOptions, QueuedSection, StyledLayout};
+use metrics::CountedLayout;
+use overlay::DebugOverlay;
+use pick::PickBoxes;
+use pipeline::{DrawState, Effects, Pipeline, PipelineOptions, VertexBatch};
+use region::ClippedLayout;
+use util::{hresult, HResult};
+use winapi::shared::dxgi::{IDXGISwapChain, DXGI_SWAP_CHAIN_DESC};
+use winapi::shared::winerror::DXGI_ERROR_INVALID_CALL;
 use winapi::um::d3d11::{
-    ID3D11DepthStencilView, ID3D11Device, ID3D11RenderTargetView, D3D11_DEPTH_STENCIL_DESC,
-    D3D11_FILTER, D3D11_RECT, D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION,
+    ID3D11Buffer, ID3D11DepthStencilState, ID3D11DepthStencilView, ID3D11Device,
+    ID3D11RenderTargetView, ID3D11ShaderResourceView, ID3D11Texture2D, D3D11_DEPTH_STENCIL_DESC,
+    D3D11_RECT,
 };
 use wio::com::ComPtr;
 
+/// Enters a span for the rest of the scope when the `tracing` feature is enabled. The fields
+/// aren't evaluated otherwise.
+macro_rules! trace_span {
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name $(, $field = $value)*).entered();
+    };
+}
+
+/// Logs the debug layer messages caused by the rest of the scope when the `validation` feature
+/// is enabled and the device has the debug layer.
+macro_rules! validation_scope {
+    ($pipeline:expr, $operation:literal) => {
+        #[cfg(feature = "validation")]
+        let _validation = $pipeline
+            .validation
+            .as_ref()
+            .map(|validation| validation.scope($operation));
+    };
+}
+
+mod animation;
+mod arc;
+mod atlas;
+mod backend;
 mod builder;
 mod cache;
+#[cfg(feature = "d3dcompiler")]
+pub mod compiler;
+mod constants;
+mod document;
+mod error;
+mod extra;
+mod font;
+mod glow;
+mod image_font;
+mod keyed;
+mod layout;
+mod lines;
+mod metrics;
+mod multithread;
+mod offscreen;
+mod overlay;
+mod passthrough;
+mod pick;
 mod pipeline;
+mod queue;
+mod rasterizer;
+mod region;
+mod runtime_compiler;
+#[cfg(feature = "shaping")]
+mod shaping;
+mod shared_layout;
+mod state_cache;
+#[cfg(feature = "tessellation")]
+pub mod tessellation;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transform;
 mod util;
+#[cfg(feature = "validation")]
+mod validation;
+mod vertex;
+#[cfg(feature = "windows")]
+pub mod windows_interop;
+
+/// A render target to draw processed text into, see
+/// [`draw_queued_to_targets`](struct.GlyphBrush.html#method.draw_queued_to_targets).
+#[derive(Clone, Copy)]
+pub struct DrawTarget<'a> {
+    pub target: &'a ComPtr<ID3D11RenderTargetView>,
+    /// Only used if the brush draws with [`DepthMode::Enabled`](enum.DepthMode.html).
+    pub depth_stencil_view: Option<&'a ComPtr<ID3D11DepthStencilView>>,
+    pub transform: Transform,
+    pub scissor: Option<D3D11_RECT>,
+    /// Clips the drawn text against a rounded rectangle or a mask texture.
+    pub clip_shape: Option<ClipShape<'a>>,
+}
 
-pub struct GlyphBrush<Depth, F = ab_glyph::FontArc, H = DefaultSectionHasher> {
-    pipeline: Pipeline<Depth>,
-    glyph_brush: glyph_brush::GlyphBrush<Vertex, Extra, F, H>,
+/// A depth stencil state overriding the one the brush has been built with for a single draw.
+#[derive(Clone, Copy)]
+pub enum DepthStencil<'a> {
+    /// Creates the state from the given description.
+    Desc(D3D11_DEPTH_STENCIL_DESC),
+    /// Uses an already created state.
+    State(&'a ComPtr<ID3D11DepthStencilState>),
 }
 
-impl<Depth, F: Font, H: BuildHasher> GlyphBrush<Depth, F, H> {
+/// Whether a brush depth tests its glyphs, see
+/// [`GlyphBrushBuilder::depth_mode`](struct.GlyphBrushBuilder.html#method.depth_mode).
+#[derive(Clone, Copy)]
+pub enum DepthMode {
+    /// Draws glyphs on top of everything without depth testing.
+    Disabled,
+    /// Draws glyphs with the depth stencil state created from the given description, testing
+    /// against the depth stencil view of the draw target.
+    Enabled(D3D11_DEPTH_STENCIL_DESC),
+}
+
+impl Default for DepthMode {
+    #[inline]
+    fn default() -> Self {
+        DepthMode::Disabled
+    }
+}
+
+/// Statistics about a `draw_queued` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawResult {
+    /// The number of glyph instances drawn.
+    pub glyphs: usize,
+    /// `true` if the vertices of the previous frame were reused as nothing changed.
+    pub redraw: bool,
+}
+
+pub struct GlyphBrush<F = ab_glyph::FontArc, H = DefaultSectionHasher, X = Extra, V = Vertex> {
+    pipeline: Pipeline<V>,
+    /// Produces each vertex with its draw state, which selects the draw call but isn't uploaded.
+    glyph_brush: glyph_brush::GlyphBrush<(V, DrawState), X, F, H>,
+    clip_stack: Vec<Region>,
+    bounds_clipping: BoundsClipping,
+    /// The sections queued since the last `process_queued` call, which are passed on to
+    /// glyph_brush when the queue is processed.
+    queued: Vec<QueuedSection<X>>,
+    /// The effects of the queued sections.
+    queued_effects: Effects,
+    /// The pick boxes of the queued glyphs, if picking is enabled.
+    pick_boxes: Option<PickBoxes>,
+    layout_options: LayoutOptions,
+    font_names: FontNames,
+    metrics: Metrics,
+    /// The layouts of the sections queued with a layout by `shared_layout::key`.
+    layouts: KeyedSections<X>,
+    keyed_sections: KeyedSections<X>,
+    /// The layouts of document lines at the origin, see `queue_document`.
+    document_lines: KeyedSections<X>,
+    /// The layouts of the sections queued with an offset by hash, see `queue_scrolled`.
+    offset_layouts: KeyedSections<X>,
+    /// The runs cached with `cache_run`.
+    runs: HashMap<u64, KeyedSection<X>>,
+    /// The swapchain the brush has been built for, if any.
+    swapchain: Option<ComPtr<IDXGISwapChain>>,
+    /// The debug overlay requested since the last `process_queued` call.
+    debug_overlay: Option<DebugOverlay<X>>,
+    /// The debug overlay whose quad ends the uploaded vertices, if any.
+    uploaded_overlay: Option<DebugOverlay<X>>,
+    shared_layouts: Option<SharedLayoutCache>,
+    /// The vertices of the batches of the last `process_batches` call by the id of the batch.
+    batches: HashMap<u64, VertexBatch<V>>,
+    /// The runs of processed vertices belonging to the brush, for `None`, or to a batch, as
+    /// their owner and length. The vertices following the runs belong to the brush.
+    vertex_owners: Vec<(Option<u64>, usize)>,
+}
+
+/// A brush as the owner of the device resources, the pipeline and the glyph cache shared by
+/// the [`TextBatch`](struct.TextBatch.html)es it draws, see
+/// [`process_batches`](struct.GlyphBrush.html#method.process_batches).
+pub type TextRenderer<F = ab_glyph::FontArc, H = DefaultSectionHasher, X = Extra, V = Vertex> =
+    GlyphBrush<F, H, X, V>;
+
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
+where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
     /// Queues a section/layout to be processed by the next call of
     /// [`process_queued`](struct.GlyphBrush.html#method.process_queued). Can be called multiple
     /// times to queue multiple sections for drawing.
@@ -38,9 +233,35 @@ impl<Depth, F: Font, H: BuildHasher> GlyphBrush<Depth, F, H> {
     #[inline]
     pub fn queue<'a, S>(&mut self, section: S)
     where
-        S: Into<Cow<'a, Section<'a>>>,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
     {
-        self.glyph_brush.queue(section)
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.queue_custom_layout(section, &layout)
+    }
+
+    /// Queues all sections of a [`TextQueue`](struct.TextQueue.html), emptying it.
+    ///
+    /// This allows filling the queue from multiple threads while the brush stays on the render
+    /// thread.
+    pub fn queue_from(&mut self, queue: &TextQueue<X>) {
+        for section in queue.take() {
+            self.queue(section.to_borrowed());
+        }
+    }
+
+    /// Queues all sections of a [`TextBatch`](struct.TextBatch.html) in the order they were
+    /// pushed, emptying it.
+    ///
+    /// Batches can be filled on any thread while the brush, which owns the device resources
+    /// and the glyph cache, stays on the render thread. Sections of multiple batches are drawn
+    /// in the order the batches are submitted. Use
+    /// [`process_batches`](#method.process_batches) instead to draw the batches separately.
+    pub fn submit(&mut self, batch: &mut TextBatch<X>) {
+        for section in batch.drain() {
+            self.queue(section.to_borrowed());
+        }
     }
 
     /// Queues a section/layout to be processed by the next call of
@@ -50,258 +271,1796 @@ impl<Depth, F: Font, H: BuildHasher> GlyphBrush<Depth, F, H> {
     /// Used to provide custom `GlyphPositioner` logic, if using built-in
     /// [`Layout`](enum.Layout.html) simply use [`queue`](struct.GlyphBrush.html#method.queue)
     ///
-    /// Benefits from caching, see [caching behaviour](#caching-behaviour).
+    /// Benefits from caching, see [caching behaviour](#caching-behaviour).
+    #[inline]
+    pub fn queue_custom_layout<'a, S, G>(&mut self, section: S, custom_layout: &G)
+    where
+        G: GlyphPositioner,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self.queue_clipped(&section, &layout),
+            None => self.queue_clipped(&section, custom_layout),
+        }
+    }
+
+    fn queue_clipped<G: GlyphPositioner>(&mut self, section: &Section<'_, X>, layout: &G) {
+        match self.clip() {
+            Some(clip) => self.queue_layout(section, &ClippedLayout { layout, clip }),
+            None => self.queue_layout(section, layout),
+        }
+    }
+
+    fn queue_layout<G: GlyphPositioner>(&mut self, section: &Section<'_, X>, layout: &G) {
+        let layout = &CountedLayout::new(layout);
+        let key = shared_layout::key(section, layout, &self.layout_options);
+        let (glyphs, bounds) = self.cached_layout(key, section, layout);
+        self.metrics.record_section(layout);
+        let extra = section.text.iter().map(|text| text.extra.clone()).collect();
+        let queued = QueuedSection::Cached {
+            key,
+            glyphs,
+            extra,
+            bounds,
+        };
+        self.push_queued(queued, Some(key));
+    }
+
+    /// Returns the glyphs and bounds of `section` laid out with `layout`, identified by `key`.
+    /// The layout is reused from the last frame or from the shared layout cache if possible.
+    fn cached_layout<G: GlyphPositioner>(
+        &mut self,
+        key: u64,
+        section: &Section<'_, X>,
+        layout: &G,
+    ) -> (Arc<[SectionGlyph]>, Rect) {
+        if let Some(cached) = self.layouts.get(key, None) {
+            return (Arc::clone(&cached.glyphs), cached.bounds);
+        }
+        let shared = (self.shared_layouts.as_ref()).and_then(|shared| shared.get(key));
+        let (glyphs, bounds) = shared.unwrap_or_else(|| {
+            let geometry = SectionGeometry::from(section);
+            let glyphs: Arc<[SectionGlyph]> = layout
+                .calculate_glyphs(self.glyph_brush.fonts(), &geometry, &section.text)
+                .into();
+            let bounds = layout.bounds_rect(&geometry);
+            if let Some(shared) = &self.shared_layouts {
+                shared.insert(key, Arc::clone(&glyphs), bounds);
+            }
+            (glyphs, bounds)
+        });
+        let cached = KeyedSection::new(Arc::clone(&glyphs), Vec::new(), bounds, None);
+        self.layouts.insert(key, cached);
+        (glyphs, bounds)
+    }
+
+    /// Appends a section to the queue. `pick_key` identifies the section for `glyph_quads`.
+    fn push_queued(&mut self, queued: QueuedSection<X>, pick_key: Option<u64>) {
+        for extra in queued.extra() {
+            self.queued_effects.include(extra);
+        }
+        if let Some(pick_boxes) = &mut self.pick_boxes {
+            let pre_positioned = matches!(queued, QueuedSection::PrePositioned { .. });
+            let (fonts, index) = (self.glyph_brush.fonts(), self.queued.len());
+            let (glyphs, bounds) = (queued.glyphs(), queued.bounds());
+            pick_boxes.record(index, pick_key, pre_positioned, fonts, glyphs, bounds);
+        }
+        self.queued.push(queued);
+    }
+
+    /// Queues a section identified by `id`, reusing its glyphs from the last time it was queued
+    /// instead of hashing and laying out its text again.
+    ///
+    /// The section is only laid out when `id` is queued for the first time, after
+    /// [`mark_dirty`](#method.mark_dirty) has been called for it or when the clip region
+    /// changed, so changes to the section are ignored until it is marked dirty. Ids which
+    /// aren't queued for a frame are forgotten by the next
+    /// [`process_queued`](#method.process_queued) call.
+    ///
+    /// The glyphs and vertices of a reused section are cached by its id, so queueing it only
+    /// hashes the id and the extra data of its texts.
+    pub fn queue_with_id<'a, S>(&mut self, id: u64, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let clip = self.clip();
+        if let Some(keyed) = self.keyed_sections.get(id, clip) {
+            let queued = QueuedSection::Cached {
+                key: keyed.key,
+                glyphs: Arc::clone(&keyed.glyphs),
+                extra: keyed.extra.clone(),
+                bounds: keyed.bounds,
+            };
+            self.metrics.record_reused_section();
+            return self.push_queued(queued, None);
+        }
+
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        let (glyphs, bounds) = match StyledLayout::new(&layout, &section, &self.layout_options) {
+            Some(styled) => self.layout_clipped(&section, &styled),
+            None => self.layout_clipped(&section, &layout),
+        };
+        let extra: Vec<_> = section.text.iter().map(|text| text.extra.clone()).collect();
+        let keyed = KeyedSection::new(glyphs, extra.clone(), bounds, clip);
+        let glyphs = Arc::clone(&keyed.glyphs);
+        let key = self.keyed_sections.insert(id, keyed);
+        let queued = QueuedSection::Cached {
+            key,
+            glyphs,
+            extra,
+            bounds,
+        };
+        self.push_queued(queued, None)
+    }
+
+    /// Queues a section with per-glyph animations at `time` seconds, e.g. a typewriter reveal
+    /// of dialog text. The section is laid out again on every call, so its text should be
+    /// queued each frame with an increasing time, e.g. the time set by
+    /// [`set_time`](#method.set_time).
+    ///
+    /// The animations are applied in order, so e.g. a wave can be combined with a fade-in.
+    pub fn queue_animated<'a, S>(&mut self, section: S, animations: &[TextAnimation], time: f32)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        let (mut glyphs, bounds) = match StyledLayout::new(&layout, &section, &self.layout_options)
+        {
+            Some(styled) => self.layout_clipped(&section, &styled),
+            None => self.layout_clipped(&section, &layout),
+        };
+        let mut extra: Vec<_> = section.text.iter().map(|text| text.extra.clone()).collect();
+        let margin = animation::animate(animations, time, &mut glyphs, &mut extra);
+        let bounds = Rect {
+            min: point(bounds.min.x - margin, bounds.min.y - margin),
+            max: point(bounds.max.x + margin, bounds.max.y + margin),
+        };
+        self.queue_pre_positioned(glyphs, extra, bounds)
+    }
+
+    /// Queues a section laid out on a single line along a circular arc, e.g. the label of a
+    /// gauge or the text around a stamp.
+    ///
+    /// The horizontal alignment of the section's layout aligns the text within the length of
+    /// the arc, its position, bounds and line breaks are ignored.
+    pub fn queue_on_arc<'a, S>(&mut self, section: S, arc: &TextArc)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let h_align = match section.layout {
+            Layout::SingleLine { h_align, .. } | Layout::Wrap { h_align, .. } => h_align,
+        };
+        let mut straight = section.into_owned();
+        straight.screen_position = (0.0, 0.0);
+        straight.bounds = (f32::INFINITY, f32::INFINITY);
+        straight.layout = Layout::default_single_line();
+        let layout = layout::grapheme_layout(straight.layout);
+        let (mut glyphs, _) = match StyledLayout::new(&layout, &straight, &self.layout_options) {
+            Some(styled) => self.layout_counted(&straight, &styled),
+            None => self.layout_counted(&straight, &layout),
+        };
+        let extra: Vec<_> = straight.text.into_iter().map(|text| text.extra).collect();
+        let rotate = X::supports_rotation();
+        let mut rotations = Vec::new();
+        let fonts = self.glyph_brush.fonts();
+        let bounds = arc::bend(arc, h_align, fonts, &mut glyphs, rotate, &mut rotations);
+        if !rotate {
+            return self.queue_pre_positioned(glyphs, extra, bounds);
+        }
+        // every glyph gets extra data of its own carrying its rotation
+        let mut rotated = Vec::with_capacity(glyphs.len());
+        for (glyph, &rotation) in glyphs.iter_mut().zip(&rotations) {
+            rotated.push(extra[glyph.section_index].rotated(rotation));
+            glyph.section_index = rotated.len() - 1;
+        }
+        self.queue_pre_positioned(glyphs, rotated, bounds)
+    }
+
+    /// Makes the next [`queue_with_id`](#method.queue_with_id) call for `id` lay out its
+    /// section again, e.g. after its text changed.
+    #[inline]
+    pub fn mark_dirty(&mut self, id: u64) {
+        self.keyed_sections.mark_dirty(id);
+    }
+
+    /// Lays out a section once and caches its glyphs under `key`, replacing any run cached
+    /// under the same key. The run is then queued by key with [`queue_run`](#method.queue_run)
+    /// without being laid out again.
+    ///
+    /// Runs are kept until they are removed or the fonts of the brush change.
+    pub fn cache_run<'a, S>(&mut self, key: u64, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        let (glyphs, bounds) = match StyledLayout::new(&layout, &section, &self.layout_options) {
+            Some(styled) => self.layout_counted(&section, &styled),
+            None => self.layout_counted(&section, &layout),
+        };
+        let extra = section.text.iter().map(|text| text.extra.clone()).collect();
+        self.runs
+            .insert(key, KeyedSection::new(glyphs, extra, bounds, None));
+    }
+
+    /// Queues the run cached under `key`, moved by `offset` pixels, and returns `false` if
+    /// there is no such run. The run is clipped to the current clip region.
+    ///
+    /// Unlike the sections of [`queue_with_id`](#method.queue_with_id), the glyphs of the run
+    /// are moved by the offset and copied into the queue on every call, only their layout is
+    /// reused.
+    pub fn queue_run(&mut self, key: u64, offset: (f32, f32)) -> bool {
+        let run = match self.runs.get(&key) {
+            Some(run) => run,
+            None => return false,
+        };
+        let (dx, dy) = offset;
+        let glyphs = (run.glyphs.iter().cloned())
+            .map(|mut glyph| {
+                glyph.glyph.position.x += dx;
+                glyph.glyph.position.y += dy;
+                glyph
+            })
+            .collect();
+        let mut bounds = run.bounds;
+        bounds.min.x += dx;
+        bounds.min.y += dy;
+        bounds.max.x += dx;
+        bounds.max.y += dy;
+        let extra = run.extra.clone();
+        self.metrics.record_reused_section();
+        self.queue_pre_positioned(glyphs, extra, bounds);
+        true
+    }
+
+    /// Queues a section moved by `offset` pixels after layout and clipped to `clip`, e.g. the
+    /// scrolled contents of a chat or console window.
+    ///
+    /// The offset isn't part of the section, so scrolling reuses the cached layout of an
+    /// unchanged section instead of laying it out again. Lines scrolled entirely outside of
+    /// `clip` are dropped before their glyphs are rasterized. The clip is intersected with the
+    /// current clip region.
+    pub fn queue_scrolled<'a, S>(&mut self, section: S, offset: (f32, f32), clip: Region)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        self.push_clip(clip);
+        self.queue_offset(&section.into(), offset);
+        self.pop_clip();
+    }
+
+    /// Queues a section moved by `offset` after layout, dropping the lines outside of the
+    /// current clip region.
+    fn queue_offset(&mut self, section: &Section<'_, X>, offset: (f32, f32)) {
+        let (glyphs, extra, bounds) = self.layout_offset(section);
+        let visible = self.clip().map(Region::to_rect);
+        let (dx, dy) = offset;
+        let glyphs = (glyphs.into_iter())
+            .filter_map(|mut glyph| {
+                glyph.glyph.position.x += dx;
+                glyph.glyph.position.y += dy;
+                // the glyph lies within its scale above and below its baseline
+                let (y, scale) = (glyph.glyph.position.y, glyph.glyph.scale.y);
+                // `is_none_or` would raise the minimum supported Rust version to 1.82
+                #[allow(clippy::unnecessary_map_or)]
+                let visible = visible.map_or(true, |visible| {
+                    y + scale > visible.min.y && y - scale < visible.max.y
+                });
+                visible.then_some(glyph)
+            })
+            .collect();
+        let bounds = Rect {
+            min: point(bounds.min.x + dx, bounds.min.y + dy),
+            max: point(bounds.max.x + dx, bounds.max.y + dy),
+        };
+        self.queue_pre_positioned(glyphs, extra, bounds);
+    }
+
+    /// Returns the glyphs, extra data and bounds of a section queued with an offset.
+    ///
+    /// The layouts are cached by the brush rather than by glyph_brush, which would keep the
+    /// glyphs of the whole section rasterized even if most of its lines are scrolled out.
+    fn layout_offset(&mut self, section: &Section<'_, X>) -> (Vec<SectionGlyph>, Vec<X>, Rect) {
+        let layout = layout::grapheme_layout(section.layout);
+        let key = shared_layout::key(section, &layout, &self.layout_options);
+        let extra: Vec<_> = section.text.iter().map(|text| text.extra.clone()).collect();
+        if let Some(cached) = self.offset_layouts.get(key, None) {
+            self.metrics.record_reused_section();
+            return (cached.glyphs.to_vec(), extra, cached.bounds);
+        }
+        let (glyphs, bounds) = match StyledLayout::new(&layout, section, &self.layout_options) {
+            Some(styled) => self.calculate_counted(section, &styled),
+            None => self.calculate_counted(section, &layout),
+        };
+        self.offset_layouts.insert(
+            key,
+            KeyedSection::new(glyphs.clone(), Vec::new(), bounds, None),
+        );
+        (glyphs, extra, bounds)
+    }
+
+    /// Queues the sections of a [`TextGroup`](struct.TextGroup.html) moved by its offset and
+    /// clipped to its clip region, or only keeps their layouts cached if the group is hidden.
+    ///
+    /// Like [`queue_scrolled`](#method.queue_scrolled), moving the group doesn't lay out its
+    /// sections again.
+    pub fn queue_group(&mut self, group: &TextGroup<X>) {
+        if !group.is_visible() {
+            return self.keep_cached_group(group);
+        }
+        if let Some(clip) = group.clip() {
+            self.push_clip(clip);
+        }
+        for section in group.sections() {
+            self.queue_offset(&section.to_borrowed(), group.offset());
+        }
+        if group.clip().is_some() {
+            self.pop_clip();
+        }
+    }
+
+    /// Retains the layouts of the sections of a group in the cache as if the group had been
+    /// queued in the last draw-frame, e.g. while it is hidden.
+    pub fn keep_cached_group(&mut self, group: &TextGroup<X>) {
+        for section in group.sections() {
+            let (glyphs, ..) = self.layout_offset(&section.to_borrowed());
+            self.keep_cached_pre_positioned(&glyphs);
+        }
+    }
+
+    /// Removes the run cached under `key`, returning `false` if there is no such run.
+    #[inline]
+    pub fn remove_run(&mut self, key: u64) -> bool {
+        self.runs.remove(&key).is_some()
+    }
+
+    #[inline]
+    pub fn clear_runs(&mut self) {
+        self.runs.clear();
+    }
+
+    /// Queues the lines of `document` visible in the viewport of `view`, clipped to the
+    /// viewport, and returns the range of queued lines.
+    ///
+    /// Lines aren't wrapped, so finding the visible lines doesn't depend on the length of the
+    /// document. The layout of each line is cached independently of its position, so lines
+    /// staying visible while scrolling aren't laid out again.
+    pub fn queue_document(&mut self, document: &Document, view: &DocumentView<X>) -> Range<usize> {
+        let metrics = self.font_metrics(view.font_id, view.scale);
+        let line_height = metrics.line_height();
+        let lines = document.visible_lines(view.region.height as f32, view.scroll, line_height);
+        self.push_clip(view.region);
+        for index in lines.clone() {
+            let line = document.line(index).unwrap_or_default();
+            let key = document::line_key(line, view.font_id, view.scale);
+            let glyphs = match self.document_lines.get(key, None) {
+                Some(cached) => {
+                    self.metrics.record_reused_section();
+                    cached.glyphs.to_vec()
+                }
+                None => {
+                    let section = Section {
+                        screen_position: (0.0, 0.0),
+                        bounds: (f32::INFINITY, f32::INFINITY),
+                        layout: Layout::default_single_line(),
+                        text: vec![Text::<Extra>::new(line)
+                            .with_font_id(view.font_id)
+                            .with_scale(view.scale)
+                            .with_extra(view.extra.clone())],
+                    };
+                    let layout = layout::grapheme_layout(section.layout);
+                    let (glyphs, _) =
+                        match StyledLayout::new(&layout, &section, &self.layout_options) {
+                            Some(styled) => self.layout_counted(&section, &styled),
+                            None => self.layout_counted(&section, &layout),
+                        };
+                    let cached =
+                        KeyedSection::new(glyphs.clone(), Vec::new(), Rect::default(), None);
+                    self.document_lines.insert(key, cached);
+                    glyphs
+                }
+            };
+
+            let mut y = view.region.y as f32 + index as f32 * line_height - view.scroll;
+            if self.layout_options.subpixel_variants.is_some() {
+                y = y.round();
+            }
+            let glyphs = glyphs
+                .into_iter()
+                .map(|mut glyph| {
+                    glyph.glyph.position.x += view.region.x as f32;
+                    glyph.glyph.position.y += y;
+                    glyph
+                })
+                .collect();
+            self.queue_pre_positioned(glyphs, vec![view.extra.clone()], view.region.to_rect());
+        }
+        self.pop_clip();
+        lines
+    }
+
+    /// Returns the glyphs and bounds of a section laid out within the current clip region.
+    fn layout_clipped<G>(
+        &mut self,
+        section: &Section<'_, X>,
+        layout: &G,
+    ) -> (Vec<SectionGlyph>, Rect)
+    where
+        G: GlyphPositioner,
+    {
+        match self.clip() {
+            Some(clip) => self.layout_counted(section, &ClippedLayout { layout, clip }),
+            None => self.layout_counted(section, layout),
+        }
+    }
+
+    fn layout_counted<G>(
+        &mut self,
+        section: &Section<'_, X>,
+        layout: &G,
+    ) -> (Vec<SectionGlyph>, Rect)
+    where
+        G: GlyphPositioner,
+    {
+        let layout = &CountedLayout::new(layout);
+        let bounds = layout.bounds_rect(&SectionGeometry::from(section));
+        let glyphs = self
+            .glyph_brush
+            .glyphs_custom_layout(section, layout)
+            .cloned()
+            .collect();
+        self.metrics.record_section(layout);
+        (glyphs, bounds)
+    }
+
+    /// Returns the glyphs and bounds of a section laid out without glyph_brush, which would keep
+    /// its glyphs in the cache.
+    fn calculate_counted<G>(
+        &mut self,
+        section: &Section<'_, X>,
+        layout: &G,
+    ) -> (Vec<SectionGlyph>, Rect)
+    where
+        G: GlyphPositioner,
+    {
+        let layout = &CountedLayout::new(layout);
+        let geometry = SectionGeometry::from(section);
+        let glyphs = layout.calculate_glyphs(self.glyph_brush.fonts(), &geometry, &section.text);
+        self.metrics.record_section(layout);
+        (glyphs, layout.bounds_rect(&geometry))
+    }
+
+    /// Queues pre-positioned glyphs to be processed by the next call of
+    /// [`process_queued`](struct.GlyphBrush.html#method.process_queued). Can be called multiple
+    /// times.
+    #[inline]
+    pub fn queue_pre_positioned(&mut self, glyphs: Vec<SectionGlyph>, extra: Vec<X>, bounds: Rect) {
+        let bounds = match self.clip() {
+            Some(clip) => region::intersect_rect(bounds, clip.to_rect()),
+            None => bounds,
+        };
+        let queued = QueuedSection::PrePositioned {
+            glyphs,
+            extra,
+            bounds,
+        };
+        self.push_queued(queued, None)
+    }
+
+    /// Retains pre-positioned glyphs in the cache as if they had been queued with
+    /// [`queue_pre_positioned`](#method.queue_pre_positioned) in the last draw-frame, e.g.
+    /// while their text is temporarily hidden.
+    #[inline]
+    pub fn keep_cached_pre_positioned(&mut self, glyphs: &[SectionGlyph]) {
+        layout::keep_cached_pre_positioned(&mut self.glyph_brush, glyphs)
+    }
+
+    /// Retains the section in the cache as if it had been used in the last draw-frame.
+    ///
+    /// Should not generally be necessary, see [caching behaviour](#caching-behaviour).
+    #[inline]
+    pub fn keep_cached_custom_layout<'a, S, G>(&mut self, section: S, custom_layout: &G)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+        G: GlyphPositioner,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self.keep_cached_clipped(&section, &layout),
+            None => self.keep_cached_clipped(&section, custom_layout),
+        }
+    }
+
+    fn keep_cached_clipped<G: GlyphPositioner>(&mut self, section: &Section<'_, X>, layout: &G) {
+        match self.clip() {
+            Some(clip) => self.keep_cached_layout(section, &ClippedLayout { layout, clip }),
+            None => self.keep_cached_layout(section, layout),
+        }
+    }
+
+    fn keep_cached_layout<G: GlyphPositioner>(&mut self, section: &Section<'_, X>, layout: &G) {
+        let key = shared_layout::key(section, layout, &self.layout_options);
+        let (glyphs, bounds) = self.cached_layout(key, section, layout);
+        let extra: Vec<_> = section.text.iter().map(|text| text.extra.clone()).collect();
+        let layout = CachedLayout {
+            key,
+            glyphs: &glyphs,
+            bounds,
+        };
+        layout::keep_cached(&mut self.glyph_brush, &layout, &extra);
+    }
+
+    /// Retains the section in the cache as if it had been used in the last draw-frame.
+    ///
+    /// Should not generally be necessary, see [caching behaviour](#caching-behaviour).
+    #[inline]
+    pub fn keep_cached<'a, S>(&mut self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.keep_cached_custom_layout(section, &layout)
+    }
+
+    /// Lays out the section and returns its lines and their words, e.g. for word-level hit
+    /// targets, word selection or per-line decorations.
+    pub fn lines<'a, S>(&mut self, section: S) -> Vec<Line>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let mut glyphs: Vec<_> = self.glyphs(section.as_ref()).cloned().collect();
+        lines::lines(self.fonts(), &section, &mut glyphs)
+    }
+
+    /// Enables or disables recording the glyphs of queued sections for
+    /// [`pick`](#method.pick) and [`glyph_quads`](#method.glyph_quads).
+    ///
+    /// While enabled, queueing a section additionally looks up its laid out glyphs and hashes
+    /// it. Disabled by default.
+    pub fn set_picking(&mut self, enabled: bool) {
+        if enabled != self.pick_boxes.is_some() {
+            self.pick_boxes = if enabled {
+                Some(PickBoxes::default())
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Returns the topmost glyph of the last [`process_queued`](#method.process_queued) call at
+    /// `point`, in the coordinate space of the sections.
+    ///
+    /// A glyph covers the area from its origin to its advance horizontally and from the ascent
+    /// to the descent of its font vertically, restricted to the bounds of its section. Requires
+    /// picking to be enabled with [`set_picking`](#method.set_picking) before queueing.
+    pub fn pick(&self, point: (f32, f32)) -> Option<PickResult> {
+        self.pick_boxes
+            .as_ref()?
+            .pick(ab_glyph::point(point.0, point.1))
+    }
+
+    /// Pushes a clip region onto the clip stack.
+    ///
+    /// Sections queued while a clip is active are clipped to the intersection of all regions on
+    /// the stack. Regions are in the coordinate space of the sections, which matches pixels
+    /// when drawing with the default [`orthographic_projection`](fn.orthographic_projection.html).
+    pub fn push_clip(&mut self, region: Region) {
+        let region = match self.clip() {
+            Some(clip) => clip.intersect(region),
+            None => region,
+        };
+        self.clip_stack.push(region);
+    }
+
+    /// Pops the most recently pushed clip region off the clip stack.
+    pub fn pop_clip(&mut self) -> Option<Region> {
+        self.clip_stack.pop()
+    }
+
+    /// Returns the currently active clip region, the intersection of all pushed regions.
+    #[inline]
+    pub fn clip(&self) -> Option<Region> {
+        self.clip_stack.last().copied()
+    }
+
+    /// Returns the available fonts.
+    ///
+    /// The `FontId` corresponds to the index of the font data.
+    #[inline]
+    pub fn fonts(&self) -> &[F] {
+        self.glyph_brush.fonts()
+    }
+
+    pub fn add_font(&mut self, font: F) -> FontId {
+        self.glyph_brush.add_font(font)
+    }
+
+    /// Returns the vertical metrics of the font `font_id` at `scale`, e.g. to reserve space for
+    /// text before laying it out.
+    pub fn font_metrics(&self, font_id: FontId, scale: impl Into<PxScale>) -> FontMetrics {
+        FontMetrics::new(&self.fonts()[font_id.0], scale.into())
+    }
+
+    /// Adds a font that can be looked up by `name` with [`font_id`](#method.font_id). A font
+    /// previously registered under the same name keeps its id but can't be looked up anymore.
+    pub fn add_font_named(&mut self, name: impl Into<String>, font: F) -> FontId {
+        let font_id = self.add_font(font);
+        self.font_names.insert(name.into(), font_id);
+        font_id
+    }
+
+    /// Returns the id of the font added under `name` with
+    /// [`add_font_named`](#method.add_font_named).
+    #[inline]
+    pub fn font_id(&self, name: &str) -> Option<FontId> {
+        self.font_names.get(name)
+    }
+
+    /// Maps the face at `index` of a font file into memory and adds it, see
+    /// [`map_font`](fn.map_font.html).
+    pub fn add_font_file(&mut self, path: impl AsRef<Path>, index: u32) -> io::Result<FontId>
+    where
+        F: From<FontRef<'static>>,
+    {
+        Ok(self.add_font(font::map_font(path, index)?.into()))
+    }
+
+    /// Replaces the font `font_id`, returning the previous font, e.g. to switch themes.
+    ///
+    /// All cached layouts and glyphs are discarded, as are the queued sections and the batch of
+    /// the last [`process_queued`](#method.process_queued) call.
+    pub fn replace_font(&mut self, font_id: FontId, font: F) -> F
+    where
+        F: Clone,
+        H: Clone,
+    {
+        let previous = font::rebuild_fonts(&mut self.glyph_brush, |fonts| {
+            mem::replace(&mut fonts[font_id.0], font)
+        });
+        self.discard_fonts_cache();
+        previous
+    }
+
+    /// Removes the font `font_id`, returning it. The ids of the fonts added after it are
+    /// decremented by one.
+    ///
+    /// All cached layouts and glyphs are discarded, as are the queued sections and the batch of
+    /// the last [`process_queued`](#method.process_queued) call.
+    pub fn remove_font(&mut self, font_id: FontId) -> F
+    where
+        F: Clone,
+        H: Clone,
+    {
+        let removed = font::rebuild_fonts(&mut self.glyph_brush, |fonts| fonts.remove(font_id.0));
+        self.layout_options.remove_font(font_id);
+        self.font_names.remove_font(font_id);
+        self.discard_fonts_cache();
+        removed
+    }
+
+    /// Discards the state referring to the glyphs of the previous fonts.
+    fn discard_fonts_cache(&mut self) {
+        self.clear_queue();
+        if let Some(pick_boxes) = &mut self.pick_boxes {
+            pick_boxes.clear();
+        }
+        self.layouts.clear();
+        self.keyed_sections.clear();
+        self.document_lines.clear();
+        self.offset_layouts.clear();
+        self.runs.clear();
+        // the fonts of the sharing brushes have to change alike, their layouts are outdated too
+        if let Some(shared) = &self.shared_layouts {
+            shared.clear();
+        }
+        // the uploaded quads point into the glyph cache of the previous fonts
+        self.pipeline.set_effects(Effects::default());
+        self.batches.clear();
+        self.vertex_owners.clear();
+        self.uploaded_overlay = None;
+        if let Err(error) = self.pipeline.upload(&[]) {
+            log::warn!("Failed to discard the glyph quads: {}", error);
+        }
+    }
+
+    /// Discards the sections queued since the last [`process_queued`](#method.process_queued)
+    /// call without drawing them. The batch of the last call is kept, so it can still be drawn.
+    ///
+    /// The layouts of the discarded sections stay cached for the next frame. Sections queued
+    /// before a failed `process_queued` call have already been passed on to glyph_brush and
+    /// stay queued.
+    pub fn clear_queue(&mut self) {
+        self.queued.clear();
+        self.queued_effects = Effects::default();
+        self.debug_overlay = None;
+        if let Some(pick_boxes) = &mut self.pick_boxes {
+            pick_boxes.clear_queued();
+        }
+    }
+
+    /// Returns the current dimensions of the glyph cache, including the rows the texture
+    /// hasn't grown into yet if a
+    /// [height is reserved](struct.GlyphBrushBuilder.html#method.reserve_cache_height).
+    #[inline]
+    pub fn texture_dimensions(&self) -> (u32, u32) {
+        self.glyph_brush.texture_dimensions()
+    }
+
+    /// Returns the caching counters accumulated since the brush was created or
+    /// [`reset_metrics`](#method.reset_metrics) was last called.
+    #[inline]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Resets all counters of [`metrics`](#method.metrics) to zero.
+    #[inline]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = Metrics::default();
+    }
+
+    /// Returns the number of sections and pre-positioned glyph batches queued since the last
+    /// [`process_queued`](#method.process_queued) call.
+    #[inline]
+    pub fn queued_sections(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Returns `true` if anything has been queued since the last
+    /// [`process_queued`](#method.process_queued) call.
+    #[inline]
+    pub fn has_queued_sections(&self) -> bool {
+        !self.queued.is_empty()
+    }
+
+    /// Sets the font emojis fall back to if the font of their text doesn't contain them, e.g.
+    /// the font returned by [`system_emoji_font`](fn.system_emoji_font.html).
+    ///
+    /// Lines are wrapped before falling back, using the advance of the missing glyph.
+    pub fn set_emoji_font(&mut self, font_id: Option<FontId>) {
+        self.layout_options.emoji_font = font_id;
+        self.keyed_sections.clear();
+        self.document_lines.clear();
+        self.offset_layouts.clear();
+    }
+
+    /// Draws the characters of `script` with the font `font_id`, or stops doing so if `None`,
+    /// e.g. to draw the Han characters of mixed-language text with a CJK font without splitting
+    /// it into texts.
+    ///
+    /// Texts using the default font `FontId(0)` are drawn with the fonts of their scripts, texts
+    /// using another font only fall back to them for the characters missing from their font.
+    /// Characters the mapped font doesn't contain keep the font of their text. Like the
+    /// [emoji font](#method.set_emoji_font), this applies after lines are wrapped.
+    pub fn set_script_font(&mut self, script: Script, font_id: Option<FontId>) {
+        let script_fonts = &mut self.layout_options.script_fonts;
+        script_fonts.retain(|&(mapped, _)| mapped != script);
+        if let Some(font_id) = font_id {
+            script_fonts.push((script, font_id));
+        }
+        self.keyed_sections.clear();
+        self.document_lines.clear();
+        self.offset_layouts.clear();
+    }
+
+    /// Lays out sections with the layouts cached in `cache`, shared with other brushes using
+    /// the same fonts, e.g. one per window showing the same HUD. Sections laid out by any of
+    /// the brushes are then reused by the others. `None` stops sharing layouts.
+    ///
+    /// Only sections queued with a layout use the cache, runs and sections queued with an id
+    /// keep their layouts per brush. See [`SharedLayoutCache`](struct.SharedLayoutCache.html)
+    /// for the cost of queueing reused layouts.
+    pub fn set_shared_layout_cache(&mut self, cache: Option<SharedLayoutCache>) {
+        self.shared_layouts = cache;
+    }
+
+    /// Sets what is drawn for characters missing from the font of their text and, if set, the
+    /// [emoji font](#method.set_emoji_font). Defaults to
+    /// [`MissingGlyph::Notdef`](enum.MissingGlyph.html#variant.Notdef).
+    pub fn set_missing_glyph(&mut self, missing_glyph: MissingGlyph) {
+        self.layout_options.missing_glyph = missing_glyph;
+        self.keyed_sections.clear();
+        self.document_lines.clear();
+        self.offset_layouts.clear();
+    }
+
+    /// Returns the dimensions and contents of the glyph cache texture, one coverage byte per
+    /// texel in row-major order, or `None` unless the brush has been built with a
+    /// [CPU copy of the cache](struct.GlyphBrushBuilder.html#method.cache_shadow_copy).
+    ///
+    /// An [SDF cache](struct.GlyphBrushBuilder.html#method.cache_format) holds the signed
+    /// distances instead. With
+    /// [single pass outlines](struct.GlyphBrushBuilder.html#method.single_pass_outlines) the
+    /// first byte of each texel is followed by its distance to the glyph.
+    ///
+    /// The contents are read from the CPU copy rather than from the GPU, so they can be used to
+    /// inspect rasterized glyphs, e.g. in tests or debug tooling.
+    #[inline]
+    pub fn cache_contents(&self) -> Option<((u32, u32), &[u8])> {
+        let cache = self.pipeline.cache();
+        cache.data().map(|data| (cache.dimensions(), data))
+    }
+
+    /// Returns the instance buffer holding the vertices of the last processed batch, e.g. to
+    /// inspect it in custom tooling.
+    ///
+    /// # Safety
+    ///
+    /// The buffer is replaced when a larger batch is uploaded. It must not be written to, as
+    /// the brush assumes its contents match the last uploaded vertices.
+    #[inline]
+    pub unsafe fn raw_vertex_buffer(&self) -> ComPtr<ID3D11Buffer> {
+        self.pipeline.vertex_buffer().clone()
+    }
+
+    /// Returns the constant buffer holding the transform of the last draw. The brush cycles
+    /// through a few transform buffers, so draws with a different transform may use another one.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must not be written to, as the brush only updates it when the transform of a
+    /// draw isn't held by any of its transform buffers.
+    #[inline]
+    pub unsafe fn raw_transform_buffer(&self) -> ComPtr<ID3D11Buffer> {
+        self.pipeline.transform_buffer().clone()
+    }
+
+    /// Returns the glyph cache texture, e.g. to copy it into a descriptor heap of another
+    /// renderer. Glyphs rasterized since the last draw are only uploaded by the next one, and
+    /// after growing with
+    /// [`cache_migration_rows`](struct.GlyphBrushBuilder.html#method.cache_migration_rows) the
+    /// glyphs of the old texture are only copied into it over the following draws.
+    ///
+    /// # Safety
+    ///
+    /// The texture is replaced when the cache grows. It must not be written to, as the brush
+    /// only uploads the regions of newly rasterized glyphs.
+    #[inline]
+    pub unsafe fn raw_cache_texture(&self) -> ComPtr<ID3D11Texture2D> {
+        self.pipeline.cache().texture().clone()
+    }
+
+    /// Returns the shader resource view of the glyph cache texture, see
+    /// [`raw_cache_texture`](#method.raw_cache_texture).
+    ///
+    /// # Safety
+    ///
+    /// See [`raw_cache_texture`](#method.raw_cache_texture).
+    #[inline]
+    pub unsafe fn raw_cache_view(&self) -> ComPtr<ID3D11ShaderResourceView> {
+        self.pipeline.cache().shader_resource_view().clone()
+    }
+
+    /// Registers a texture glyphs can be filled with through
+    /// [`GlyphExtra::fill`](trait.GlyphExtra.html#method.fill).
+    ///
+    /// The texture is sampled with a linear filter and repeats outside of `0..1`. Its mip
+    /// levels are used when the texture is minified.
+    pub fn add_fill_texture(&mut self, view: ComPtr<ID3D11ShaderResourceView>) -> FillTexture {
+        self.pipeline.add_fill_texture(view)
+    }
+
+    /// Replaces the view of a fill texture, e.g. after the texture was recreated, or removes it
+    /// if `None`, returning the previous view. Glyphs filled with a removed texture aren't drawn
+    /// until it is replaced, its id is reused by the next added texture.
+    pub fn set_fill_texture(
+        &mut self,
+        texture: FillTexture,
+        view: Option<ComPtr<ID3D11ShaderResourceView>>,
+    ) -> Option<ComPtr<ID3D11ShaderResourceView>> {
+        self.pipeline.set_fill_texture(texture, view)
+    }
+
+    /// Restores the whole glyph cache, e.g. after the texture contents were discarded.
+    ///
+    /// With a [CPU copy of the cache](struct.GlyphBrushBuilder.html#method.cache_shadow_copy)
+    /// the next draw re-uploads it, otherwise the glyphs are rasterized again by the next
+    /// [`process_queued`](#method.process_queued) call.
+    pub fn reupload_cache(&mut self) {
+        if !self.pipeline.invalidate_cache() {
+            let (width, height) = self.glyph_brush.texture_dimensions();
+            self.glyph_brush.resize_texture(width, height);
+        }
+    }
+
+    /// Replaces the vertex shader of the brush with the given compiled shader bytecode, e.g.
+    /// to hot-reload a custom shader.
+    ///
+    /// See [`GlyphBrushBuilder::vertex_shader`](struct.GlyphBrushBuilder.html#method.vertex_shader).
+    pub fn set_vertex_shader(&mut self, bytecode: &[u8]) -> HResult<()> {
+        self.pipeline
+            .set_vertex_shader(bytecode, &V::input_layout())
+    }
+
+    /// Replaces the pixel shader of the brush with the given compiled shader bytecode.
+    ///
+    /// See [`GlyphBrushBuilder::pixel_shader`](struct.GlyphBrushBuilder.html#method.pixel_shader).
+    pub fn set_pixel_shader(&mut self, bytecode: &[u8]) -> HResult<()> {
+        self.pipeline.set_pixel_shader(bytecode)
+    }
+
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.pipeline.time()
+    }
+
+    /// Sets the time in seconds uploaded with the shader constants of the next draws, e.g. the
+    /// time since startup. Defaults to `0.0`.
+    ///
+    /// The built-in shaders ignore it. Custom shaders can read it as `time` from the
+    /// `pixelBuffer` constant buffer, bound to slot `b0` of the pixel shader and slot `b1` of
+    /// the vertex shader, to implement pulsing, scrolling or shimmering effects, see
+    /// `src/shader/pixel.hlsl` for its layout.
     #[inline]
-    pub fn queue_custom_layout<'a, S, G>(&mut self, section: S, custom_layout: &G)
-    where
-        G: GlyphPositioner,
-        S: Into<Cow<'a, Section<'a>>>,
-    {
-        self.glyph_brush.queue_custom_layout(section, custom_layout)
+    pub fn set_time(&mut self, seconds: f32) {
+        self.pipeline.set_time(seconds);
     }
 
-    /// Queues pre-positioned glyphs to be processed by the next call of
-    /// [`process_queued`](struct.GlyphBrush.html#method.process_queued). Can be called multiple
-    /// times.
-    #[inline]
-    pub fn queue_pre_positioned(
+    /// Sets a constant buffer bound to slot
+    /// [`USER_CONSTANT_BUFFER_SLOT`](constant.USER_CONSTANT_BUFFER_SLOT.html) of the vertex and
+    /// the pixel shader during every draw, or stops binding one if `None`, returning the
+    /// previous buffer.
+    ///
+    /// This passes parameters to custom shaders without re-binding them after the brush set
+    /// its own state. The buffer is owned and updated by the caller, e.g. with `Map` before the
+    /// draw, and isn't unbound afterwards.
+    pub fn set_user_constants(
         &mut self,
-        glyphs: Vec<SectionGlyph>,
-        extra: Vec<Extra>,
-        bounds: Rect,
-    ) {
-        self.glyph_brush.queue_pre_positioned(glyphs, extra, bounds)
+        buffer: Option<ComPtr<ID3D11Buffer>>,
+    ) -> Option<ComPtr<ID3D11Buffer>> {
+        self.pipeline.set_user_constants(buffer)
     }
 
-    /// Retains the section in the cache as if it had been used in the last draw-frame.
+    #[inline]
+    pub fn halo(&self) -> Option<Outline> {
+        self.pipeline.halo()
+    }
+
+    /// Draws a halo around all glyphs without an [outline](trait.GlyphExtra.html#method.outline)
+    /// of their own, or stops doing so if `None`, keeping text readable over backgrounds it
+    /// may blend into, e.g. map overlays or video.
     ///
-    /// Should not generally be necessary, see [caching behaviour](#caching-behaviour).
+    /// The halo is drawn in the outline pass by dilating the glyph coverage in the pixel
+    /// shader, so it takes effect on the next draw without processing the queue again. Custom
+    /// shaders have to read it from the `pixelBuffer` constants to support it.
+    pub fn set_halo(&mut self, halo: Option<Outline>) {
+        self.pipeline.set_halo(halo);
+    }
+
     #[inline]
-    pub fn keep_cached_custom_layout<'a, S, G>(&mut self, section: S, custom_layout: &G)
-    where
-        S: Into<Cow<'a, Section<'a>>>,
-        G: GlyphPositioner,
-    {
-        self.glyph_brush
-            .keep_cached_custom_layout(section, custom_layout)
+    pub fn coverage_gamma(&self) -> f32 {
+        self.pipeline.coverage_gamma()
     }
 
-    /// Retains the section in the cache as if it had been used in the last draw-frame.
+    /// Raises the coverage of the glyphs to `1.0 / gamma` when drawing, like the stem darkening
+    /// of other text renderers. Values above `1.0` thicken the glyphs, which keeps small light
+    /// text on dark backgrounds from looking thin, values below `1.0` make them lighter.
     ///
-    /// Should not generally be necessary, see [caching behaviour](#caching-behaviour).
+    /// Outlines, shadows and glows are derived from the corrected coverage. Takes effect on the
+    /// next draw without processing the queue again.
+    pub fn set_coverage_gamma(&mut self, gamma: f32) {
+        self.pipeline
+            .set_coverage_gamma(gamma.max(f32::MIN_POSITIVE));
+    }
+
     #[inline]
-    pub fn keep_cached<'a, S>(&mut self, section: S)
-    where
-        S: Into<Cow<'a, Section<'a>>>,
-    {
-        self.glyph_brush.keep_cached(section)
+    pub fn sdf_smoothing(&self) -> SdfSmoothing {
+        self.pipeline.sdf_smoothing()
     }
 
-    /// Returns the available fonts.
+    /// Sets the edge of the glyphs in the signed distance fields of an
+    /// [SDF cache](struct.GlyphBrushBuilder.html#method.cache_format) and the width it is
+    /// smoothed over, e.g. widening the edge for text magnified far beyond its rasterized size
+    /// or moving it outwards to keep small text readable.
     ///
-    /// The `FontId` corresponds to the index of the font data.
+    /// Takes effect on the next draw without processing the queue again, so it can be set
+    /// before each draw. Has no effect on coverage caches.
+    pub fn set_sdf_smoothing(&mut self, smoothing: SdfSmoothing) {
+        self.pipeline.set_sdf_smoothing(smoothing);
+    }
+
     #[inline]
-    pub fn fonts(&self) -> &[F] {
-        self.glyph_brush.fonts()
+    pub fn depth_mode(&self) -> DepthMode {
+        self.pipeline.depth_mode()
     }
 
-    pub fn add_font(&mut self, font: F) -> FontId {
-        self.glyph_brush.add_font(font)
+    /// Replaces the depth stencil state glyphs are drawn with, e.g. to switch between depth
+    /// tested world space labels and an overlay at runtime.
+    ///
+    /// See [`GlyphBrushBuilder::depth_mode`](struct.GlyphBrushBuilder.html#method.depth_mode).
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) -> HResult<()> {
+        self.pipeline.set_depth_mode(depth_mode)
     }
-}
 
-impl<F, H> GlyphBrush<(), F, H>
-where
-    F: Font,
-    H: BuildHasher,
-{
-    fn new(
-        device: ComPtr<ID3D11Device>,
-        filter_mode: D3D11_FILTER,
-        raw_builder: glyph_brush::GlyphBrushBuilder<F, H>,
-    ) -> HResult<Self> {
-        let glyph_brush = raw_builder.build();
-        let (cache_width, cache_height) = glyph_brush.texture_dimensions();
-        Ok(GlyphBrush {
-            pipeline: Pipeline::<()>::new(device, filter_mode, cache_width, cache_height)?,
-            glyph_brush,
-        })
+    /// Lays out the section and tessellates the outlines of its glyphs into a triangle mesh.
+    ///
+    /// See [`tessellation::tessellate_glyphs`](tessellation/fn.tessellate_glyphs.html).
+    #[cfg(feature = "tessellation")]
+    pub fn tessellate<'a, S>(
+        &mut self,
+        section: S,
+        tolerance: f32,
+    ) -> Result<tessellation::Mesh, tessellation::TessellationError>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let glyphs: Vec<_> = self.glyph_brush.glyphs(section).cloned().collect();
+        tessellation::tessellate_glyphs(self.glyph_brush.fonts(), &glyphs, tolerance)
+    }
+
+    /// Lays out the section and extrudes the outlines of its glyphs into a solid 3D mesh.
+    ///
+    /// See [`tessellation::extrude_glyphs`](tessellation/fn.extrude_glyphs.html).
+    #[cfg(feature = "tessellation")]
+    pub fn extrude<'a, S>(
+        &mut self,
+        section: S,
+        depth: f32,
+        tolerance: f32,
+    ) -> Result<tessellation::ExtrudedMesh, tessellation::TessellationError>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let glyphs: Vec<_> = self.glyph_brush.glyphs(section).cloned().collect();
+        tessellation::extrude_glyphs(self.glyph_brush.fonts(), &glyphs, depth, tolerance)
     }
 }
 
-impl<F, H> GlyphBrush<D3D11_DEPTH_STENCIL_DESC, F, H>
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
 where
     F: Font,
     H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
 {
     fn new(
         device: ComPtr<ID3D11Device>,
-        filter_mode: D3D11_FILTER,
-        depth_stencil_desc: D3D11_DEPTH_STENCIL_DESC,
+        options: PipelineOptions,
+        bounds_clipping: BoundsClipping,
+        layout_options: LayoutOptions,
+        depth_mode: DepthMode,
         raw_builder: glyph_brush::GlyphBrushBuilder<F, H>,
     ) -> HResult<Self> {
-        let glyph_brush = raw_builder.build();
+        let mut glyph_brush = raw_builder.build();
         let (cache_width, cache_height) = glyph_brush.texture_dimensions();
+        let layout_height = cache_height.max(options.reserved_cache_height);
+        util::validate_cache_size(&device, cache_width, layout_height)?;
+        if layout_height > cache_height {
+            // the texture starts at the initial height and grows into the layout
+            glyph_brush.resize_texture(cache_width, layout_height);
+        }
         Ok(GlyphBrush {
-            pipeline: Pipeline::<D3D11_DEPTH_STENCIL_DESC>::new(
+            pipeline: Pipeline::new(
                 device,
-                filter_mode,
-                depth_stencil_desc,
+                options,
+                &V::input_layout(),
+                depth_mode,
                 cache_width,
                 cache_height,
             )?,
             glyph_brush,
+            clip_stack: Vec::new(),
+            queued: Vec::new(),
+            queued_effects: Effects::default(),
+            pick_boxes: None,
+            layout_options,
+            font_names: FontNames::default(),
+            metrics: Metrics::default(),
+            layouts: KeyedSections::default(),
+            keyed_sections: KeyedSections::default(),
+            document_lines: KeyedSections::default(),
+            offset_layouts: KeyedSections::default(),
+            runs: HashMap::new(),
+            swapchain: None,
+            debug_overlay: None,
+            uploaded_overlay: None,
+            shared_layouts: None,
+            batches: HashMap::new(),
+            vertex_owners: Vec::new(),
+            bounds_clipping,
         })
     }
 }
 
-impl<D, F, H> GlyphBrush<D, F, H>
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
 where
     F: Font + Sync,
     H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
 {
-    fn process_queued(&mut self) -> HResult<()> {
-        let pipeline = &mut self.pipeline;
+    /// Processes all queued sections, updating the glyph cache and uploading the resulting
+    /// vertices.
+    ///
+    /// Returns `true` if the vertices changed since the last call, `false` if the previously
+    /// uploaded vertices are reused. The processed batch can then be drawn any number of times
+    /// with `draw`, which allows drawing the same text into multiple passes without
+    /// re-queueing it.
+    ///
+    /// Fails if the glyph cache has to grow and the larger texture can't be created, e.g. after
+    /// the device was removed. When running out of video memory, smaller sizes are tried before
+    /// failing, while glyphs not used by the queued sections are evicted to make room. The
+    /// queue is kept on failure, so processing can be retried.
+    pub fn process_queued(&mut self) -> HResult<bool> {
+        self.process_batches(iter::empty())
+    }
 
-        let mut brush_action;
-
-        let brush_action = loop {
-            brush_action = self.glyph_brush.process_queued(
-                |rect, tex_data| {
-                    pipeline.update_cache(rect, tex_data);
-                },
-                |v| v.into(),
-            );
-
-            match brush_action {
-                Ok(action) => break action,
-                Err(BrushError::TextureTooSmall { suggested }) => {
-                    let max_image_dimension = D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
-
-                    let (new_width, new_height) = if (suggested.0 > max_image_dimension
-                        || suggested.1 > max_image_dimension)
-                        && (self.glyph_brush.texture_dimensions().0 < max_image_dimension
-                            || self.glyph_brush.texture_dimensions().1 < max_image_dimension)
-                    {
-                        (max_image_dimension, max_image_dimension)
-                    } else {
-                        suggested
-                    };
+    /// Processes the queued sections like [`process_queued`](#method.process_queued) along with
+    /// the sections of `batches`, emptying them. The vertices of each batch are uploaded to a
+    /// vertex buffer of its own and drawn with [`draw_batch`](#method.draw_batch), sharing the
+    /// pipeline and the glyph cache of the brush.
+    ///
+    /// The batches are processed together, so they can be drawn in any order and any number
+    /// of times until the next call. As that call may rearrange the glyph cache, it discards
+    /// the vertices of the batches not passed to it. Returns whether the vertices of the queued
+    /// sections changed, like `process_queued`. On failure the sections of the batches stay
+    /// queued in the brush.
+    pub fn process_batches<'b, I>(&mut self, batches: I) -> HResult<bool>
+    where
+        I: IntoIterator<Item = &'b mut TextBatch<X>>,
+        X: 'b,
+    {
+        let queued_effects = self.queued_effects;
+        let mut owners = vec![None; self.queued.len()];
+        let mut processed = Vec::new();
+        for batch in batches {
+            let mut effects = Effects::default();
+            for section in batch.drain() {
+                for text in &section.text {
+                    effects.include(&text.extra);
+                }
+                self.queue(section.to_borrowed());
+            }
+            owners.resize(self.queued.len(), Some(batch.id()));
+            processed.push((batch.id(), effects));
+        }
+        // glyph_brush generates the vertices of the laid out sections first, which are all
+        // sections of the batches
+        let mut laid_out = Vec::new();
+        if !processed.is_empty() {
+            for (queued, owner) in self.queued.iter().zip(owners) {
+                if let QueuedSection::Cached { glyphs, bounds, .. } = queued {
+                    laid_out.push((owner, Arc::clone(glyphs), *bounds));
+                }
+            }
+        }
+
+        let glyph_brush = &mut self.glyph_brush;
+        for queued in self.queued.drain(..) {
+            queued.queue(glyph_brush);
+        }
+        let instances = self.process_glyphs()?;
+        // the effects of the batches only apply to their own vertices
+        self.queued_effects = queued_effects;
+        let mut vertex_owners: Vec<(Option<u64>, usize)> = Vec::new();
+        for (owner, glyphs, bounds) in laid_out {
+            let glyph_brush = &self.glyph_brush;
+            let count = (glyphs.iter())
+                .filter(|glyph| is_drawn(glyph_brush, glyph, bounds))
+                .count();
+            match vertex_owners.last_mut() {
+                Some((last, len)) if *last == owner => *len += count,
+                _ => vertex_owners.push((owner, count)),
+            }
+        }
+        let instances = match instances {
+            // the same vertices may belong to other batches than last time
+            None if vertex_owners != self.vertex_owners => Some(self.processed_instances()),
+            instances => instances,
+        };
+        let uploaded = match instances {
+            Some(instances) => {
+                self.upload_instances(instances, &vertex_owners, &processed)?;
+                true
+            }
+            None => false,
+        };
+        self.vertex_owners = vertex_owners;
+        self.batches
+            .retain(|id, _| processed.iter().any(|(processed, _)| processed == id));
+        for (id, effects) in processed {
+            self.batches.entry(id).or_default().set_effects(effects);
+        }
+
+        let uploaded = self.upload_debug_overlay(uploaded)? || uploaded;
+        let effects = mem::take(&mut self.queued_effects);
+        self.layouts.finish_frame();
+        self.keyed_sections.finish_frame();
+        self.document_lines.finish_frame();
+        self.offset_layouts.finish_frame();
+        if let Some(shared) = &self.shared_layouts {
+            shared.finish_frame();
+        }
+        if let Some(pick_boxes) = &mut self.pick_boxes {
+            let glyph_brush = &self.glyph_brush;
+            pick_boxes.finish_frame(|glyph, bounds| is_drawn(glyph_brush, glyph, bounds));
+        }
+        if uploaded {
+            self.pipeline.set_effects(effects);
+        }
+        Ok(uploaded)
+    }
+
+    /// Processes the queue of glyph_brush, returning the vertices with their draw states if
+    /// they changed.
+    fn process_glyphs(&mut self) -> HResult<Option<Vec<(V, DrawState)>>> {
+        let bounds_clipping = self.bounds_clipping;
+        backend::process_queued(
+            &mut self.glyph_brush,
+            &mut self.pipeline,
+            &mut self.metrics,
+            |vertex| {
+                let state = DrawState::new(vertex.extra);
+                (V::from_glyph(vertex, bounds_clipping), state)
+            },
+        )
+    }
+
+    /// Uploads the processed vertices, the runs of `owners` to their batch and the rest to the
+    /// brush. Batches without vertices are emptied.
+    fn upload_instances(
+        &mut self,
+        instances: Vec<(V, DrawState)>,
+        owners: &[(Option<u64>, usize)],
+        batches: &[(u64, Effects)],
+    ) -> HResult<()> {
+        let mut batch_instances: HashMap<_, _> =
+            batches.iter().map(|&(id, _)| (id, Vec::new())).collect();
+        let mut own = Vec::new();
+        let mut instances = instances.into_iter();
+        for &(owner, len) in owners {
+            let owned = match owner {
+                Some(id) => batch_instances.entry(id).or_default(),
+                None => &mut own,
+            };
+            owned.extend(instances.by_ref().take(len));
+        }
+        own.extend(instances);
+
+        let (vertices, states): (Vec<_>, Vec<_>) = own.into_iter().unzip();
+        self.pipeline.upload(&vertices)?;
+        self.pipeline.set_draw_states(states);
+        for (id, instances) in batch_instances {
+            let (vertices, states): (Vec<_>, Vec<_>) = instances.into_iter().unzip();
+            let batch = self.batches.entry(id).or_default();
+            self.pipeline.upload_batch(batch, &vertices)?;
+            batch.set_draw_states(states);
+        }
+        Ok(())
+    }
 
-                    if log::log_enabled!(log::Level::Warn) {
-                        log::warn!(
-                            "Increasing glyph texture size {old:?} -> {new:?}. \
-                             Consider building with `.initial_cache_size({new:?})` to avoid \
-                             resizing",
-                            old = self.glyph_brush.texture_dimensions(),
-                            new = (new_width, new_height),
-                        );
-                    }
-
-                    pipeline.increase_cache_size(new_width, new_height);
-                    self.glyph_brush.resize_texture(new_width, new_height);
+    /// Returns the uploaded vertices with their draw states in the order glyph_brush generated
+    /// them, undoing `upload_instances`.
+    fn processed_instances(&self) -> Vec<(V, DrawState)> {
+        let mut own = self.pipeline.batch().instances();
+        let mut batches: HashMap<_, _> = (self.batches.iter())
+            .map(|(&id, batch)| (id, batch.instances()))
+            .collect();
+        let mut processed = Vec::new();
+        let mut own_len = self.pipeline.glyph_count() - self.uploaded_overlay.is_some() as usize;
+        for &(owner, len) in &self.vertex_owners {
+            match owner {
+                Some(id) => processed.extend(batches.get_mut(&id).into_iter().flatten().take(len)),
+                None => {
+                    processed.extend(own.by_ref().take(len));
+                    own_len -= len;
                 }
             }
+        }
+        processed.extend(own.take(own_len));
+        processed
+    }
+
+    /// Returns the screen-space quads of the glyphs of a section as drawn by the last
+    /// [`process_queued`](#method.process_queued) call, e.g. for glyph-accurate picking.
+    ///
+    /// Requires picking to be enabled with [`set_picking`](#method.set_picking) before
+    /// queueing, which records the vertices of each queued section. The section has to be part
+    /// of the processed batch and the clip stack has to match the one it was queued with,
+    /// otherwise no quads are returned. Invisible glyphs and glyphs outside of the bounds of
+    /// the section don't have a quad. Requires a vertex type reporting its
+    /// [`coords`](trait.BrushVertex.html#method.coords).
+    pub fn glyph_quads<'a, S>(&self, section: S) -> Vec<GlyphQuad>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        match StyledLayout::new(&layout, &section, &self.layout_options) {
+            Some(styled) => self.clipped_glyph_quads(&section, &styled),
+            None => self.clipped_glyph_quads(&section, &layout),
+        }
+    }
+
+    fn clipped_glyph_quads<G>(&self, section: &Section<'_, X>, layout: &G) -> Vec<GlyphQuad>
+    where
+        G: GlyphPositioner + std::hash::Hash,
+    {
+        let key = match self.clip() {
+            Some(clip) => shared_layout::key(
+                section,
+                &ClippedLayout { layout, clip },
+                &self.layout_options,
+            ),
+            None => shared_layout::key(section, layout, &self.layout_options),
         };
+        let pick_boxes = match &self.pick_boxes {
+            Some(pick_boxes) => pick_boxes,
+            None => return Vec::new(),
+        };
+        // the sections only account for the vertices if they were assigned like glyph_brush did
+        let vertices = self.pipeline.vertices();
+        let glyphs = vertices.len() - self.uploaded_overlay.is_some() as usize;
+        let section = match pick_boxes.section_vertices(key) {
+            Some(section) if pick_boxes.vertex_count() == glyphs => section,
+            _ => return Vec::new(),
+        };
+        (section.glyphs.iter())
+            .zip(&vertices[section.range.clone()])
+            .map(|(&(section_index, byte_index), vertex)| {
+                let (pixel_coords, tex_coords) = vertex.coords()?;
+                Some(GlyphQuad {
+                    section_index,
+                    byte_index,
+                    pixel_coords,
+                    tex_coords,
+                })
+            })
+            .collect::<Option<_>>()
+            .unwrap_or_default()
+    }
+
+    /// Processes the queued sections once and draws the resulting vertices into each of the
+    /// given targets, e.g. for split-screen or picture-in-picture rendering.
+    pub fn draw_queued_to_targets(&mut self, targets: &[DrawTarget<'_>]) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        targets
+            .iter()
+            .try_for_each(|target| self.pipeline.draw_target(target))
+            .map(|()| result)
+    }
+
+    /// Draws the vertices of a batch processed by the last
+    /// [`process_batches`](#method.process_batches) call into `target`. Batches which weren't
+    /// part of that call draw nothing.
+    pub fn draw_batch(&mut self, batch: &TextBatch<X>, target: &DrawTarget<'_>) -> HResult<()> {
+        match self.batches.get(&batch.id()) {
+            Some(vertices) => self.pipeline.draw_batch(vertices, target),
+            None => Ok(()),
+        }
+    }
+
+    /// Rasterizes the glyphs of `chars` into the glyph cache ahead of time, e.g. during a
+    /// loading screen, so the first frame using them doesn't stall on rasterization or cache
+    /// resizing.
+    ///
+    /// See [`precache_sections`](#method.precache_sections).
+    pub fn precache(
+        &mut self,
+        font_id: FontId,
+        scale: impl Into<PxScale>,
+        chars: &str,
+    ) -> HResult<()>
+    where
+        X: Default,
+    {
+        self.precache_sections(Some(
+            Section::new().add_text(Text::new(chars).with_font_id(font_id).with_scale(scale)),
+        ))
+    }
+
+    /// Rasterizes the glyphs of the given sections into the glyph cache ahead of time.
+    ///
+    /// This processes the queue, so any previously queued sections are processed with them.
+    /// The resulting vertices replace the ones of the last frame, so this should be called
+    /// before queueing the sections of the next frame rather than in between `process_queued`
+    /// and `draw`.
+    pub fn precache_sections<'a, I, S>(&mut self, sections: I) -> HResult<()>
+    where
+        I: IntoIterator<Item = S>,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        for section in sections {
+            self.queue(section);
+        }
+        self.process_queued().map(|_| ())
+    }
+
+    /// Processes the queued sections and draws them into `target` using the given depth
+    /// stencil state instead of the one the brush has been built with.
+    ///
+    /// This allows drawing some text depth-tested and some always on top with the same brush.
+    pub fn draw_queued_with_depth(
+        &mut self,
+        target: &DrawTarget<'_>,
+        depth_stencil: DepthStencil<'_>,
+    ) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        self.pipeline
+            .draw_target_with_depth(target, depth_stencil)
+            .map(|()| result)
+    }
+
+    /// Draws the given sections into a newly created `width` x `height` texture and returns it.
+    ///
+    /// The texture has the `DXGI_FORMAT_R8G8B8A8_UNORM` format, is cleared to transparent
+    /// black and can be bound as a shader resource, which is useful for caching expensive
+    /// static text or creating sprites from text. The previously bound render targets and
+    /// viewports are restored afterwards.
+    ///
+    /// Like [`precache_sections`](#method.precache_sections) this processes the queue.
+    pub fn render_to_texture<'a, I, S>(
+        &mut self,
+        sections: I,
+        width: u32,
+        height: u32,
+    ) -> HResult<ComPtr<ID3D11Texture2D>>
+    where
+        I: IntoIterator<Item = S>,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let (texture, view) =
+            unsafe { offscreen::create_render_target(self.pipeline.device(), width, height)? };
+        for section in sections {
+            self.queue(section);
+        }
+        self.process_queued()?;
+
+        let ctx = self.pipeline.context().clone();
+        let _lock = self.pipeline.lock_context();
+        let pipeline = &mut self.pipeline;
+        unsafe {
+            offscreen::with_render_target(&ctx, &view, width, height, || {
+                pipeline.draw_target(&DrawTarget {
+                    target: &view,
+                    depth_stencil_view: None,
+                    transform: orthographic_projection(width, height),
+                    scissor: None,
+                    clip_shape: None,
+                })
+            })?;
+        }
+        Ok(texture)
+    }
 
-        match brush_action {
-            BrushAction::Draw(verts) => self.pipeline.upload(&verts),
-            BrushAction::ReDraw => Ok(()),
+    /// Processes the queued sections and draws them into `target`, depth tested against
+    /// `depth_stencil_view` if the brush draws with
+    /// [`DepthMode::Enabled`](enum.DepthMode.html).
+    ///
+    /// The brush binds both views itself. With `restore_targets` the render target and depth
+    /// stencil view bound before are rebound afterwards, so text can be drawn into another
+    /// target in the middle of a pass without disturbing it.
+    pub fn draw_queued_into(
+        &mut self,
+        target: &ComPtr<ID3D11RenderTargetView>,
+        depth_stencil_view: Option<&ComPtr<ID3D11DepthStencilView>>,
+        transform: Transform,
+        restore_targets: bool,
+    ) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        let ctx = self.pipeline.context().clone();
+        let _lock = self.pipeline.lock_context();
+        let pipeline = &mut self.pipeline;
+        let mut draw = || pipeline.draw(target, depth_stencil_view, transform, None);
+        if restore_targets {
+            unsafe { offscreen::restoring_render_targets(&ctx, draw)? };
+        } else {
+            draw()?;
         }
+        Ok(result)
+    }
+
+    /// Re-draws the vertices of the last processed frame into `target` without touching the
+    /// queue.
+    ///
+    /// This is a fast path for static text that is known to be unchanged since the last
+    /// [`process_queued`](#method.process_queued) call as it skips queueing and hashing
+    /// sections entirely. Any sections queued since are retained for the next
+    /// `process_queued` call.
+    pub fn redraw(&mut self, target: &DrawTarget<'_>) -> HResult<DrawResult> {
+        self.pipeline.draw_target(target)?;
+        Ok(DrawResult {
+            glyphs: self.pipeline.glyph_count(),
+            redraw: true,
+        })
+    }
+
+    fn process_for_draw(&mut self) -> HResult<DrawResult> {
+        let redraw = !self.process_queued()?;
+        Ok(DrawResult {
+            glyphs: self.pipeline.glyph_count(),
+            redraw,
+        })
     }
 }
 
-impl<F: Font + Sync, H: BuildHasher> GlyphBrush<(), F, H> {
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
+where
+    F: Font + Sync,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
+    /// Processes the queued sections and draws them into a `target_width` x `target_height`
+    /// target.
+    ///
+    /// A zero-sized target, e.g. the back buffer of a minimized window, has no projection. The
+    /// queue is then processed without drawing anything.
     #[inline]
     pub fn draw_queued(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
         target_width: u32,
         target_height: u32,
-    ) -> HResult<()> {
+    ) -> HResult<DrawResult> {
+        if target_width == 0 || target_height == 0 {
+            return self.process_for_draw();
+        }
         self.draw_queued_with_transform(
             target,
             orthographic_projection(target_width, target_height),
         )
     }
 
+    /// Like [`draw_queued`](#method.draw_queued), but projects onto the current back buffer
+    /// size of the swapchain the brush has been built for with
+    /// [`build_for_swapchain`](struct.GlyphBrushBuilder.html#method.build_for_swapchain).
     #[inline]
-    pub fn draw_queued_with_transform(
+    pub fn draw_queued_to_back_buffer(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
-        transform: [f32; 16],
-    ) -> HResult<()> {
-        self.process_queued()?;
-        self.pipeline.draw(target, transform, None)
+    ) -> HResult<DrawResult> {
+        let (width, height) = self.back_buffer_size()?;
+        self.draw_queued(target, width, height)
     }
 
-    #[inline]
-    pub fn draw_queued_with_transform_and_scissoring(
-        &mut self,
-        target: &ComPtr<ID3D11RenderTargetView>,
-        transform: [f32; 16],
-        rect: D3D11_RECT,
-    ) -> HResult<()> {
-        self.process_queued()?;
-        self.pipeline.draw(target, transform, Some(rect))
+    /// Returns the current back buffer size of the swapchain the brush has been built for,
+    /// which follows resizes of the swapchain.
+    ///
+    /// Fails with `DXGI_ERROR_INVALID_CALL` if the brush hasn't been built with
+    /// [`build_for_swapchain`](struct.GlyphBrushBuilder.html#method.build_for_swapchain).
+    pub fn back_buffer_size(&self) -> HResult<(u32, u32)> {
+        let swapchain = match &self.swapchain {
+            Some(swapchain) => swapchain,
+            None => {
+                return Err(Error::HResult {
+                    call: "GlyphBrush::back_buffer_size",
+                    code: NonZeroI32::new(DXGI_ERROR_INVALID_CALL).unwrap(),
+                })
+            }
+        };
+        unsafe {
+            let mut desc = mem::zeroed::<DXGI_SWAP_CHAIN_DESC>();
+            hresult("GetDesc", swapchain.GetDesc(&mut desc))?;
+            Ok((desc.BufferDesc.Width, desc.BufferDesc.Height))
+        }
     }
-}
 
-impl<F: Font + Sync, H: BuildHasher> GlyphBrush<D3D11_DEPTH_STENCIL_DESC, F, H> {
+    /// Like [`draw_queued`](#method.draw_queued), but also binds `depth_stencil_view` for the
+    /// depth test of a brush drawing with [`DepthMode::Enabled`](enum.DepthMode.html).
+    /// Nothing is drawn into a zero-sized target.
     #[inline]
-    pub fn draw_queued(
+    pub fn draw_queued_with_depth_view(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
         depth_stencil_view: &ComPtr<ID3D11DepthStencilView>,
         target_width: u32,
         target_height: u32,
-    ) -> HResult<()> {
-        self.draw_queued_with_transform(
+    ) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        if target_width == 0 || target_height == 0 {
+            return Ok(result);
+        }
+        self.draw_with_depth_view(
             target,
             depth_stencil_view,
             orthographic_projection(target_width, target_height),
         )
+        .map(|()| result)
+    }
+
+    /// Processes the queued sections and draws them into the rectangle `viewport` of a
+    /// `target_width` x `target_height` target, e.g. a split-screen view or a docked pane.
+    ///
+    /// Sections are laid out relative to the top left corner of the rectangle and clipped to it
+    /// with a scissor rect, so the text of a view can be queued the same way regardless of where
+    /// the view is placed. Nothing is drawn into a zero-sized target.
+    pub fn draw_queued_in_viewport(
+        &mut self,
+        target: &ComPtr<ID3D11RenderTargetView>,
+        target_width: u32,
+        target_height: u32,
+        viewport: D3D11_RECT,
+    ) -> HResult<DrawResult> {
+        if target_width == 0 || target_height == 0 {
+            return self.process_for_draw();
+        }
+        let (left, top) = (viewport.left as f32, viewport.top as f32);
+        let transform = Transform::orthographic_off_center(
+            -left,
+            target_width as f32 - left,
+            -top,
+            target_height as f32 - top,
+        );
+        self.draw_queued_with_transform_and_scissoring(target, transform, viewport)
     }
 
     #[inline]
     pub fn draw_queued_with_transform(
+        &mut self,
+        target: &ComPtr<ID3D11RenderTargetView>,
+        transform: Transform,
+    ) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        self.draw(target, transform).map(|()| result)
+    }
+
+    #[inline]
+    pub fn draw_queued_with_transform_and_scissoring(
+        &mut self,
+        target: &ComPtr<ID3D11RenderTargetView>,
+        transform: Transform,
+        rect: D3D11_RECT,
+    ) -> HResult<DrawResult> {
+        let result = self.process_for_draw()?;
+        self.draw_with_scissoring(target, transform, rect)
+            .map(|()| result)
+    }
+
+    /// Begins a frame of draws, e.g. of several batches drawn back-to-back. Until
+    /// [`end_frame`](#method.end_frame), draws only bind the state that differs from the one
+    /// bound by the previous draw of the brush.
+    ///
+    /// The state bound by the brush must not be changed by other rendering on the device
+    /// context during the frame. Call this again after such rendering to bind all state with
+    /// the next draw.
+    #[inline]
+    pub fn begin_frame(&mut self) {
+        self.pipeline.begin_frame();
+    }
+
+    /// Ends the frame begun with [`begin_frame`](#method.begin_frame), so every draw binds all
+    /// its state again.
+    #[inline]
+    pub fn end_frame(&mut self) {
+        self.pipeline.end_frame();
+    }
+
+    /// Draws the vertices of the last [`process_queued`](#method.process_queued) call without
+    /// processing the queue.
+    #[inline]
+    pub fn draw(
+        &mut self,
+        target: &ComPtr<ID3D11RenderTargetView>,
+        transform: Transform,
+    ) -> HResult<()> {
+        self.pipeline.draw(target, None, transform, None)
+    }
+
+    /// Draws the vertices of the last [`process_queued`](#method.process_queued) call without
+    /// processing the queue, depth testing them against `depth_stencil_view`.
+    #[inline]
+    pub fn draw_with_depth_view(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
         depth_stencil_view: &ComPtr<ID3D11DepthStencilView>,
-        transform: [f32; 16],
+        transform: Transform,
     ) -> HResult<()> {
-        self.process_queued()?;
         self.pipeline
-            .draw(target, depth_stencil_view, transform, None)
+            .draw(target, Some(depth_stencil_view), transform, None)
     }
 
+    /// Draws the vertices of the last [`process_queued`](#method.process_queued) call without
+    /// processing the queue.
     #[inline]
-    pub fn draw_queued_with_transform_and_scissoring(
+    pub fn draw_with_scissoring(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
-        depth_stencil_view: &ComPtr<ID3D11DepthStencilView>,
-        transform: [f32; 16],
+        transform: Transform,
         rect: D3D11_RECT,
     ) -> HResult<()> {
-        self.process_queued()?;
-        self.pipeline
-            .draw(target, depth_stencil_view, transform, Some(rect))
+        self.pipeline.draw(target, None, transform, Some(rect))
     }
 }
 
-#[rustfmt::skip]
-pub fn orthographic_projection(width: u32, height: u32) -> [f32; 16] {
-    let width = width as f32;
-    let height = height as f32;
-    [
-         2.0 / width, 0.0,           0.0, 0.0,
-         0.0,         -2.0 / height, 0.0, 0.0,
-         0.0,         0.0,           1.0, 0.0,
-        -1.0,         1.0,           0.0, 1.0,
-    ]
+/// Returns the dimensions the glyph cache should be grown to, clamping the suggested size to
+/// `max_image_dimension`.
+fn grown_cache_size(
+    current: (u32, u32),
+    suggested: (u32, u32),
+    max_image_dimension: u32,
+) -> (u32, u32) {
+    let (new_width, new_height) = if (suggested.0 > max_image_dimension
+        || suggested.1 > max_image_dimension)
+        && (current.0 < max_image_dimension || current.1 < max_image_dimension)
+    {
+        (max_image_dimension, max_image_dimension)
+    } else {
+        suggested
+    };
+
+    if log::log_enabled!(log::Level::Warn) {
+        log::warn!(
+            "Increasing glyph texture size {old:?} -> {new:?}. \
+             Consider building with `.initial_cache_size({new:?})` to avoid \
+             resizing",
+            old = current,
+            new = (new_width, new_height),
+        );
+    }
+
+    (new_width, new_height)
+}
+
+/// Returns whether glyph_brush has turned `glyph` of a section with `bounds` into a vertex,
+/// which it does for cached glyphs not entirely outside of the bounds.
+fn is_drawn<F, X, V, H>(
+    glyph_brush: &glyph_brush::GlyphBrush<V, X, F, H>,
+    glyph: &SectionGlyph,
+    bounds: Rect,
+) -> bool
+where
+    F: Font + Sync,
+    X: Clone + std::hash::Hash + PartialEq,
+    V: Clone + 'static,
+    H: BuildHasher,
+{
+    if !glyph_brush.is_draw_cached(glyph.font_id, &glyph.glyph) {
+        return false;
+    }
+    let font = &glyph_brush.fonts()[glyph.font_id.0];
+    font.outline_glyph(glyph.glyph.clone())
+        .map(|outline| outline.px_bounds())
+        .is_some_and(|rect| {
+            rect.min.x <= bounds.max.x
+                && rect.min.y <= bounds.max.y
+                && bounds.min.x <= rect.max.x
+                && bounds.min.y <= rect.max.y
+        })
+}
+
+/// Loads the Segoe UI Emoji font shipped with Windows, to be used as the
+/// [emoji fallback font](struct.GlyphBrush.html#method.set_emoji_font).
+///
+/// Returns `None` if the font isn't installed.
+pub fn system_emoji_font() -> Option<ab_glyph::FontVec> {
+    let windows = std::env::var_os("WINDIR")?;
+    load_font(Path::new(&windows).join("Fonts").join("seguiemj.ttf"), 0).ok()
+}
+
+/// Shorthand for [`Transform::orthographic`](struct.Transform.html#method.orthographic).
+#[inline]
+pub fn orthographic_projection(width: u32, height: u32) -> Transform {
+    Transform::orthographic(width, height)
 }
 
-impl<D, F: Font, H: BuildHasher> GlyphCruncher<F> for GlyphBrush<D, F, H> {
+impl<F, H, X, V> GlyphCruncher<F, X> for GlyphBrush<F, H, X, V>
+where
+    F: Font,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
     #[inline]
     fn glyphs_custom_layout<'a, 'b, S, L>(
         &'b mut self,
@@ -310,10 +2069,27 @@ impl<D, F: Font, H: BuildHasher> GlyphCruncher<F> for GlyphBrush<D, F, H> {
     ) -> SectionGlyphIter<'b>
     where
         L: GlyphPositioner + std::hash::Hash,
-        S: Into<Cow<'a, Section<'a>>>,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self.glyph_brush.glyphs_custom_layout(section, &layout),
+            None => self
+                .glyph_brush
+                .glyphs_custom_layout(section, custom_layout),
+        }
+    }
+
+    #[inline]
+    fn glyphs<'a, 'b, S>(&'b mut self, section: S) -> SectionGlyphIter<'b>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
     {
-        self.glyph_brush
-            .glyphs_custom_layout(section, custom_layout)
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.glyphs_custom_layout(section, &layout)
     }
 
     #[inline]
@@ -321,6 +2097,17 @@ impl<D, F: Font, H: BuildHasher> GlyphCruncher<F> for GlyphBrush<D, F, H> {
         self.glyph_brush.fonts()
     }
 
+    #[inline]
+    fn glyph_bounds<'a, S>(&mut self, section: S) -> Option<Rect>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.glyph_bounds_custom_layout(section, &layout)
+    }
+
     #[inline]
     fn glyph_bounds_custom_layout<'a, S, L>(
         &mut self,
@@ -329,14 +2116,22 @@ impl<D, F: Font, H: BuildHasher> GlyphCruncher<F> for GlyphBrush<D, F, H> {
     ) -> Option<Rect>
     where
         L: GlyphPositioner + std::hash::Hash,
-        S: Into<Cow<'a, Section<'a>>>,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
     {
-        self.glyph_brush
-            .glyph_bounds_custom_layout(section, custom_layout)
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self
+                .glyph_brush
+                .glyph_bounds_custom_layout(section, &layout),
+            None => self
+                .glyph_brush
+                .glyph_bounds_custom_layout(section, custom_layout),
+        }
     }
 }
 
-impl<F, H> std::fmt::Debug for GlyphBrush<F, H> {
+impl<F, H, X, V> std::fmt::Debug for GlyphBrush<F, H, X, V> {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "GlyphBrush")
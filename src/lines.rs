@@ -0,0 +1,99 @@
+use glyph_brush::ab_glyph::{point, Font, Rect, ScaleFont};
+use glyph_brush::{Section, SectionGlyph};
+
+use crate::layout;
+
+/// A position in the texts of a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextPosition {
+    /// The index of the text in the section.
+    pub text_index: usize,
+    /// The byte offset in the text.
+    pub byte_offset: usize,
+}
+
+/// A range of the texts of a section, which may span multiple texts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextRange {
+    pub start: TextPosition,
+    /// The exclusive end of the range.
+    pub end: TextPosition,
+}
+
+/// A line of a laid out section, see
+/// [`GlyphBrush::lines`](struct.GlyphBrush.html#method.lines).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// The text laid out on the line.
+    pub range: TextRange,
+    /// The area covered by the line, from the ascent to the descent of its fonts.
+    pub rect: Rect,
+    /// The words of the line, i.e. the runs of non-whitespace characters.
+    pub words: Vec<TextRange>,
+}
+
+/// Splits the laid out `glyphs` of `section` into lines and words.
+pub(crate) fn lines<F: Font, X>(
+    fonts: &[F],
+    section: &Section<'_, X>,
+    glyphs: &mut [SectionGlyph],
+) -> Vec<Line> {
+    let character = |glyph: &SectionGlyph| {
+        section.text[glyph.section_index].text[glyph.byte_index..]
+            .chars()
+            .next()
+            .unwrap_or_default()
+    };
+    let start = |glyph: &SectionGlyph| TextPosition {
+        text_index: glyph.section_index,
+        byte_offset: glyph.byte_index,
+    };
+    let end = |glyph: &SectionGlyph| TextPosition {
+        text_index: glyph.section_index,
+        byte_offset: glyph.byte_index + character(glyph).len_utf8(),
+    };
+
+    layout::lines(glyphs)
+        .map(|line| {
+            let (first, last) = (&line[0], &line[line.len() - 1]);
+            let baseline = first.glyph.position.y;
+            let (mut ascent, mut descent) = (0.0f32, 0.0f32);
+            for glyph in line.iter() {
+                let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+                ascent = ascent.max(font.ascent());
+                descent = descent.min(font.descent());
+            }
+            let last_font = fonts[last.font_id.0].as_scaled(last.glyph.scale);
+
+            let mut words = Vec::new();
+            let mut word: Option<TextRange> = None;
+            for glyph in line.iter() {
+                if character(glyph).is_whitespace() {
+                    words.extend(word.take());
+                } else {
+                    let word = word.get_or_insert(TextRange {
+                        start: start(glyph),
+                        end: end(glyph),
+                    });
+                    word.end = end(glyph);
+                }
+            }
+            words.extend(word);
+
+            Line {
+                range: TextRange {
+                    start: start(first),
+                    end: end(last),
+                },
+                rect: Rect {
+                    min: point(first.glyph.position.x, baseline - ascent),
+                    max: point(
+                        last.glyph.position.x + last_font.h_advance(last.glyph.id),
+                        baseline - descent,
+                    ),
+                },
+                words,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,109 @@
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+
+use glyph_brush::ab_glyph::{Font, Rect};
+use glyph_brush::{GlyphChange, GlyphPositioner, SectionGeometry, SectionGlyph, ToSectionText};
+
+/// Counters of the caching behaviour of a brush, accumulated since it was created or the
+/// metrics were last reset, see [`GlyphBrush::metrics`](struct.GlyphBrush.html#method.metrics).
+///
+/// Frequent cache resizes, or many rasterized glyphs in frames showing the same text, indicate
+/// a glyph cache too small for the working set of glyphs, see
+/// [`GlyphBrushBuilder::initial_cache_size`](struct.GlyphBrushBuilder.html#method.initial_cache_size).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of sections queued with a layout, excluding pre-positioned glyphs.
+    pub sections: usize,
+    /// The number of queued sections whose layout was reused from the cache instead of being
+    /// calculated.
+    pub layout_cache_hits: usize,
+    /// The number of glyph cache updates, one for each newly rasterized glyph.
+    pub rasterized_glyphs: usize,
+    /// The number of times the glyph cache was grown, discarding all cached glyphs.
+    pub cache_resizes: usize,
+}
+
+impl Metrics {
+    /// Returns the number of queued sections whose layout had to be calculated.
+    #[inline]
+    pub fn layout_cache_misses(&self) -> usize {
+        self.sections - self.layout_cache_hits
+    }
+
+    /// Records a queued section, which missed the layout cache if `layout` was used.
+    pub(crate) fn record_section<L>(&mut self, layout: &CountedLayout<'_, L>) {
+        self.sections += 1;
+        if layout.calculations.get() == 0 {
+            self.layout_cache_hits += 1;
+        }
+    }
+
+    /// Records a queued section whose layout was reused without looking it up in the cache.
+    #[inline]
+    pub(crate) fn record_reused_section(&mut self) {
+        self.sections += 1;
+        self.layout_cache_hits += 1;
+    }
+}
+
+/// Wraps a layout, counting how often glyphs are calculated with it.
+pub(crate) struct CountedLayout<'a, L> {
+    layout: &'a L,
+    calculations: Cell<usize>,
+}
+
+impl<'a, L> CountedLayout<'a, L> {
+    #[inline]
+    pub fn new(layout: &'a L) -> Self {
+        CountedLayout {
+            layout,
+            calculations: Cell::new(0),
+        }
+    }
+}
+
+impl<L: Hash> Hash for CountedLayout<'_, L> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.layout.hash(state);
+    }
+}
+
+impl<L: GlyphPositioner> GlyphPositioner for CountedLayout<'_, L> {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        self.calculations.set(self.calculations.get() + 1);
+        self.layout.calculate_glyphs(fonts, geometry, sections)
+    }
+
+    #[inline]
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect {
+        self.layout.bounds_rect(geometry)
+    }
+
+    fn recalculate_glyphs<F, S, P>(
+        &self,
+        previous: P,
+        change: GlyphChange,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+        P: IntoIterator<Item = SectionGlyph>,
+    {
+        self.calculations.set(self.calculations.get() + 1);
+        self.layout
+            .recalculate_glyphs(previous, change, fonts, geometry, sections)
+    }
+}
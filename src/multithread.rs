@@ -0,0 +1,58 @@
+//! Entering the critical section of devices whose immediate context is shared between threads.
+#![allow(non_snake_case)]
+use winapi::shared::minwindef::BOOL;
+use winapi::um::d3d11::ID3D11Device;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::{Interface, RIDL};
+use wio::com::ComPtr;
+
+use crate::util::{com_ptr_from_fn, HResult};
+
+// not part of winapi 0.3, shares its IID with ID3D10Multithread
+RIDL! {#[uuid(0x9b7e4e00, 0x342c, 0x4106, 0xa1, 0x9f, 0x4f, 0x27, 0x04, 0xf6, 0x89, 0xf0)]
+interface ID3D11Multithread(ID3D11MultithreadVtbl): IUnknown(IUnknownVtbl) {
+    fn Enter() -> (),
+    fn Leave() -> (),
+    fn SetMultithreadProtected(
+        bMTProtect: BOOL,
+    ) -> BOOL,
+    fn GetMultithreadProtected() -> BOOL,
+}}
+
+/// The critical section of a device, which is entered around all uses of its immediate
+/// context by the brush.
+pub struct Multithread(ComPtr<ID3D11Multithread>);
+
+impl Multithread {
+    /// Fails with `E_NOINTERFACE` if the runtime doesn't support `ID3D11Multithread`.
+    pub fn new(device: &ComPtr<ID3D11Device>) -> HResult<Self> {
+        let multithread = unsafe {
+            com_ptr_from_fn(
+                "QueryInterface",
+                |multithread: &mut *mut ID3D11Multithread| {
+                    device.QueryInterface(
+                        &ID3D11Multithread::uuidof(),
+                        (multithread as *mut *mut ID3D11Multithread).cast(),
+                    )
+                },
+            )?
+        };
+        Ok(Multithread(multithread))
+    }
+
+    /// Enters the critical section until the returned guard is dropped.
+    #[inline]
+    pub fn lock(&self) -> MultithreadGuard {
+        unsafe { self.0.Enter() };
+        MultithreadGuard(self.0.clone())
+    }
+}
+
+pub struct MultithreadGuard(ComPtr<ID3D11Multithread>);
+
+impl Drop for MultithreadGuard {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.0.Leave() };
+    }
+}
@@ -0,0 +1,122 @@
+use std::ptr;
+
+use winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM;
+use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
+use winapi::um::d3d11::{
+    D3D11CreateDevice, ID3D11DepthStencilView, ID3D11Device, ID3D11DeviceContext,
+    ID3D11RenderTargetView, ID3D11Texture2D, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE,
+    D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_VIEWPORT,
+    D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE,
+};
+use winapi::um::d3dcommon::D3D_DRIVER_TYPE_WARP;
+use wio::com::ComPtr;
+
+use crate::util::{com_ptr_from_fn, com_ref_cast, HResult};
+
+/// Creates a device and its immediate context on WARP, the software rasterizer shipped with
+/// Windows, to render text into textures on machines without a GPU, e.g. servers and CI runners.
+///
+/// See also
+/// [`GlyphBrushBuilder::build_warp`](struct.GlyphBrushBuilder.html#method.build_warp).
+pub fn create_warp_device() -> HResult<(ComPtr<ID3D11Device>, ComPtr<ID3D11DeviceContext>)> {
+    let mut context = ptr::null_mut();
+    let device = unsafe {
+        com_ptr_from_fn("D3D11CreateDevice", |device| {
+            D3D11CreateDevice(
+                ptr::null_mut(),
+                D3D_DRIVER_TYPE_WARP,
+                ptr::null_mut(),
+                0,
+                ptr::null(),
+                0,
+                D3D11_SDK_VERSION,
+                device,
+                ptr::null_mut(),
+                &mut context,
+            )
+        })?
+    };
+    Ok((device, unsafe { ComPtr::from_raw(context) }))
+}
+
+/// Creates a texture usable as both render target and shader resource.
+pub unsafe fn create_render_target(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+) -> HResult<(ComPtr<ID3D11Texture2D>, ComPtr<ID3D11RenderTargetView>)> {
+    let desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_DEFAULT,
+        BindFlags: D3D11_BIND_RENDER_TARGET | D3D11_BIND_SHADER_RESOURCE,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+    };
+    let texture = com_ptr_from_fn("CreateTexture2D", |texture| {
+        device.CreateTexture2D(&desc, ptr::null(), texture)
+    })?;
+    let view = com_ptr_from_fn("CreateRenderTargetView", |view| {
+        device.CreateRenderTargetView(com_ref_cast(&texture).as_raw(), ptr::null(), view)
+    })?;
+    Ok((texture, view))
+}
+
+/// Binds `target` with a viewport covering it and clears it, runs `f` and restores the
+/// previously bound render targets and viewports afterwards.
+pub unsafe fn with_render_target<T>(
+    ctx: &ID3D11DeviceContext,
+    target: &ComPtr<ID3D11RenderTargetView>,
+    width: u32,
+    height: u32,
+    f: impl FnOnce() -> T,
+) -> T {
+    let mut old_viewport_count = D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE;
+    let mut old_viewports = [std::mem::zeroed::<D3D11_VIEWPORT>();
+        D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE as usize];
+    ctx.RSGetViewports(&mut old_viewport_count, old_viewports.as_mut_ptr());
+
+    ctx.ClearRenderTargetView(target.as_raw(), &[0.0; 4]);
+    ctx.RSSetViewports(
+        1,
+        &D3D11_VIEWPORT {
+            TopLeftX: 0.0,
+            TopLeftY: 0.0,
+            Width: width as f32,
+            Height: height as f32,
+            MinDepth: 0.0,
+            MaxDepth: 1.0,
+        },
+    );
+
+    let result = restoring_render_targets(ctx, f);
+
+    ctx.RSSetViewports(old_viewport_count, old_viewports.as_ptr());
+    result
+}
+
+/// Runs `f` and rebinds the render target and depth stencil view bound before afterwards.
+pub unsafe fn restoring_render_targets<T>(ctx: &ID3D11DeviceContext, f: impl FnOnce() -> T) -> T {
+    let mut old_target = ptr::null_mut();
+    let mut old_depth_stencil_view: *mut ID3D11DepthStencilView = ptr::null_mut();
+    ctx.OMGetRenderTargets(1, &mut old_target, &mut old_depth_stencil_view);
+
+    let result = f();
+
+    ctx.OMSetRenderTargets(1, &old_target, old_depth_stencil_view);
+    // OMGetRenderTargets adds references which have to be released again
+    if let Some(old_target) = old_target.as_ref() {
+        old_target.Release();
+    }
+    if let Some(old_depth_stencil_view) = old_depth_stencil_view.as_ref() {
+        old_depth_stencil_view.Release();
+    }
+    result
+}
@@ -0,0 +1,112 @@
+use std::hash::BuildHasher;
+
+use glyph_brush::ab_glyph::{point, Font, Rect};
+use glyph_brush::{Extra, GlyphVertex, Section, Text};
+
+use crate::pipeline::DrawState;
+use crate::util::HResult;
+use crate::{BrushVertex, GlyphBrush, GlyphExtra};
+
+/// The pixel size of the statistics text of the debug overlay.
+const TEXT_SCALE: f32 = 16.0;
+
+/// The quad of the glyph cache drawn by the debug overlay and the extra it is drawn with.
+pub(crate) type DebugOverlay<X> = (Rect, X);
+
+impl<F, H, X, V> GlyphBrush<F, H, X, V>
+where
+    F: Font + Sync,
+    H: BuildHasher,
+    X: GlyphExtra,
+    V: BrushVertex<X>,
+{
+    /// Draws the glyph cache scaled by `scale` at `(x, y)` with statistics below it, e.g. to
+    /// diagnose cache resizes or missing glyphs in a running game.
+    ///
+    /// The overlay is queued like a section and drawn by the next draw of the queue on top of
+    /// the other text, using the color and effects of `extra`. It shows the size of the cache
+    /// and the share of its rows the packed glyphs reach into, which requires a
+    /// [CPU copy of the cache](struct.GlyphBrushBuilder.html#method.cache_shadow_copy), the
+    /// sections queued before the overlay, the glyph instances drawn by the last frame and the
+    /// [metrics](#method.metrics).
+    pub fn draw_debug_overlay(&mut self, x: f32, y: f32, scale: f32, extra: X) {
+        let (width, height) = self.pipeline.cache().dimensions();
+        // glyphs are packed in rows from the top, so the lowest covered texel bounds them
+        let texel_size = self.pipeline.cache().texel_size();
+        let used_rows = self.cache_contents().map(|(_, data)| {
+            data.iter()
+                .rposition(|&texel| texel != 0)
+                .map_or(0, |index| index / (width as usize * texel_size) + 1)
+        });
+        let used_rows = match used_rows {
+            Some(rows) => format!(", {:.0}% of rows used", 100.0 * rows as f32 / height as f32),
+            None => String::new(),
+        };
+        let metrics = self.metrics;
+        let stats = format!(
+            "cache {}x{}{}\n\
+             {} sections queued, {} glyphs drawn last frame\n\
+             {} glyphs rasterized, {} cache resizes",
+            width,
+            height,
+            used_rows,
+            self.queued.len(),
+            self.pipeline.glyph_count() - self.uploaded_overlay.is_some() as usize,
+            metrics.rasterized_glyphs,
+            metrics.cache_resizes,
+        );
+
+        let quad = Rect {
+            min: point(x, y),
+            max: point(x + width as f32 * scale, y + height as f32 * scale),
+        };
+        let text = Text::<Extra>::new(&stats)
+            .with_scale(TEXT_SCALE)
+            .with_extra(extra.clone());
+        self.queue(Section::from(text).with_screen_position((x, quad.max.y + TEXT_SCALE / 4.0)));
+        self.debug_overlay = Some((quad, extra));
+    }
+
+    /// Appends the quad of the overlay requested since the last `process_queued` call to the
+    /// uploaded vertices, or removes the one of the last frame, if it changed.
+    ///
+    /// `uploaded` is `true` if new vertices without an overlay have just been uploaded.
+    /// Returns `true` if the vertices have been uploaded again.
+    pub(crate) fn upload_debug_overlay(&mut self, uploaded: bool) -> HResult<bool> {
+        if uploaded {
+            self.uploaded_overlay = None;
+        }
+        let overlay = self.debug_overlay.take();
+        if overlay == self.uploaded_overlay {
+            return Ok(false);
+        }
+        let mut vertices = self.pipeline.vertices().to_vec();
+        let mut states = self.pipeline.draw_states();
+        if self.uploaded_overlay.is_some() {
+            vertices.pop();
+            states.pop();
+        }
+        if let Some((quad, extra)) = &overlay {
+            // the shader scales texture coordinates from the layout to the texture height
+            let cache = self.pipeline.cache();
+            let tex_bottom = cache.dimensions().1 as f32 / cache.layout_height() as f32;
+            states.push(DrawState::new(extra));
+            vertices.push(V::from_glyph(
+                GlyphVertex {
+                    tex_coords: Rect {
+                        min: point(0.0, 0.0),
+                        max: point(1.0, tex_bottom),
+                    },
+                    pixel_coords: *quad,
+                    bounds: *quad,
+                    extra,
+                },
+                self.bounds_clipping,
+            ));
+        }
+        self.pipeline.upload(&vertices)?;
+        self.pipeline.set_draw_states(states);
+        self.uploaded_overlay = overlay;
+        Ok(true)
+    }
+}
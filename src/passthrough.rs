@@ -0,0 +1,417 @@
+use std::borrow::Cow;
+use std::hash::BuildHasher;
+use std::io;
+use std::path::Path;
+
+use glyph_brush::ab_glyph::{Font, FontArc, FontRef, PxScale, Rect};
+use glyph_brush::{BrushAction, BrushError, DefaultSectionHasher, Extra, Rectangle};
+use winapi::um::d3d11::D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
+
+use crate::backend::{self, TextRenderBackend};
+use crate::font::{self, FontMetrics, FontNames};
+use crate::layout::{self, LayoutOptions, StyledLayout};
+use crate::lines::{self, Line};
+use crate::metrics::{CountedLayout, Metrics};
+use crate::pipeline::{BoundsClipping, Vertex};
+use crate::util::HResult;
+use crate::{
+    grown_cache_size, BrushVertex, DrawTarget, Error, FontId, GlyphCruncher, GlyphExtra,
+    GlyphPositioner, MissingGlyph, Section, SectionGlyph, SectionGlyphIter,
+};
+
+/// A brush that lays out and caches glyphs like [`GlyphBrush`](struct.GlyphBrush.html) but
+/// doesn't own any GPU resources.
+///
+/// Instead of uploading and drawing, glyph cache updates and the generated glyph quads are
+/// handed to the caller, allowing the layout and caching machinery to be used by a custom
+/// renderer. Built with
+/// [`GlyphBrushBuilder::build_passthrough`](struct.GlyphBrushBuilder.html#method.build_passthrough).
+pub struct PassthroughBrush<F = FontArc, H = DefaultSectionHasher, X = Extra, V = Vertex> {
+    glyph_brush: glyph_brush::GlyphBrush<V, X, F, H>,
+    bounds_clipping: BoundsClipping,
+    layout_options: LayoutOptions,
+    font_names: FontNames,
+    metrics: Metrics,
+}
+
+impl<F: Font, H: BuildHasher, X: GlyphExtra, V: BrushVertex<X>> PassthroughBrush<F, H, X, V> {
+    pub(crate) fn new(
+        bounds_clipping: BoundsClipping,
+        layout_options: LayoutOptions,
+        raw_builder: glyph_brush::GlyphBrushBuilder<F, H>,
+    ) -> Self {
+        PassthroughBrush {
+            glyph_brush: raw_builder.build(),
+            bounds_clipping,
+            layout_options,
+            font_names: FontNames::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Queues a section/layout to be processed by the next call of
+    /// [`process_queued`](#method.process_queued).
+    #[inline]
+    pub fn queue<'a, S>(&mut self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.queue_custom_layout(section, &layout)
+    }
+
+    /// Queues a section/layout with a custom `GlyphPositioner` to be processed by the next call
+    /// of [`process_queued`](#method.process_queued).
+    #[inline]
+    pub fn queue_custom_layout<'a, S, G>(&mut self, section: S, custom_layout: &G)
+    where
+        G: GlyphPositioner,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self.queue_counted(section, &layout),
+            None => self.queue_counted(section, custom_layout),
+        }
+    }
+
+    fn queue_counted<'a, G>(&mut self, section: Cow<'a, Section<'a, X>>, layout: &G)
+    where
+        G: GlyphPositioner,
+        X: 'a,
+    {
+        let layout = CountedLayout::new(layout);
+        self.glyph_brush.queue_custom_layout(section, &layout);
+        self.metrics.record_section(&layout);
+    }
+
+    /// Queues pre-positioned glyphs to be processed by the next call of
+    /// [`process_queued`](#method.process_queued).
+    #[inline]
+    pub fn queue_pre_positioned(&mut self, glyphs: Vec<SectionGlyph>, extra: Vec<X>, bounds: Rect) {
+        self.glyph_brush.queue_pre_positioned(glyphs, extra, bounds)
+    }
+
+    /// Retains pre-positioned glyphs in the cache as if they had been queued in the last
+    /// draw-frame.
+    #[inline]
+    pub fn keep_cached_pre_positioned(&mut self, glyphs: &[SectionGlyph]) {
+        layout::keep_cached_pre_positioned(&mut self.glyph_brush, glyphs)
+    }
+
+    /// Retains the section in the cache as if it had been used in the last draw-frame.
+    #[inline]
+    pub fn keep_cached<'a, S>(&mut self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        match StyledLayout::new(&layout, &section, &self.layout_options) {
+            Some(styled) => self.glyph_brush.keep_cached_custom_layout(section, &styled),
+            None => self.glyph_brush.keep_cached_custom_layout(section, &layout),
+        }
+    }
+
+    /// Lays out the section and returns its lines and their words, e.g. for word-level hit
+    /// targets, word selection or per-line decorations.
+    pub fn lines<'a, S>(&mut self, section: S) -> Vec<Line>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let mut glyphs: Vec<_> = self.glyphs(section.as_ref()).cloned().collect();
+        lines::lines(self.fonts(), &section, &mut glyphs)
+    }
+
+    /// Returns the available fonts.
+    #[inline]
+    pub fn fonts(&self) -> &[F] {
+        self.glyph_brush.fonts()
+    }
+
+    pub fn add_font(&mut self, font: F) -> FontId {
+        self.glyph_brush.add_font(font)
+    }
+
+    /// Returns the vertical metrics of the font `font_id` at `scale`, e.g. to reserve space for
+    /// text before laying it out.
+    pub fn font_metrics(&self, font_id: FontId, scale: impl Into<PxScale>) -> FontMetrics {
+        FontMetrics::new(&self.fonts()[font_id.0], scale.into())
+    }
+
+    /// Adds a font that can be looked up by `name` with [`font_id`](#method.font_id). A font
+    /// previously registered under the same name keeps its id but can't be looked up anymore.
+    pub fn add_font_named(&mut self, name: impl Into<String>, font: F) -> FontId {
+        let font_id = self.add_font(font);
+        self.font_names.insert(name.into(), font_id);
+        font_id
+    }
+
+    /// Returns the id of the font added under `name` with
+    /// [`add_font_named`](#method.add_font_named).
+    #[inline]
+    pub fn font_id(&self, name: &str) -> Option<FontId> {
+        self.font_names.get(name)
+    }
+
+    /// Maps the face at `index` of a font file into memory and adds it, see
+    /// [`map_font`](fn.map_font.html).
+    pub fn add_font_file(&mut self, path: impl AsRef<Path>, index: u32) -> io::Result<FontId>
+    where
+        F: From<FontRef<'static>>,
+    {
+        Ok(self.add_font(font::map_font(path, index)?.into()))
+    }
+
+    /// Sets the font emojis fall back to if the font of their text doesn't contain them.
+    ///
+    /// See [`GlyphBrush::set_emoji_font`](struct.GlyphBrush.html#method.set_emoji_font).
+    pub fn set_emoji_font(&mut self, font_id: Option<FontId>) {
+        self.layout_options.emoji_font = font_id;
+    }
+
+    /// Sets what is drawn for characters missing from the font of their text.
+    ///
+    /// See [`GlyphBrush::set_missing_glyph`](struct.GlyphBrush.html#method.set_missing_glyph).
+    pub fn set_missing_glyph(&mut self, missing_glyph: MissingGlyph) {
+        self.layout_options.missing_glyph = missing_glyph;
+    }
+
+    /// Replaces the font `font_id`, returning the previous font.
+    ///
+    /// All cached layouts and glyphs are discarded, as are the queued sections. The whole cache
+    /// texture content is invalid afterwards.
+    pub fn replace_font(&mut self, font_id: FontId, font: F) -> F
+    where
+        F: Clone,
+        H: Clone,
+    {
+        font::rebuild_fonts(&mut self.glyph_brush, |fonts| {
+            std::mem::replace(&mut fonts[font_id.0], font)
+        })
+    }
+
+    /// Removes the font `font_id`, returning it. The ids of the fonts added after it are
+    /// decremented by one.
+    ///
+    /// All cached layouts and glyphs are discarded, as are the queued sections. The whole cache
+    /// texture content is invalid afterwards.
+    pub fn remove_font(&mut self, font_id: FontId) -> F
+    where
+        F: Clone,
+        H: Clone,
+    {
+        let removed = font::rebuild_fonts(&mut self.glyph_brush, |fonts| fonts.remove(font_id.0));
+        self.layout_options.remove_font(font_id);
+        self.font_names.remove_font(font_id);
+        removed
+    }
+
+    /// Returns the current dimensions of the glyph cache texture.
+    #[inline]
+    pub fn texture_dimensions(&self) -> (u32, u32) {
+        self.glyph_brush.texture_dimensions()
+    }
+
+    /// Returns the caching counters accumulated since the brush was created or
+    /// [`reset_metrics`](#method.reset_metrics) was last called.
+    #[inline]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Resets all counters of [`metrics`](#method.metrics) to zero.
+    #[inline]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = Metrics::default();
+    }
+}
+
+impl<F: Font + Sync, H: BuildHasher, X: GlyphExtra, V: BrushVertex<X>>
+    PassthroughBrush<F, H, X, V>
+{
+    /// Processes all queued sections.
+    ///
+    /// `update_cache` is called with the rect and the single channel coverage data of each
+    /// glyph newly rasterized into the cache. `resize_cache` is called with the new dimensions
+    /// whenever the cache has to grow, after which the whole cache texture content is invalid.
+    ///
+    /// Returns the glyph quads to draw, or `None` if the quads of the previous call can be
+    /// drawn again unmodified. Fails with
+    /// [`Error::CacheTooLarge`](enum.Error.html#variant.CacheTooLarge) if the cache would have
+    /// to grow past the maximum texture dimension of D3D11.
+    pub fn process_queued<U, R>(
+        &mut self,
+        mut update_cache: U,
+        mut resize_cache: R,
+    ) -> HResult<Option<Vec<V>>>
+    where
+        U: FnMut(Rectangle<u32>, &[u8]),
+        R: FnMut(u32, u32),
+    {
+        trace_span!(
+            "process_queued",
+            cache_width = self.glyph_brush.texture_dimensions().0,
+            cache_height = self.glyph_brush.texture_dimensions().1,
+        );
+        let bounds_clipping = self.bounds_clipping;
+        let metrics = &mut self.metrics;
+        loop {
+            let brush_action = self.glyph_brush.process_queued(
+                |rect, tex_data| {
+                    metrics.rasterized_glyphs += 1;
+                    update_cache(rect, tex_data)
+                },
+                |v| V::from_glyph(v, bounds_clipping),
+            );
+
+            match brush_action {
+                Ok(BrushAction::Draw(vertices)) => return Ok(Some(vertices)),
+                Ok(BrushAction::ReDraw) => return Ok(None),
+                Err(BrushError::TextureTooSmall { suggested }) => {
+                    let max_dimension = D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
+                    let (new_width, new_height) = grown_cache_size(
+                        self.glyph_brush.texture_dimensions(),
+                        suggested,
+                        max_dimension,
+                    );
+                    // the suggestion is only clamped while the cache can still grow
+                    if new_width > max_dimension || new_height > max_dimension {
+                        return Err(Error::CacheTooLarge {
+                            requested: (new_width, new_height),
+                            max_dimension,
+                        });
+                    }
+                    trace_span!("resize_cache", width = new_width, height = new_height);
+                    resize_cache(new_width, new_height);
+                    metrics.cache_resizes += 1;
+                    self.glyph_brush.resize_texture(new_width, new_height);
+                }
+            }
+        }
+    }
+}
+
+impl<F: Font + Sync, H: BuildHasher, X: GlyphExtra, V: BrushVertex<X>>
+    PassthroughBrush<F, H, X, V>
+{
+    /// Processes all queued sections using `backend` to update the cache and upload the glyph
+    /// quads.
+    ///
+    /// Returns `true` if new quads have been uploaded.
+    pub fn process_queued_with<B>(&mut self, backend: &mut B) -> Result<bool, B::Error>
+    where
+        B: TextRenderBackend<V>,
+    {
+        let bounds_clipping = self.bounds_clipping;
+        let vertices = backend::process_queued(
+            &mut self.glyph_brush,
+            backend,
+            &mut self.metrics,
+            |vertex| V::from_glyph(vertex, bounds_clipping),
+        )?;
+        match vertices {
+            Some(vertices) => backend.upload_vertices(&vertices).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Processes all queued sections using `backend` and draws them into `target`.
+    pub fn draw_queued_with<B>(
+        &mut self,
+        backend: &mut B,
+        target: &DrawTarget<'_>,
+    ) -> Result<(), B::Error>
+    where
+        B: TextRenderBackend<V>,
+    {
+        self.process_queued_with(backend)?;
+        backend.draw(target)
+    }
+}
+
+impl<F: Font, H: BuildHasher, X: GlyphExtra, V: BrushVertex<X>> GlyphCruncher<F, X>
+    for PassthroughBrush<F, H, X, V>
+{
+    #[inline]
+    fn glyphs_custom_layout<'a, 'b, S, L>(
+        &'b mut self,
+        section: S,
+        custom_layout: &L,
+    ) -> SectionGlyphIter<'b>
+    where
+        L: GlyphPositioner + std::hash::Hash,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self.glyph_brush.glyphs_custom_layout(section, &layout),
+            None => self
+                .glyph_brush
+                .glyphs_custom_layout(section, custom_layout),
+        }
+    }
+
+    #[inline]
+    fn glyphs<'a, 'b, S>(&'b mut self, section: S) -> SectionGlyphIter<'b>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.glyphs_custom_layout(section, &layout)
+    }
+
+    #[inline]
+    fn fonts(&self) -> &[F] {
+        self.glyph_brush.fonts()
+    }
+
+    #[inline]
+    fn glyph_bounds<'a, S>(&mut self, section: S) -> Option<Rect>
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        let layout = layout::grapheme_layout(section.layout);
+        self.glyph_bounds_custom_layout(section, &layout)
+    }
+
+    #[inline]
+    fn glyph_bounds_custom_layout<'a, S, L>(
+        &mut self,
+        section: S,
+        custom_layout: &L,
+    ) -> Option<Rect>
+    where
+        L: GlyphPositioner + std::hash::Hash,
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = section.into();
+        match StyledLayout::new(custom_layout, &section, &self.layout_options) {
+            Some(layout) => self
+                .glyph_brush
+                .glyph_bounds_custom_layout(section, &layout),
+            None => self
+                .glyph_brush
+                .glyph_bounds_custom_layout(section, custom_layout),
+        }
+    }
+}
+
+impl<F, H, X, V> std::fmt::Debug for PassthroughBrush<F, H, X, V> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PassthroughBrush")
+    }
+}
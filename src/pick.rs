@@ -0,0 +1,208 @@
+use std::mem;
+use std::ops::Range;
+
+use glyph_brush::ab_glyph::{point, Font, Point, Rect, ScaleFont};
+use glyph_brush::SectionGlyph;
+
+use crate::region;
+
+/// The glyph found by [`GlyphBrush::pick`](struct.GlyphBrush.html#method.pick).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PickResult {
+    /// The index of the section in the order it was queued in, counting pre-positioned glyph
+    /// batches as a section.
+    pub section_index: usize,
+    /// The index of the text of the section containing the glyph.
+    pub text_index: usize,
+    /// The byte offset of the glyph's character in its text.
+    pub byte_offset: usize,
+}
+
+/// The area covered by a glyph, from its origin to its advance and from the ascent to the
+/// descent of its font.
+struct PickBox {
+    rect: Rect,
+    result: PickResult,
+}
+
+/// The glyphs of a queued section, which are matched with its vertices once processed.
+struct QueuedSection {
+    /// Identifies the section and its layout, `None` for pre-positioned glyphs.
+    key: Option<u64>,
+    /// Whether the glyphs are queued as pre-positioned glyphs, whose vertices glyph_brush
+    /// places after those of all laid out sections.
+    pre_positioned: bool,
+    glyphs: Vec<SectionGlyph>,
+    bounds: Rect,
+    boxes: Vec<PickBox>,
+}
+
+/// The vertices of a section of the processed batch.
+pub(crate) struct SectionVertices {
+    pub range: Range<usize>,
+    /// The text index and byte offset of the glyph of each vertex.
+    pub glyphs: Vec<(usize, usize)>,
+}
+
+/// The pick boxes of the queued sections and of the last processed batch.
+#[derive(Default)]
+pub(crate) struct PickBoxes {
+    /// The pick boxes of the processed batch in the order they are drawn in.
+    processed: Vec<PickBox>,
+    queued_sections: Vec<QueuedSection>,
+    processed_sections: Vec<(Option<u64>, SectionVertices)>,
+}
+
+impl PickBoxes {
+    /// Records the glyphs of a queued section, restricted to its `bounds`, identified by `key`
+    /// unless they are pre-positioned glyphs of the user.
+    pub fn record<F: Font>(
+        &mut self,
+        section_index: usize,
+        key: Option<u64>,
+        pre_positioned: bool,
+        fonts: &[F],
+        glyphs: &[SectionGlyph],
+        bounds: Rect,
+    ) {
+        let boxes = glyphs.iter().map(|glyph| {
+            let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+            let position = glyph.glyph.position;
+            let rect = Rect {
+                min: point(position.x, position.y - font.ascent()),
+                max: point(
+                    position.x + font.h_advance(glyph.glyph.id),
+                    position.y - font.descent(),
+                ),
+            };
+            PickBox {
+                rect: region::intersect_rect(rect, bounds),
+                result: PickResult {
+                    section_index,
+                    text_index: glyph.section_index,
+                    byte_offset: glyph.byte_index,
+                },
+            }
+        });
+        self.queued_sections.push(QueuedSection {
+            key,
+            pre_positioned,
+            glyphs: glyphs.to_vec(),
+            bounds,
+            boxes: boxes.collect(),
+        });
+    }
+
+    /// Makes the queued pick boxes the ones of the processed batch, assigning the vertices of
+    /// the batch to the queued sections in the order glyph_brush generates them, the laid out
+    /// sections in the order they were queued in followed by the pre-positioned glyphs.
+    /// `is_drawn` returns whether a glyph of a section with the given bounds has been turned
+    /// into a vertex.
+    pub fn finish_frame(&mut self, is_drawn: impl Fn(&SectionGlyph, Rect) -> bool) {
+        let mut sections = mem::take(&mut self.queued_sections);
+        // the sort is stable, so the sections keep their order within both groups
+        sections.sort_by_key(|section| section.pre_positioned);
+        self.processed.clear();
+        self.processed_sections.clear();
+        let mut start = 0;
+        for section in sections {
+            let glyphs: Vec<_> = (section.glyphs.iter())
+                .filter(|glyph| is_drawn(glyph, section.bounds))
+                .map(|glyph| (glyph.section_index, glyph.byte_index))
+                .collect();
+            let range = start..start + glyphs.len();
+            start = range.end;
+            (self.processed_sections).push((section.key, SectionVertices { range, glyphs }));
+            self.processed.extend(section.boxes);
+        }
+    }
+
+    /// Forgets the queued sections, keeping the pick boxes of the processed batch.
+    #[inline]
+    pub fn clear_queued(&mut self) {
+        self.queued_sections.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.processed.clear();
+        self.queued_sections.clear();
+        self.processed_sections.clear();
+    }
+
+    /// Returns the vertices of the first section of the processed batch identified by `key`.
+    pub fn section_vertices(&self, key: u64) -> Option<&SectionVertices> {
+        (self.processed_sections.iter())
+            .find(|(section_key, _)| *section_key == Some(key))
+            .map(|(_, vertices)| vertices)
+    }
+
+    /// Returns the number of vertices of the processed batch assigned to its sections.
+    pub fn vertex_count(&self) -> usize {
+        (self.processed_sections.iter())
+            .map(|(_, vertices)| vertices.range.end)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the topmost glyph of the processed batch containing `point`.
+    pub fn pick(&self, point: Point) -> Option<PickResult> {
+        self.processed
+            .iter()
+            .rev()
+            .find(|pick_box| {
+                let rect = pick_box.rect;
+                point.x >= rect.min.x
+                    && point.x < rect.max.x
+                    && point.y >= rect.min.y
+                    && point.y < rect.max.y
+            })
+            .map(|pick_box| pick_box.result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glyph_brush::ab_glyph::FontRef;
+
+    use super::*;
+
+    fn inconsolata() -> FontRef<'static> {
+        FontRef::try_from_slice(include_bytes!("../examples/Inconsolata-Regular.ttf")).unwrap()
+    }
+
+    fn glyphs(count: usize) -> Vec<SectionGlyph> {
+        let id = inconsolata().glyph_id('a');
+        (0..count)
+            .map(|index| SectionGlyph {
+                section_index: 0,
+                byte_index: index,
+                glyph: id.with_scale_and_position(16.0, point(index as f32 * 10.0, 20.0)),
+                font_id: Default::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pre_positioned_vertices_follow_laid_out_sections() {
+        let fonts = [inconsolata()];
+        let bounds = Rect {
+            min: point(0.0, 0.0),
+            max: point(100.0, 100.0),
+        };
+        let mut pick_boxes = PickBoxes::default();
+        pick_boxes.record(0, Some(1), true, &fonts, &glyphs(2), bounds);
+        pick_boxes.record(1, Some(2), false, &fonts, &glyphs(3), bounds);
+        pick_boxes.record(2, None, true, &fonts, &glyphs(1), bounds);
+        pick_boxes.record(3, Some(4), false, &fonts, &glyphs(1), bounds);
+        pick_boxes.finish_frame(|_, _| true);
+
+        let range = |key| pick_boxes.section_vertices(key).unwrap().range.clone();
+        assert_eq!(range(2), 0..3);
+        assert_eq!(range(4), 3..4);
+        assert_eq!(range(1), 4..6);
+        assert_eq!(pick_boxes.vertex_count(), 7);
+        // the pre-positioned glyphs are drawn on top
+        let picked = pick_boxes.pick(point(5.0, 15.0)).unwrap();
+        assert_eq!(picked.section_index, 2);
+    }
+}
@@ -1,190 +1,808 @@
+use std::borrow::Cow;
 use std::convert::TryInto;
+use std::ffi::CStr;
 use std::marker::PhantomData;
 use std::{mem, ptr};
 
+use glyph_brush::ab_glyph::Rect;
 use glyph_brush::Rectangle;
-use winapi::shared::dxgiformat::{
-    DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R32G32B32_FLOAT, DXGI_FORMAT_R32G32_FLOAT,
-};
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R32_TYPELESS};
 use winapi::shared::minwindef::{FALSE, TRUE};
 use winapi::um::d3d11::{
     ID3D11BlendState, ID3D11Buffer, ID3D11DepthStencilState, ID3D11DepthStencilView, ID3D11Device,
     ID3D11DeviceContext, ID3D11InputLayout, ID3D11PixelShader, ID3D11RasterizerState,
-    ID3D11RenderTargetView, ID3D11SamplerState, ID3D11VertexShader, D3D11_BLEND_DESC,
-    D3D11_BUFFER_DESC, D3D11_DEPTH_STENCILOP_DESC, D3D11_DEPTH_STENCIL_DESC, D3D11_FILTER,
-    D3D11_INPUT_ELEMENT_DESC, D3D11_RASTERIZER_DESC, D3D11_RECT, D3D11_RENDER_TARGET_BLEND_DESC,
-    D3D11_SAMPLER_DESC, D3D11_SUBRESOURCE_DATA,
+    ID3D11RenderTargetView, ID3D11SamplerState, ID3D11ShaderResourceView, ID3D11VertexShader,
+    D3D11_BLEND_DESC, D3D11_BUFFER_DESC, D3D11_DEPTH_STENCILOP_DESC, D3D11_DEPTH_STENCIL_DESC,
+    D3D11_FILTER, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_INPUT_ELEMENT_DESC, D3D11_RASTERIZER_DESC,
+    D3D11_RECT, D3D11_RENDER_TARGET_BLEND_DESC, D3D11_SAMPLER_DESC,
+    D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_SUBRESOURCE_DATA,
 };
 use winapi::um::d3d11::{
-    D3D11_BIND_CONSTANT_BUFFER, D3D11_BIND_VERTEX_BUFFER, D3D11_BLEND_INV_SRC_ALPHA,
-    D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA, D3D11_COLOR_WRITE_ENABLE_ALL,
-    D3D11_COMPARISON_ALWAYS, D3D11_CPU_ACCESS_WRITE, D3D11_CULL_NONE, D3D11_DEPTH_WRITE_MASK_ALL,
-    D3D11_FILL_SOLID, D3D11_INPUT_PER_INSTANCE_DATA, D3D11_MAP_WRITE_DISCARD,
-    D3D11_STENCIL_OP_KEEP, D3D11_TEXTURE_ADDRESS_CLAMP, D3D11_USAGE_DYNAMIC,
+    D3D11_BIND_SHADER_RESOURCE, D3D11_BIND_VERTEX_BUFFER, D3D11_BLEND_INV_SRC_ALPHA,
+    D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA, D3D11_BLEND_SRC_COLOR,
+    D3D11_BLEND_ZERO, D3D11_BUFFEREX_SRV, D3D11_BUFFEREX_SRV_FLAG_RAW,
+    D3D11_COLOR_WRITE_ENABLE_ALL, D3D11_COMPARISON_ALWAYS, D3D11_CPU_ACCESS_WRITE, D3D11_CULL_NONE,
+    D3D11_DEPTH_WRITE_MASK_ALL, D3D11_FILL_SOLID, D3D11_INPUT_PER_VERTEX_DATA,
+    D3D11_MAP_WRITE_DISCARD, D3D11_RESOURCE_MISC_BUFFER_ALLOW_RAW_VIEWS, D3D11_STENCIL_OP_KEEP,
+    D3D11_TEXTURE_ADDRESS_CLAMP, D3D11_TEXTURE_ADDRESS_WRAP, D3D11_USAGE_DYNAMIC,
+    D3D11_USAGE_IMMUTABLE,
+};
+use winapi::um::d3dcommon::{
+    D3D11_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP, D3D11_SRV_DIMENSION_BUFFEREX, D3D_FEATURE_LEVEL,
+    D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_11_0,
 };
-use winapi::um::d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
 use wio::com::ComPtr;
 
-use crate::cache::Cache;
-use crate::util::{com_ptr_from_fn, com_ref_cast, hresult, HResult};
+use crate::cache::{Cache, CacheUpdateMethod};
+use crate::constants::ConstantBufferPool;
+use crate::glow::GlowPass;
+use crate::multithread::{Multithread, MultithreadGuard};
+use crate::runtime_compiler;
+use crate::state_cache::{depth_stencil_key, DepthStencilKey, StateCache};
+use crate::util::{com_ptr_from_fn, com_ref_cast, hresult, max_texture_dimension, HResult};
+#[cfg(feature = "validation")]
+use crate::validation::Validation;
+use crate::{
+    AtlasFormat, BlendMode, BrushVertex, ClipShape, DepthMode, DepthStencil, DrawTarget, Error,
+    FillTexture, GlyphExtra, Outline, SdfSmoothing, TextRenderBackend, Transform,
+};
 
 #[derive(Debug)]
 struct Buffer {
     ptr: ComPtr<ID3D11Buffer>,
+    /// The raw view the vertex shader reads the instances through, if the pipeline has no
+    /// input layout.
+    view: Option<ComPtr<ID3D11ShaderResourceView>>,
     capacity: usize,
-    len: usize,
 }
 
-pub struct Pipeline<Depth> {
+/// Glyph instances uploaded to a vertex buffer of their own, which are drawn with the shaders,
+/// states and glyph cache shared by all batches of a pipeline.
+pub(crate) struct VertexBatch<V> {
+    /// The buffer holding the uploaded vertices, `None` until the first upload.
+    buffer: Option<Buffer>,
+    /// A copy of the uploaded vertices.
+    vertices: Vec<V>,
+    /// The runs of consecutive uploaded vertices sharing a draw state, as their state and
+    /// length.
+    draw_runs: Vec<(DrawState, usize)>,
+    effects: Effects,
+}
+
+impl<V> Default for VertexBatch<V> {
+    #[inline]
+    fn default() -> Self {
+        VertexBatch {
+            buffer: None,
+            vertices: Vec::new(),
+            draw_runs: Vec::new(),
+            effects: Effects::default(),
+        }
+    }
+}
+
+impl<V: Copy> VertexBatch<V> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    #[inline]
+    pub fn vertices(&self) -> &[V] {
+        &self.vertices
+    }
+
+    /// Returns the draw state of each uploaded vertex.
+    pub fn draw_states(&self) -> Vec<DrawState> {
+        if self.draw_runs.is_empty() {
+            return vec![DrawState::default(); self.vertices.len()];
+        }
+        (self.draw_runs.iter())
+            .flat_map(|&(state, len)| (0..len).map(move |_| state))
+            .collect()
+    }
+
+    /// Returns the uploaded vertices with their draw states.
+    pub fn instances(&self) -> impl Iterator<Item = (V, DrawState)> + '_ {
+        (self.vertices.iter().copied()).zip(self.draw_states())
+    }
+
+    /// Sets the draw states of the uploaded vertices, which are drawn with the default state
+    /// until this is called after an upload.
+    pub fn set_draw_states(&mut self, states: impl IntoIterator<Item = DrawState>) {
+        self.draw_runs.clear();
+        for state in states {
+            match self.draw_runs.last_mut() {
+                Some((last, len)) if *last == state => *len += 1,
+                _ => self.draw_runs.push((state, 1)),
+            }
+        }
+    }
+
+    /// Sets the effects used by the uploaded glyphs, which are drawn in separate passes.
+    #[inline]
+    pub fn set_effects(&mut self, effects: Effects) {
+        self.effects = effects;
+    }
+}
+
+/// The objects bound to the device context by the last draw, used to skip binding them again.
+/// Null pointers haven't been bound yet, unless noted otherwise.
+#[derive(Clone, Copy)]
+struct BoundState {
+    /// Whether the state which never changes has been bound.
+    fixed_state: bool,
+    /// The input layout, which is null if the instances are read from a raw view.
+    input_layout: Option<*mut ID3D11InputLayout>,
+    vertex_buffer: *mut ID3D11Buffer,
+    transform_buf: *mut ID3D11Buffer,
+    pixel_buf: *mut ID3D11Buffer,
+    instance_view: *mut ID3D11ShaderResourceView,
+    vertex_shader: *mut ID3D11VertexShader,
+    pixel_shader: *mut ID3D11PixelShader,
+    user_constants: *mut ID3D11Buffer,
+    depth_stencil_state: *mut ID3D11DepthStencilState,
+    /// The glyph cache and the clip mask.
+    shader_resources: [*mut ID3D11ShaderResourceView; 2],
+    sampler: *mut ID3D11SamplerState,
+    /// The fill texture, which may be bound as null.
+    fill_texture: Option<*mut ID3D11ShaderResourceView>,
+    /// The texture the cache is migrating from, which may be bound as null.
+    migration_view: Option<*mut ID3D11ShaderResourceView>,
+    blend_state: *mut ID3D11BlendState,
+}
+
+impl BoundState {
+    const NONE: BoundState = BoundState {
+        fixed_state: false,
+        input_layout: None,
+        vertex_buffer: ptr::null_mut(),
+        transform_buf: ptr::null_mut(),
+        pixel_buf: ptr::null_mut(),
+        instance_view: ptr::null_mut(),
+        vertex_shader: ptr::null_mut(),
+        pixel_shader: ptr::null_mut(),
+        user_constants: ptr::null_mut(),
+        depth_stencil_state: ptr::null_mut(),
+        shader_resources: [ptr::null_mut(); 2],
+        sampler: ptr::null_mut(),
+        fill_texture: None,
+        migration_view: None,
+        blend_state: ptr::null_mut(),
+    };
+}
+
+/// The effects of the drawn glyphs which require additional passes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Effects {
+    pub outline: bool,
+    pub glow: bool,
+}
+
+impl Effects {
+    /// Adds the effects of a text with the given extra data.
+    pub fn include<X: GlyphExtra>(&mut self, extra: &X) {
+        self.outline |= extra.outline().is_some();
+        self.glow |= extra.glow().is_some();
+    }
+}
+
+/// The state a run of consecutive glyphs is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DrawState {
+    pub blend_mode: BlendMode,
+    /// The filter overriding the sampler of the brush.
+    pub texture_filter: Option<D3D11_FILTER>,
+    pub fill_texture: Option<FillTexture>,
+}
+
+impl DrawState {
+    /// The state glyphs with the given extra data are drawn with.
+    #[inline]
+    pub fn new<X: GlyphExtra>(extra: &X) -> Self {
+        DrawState {
+            blend_mode: extra.blend_mode(),
+            texture_filter: extra.texture_filter(),
+            fill_texture: extra.fill().map(|fill| fill.texture),
+        }
+    }
+}
+
+impl Default for DrawState {
+    #[inline]
+    fn default() -> Self {
+        DrawState {
+            blend_mode: BlendMode::Alpha,
+            texture_filter: None,
+            fill_texture: None,
+        }
+    }
+}
+
+/// Device state configured through the builder.
+#[derive(Clone)]
+pub(crate) struct PipelineOptions {
+    pub filter_mode: D3D11_FILTER,
+    pub max_anisotropy: u32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub rasterizer: D3D11_RASTERIZER_DESC,
+    pub vertex_shader: Option<Vec<u8>>,
+    pub pixel_shader: Option<Vec<u8>>,
+    pub cache_update_method: CacheUpdateMethod,
+    pub cache_shadow_copy: bool,
+    pub glow_radius: f32,
+    pub multithread_protection: bool,
+    pub halo: Option<Outline>,
+    pub single_pass_outlines: bool,
+    pub max_outline_width: f32,
+    pub coverage_gamma: f32,
+    pub cache_format: AtlasFormat,
+    pub sdf_smoothing: SdfSmoothing,
+    /// The height the draw cache is laid out for while the texture starts at its initial
+    /// height, zero if it isn't larger.
+    pub reserved_cache_height: u32,
+    /// The rows copied into a grown cache texture per draw, zero to copy them at once.
+    pub cache_migration_rows: u32,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            filter_mode: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            max_anisotropy: 1,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            rasterizer: D3D11_RASTERIZER_DESC {
+                FillMode: D3D11_FILL_SOLID,
+                CullMode: D3D11_CULL_NONE,
+                FrontCounterClockwise: FALSE,
+                DepthBias: 0,
+                DepthBiasClamp: 0.0,
+                SlopeScaledDepthBias: 0.0,
+                DepthClipEnable: FALSE,
+                ScissorEnable: TRUE,
+                MultisampleEnable: FALSE,
+                AntialiasedLineEnable: FALSE,
+            },
+            vertex_shader: None,
+            pixel_shader: None,
+            cache_update_method: CacheUpdateMethod::default(),
+            cache_shadow_copy: false,
+            glow_radius: 8.0,
+            multithread_protection: false,
+            halo: None,
+            single_pass_outlines: false,
+            max_outline_width: 4.0,
+            coverage_gamma: 1.0,
+            cache_format: AtlasFormat::Coverage,
+            sdf_smoothing: SdfSmoothing::default(),
+            reserved_cache_height: 0,
+            cache_migration_rows: 0,
+        }
+    }
+}
+
+pub struct Pipeline<V = Vertex> {
     device: ComPtr<ID3D11Device>,
     ctx: ComPtr<ID3D11DeviceContext>,
-    vertex_buffer: Buffer,
-    transform_buf: ComPtr<ID3D11Buffer>,
-    transform: [f32; 16],
+    /// The critical section entered around uses of `ctx`, if multithread protection is enabled.
+    multithread: Option<Multithread>,
+    /// The info queue of a device with the debug layer, whose messages are logged.
+    #[cfg(feature = "validation")]
+    validation: Option<Validation>,
+    /// The vertices uploaded by `upload`, whose buffer is created with the pipeline.
+    batch: VertexBatch<V>,
+    corner_buf: ComPtr<ID3D11Buffer>,
+    /// The column-major transforms of the recent draws.
+    transform_bufs: ConstantBufferPool<[f32; 16]>,
+    /// The pixel constants of the recent draw calls.
+    pixel_bufs: ConstantBufferPool<PixelConstants>,
+    glow: Option<GlowPass>,
+    glow_radius: f32,
+    /// The outline drawn around glyphs without an outline of their own.
+    halo: Option<Outline>,
+    /// The time in seconds passed to the shaders.
+    time: f32,
+    /// The exponent glyph coverage is raised to the inverse of.
+    coverage_gamma: f32,
+    /// How the signed distance field of an SDF cache is turned into coverage.
+    sdf_smoothing: SdfSmoothing,
+    /// The constant buffer of custom shaders, bound to `USER_CONSTANT_BUFFER_SLOT`.
+    user_constants: Option<ComPtr<ID3D11Buffer>>,
+    /// The textures glyphs can be filled with, indexed by `FillTexture`.
+    fill_textures: Vec<Option<ComPtr<ID3D11ShaderResourceView>>>,
     sampler: ComPtr<ID3D11SamplerState>,
+    sampler_desc: D3D11_SAMPLER_DESC,
+    /// The wrapping sampler of fill textures.
+    fill_sampler: ComPtr<ID3D11SamplerState>,
+    /// The samplers of texture filters overriding the one of the brush.
+    samplers: StateCache<D3D11_FILTER, ID3D11SamplerState>,
     cache: Cache,
-    blend_state: ComPtr<ID3D11BlendState>,
+    max_texture_dimension: u32,
+    /// The blend states indexed by `BlendMode`.
+    blend_states: [ComPtr<ID3D11BlendState>; 3],
     rasterizer_state: ComPtr<ID3D11RasterizerState>,
+    depth_mode: DepthMode,
     depth_stencil_state: ComPtr<ID3D11DepthStencilState>,
-    input_layout: ComPtr<ID3D11InputLayout>,
+    depth_stencil_states: StateCache<DepthStencilKey, ID3D11DepthStencilState>,
+    /// The input layout of the instance stream, or `None` if the built-in vertex shader reads
+    /// the instances from a raw view of the vertex buffer instead.
+    input_layout: Option<ComPtr<ID3D11InputLayout>>,
     pixel_shader: ComPtr<ID3D11PixelShader>,
     vertex_shader: ComPtr<ID3D11VertexShader>,
-    _pd: PhantomData<Depth>,
+    /// Whether a frame has been begun with `begin_frame`, keeping `bound` across draws.
+    in_frame: bool,
+    bound: BoundState,
+    /// The options the pipeline has been built with, including the shaders set since.
+    options: PipelineOptions,
+    _pd: PhantomData<V>,
 }
 
-impl Pipeline<()> {
+impl<V: Copy> Pipeline<V> {
     #[inline]
     pub fn new(
         device: ComPtr<ID3D11Device>,
-        filter_mode: D3D11_FILTER,
+        options: PipelineOptions,
+        input_layout: &[D3D11_INPUT_ELEMENT_DESC],
+        depth_mode: DepthMode,
         cache_width: u32,
         cache_height: u32,
-    ) -> HResult<Pipeline<()>> {
-        unsafe { build(device, filter_mode, None, cache_width, cache_height) }
+    ) -> HResult<Self> {
+        unsafe {
+            build(
+                device,
+                options,
+                input_layout,
+                depth_mode,
+                cache_width,
+                cache_height,
+            )
+        }
     }
 
     #[inline]
     pub fn draw(
         &mut self,
         target: &ComPtr<ID3D11RenderTargetView>,
-        transform: [f32; 16],
+        depth_stencil_view: Option<&ComPtr<ID3D11DepthStencilView>>,
+        transform: Transform,
         rect: Option<D3D11_RECT>,
     ) -> HResult<()> {
-        unsafe { draw(self, target, None, transform, rect) }
+        let target = DrawTarget {
+            target,
+            depth_stencil_view,
+            transform,
+            scissor: rect,
+            clip_shape: None,
+        };
+        self.draw_target(&target)
     }
-}
 
-impl Pipeline<D3D11_DEPTH_STENCIL_DESC> {
     #[inline]
-    pub fn new(
-        device: ComPtr<ID3D11Device>,
-        filter_mode: D3D11_FILTER,
-        depth_stencil_desc: D3D11_DEPTH_STENCIL_DESC,
-        cache_width: u32,
-        cache_height: u32,
-    ) -> HResult<Self> {
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    /// Replaces the depth stencil state glyphs are drawn with. The current state is kept if
+    /// creating the new one fails.
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) -> HResult<()> {
+        self.depth_stencil_state = unsafe { create_depth_stencil_state(&self.device, depth_mode)? };
+        self.depth_mode = depth_mode;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn device(&self) -> &ComPtr<ID3D11Device> {
+        &self.device
+    }
+
+    #[inline]
+    pub fn context(&self) -> &ComPtr<ID3D11DeviceContext> {
+        &self.ctx
+    }
+
+    /// Enters the critical section of the device if multithread protection is enabled. Can be
+    /// nested.
+    #[inline]
+    pub fn lock_context(&self) -> Option<MultithreadGuard> {
+        self.multithread.as_ref().map(Multithread::lock)
+    }
+
+    /// Draws the vertices of the last `upload`.
+    pub fn draw_target(&mut self, target: &DrawTarget<'_>) -> HResult<()> {
+        let batch = mem::take(&mut self.batch);
+        let result = unsafe { draw(self, &batch, target, None) };
+        self.batch = batch;
+        result
+    }
+
+    /// Draws a batch uploaded with `upload_batch`.
+    #[inline]
+    pub fn draw_batch(&mut self, batch: &VertexBatch<V>, target: &DrawTarget<'_>) -> HResult<()> {
+        unsafe { draw(self, batch, target, None) }
+    }
+
+    pub fn draw_target_with_depth(
+        &mut self,
+        target: &DrawTarget<'_>,
+        depth_stencil: DepthStencil<'_>,
+    ) -> HResult<()> {
         unsafe {
-            build(
-                device,
-                filter_mode,
-                Some(depth_stencil_desc),
-                cache_width,
-                cache_height,
-            )
+            let depth_stencil_state = match depth_stencil {
+                DepthStencil::Desc(desc) => {
+                    let device = &self.device;
+                    self.depth_stencil_states
+                        .get_or_create(depth_stencil_key(&desc), || {
+                            com_ptr_from_fn("CreateDepthStencilState", |depth_stencil_state| {
+                                device.CreateDepthStencilState(&desc, depth_stencil_state)
+                            })
+                        })?
+                }
+                DepthStencil::State(state) => state.clone(),
+            };
+            let batch = mem::take(&mut self.batch);
+            let result = draw(self, &batch, target, Some(&depth_stencil_state));
+            self.batch = batch;
+            result
         }
     }
 
+    /// Keeps track of the state bound by draws until [`end_frame`](#method.end_frame), so
+    /// consecutive draws skip binding the same state again.
     #[inline]
-    pub fn draw(
+    pub fn begin_frame(&mut self) {
+        self.in_frame = true;
+        self.bound = BoundState::NONE;
+    }
+
+    #[inline]
+    pub fn end_frame(&mut self) {
+        self.in_frame = false;
+        self.bound = BoundState::NONE;
+    }
+
+    /// Replaces the vertex shader, recreating the input layout for it. The current shader is
+    /// kept if creating the new one fails.
+    pub fn set_vertex_shader(
         &mut self,
-        target: &ComPtr<ID3D11RenderTargetView>,
-        depth_stencil_view: &ComPtr<ID3D11DepthStencilView>,
-        transform: [f32; 16],
-        rect: Option<D3D11_RECT>,
+        bytecode: &[u8],
+        input_layout: &[D3D11_INPUT_ELEMENT_DESC],
     ) -> HResult<()> {
-        unsafe { draw(self, target, Some(depth_stencil_view), transform, rect) }
+        let (vertex_shader, input_layout) =
+            unsafe { create_vertex_shader(&self.device, bytecode, input_layout)? };
+        self.vertex_shader = vertex_shader;
+        self.input_layout = Some(input_layout);
+        self.options.vertex_shader = Some(bytecode.to_vec());
+        Ok(())
+    }
+
+    /// Replaces the pixel shader. The current shader is kept if creating the new one fails.
+    pub fn set_pixel_shader(&mut self, bytecode: &[u8]) -> HResult<()> {
+        self.pixel_shader = unsafe { create_pixel_shader(&self.device, bytecode)? };
+        self.options.pixel_shader = Some(bytecode.to_vec());
+        Ok(())
+    }
+
+    /// The options to build an equivalent pipeline with.
+    pub fn options(&self) -> PipelineOptions {
+        PipelineOptions {
+            halo: self.halo,
+            coverage_gamma: self.coverage_gamma,
+            sdf_smoothing: self.sdf_smoothing,
+            ..self.options.clone()
+        }
+    }
+
+    #[inline]
+    pub fn glyph_count(&self) -> usize {
+        self.batch.len()
     }
-}
 
-impl<Depth> Pipeline<Depth> {
     #[inline]
     pub fn update_cache(&mut self, rect: Rectangle<u32>, data: &[u8]) {
-        self.cache.update(&self.ctx, rect, data);
+        self.cache.update(rect, data);
     }
 
+    /// Sets the effects used by the uploaded glyphs, which are drawn in separate passes.
     #[inline]
-    pub fn increase_cache_size(&mut self, width: u32, height: u32) {
-        self.cache = Cache::new(&self.device, width, height).unwrap();
+    pub fn set_effects(&mut self, effects: Effects) {
+        self.batch.set_effects(effects);
     }
 
-    pub fn upload(&mut self, vertices: &[Vertex]) -> HResult<()> {
+    #[inline]
+    pub fn halo(&self) -> Option<Outline> {
+        self.halo
+    }
+
+    #[inline]
+    pub fn set_halo(&mut self, halo: Option<Outline>) {
+        self.halo = halo;
+    }
+
+    #[inline]
+    pub fn coverage_gamma(&self) -> f32 {
+        self.coverage_gamma
+    }
+
+    #[inline]
+    pub fn set_coverage_gamma(&mut self, gamma: f32) {
+        self.coverage_gamma = gamma;
+    }
+
+    #[inline]
+    pub fn sdf_smoothing(&self) -> SdfSmoothing {
+        self.sdf_smoothing
+    }
+
+    #[inline]
+    pub fn set_sdf_smoothing(&mut self, smoothing: SdfSmoothing) {
+        self.sdf_smoothing = smoothing;
+    }
+
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    pub fn set_time(&mut self, seconds: f32) {
+        self.time = seconds;
+    }
+
+    #[inline]
+    pub fn set_user_constants(
+        &mut self,
+        buffer: Option<ComPtr<ID3D11Buffer>>,
+    ) -> Option<ComPtr<ID3D11Buffer>> {
+        mem::replace(&mut self.user_constants, buffer)
+    }
+
+    pub fn add_fill_texture(&mut self, view: ComPtr<ID3D11ShaderResourceView>) -> FillTexture {
+        let index = match self.fill_textures.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.fill_textures.push(None);
+                self.fill_textures.len() - 1
+            }
+        };
+        self.fill_textures[index] = Some(view);
+        FillTexture(index)
+    }
+
+    /// Replaces or removes the view of a fill texture, returning the previous one.
+    pub fn set_fill_texture(
+        &mut self,
+        texture: FillTexture,
+        view: Option<ComPtr<ID3D11ShaderResourceView>>,
+    ) -> Option<ComPtr<ID3D11ShaderResourceView>> {
+        let slot = self.fill_textures.get_mut(texture.0)?;
+        mem::replace(slot, view)
+    }
+
+    #[inline]
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// Makes the next draw upload the whole cache from its CPU copy, returning `false` if
+    /// there is none.
+    #[inline]
+    pub fn invalidate_cache(&mut self) -> bool {
+        self.cache.invalidate()
+    }
+
+    /// Replaces the cache with an empty one of the given size. The current cache is kept if
+    /// creating the new one fails.
+    ///
+    /// The old texture isn't copied into the new one: glyph_brush lays out its draw cache from
+    /// scratch after a resize, so none of the old glyphs would be at their new positions. While
+    /// the texture is shorter than a reserved draw cache height, it instead grows on flush
+    /// without resizing the layout, copying the cached glyphs into the new texture with
+    /// `CopySubresourceRegion`, see `Cache::reserve_height`.
+    #[inline]
+    pub fn increase_cache_size(&mut self, width: u32, height: u32) -> HResult<()> {
+        validation_scope!(self, "resize_cache");
+        let shadow_copy = self.cache.data().is_some();
+        let mut cache = Cache::new(
+            &self.device,
+            width,
+            height,
+            self.cache.update_method(),
+            shadow_copy,
+            self.cache.format(),
+            self.cache.outline_radius(),
+        )?;
+        cache.set_migration_rows(self.cache.migration_rows());
+        self.cache = cache;
+        Ok(())
+    }
+
+    /// Returns the vertices of the last upload.
+    #[inline]
+    pub fn vertex_buffer(&self) -> &ComPtr<ID3D11Buffer> {
+        let buffer = self.batch.buffer.as_ref();
+        &buffer
+            .expect("the vertex buffer is created with the pipeline")
+            .ptr
+    }
+
+    #[inline]
+    pub fn transform_buffer(&self) -> &ComPtr<ID3D11Buffer> {
+        self.transform_bufs.current()
+    }
+
+    /// The vertices uploaded by `upload`.
+    #[inline]
+    pub fn batch(&self) -> &VertexBatch<V> {
+        &self.batch
+    }
+
+    #[inline]
+    pub fn vertices(&self) -> &[V] {
+        self.batch.vertices()
+    }
+
+    #[inline]
+    pub fn draw_states(&self) -> Vec<DrawState> {
+        self.batch.draw_states()
+    }
+
+    #[inline]
+    pub fn set_draw_states(&mut self, states: impl IntoIterator<Item = DrawState>) {
+        self.batch.set_draw_states(states);
+    }
+
+    pub fn upload(&mut self, vertices: &[V]) -> HResult<()> {
+        let mut batch = mem::take(&mut self.batch);
+        let result = self.upload_batch(&mut batch, vertices);
+        self.batch = batch;
+        result
+    }
+
+    /// Replaces the vertices of `batch`, growing its buffer if they don't fit. Clears its draw
+    /// states.
+    pub fn upload_batch(&self, batch: &mut VertexBatch<V>, vertices: &[V]) -> HResult<()> {
+        trace_span!("upload_vertices", glyphs = vertices.len());
+        validation_scope!(self, "upload_vertices");
+        batch.draw_runs.clear();
+        batch.vertices.clear();
+        batch.vertices.extend_from_slice(vertices);
         if vertices.is_empty() {
-            self.vertex_buffer.len = 0;
             return Ok(());
         }
 
-        if vertices.len() > self.vertex_buffer.capacity {
-            self.vertex_buffer =
-                unsafe { Self::create_vertex_buffer(&self.device, vertices.len())? };
-        }
+        let buffer = match &mut batch.buffer {
+            Some(buffer) if buffer.capacity >= vertices.len() => buffer,
+            buffer => {
+                let raw = self.input_layout.is_none();
+                let created =
+                    unsafe { Self::create_vertex_buffer(&self.device, vertices.len(), raw)? };
+                buffer.insert(created)
+            }
+        };
 
+        let _lock = self.lock_context();
         unsafe {
             let vtx_resource = {
                 let mut vtx_resource = mem::MaybeUninit::zeroed();
-                hresult(self.ctx.Map(
-                    com_ref_cast(&self.vertex_buffer.ptr).as_raw(),
-                    0,
-                    D3D11_MAP_WRITE_DISCARD,
-                    0,
-                    vtx_resource.as_mut_ptr(),
-                ))?;
+                hresult(
+                    "Map",
+                    self.ctx.Map(
+                        com_ref_cast(&buffer.ptr).as_raw(),
+                        0,
+                        D3D11_MAP_WRITE_DISCARD,
+                        0,
+                        vtx_resource.as_mut_ptr(),
+                    ),
+                )?;
                 vtx_resource.assume_init()
             };
             ptr::copy_nonoverlapping(
                 vertices.as_ptr(),
-                vtx_resource.pData.cast::<Vertex>(),
+                vtx_resource.pData.cast::<V>(),
                 vertices.len(),
             );
-            self.ctx.Unmap(self.vertex_buffer.ptr.as_raw().cast(), 0);
+            self.ctx.Unmap(buffer.ptr.as_raw().cast(), 0);
         }
-        self.vertex_buffer.len = vertices.len();
         Ok(())
     }
 
-    unsafe fn create_vertex_buffer(device: &ID3D11Device, capacity: usize) -> HResult<Buffer> {
+    /// Creates a vertex buffer for `capacity` instances, which can also be read through a raw
+    /// view if `raw` is set.
+    unsafe fn create_vertex_buffer(
+        device: &ID3D11Device,
+        capacity: usize,
+        raw: bool,
+    ) -> HResult<Buffer> {
+        let byte_width = (capacity * mem::size_of::<V>()).try_into().unwrap();
         let desc = D3D11_BUFFER_DESC {
-            ByteWidth: (capacity * mem::size_of::<Vertex>()).try_into().unwrap(),
+            ByteWidth: byte_width,
             Usage: D3D11_USAGE_DYNAMIC,
-            BindFlags: D3D11_BIND_VERTEX_BUFFER,
+            BindFlags: if raw {
+                D3D11_BIND_VERTEX_BUFFER | D3D11_BIND_SHADER_RESOURCE
+            } else {
+                D3D11_BIND_VERTEX_BUFFER
+            },
             CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
-            MiscFlags: 0,
+            MiscFlags: if raw {
+                D3D11_RESOURCE_MISC_BUFFER_ALLOW_RAW_VIEWS
+            } else {
+                0
+            },
             StructureByteStride: 0,
         };
-        com_ptr_from_fn(|vertex_buffer| device.CreateBuffer(&desc, ptr::null(), vertex_buffer)).map(
-            |vb| Buffer {
-                ptr: vb,
-                capacity,
-                len: 0,
-            },
-        )
+        let ptr = com_ptr_from_fn("CreateBuffer", |vertex_buffer| {
+            device.CreateBuffer(&desc, ptr::null(), vertex_buffer)
+        })?;
+        let view = if raw {
+            let view = com_ptr_from_fn("CreateShaderResourceView", |view| {
+                let mut desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+                    Format: DXGI_FORMAT_R32_TYPELESS,
+                    ViewDimension: D3D11_SRV_DIMENSION_BUFFEREX,
+                    u: mem::zeroed(),
+                };
+                *desc.u.BufferEx_mut() = D3D11_BUFFEREX_SRV {
+                    FirstElement: 0,
+                    NumElements: byte_width / 4,
+                    Flags: D3D11_BUFFEREX_SRV_FLAG_RAW,
+                };
+                device.CreateShaderResourceView(com_ref_cast(&ptr).as_raw(), &desc, view)
+            })?;
+            Some(view)
+        } else {
+            None
+        };
+        Ok(Buffer {
+            ptr,
+            view,
+            capacity,
+        })
     }
 }
 
-#[rustfmt::skip]
-const IDENTITY_MATRIX: [f32; 16] = [
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 1.0, 0.0,
-    0.0, 0.0, 0.0, 1.0,
-];
+impl<V: Copy> TextRenderBackend<V> for Pipeline<V> {
+    type Error = Error;
+
+    #[inline]
+    fn max_cache_dimension(&self) -> u32 {
+        self.max_texture_dimension
+    }
+
+    #[inline]
+    fn update_cache(&mut self, rect: Rectangle<u32>, data: &[u8]) {
+        Pipeline::update_cache(self, rect, data);
+    }
+
+    #[inline]
+    fn resize_cache(&mut self, width: u32, height: u32) -> HResult<()> {
+        self.increase_cache_size(width, height)
+    }
+
+    #[inline]
+    fn is_out_of_memory(&self, error: &Error) -> bool {
+        error.is_out_of_memory()
+    }
+
+    #[inline]
+    fn upload_vertices(&mut self, vertices: &[V]) -> HResult<()> {
+        self.upload(vertices)
+    }
+
+    #[inline]
+    fn draw(&mut self, target: &DrawTarget<'_>) -> HResult<()> {
+        self.draw_target(target)
+    }
+}
 
-unsafe fn build<D>(
+unsafe fn build<V: Copy>(
     device: ComPtr<ID3D11Device>,
-    filter_mode: D3D11_FILTER,
-    depth_stencil_desc: Option<D3D11_DEPTH_STENCIL_DESC>,
+    options: PipelineOptions,
+    input_layout: &[D3D11_INPUT_ELEMENT_DESC],
+    depth_mode: DepthMode,
     cache_width: u32,
     cache_height: u32,
-) -> HResult<Pipeline<D>> {
+) -> HResult<Pipeline<V>> {
     let context = {
         let mut context = ptr::null_mut();
         device.GetImmediateContext(&mut context);
@@ -206,297 +824,856 @@ unsafe fn build<D>(
         BlendOpAlpha: D3D11_BLEND_OP_ADD,
         RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL as u8,
     };
-    let blend_state = com_ptr_from_fn(|blend_state| device.CreateBlendState(&desc, blend_state))?;
-
-    let desc = D3D11_RASTERIZER_DESC {
-        FillMode: D3D11_FILL_SOLID,
-        CullMode: D3D11_CULL_NONE,
-        FrontCounterClockwise: 0,
-        DepthBias: 0,
-        DepthBiasClamp: 0.0,
-        SlopeScaledDepthBias: 0.0,
-        DepthClipEnable: FALSE,
-        ScissorEnable: TRUE,
-        MultisampleEnable: 0,
-        AntialiasedLineEnable: 0,
+    let alpha = com_ptr_from_fn("CreateBlendState", |blend_state| {
+        device.CreateBlendState(&desc, blend_state)
+    })?;
+    desc.RenderTarget[0].DestBlend = D3D11_BLEND_ONE;
+    let additive = com_ptr_from_fn("CreateBlendState", |blend_state| {
+        device.CreateBlendState(&desc, blend_state)
+    })?;
+    // the pixel shader blends the color towards white by its coverage in multiply mode
+    desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+        SrcBlend: D3D11_BLEND_ZERO,
+        DestBlend: D3D11_BLEND_SRC_COLOR,
+        SrcBlendAlpha: D3D11_BLEND_ZERO,
+        DestBlendAlpha: D3D11_BLEND_ONE,
+        ..desc.RenderTarget[0]
     };
-    let rasterizer_state =
-        com_ptr_from_fn(|rasterizer_state| device.CreateRasterizerState(&desc, rasterizer_state))?;
-
-    let desc = depth_stencil_desc.unwrap_or({
-        let stencil_op_desc = D3D11_DEPTH_STENCILOP_DESC {
-            StencilFailOp: D3D11_STENCIL_OP_KEEP,
-            StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
-            StencilPassOp: D3D11_STENCIL_OP_KEEP,
-            StencilFunc: D3D11_COMPARISON_ALWAYS,
-        };
-        D3D11_DEPTH_STENCIL_DESC {
-            DepthEnable: FALSE,
-            DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ALL,
-            DepthFunc: D3D11_COMPARISON_ALWAYS,
-            StencilEnable: FALSE,
-            StencilReadMask: 0,
-            StencilWriteMask: 0,
-            FrontFace: stencil_op_desc,
-            BackFace: stencil_op_desc,
-        }
-    });
-    let depth_stencil_state = com_ptr_from_fn(|depth_stencil_state| {
-        device.CreateDepthStencilState(&desc, depth_stencil_state)
+    let multiply = com_ptr_from_fn("CreateBlendState", |blend_state| {
+        device.CreateBlendState(&desc, blend_state)
     })?;
+    let blend_states = [alpha, additive, multiply];
 
-    let desc = D3D11_BUFFER_DESC {
-        ByteWidth: mem::size_of::<[f32; 16]>() as _,
-        Usage: D3D11_USAGE_DYNAMIC,
-        BindFlags: D3D11_BIND_CONSTANT_BUFFER,
-        CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
-        MiscFlags: 0,
-        StructureByteStride: 0,
-    };
-    let transform_buf = com_ptr_from_fn(|vertex_constant_buffer| {
-        let subresource = D3D11_SUBRESOURCE_DATA {
-            pSysMem: IDENTITY_MATRIX.as_ptr().cast(),
-            SysMemPitch: 0,
-            SysMemSlicePitch: 0,
-        };
-        device.CreateBuffer(&desc, &subresource, vertex_constant_buffer)
+    let rasterizer_state = com_ptr_from_fn("CreateRasterizerState", |rasterizer_state| {
+        device.CreateRasterizerState(&options.rasterizer, rasterizer_state)
     })?;
 
-    let desc = D3D11_SAMPLER_DESC {
-        Filter: filter_mode,
+    let depth_stencil_state = create_depth_stencil_state(&device, depth_mode)?;
+
+    let transform_bufs = ConstantBufferPool::new(&device, Transform::IDENTITY.to_column_major())?;
+    let pixel_bufs = ConstantBufferPool::new(&device, PixelConstants::NONE)?;
+
+    let sampler_desc = D3D11_SAMPLER_DESC {
+        Filter: options.filter_mode,
         AddressU: D3D11_TEXTURE_ADDRESS_CLAMP,
         AddressV: D3D11_TEXTURE_ADDRESS_CLAMP,
         AddressW: D3D11_TEXTURE_ADDRESS_CLAMP,
         MipLODBias: 0.0,
-        MaxAnisotropy: 0,
+        MaxAnisotropy: options.max_anisotropy,
         ComparisonFunc: D3D11_COMPARISON_ALWAYS,
         BorderColor: [0.0; 4],
+        MinLOD: options.min_lod,
+        MaxLOD: options.max_lod,
+    };
+    let sampler = com_ptr_from_fn("CreateSamplerState", |sampler| {
+        device.CreateSamplerState(&sampler_desc, sampler)
+    })?;
+    // fill textures repeat, unlike the glyph cache
+    let fill_sampler_desc = D3D11_SAMPLER_DESC {
+        Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        AddressU: D3D11_TEXTURE_ADDRESS_WRAP,
+        AddressV: D3D11_TEXTURE_ADDRESS_WRAP,
+        AddressW: D3D11_TEXTURE_ADDRESS_WRAP,
         MinLOD: 0.0,
-        MaxLOD: 0.0,
+        MaxLOD: f32::MAX,
+        ..sampler_desc
     };
-    let sampler = com_ptr_from_fn(|sampler| device.CreateSamplerState(&desc, sampler))?;
-
-    let cache = Cache::new(&device, cache_width, cache_height)?;
+    let fill_sampler = com_ptr_from_fn("CreateSamplerState", |sampler| {
+        device.CreateSamplerState(&fill_sampler_desc, sampler)
+    })?;
 
-    let vertices = Pipeline::<()>::create_vertex_buffer(&device, 1024)?;
+    let mut cache = Cache::new(
+        &device,
+        cache_width,
+        cache_height,
+        options.cache_update_method,
+        options.cache_shadow_copy,
+        options.cache_format,
+        // the outlines of single pass outlines are read from the second channel of the cache
+        Some(options.max_outline_width).filter(|_| options.single_pass_outlines),
+    )?;
+    cache.reserve_height(options.reserved_cache_height);
+    // the shaders of feature level 9.3 only read the current texture
+    if device.GetFeatureLevel() >= D3D_FEATURE_LEVEL_10_0 {
+        cache.set_migration_rows(options.cache_migration_rows);
+    }
+    let max_texture_dimension = max_texture_dimension(&device);
 
-    const VERTEX_SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader.vs_4_0"));
-    let vertex_shader = com_ptr_from_fn(|vs_shader| {
-        device.CreateVertexShader(
-            VERTEX_SHADER.as_ptr().cast(),
-            VERTEX_SHADER.len(),
-            ptr::null_mut(),
-            vs_shader,
-        )
-    })?;
+    let multithread = if options.multithread_protection {
+        Some(Multithread::new(&device)?)
+    } else {
+        None
+    };
 
-    let local_layout = [
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: "POSITION\0".as_ptr().cast(),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32B32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 0,
-            InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
-            InstanceDataStepRate: 1,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: "POSITION\0".as_ptr().cast(),
-            SemanticIndex: 1,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 4 * 3,
-            InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
-            InstanceDataStepRate: 1,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: "TEXCOORD\0".as_ptr().cast(),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 4 * (3 + 2),
-            InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
-            InstanceDataStepRate: 1,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: "TEXCOORD\0".as_ptr().cast(),
-            SemanticIndex: 1,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 4 * (3 + 2 + 2),
-            InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
-            InstanceDataStepRate: 1,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: "COLOR\0".as_ptr().cast(),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 4 * (3 + 2 + 2 + 2),
-            InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
-            InstanceDataStepRate: 1,
-        },
-    ];
-
-    let input_layout = com_ptr_from_fn(|input_layout| {
-        device.CreateInputLayout(
-            local_layout.as_ptr(),
-            local_layout.len() as _,
-            VERTEX_SHADER.as_ptr().cast(),
-            VERTEX_SHADER.len(),
-            input_layout,
-        )
+    let desc = D3D11_BUFFER_DESC {
+        ByteWidth: mem::size_of_val(&QUAD_CORNERS) as _,
+        Usage: D3D11_USAGE_IMMUTABLE,
+        BindFlags: D3D11_BIND_VERTEX_BUFFER,
+        CPUAccessFlags: 0,
+        MiscFlags: 0,
+        StructureByteStride: 0,
+    };
+    let corner_buf = com_ptr_from_fn("CreateBuffer", |corner_buffer| {
+        let subresource = D3D11_SUBRESOURCE_DATA {
+            pSysMem: QUAD_CORNERS.as_ptr().cast(),
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+        device.CreateBuffer(&desc, &subresource, corner_buffer)
     })?;
 
-    const PIXEL_SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/pixel_shader.ps_4_0"));
-    let pixel_shader = com_ptr_from_fn(|ps_shader| {
-        device.CreatePixelShader(
-            PIXEL_SHADER.as_ptr().cast(),
-            PIXEL_SHADER.len(),
-            ptr::null_mut(),
-            ps_shader,
-        )
-    })?;
+    let (model, vertex_shader_code, pixel_shader_code) = builtin_shaders(device.GetFeatureLevel());
+    // the built-in shader reads the built-in vertex from a raw view of the vertex buffer on
+    // shader model 5.0, avoiding the input layout. Raw loads have to be aligned to 4 bytes.
+    let raw_instances = options.vertex_shader.is_none()
+        && model == "5_0"
+        && mem::align_of::<V>() >= 4
+        && is_builtin_layout(input_layout);
+    let (vertex_shader, input_layout) = match &options.vertex_shader {
+        Some(code) => {
+            let (vertex_shader, input_layout) = create_vertex_shader(&device, code, input_layout)?;
+            (vertex_shader, Some(input_layout))
+        }
+        None if raw_instances => {
+            let code = runtime_compiler::or_compile(
+                include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader_raw.vs_5_0")),
+                include_str!("shader/vertex.hlsl"),
+                "vs",
+                model,
+                Some("RAW_INSTANCES"),
+            )?;
+            let vertex_shader = com_ptr_from_fn("CreateVertexShader", |vs_shader| {
+                device.CreateVertexShader(
+                    code.as_ptr().cast(),
+                    code.len(),
+                    ptr::null_mut(),
+                    vs_shader,
+                )
+            })?;
+            (vertex_shader, None)
+        }
+        None => {
+            let code = runtime_compiler::or_compile(
+                vertex_shader_code,
+                include_str!("shader/vertex.hlsl"),
+                "vs",
+                model,
+                None,
+            )?;
+            let (vertex_shader, input_layout) = create_vertex_shader(&device, &code, input_layout)?;
+            (vertex_shader, Some(input_layout))
+        }
+    };
+    let batch = VertexBatch {
+        buffer: Some(Pipeline::<V>::create_vertex_buffer(
+            &device,
+            1024,
+            raw_instances,
+        )?),
+        ..VertexBatch::default()
+    };
+    let pixel_shader_code = match &options.pixel_shader {
+        Some(code) => Cow::Borrowed(&code[..]),
+        None => runtime_compiler::or_compile(
+            pixel_shader_code,
+            include_str!("shader/pixel.hlsl"),
+            "ps",
+            model,
+            None,
+        )?,
+    };
+    let pixel_shader = create_pixel_shader(&device, &pixel_shader_code)?;
 
     Ok(Pipeline {
+        #[cfg(feature = "validation")]
+        validation: Validation::new(&device),
         device,
+        multithread,
         ctx: context,
-        blend_state,
+        blend_states,
         rasterizer_state,
+        depth_mode,
         depth_stencil_state,
-        vertex_buffer: vertices,
-        transform_buf,
-        transform: IDENTITY_MATRIX,
+        depth_stencil_states: StateCache::new(),
+        batch,
+        corner_buf,
+        transform_bufs,
+        pixel_bufs,
+        glow: None,
+        glow_radius: options.glow_radius,
+        halo: options.halo,
+        time: 0.0,
+        coverage_gamma: options.coverage_gamma,
+        sdf_smoothing: options.sdf_smoothing,
+        user_constants: None,
+        fill_textures: Vec::new(),
         cache,
+        max_texture_dimension,
         input_layout,
         sampler,
+        sampler_desc,
+        fill_sampler,
+        samplers: StateCache::new(),
         vertex_shader,
         pixel_shader,
+        in_frame: false,
+        bound: BoundState::NONE,
+        options,
         _pd: PhantomData,
     })
 }
 
-unsafe fn draw<D>(
-    pipeline: &mut Pipeline<D>,
-    target: &ComPtr<ID3D11RenderTargetView>,
-    depth_stencil_view: Option<&ComPtr<ID3D11DepthStencilView>>,
-    transform: [f32; 16],
-    rect: Option<D3D11_RECT>,
+/// The corners of a glyph quad in triangle strip order, fed to the vertex shader on feature
+/// level 9.3 which lacks `SV_VertexID`.
+const QUAD_CORNERS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+
+/// Returns the shader model and the built-in vertex and pixel shader compiled for the highest
+/// shader model supported by the feature level.
+///
+/// The shaders are empty if the build script couldn't compile them.
+fn builtin_shaders(
+    feature_level: D3D_FEATURE_LEVEL,
+) -> (&'static str, &'static [u8], &'static [u8]) {
+    if feature_level >= D3D_FEATURE_LEVEL_11_0 {
+        (
+            "5_0",
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader.vs_5_0")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/pixel_shader.ps_5_0")),
+        )
+    } else if feature_level >= D3D_FEATURE_LEVEL_10_0 {
+        (
+            "4_0",
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader.vs_4_0")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/pixel_shader.ps_4_0")),
+        )
+    } else {
+        (
+            "4_0_level_9_3",
+            include_bytes!(concat!(env!("OUT_DIR"), "/vertex_shader.vs_4_0_level_9_3")),
+            include_bytes!(concat!(env!("OUT_DIR"), "/pixel_shader.ps_4_0_level_9_3")),
+        )
+    }
+}
+
+unsafe fn create_vertex_shader(
+    device: &ID3D11Device,
+    bytecode: &[u8],
+    input_layout: &[D3D11_INPUT_ELEMENT_DESC],
+) -> HResult<(ComPtr<ID3D11VertexShader>, ComPtr<ID3D11InputLayout>)> {
+    let vertex_shader = com_ptr_from_fn("CreateVertexShader", |vs_shader| {
+        device.CreateVertexShader(
+            bytecode.as_ptr().cast(),
+            bytecode.len(),
+            ptr::null_mut(),
+            vs_shader,
+        )
+    })?;
+    let mut input_layout = input_layout.to_vec();
+    input_layout.push(D3D11_INPUT_ELEMENT_DESC {
+        SemanticName: "CORNER\0".as_ptr().cast(),
+        SemanticIndex: 0,
+        Format: DXGI_FORMAT_R32G32_FLOAT,
+        InputSlot: 1,
+        AlignedByteOffset: 0,
+        InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    });
+    let input_layout = com_ptr_from_fn("CreateInputLayout", |layout| {
+        device.CreateInputLayout(
+            input_layout.as_ptr(),
+            input_layout.len() as _,
+            bytecode.as_ptr().cast(),
+            bytecode.len(),
+            layout,
+        )
+    })?;
+    Ok((vertex_shader, input_layout))
+}
+
+/// Returns `true` if `input_layout` is the one of the built-in vertex, so its instances can be
+/// read by the raw instance variant of the built-in vertex shader.
+fn is_builtin_layout(input_layout: &[D3D11_INPUT_ELEMENT_DESC]) -> bool {
+    let builtin = <Vertex as BrushVertex<glyph_brush::Extra>>::input_layout();
+    let semantic_name =
+        |element: &D3D11_INPUT_ELEMENT_DESC| unsafe { CStr::from_ptr(element.SemanticName) };
+    input_layout.len() == builtin.len()
+        && input_layout.iter().zip(&builtin).all(|(element, builtin)| {
+            semantic_name(element) == semantic_name(builtin)
+                && element.SemanticIndex == builtin.SemanticIndex
+                && element.Format == builtin.Format
+                && element.InputSlot == builtin.InputSlot
+                && element.AlignedByteOffset == builtin.AlignedByteOffset
+                && element.InputSlotClass == builtin.InputSlotClass
+        })
+}
+
+unsafe fn create_depth_stencil_state(
+    device: &ComPtr<ID3D11Device>,
+    depth_mode: DepthMode,
+) -> HResult<ComPtr<ID3D11DepthStencilState>> {
+    let desc = match depth_mode {
+        DepthMode::Enabled(desc) => desc,
+        DepthMode::Disabled => {
+            let stencil_op_desc = D3D11_DEPTH_STENCILOP_DESC {
+                StencilFailOp: D3D11_STENCIL_OP_KEEP,
+                StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+                StencilPassOp: D3D11_STENCIL_OP_KEEP,
+                StencilFunc: D3D11_COMPARISON_ALWAYS,
+            };
+            D3D11_DEPTH_STENCIL_DESC {
+                DepthEnable: FALSE,
+                DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ALL,
+                DepthFunc: D3D11_COMPARISON_ALWAYS,
+                StencilEnable: FALSE,
+                StencilReadMask: 0,
+                StencilWriteMask: 0,
+                FrontFace: stencil_op_desc,
+                BackFace: stencil_op_desc,
+            }
+        }
+    };
+    com_ptr_from_fn("CreateDepthStencilState", |depth_stencil_state| {
+        device.CreateDepthStencilState(&desc, depth_stencil_state)
+    })
+}
+
+unsafe fn create_pixel_shader(
+    device: &ID3D11Device,
+    bytecode: &[u8],
+) -> HResult<ComPtr<ID3D11PixelShader>> {
+    com_ptr_from_fn("CreatePixelShader", |ps_shader| {
+        device.CreatePixelShader(
+            bytecode.as_ptr().cast(),
+            bytecode.len(),
+            ptr::null_mut(),
+            ps_shader,
+        )
+    })
+}
+
+unsafe fn draw<V: Copy>(
+    pipeline: &mut Pipeline<V>,
+    batch: &VertexBatch<V>,
+    target: &DrawTarget<'_>,
+    depth_stencil_state: Option<&ComPtr<ID3D11DepthStencilState>>,
 ) -> HResult<()> {
-    let ctx = &*pipeline.ctx;
-    #[allow(clippy::float_cmp)]
-    if transform != pipeline.transform {
-        let mut mapped_resource = mem::MaybeUninit::zeroed();
-        hresult(ctx.Map(
-            com_ref_cast(&pipeline.transform_buf).as_raw(),
-            0,
-            D3D11_MAP_WRITE_DISCARD,
-            0,
-            mapped_resource.as_mut_ptr(),
-        ))?;
-        let mapped_resource = mapped_resource.assume_init();
+    trace_span!(
+        "draw",
+        glyphs = batch.len(),
+        draw_calls = batch.draw_runs.len().max(1),
+    );
+    let buffer = match &batch.buffer {
+        Some(buffer) => buffer,
+        None => return Ok(()),
+    };
+    validation_scope!(pipeline, "draw");
+    let _lock = pipeline.lock_context();
+    let ctx = pipeline.ctx.clone();
+    if !pipeline.in_frame {
+        pipeline.bound = BoundState::NONE;
+    }
+    let cache_size = pipeline.cache.dimensions();
+    pipeline.cache.flush(&pipeline.device, &ctx)?;
+    if pipeline.cache.dimensions() != cache_size {
+        // the view of the grown texture may reuse the address of the old one
+        pipeline.bound = BoundState::NONE;
+    }
+    (pipeline.transform_bufs).select(&ctx, target.transform.to_column_major())?;
 
-        // FIXME alignment?
-        *mapped_resource.pData.cast::<[f32; 16]>() = transform;
-        ctx.Unmap(com_ref_cast(&pipeline.transform_buf).as_raw(), 0);
+    if batch.effects.glow {
+        if pipeline.glow.is_none() {
+            pipeline.glow = GlowPass::new(&pipeline.device, pipeline.glow_radius)?;
+        }
+        if let Some(glow) = &mut pipeline.glow {
+            let mask = glow.mask_target(&pipeline.device, target.target)?.clone();
+            ctx.ClearRenderTargetView(mask.as_raw(), &[0.0; 4]);
+            bind_state(pipeline, buffer, target, &mask, None, depth_stencil_state);
+            draw_pass(pipeline, batch, target, PASS_GLOW)?;
+            if let Some(glow) = &pipeline.glow {
+                glow.composite(&ctx, target.target)?;
+            }
+            // compositing binds the state of the glow pass
+            pipeline.bound = BoundState::NONE;
+        }
+    }
 
-        pipeline.transform = transform;
+    bind_state(
+        pipeline,
+        buffer,
+        target,
+        target.target,
+        target.depth_stencil_view,
+        depth_stencil_state,
+    );
+    if !(batch.effects.outline || pipeline.halo.is_some()) {
+        return draw_pass(pipeline, batch, target, PASS_FILL);
+    }
+    if pipeline.options.single_pass_outlines {
+        return draw_pass(pipeline, batch, target, PASS_OUTLINED_FILL);
     }
+    // outlines are drawn in a separate pass first, so they don't overlap neighbouring glyphs
+    draw_pass(pipeline, batch, target, PASS_OUTLINE)?;
+    draw_pass(pipeline, batch, target, PASS_FILL)
+}
+
+unsafe fn bind_state<V>(
+    pipeline: &mut Pipeline<V>,
+    buffer: &Buffer,
+    target: &DrawTarget<'_>,
+    render_target: &ComPtr<ID3D11RenderTargetView>,
+    depth_stencil_view: Option<&ComPtr<ID3D11DepthStencilView>>,
+    depth_stencil_state: Option<&ComPtr<ID3D11DepthStencilState>>,
+) {
+    let ctx = &*pipeline.ctx;
+    let bound = &mut pipeline.bound;
+    // the render targets are rebound by draws restoring the previous ones, so they are always
+    // bound again
     ctx.OMSetRenderTargets(
         1,
-        &target.as_raw(),
+        &render_target.as_raw(),
         depth_stencil_view
             .map(ComPtr::as_raw)
             .unwrap_or_else(ptr::null_mut),
     );
 
-    let stride = mem::size_of::<Vertex>() as u32;
-    ctx.IASetInputLayout(pipeline.input_layout.as_raw());
-    ctx.IASetVertexBuffers(0, 1, &pipeline.vertex_buffer.ptr.as_raw(), &stride, &0);
-    ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
-    ctx.VSSetShader(pipeline.vertex_shader.as_raw(), ptr::null(), 0);
-    ctx.VSSetConstantBuffers(0, 1, &pipeline.transform_buf.as_raw());
-    ctx.PSSetShader(pipeline.pixel_shader.as_raw(), ptr::null(), 0);
-    ctx.PSSetSamplers(0, 1, &pipeline.sampler.as_raw());
-    ctx.GSSetShader(ptr::null_mut(), ptr::null(), 0);
-    ctx.HSSetShader(ptr::null_mut(), ptr::null(), 0);
-    ctx.DSSetShader(ptr::null_mut(), ptr::null(), 0);
-    ctx.CSSetShader(ptr::null_mut(), ptr::null(), 0);
-
-    ctx.OMSetBlendState(pipeline.blend_state.as_raw(), &[0.0; 4], 0xFFFFFFFF);
-    ctx.OMSetDepthStencilState(pipeline.depth_stencil_state.as_raw(), 0);
-    ctx.RSSetState(pipeline.rasterizer_state.as_raw());
-
-    ctx.PSSetShaderResources(0, 1, &pipeline.cache.view());
+    if !bound.fixed_state {
+        ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
+        ctx.PSSetSamplers(1, 1, &pipeline.fill_sampler.as_raw());
+        ctx.GSSetShader(ptr::null_mut(), ptr::null(), 0);
+        ctx.HSSetShader(ptr::null_mut(), ptr::null(), 0);
+        ctx.DSSetShader(ptr::null_mut(), ptr::null(), 0);
+        ctx.CSSetShader(ptr::null_mut(), ptr::null(), 0);
+        ctx.RSSetState(pipeline.rasterizer_state.as_raw());
+        bound.fixed_state = true;
+    }
+    let transform_buf = pipeline.transform_bufs.current().as_raw();
+    if bound.transform_buf != transform_buf {
+        ctx.VSSetConstantBuffers(0, 1, &transform_buf);
+        bound.transform_buf = transform_buf;
+    }
+    let input_layout = pipeline
+        .input_layout
+        .as_ref()
+        .map_or(ptr::null_mut(), ComPtr::as_raw);
+    if bound.input_layout != Some(input_layout) {
+        ctx.IASetInputLayout(input_layout);
+        bound.input_layout = Some(input_layout);
+    }
+    match &buffer.view {
+        Some(view) if pipeline.input_layout.is_none() => {
+            if bound.instance_view != view.as_raw() {
+                ctx.VSSetShaderResources(0, 1, &view.as_raw());
+                bound.instance_view = view.as_raw();
+            }
+        }
+        _ => {
+            if bound.vertex_buffer != buffer.ptr.as_raw() {
+                let strides = [
+                    mem::size_of::<V>() as u32,
+                    mem::size_of::<[f32; 2]>() as u32,
+                ];
+                ctx.IASetVertexBuffers(
+                    0,
+                    2,
+                    [buffer.ptr.as_raw(), pipeline.corner_buf.as_raw()].as_ptr(),
+                    strides.as_ptr(),
+                    [0, 0].as_ptr(),
+                );
+                bound.vertex_buffer = buffer.ptr.as_raw();
+            }
+        }
+    }
+    if bound.vertex_shader != pipeline.vertex_shader.as_raw() {
+        ctx.VSSetShader(pipeline.vertex_shader.as_raw(), ptr::null(), 0);
+        bound.vertex_shader = pipeline.vertex_shader.as_raw();
+    }
+    if bound.pixel_shader != pipeline.pixel_shader.as_raw() {
+        ctx.PSSetShader(pipeline.pixel_shader.as_raw(), ptr::null(), 0);
+        bound.pixel_shader = pipeline.pixel_shader.as_raw();
+    }
+    if let Some(user_constants) = &pipeline.user_constants {
+        if bound.user_constants != user_constants.as_raw() {
+            ctx.VSSetConstantBuffers(USER_CONSTANT_BUFFER_SLOT, 1, &user_constants.as_raw());
+            ctx.PSSetConstantBuffers(USER_CONSTANT_BUFFER_SLOT, 1, &user_constants.as_raw());
+            bound.user_constants = user_constants.as_raw();
+        }
+    }
+
+    let depth_stencil_state = depth_stencil_state
+        .unwrap_or(&pipeline.depth_stencil_state)
+        .as_raw();
+    if bound.depth_stencil_state != depth_stencil_state {
+        ctx.OMSetDepthStencilState(depth_stencil_state, 0);
+        bound.depth_stencil_state = depth_stencil_state;
+    }
+
+    let clip_mask = match target.clip_shape {
+        Some(ClipShape::Mask(mask)) => mask.as_raw(),
+        _ => ptr::null_mut(),
+    };
+    let shader_resources = [pipeline.cache.view(), clip_mask];
+    if bound.shader_resources != shader_resources {
+        ctx.PSSetShaderResources(0, 2, shader_resources.as_ptr());
+        bound.shader_resources = shader_resources;
+    }
+    let migration_view = pipeline.cache.migration_view();
+    if bound.migration_view != Some(migration_view) {
+        ctx.PSSetShaderResources(3, 1, &migration_view);
+        bound.migration_view = Some(migration_view);
+    }
 
     ctx.RSSetScissorRects(
         1,
-        rect.as_ref().unwrap_or(&D3D11_RECT {
+        target.scissor.as_ref().unwrap_or(&D3D11_RECT {
             left: i32::MIN,
             right: i32::MAX,
             top: i32::MIN,
             bottom: i32::MAX,
         }),
     );
+}
 
-    ctx.DrawInstanced(4, pipeline.vertex_buffer.len as u32, 0, 0);
+/// Draws all glyph instances with the pixel shader set to the given pass, one draw call per
+/// run of glyphs sharing a draw state. The glow mask is always drawn with alpha blending.
+unsafe fn draw_pass<V>(
+    pipeline: &mut Pipeline<V>,
+    batch: &VertexBatch<V>,
+    target: &DrawTarget<'_>,
+    draw_pass: u32,
+) -> HResult<()> {
+    let len = batch.vertices.len();
+    let runs = match &batch.draw_runs[..] {
+        [] => vec![(DrawState::default(), len)],
+        runs => runs.to_vec(),
+    };
+    let (cache_width, cache_height) = pipeline.cache.dimensions();
+    let sdf_spread = match pipeline.cache.format() {
+        AtlasFormat::Coverage => 0.0,
+        AtlasFormat::Sdf { spread } => spread.max(1.0),
+    };
+    let mut start = 0;
+    for (state, count) in runs {
+        let blend_mode = match draw_pass {
+            PASS_GLOW => BlendMode::Alpha,
+            _ => state.blend_mode,
+        };
+        let halo =
+            (pipeline.halo).filter(|_| matches!(draw_pass, PASS_OUTLINE | PASS_OUTLINED_FILL));
+        let constants = PixelConstants {
+            draw_pass,
+            blend_mode: blend_mode as u32,
+            halo_color: halo.map_or([0.0; 4], |halo| halo.color),
+            halo_width: halo.map_or(0.0, |halo| halo.width),
+            time: pipeline.time,
+            // only raw instances need the offset, which would otherwise rewrite the constants
+            // for every draw call
+            instance_offset: if pipeline.input_layout.is_none() {
+                start as u32
+            } else {
+                0
+            },
+            instance_stride: mem::size_of::<V>() as u32,
+            coverage_gamma: pipeline.coverage_gamma,
+            outline_radius: pipeline.cache.outline_radius().unwrap_or(0.0),
+            cache_size: [cache_width as f32, cache_height as f32],
+            sdf_spread,
+            sdf_edge: pipeline.sdf_smoothing.edge,
+            sdf_width: pipeline.sdf_smoothing.width.unwrap_or(0.0),
+            cache_height_scale: pipeline.cache.layout_height() as f32 / cache_height as f32,
+            migration_height: pipeline.cache.migration_height() as f32,
+            ..PixelConstants::new(target.clip_shape.as_ref())
+        };
+        let pixel_buf = pipeline
+            .pixel_bufs
+            .select(&pipeline.ctx, constants)?
+            .as_raw();
+        if pipeline.bound.pixel_buf != pixel_buf {
+            pipeline.ctx.VSSetConstantBuffers(1, 1, &pixel_buf);
+            pipeline.ctx.PSSetConstantBuffers(0, 1, &pixel_buf);
+            pipeline.bound.pixel_buf = pixel_buf;
+        }
+        let sampler = match state.texture_filter {
+            Some(filter) => {
+                let device = &pipeline.device;
+                let desc = D3D11_SAMPLER_DESC {
+                    Filter: filter,
+                    ..pipeline.sampler_desc
+                };
+                pipeline.samplers.get_or_create(filter, || {
+                    com_ptr_from_fn("CreateSamplerState", |sampler| {
+                        device.CreateSamplerState(&desc, sampler)
+                    })
+                })?
+            }
+            None => pipeline.sampler.clone(),
+        };
+        let (bound, fill_textures) = (&mut pipeline.bound, &pipeline.fill_textures);
+        if bound.sampler != sampler.as_raw() {
+            pipeline.ctx.PSSetSamplers(0, 1, &sampler.as_raw());
+            bound.sampler = sampler.as_raw();
+        }
+        let fill_texture = (state.fill_texture)
+            .and_then(|texture| fill_textures.get(texture.0)?.as_ref())
+            .map_or(ptr::null_mut(), ComPtr::as_raw);
+        if bound.fill_texture != Some(fill_texture) {
+            pipeline.ctx.PSSetShaderResources(2, 1, &fill_texture);
+            bound.fill_texture = Some(fill_texture);
+        }
+        let blend_state = pipeline.blend_states[blend_mode as usize].as_raw();
+        if bound.blend_state != blend_state {
+            pipeline
+                .ctx
+                .OMSetBlendState(blend_state, &[0.0; 4], 0xFFFFFFFF);
+            bound.blend_state = blend_state;
+        }
+        pipeline.ctx.DrawInstanced(4, count as u32, 0, start as u32);
+        start += count;
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn write_constants<T>(
+    ctx: &ID3D11DeviceContext,
+    buffer: &ComPtr<ID3D11Buffer>,
+    value: T,
+) -> HResult<()> {
+    let mut mapped_resource = mem::MaybeUninit::zeroed();
+    hresult(
+        "Map",
+        ctx.Map(
+            com_ref_cast(buffer).as_raw(),
+            0,
+            D3D11_MAP_WRITE_DISCARD,
+            0,
+            mapped_resource.as_mut_ptr(),
+        ),
+    )?;
+    let mapped_resource = mapped_resource.assume_init();
+    *mapped_resource.pData.cast::<T>() = value;
+    ctx.Unmap(com_ref_cast(buffer).as_raw(), 0);
     Ok(())
 }
 
+/// The slot the constant buffer set with
+/// [`GlyphBrush::set_user_constants`](struct.GlyphBrush.html#method.set_user_constants) is
+/// bound to in both the vertex and the pixel shader, i.e. `register(b2)`.
+pub const USER_CONSTANT_BUFFER_SLOT: u32 = 2;
+
+const PASS_FILL: u32 = 0;
+const PASS_OUTLINE: u32 = 1;
+const PASS_GLOW: u32 = 2;
+/// Draws the glyphs over their outlines in a single pass.
+const PASS_OUTLINED_FILL: u32 = 3;
+
+const CLIP_NONE: u32 = 0;
+const CLIP_ROUNDED_RECT: u32 = 1;
+const CLIP_MASK: u32 = 2;
+
+/// The clip parameters, current pass, halo and time of the pixel shader, matching `pixelBuffer` in
+/// `pixel.hlsl`. Also bound to the vertex shader, which grows the quads by the halo width and
+/// reads raw instances at `instance_offset`.
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
+struct PixelConstants {
+    rect: [f32; 4],
+    radius: f32,
+    mode: u32,
+    draw_pass: u32,
+    blend_mode: u32,
+    halo_color: [f32; 4],
+    halo_width: f32,
+    time: f32,
+    /// The index of the first instance of the draw call, which `SV_InstanceID` doesn't include.
+    instance_offset: u32,
+    instance_stride: u32,
+    coverage_gamma: f32,
+    /// The distance up to which the second channel of the cache holds the distance of its
+    /// texels to their glyph, zero if the cache has a single channel.
+    outline_radius: f32,
+    cache_size: [f32; 2],
+    /// The spread of the signed distance field of an SDF cache, zero for a coverage cache.
+    sdf_spread: f32,
+    sdf_edge: f32,
+    /// The width the edge of the distance field is smoothed over, zero to derive it per pixel.
+    sdf_width: f32,
+    /// The height of the draw cache layout over the height of the texture, which the vertical
+    /// texture coordinates of glyph_brush are scaled by.
+    cache_height_scale: f32,
+    /// The height of the texture the cache is migrating from, whose rows are sampled from it,
+    /// zero if it isn't migrating.
+    migration_height: f32,
+}
+
+impl PixelConstants {
+    const NONE: PixelConstants = PixelConstants {
+        rect: [0.0; 4],
+        radius: 0.0,
+        mode: CLIP_NONE,
+        draw_pass: PASS_FILL,
+        blend_mode: BlendMode::Alpha as u32,
+        halo_color: [0.0; 4],
+        halo_width: 0.0,
+        time: 0.0,
+        instance_offset: 0,
+        instance_stride: 0,
+        coverage_gamma: 1.0,
+        outline_radius: 0.0,
+        cache_size: [0.0; 2],
+        sdf_spread: 0.0,
+        sdf_edge: 0.5,
+        sdf_width: 0.0,
+        cache_height_scale: 1.0,
+        migration_height: 0.0,
+    };
+
+    fn new(clip_shape: Option<&ClipShape<'_>>) -> Self {
+        match clip_shape {
+            None => PixelConstants::NONE,
+            Some(ClipShape::RoundedRect { rect, radius }) => {
+                let Rect { min, max } = rect.to_rect();
+                let max_radius = (max.x - min.x).min(max.y - min.y) / 2.0;
+                PixelConstants {
+                    rect: [min.x, min.y, max.x, max.y],
+                    radius: radius.max(0.0).min(max_radius),
+                    mode: CLIP_ROUNDED_RECT,
+                    ..PixelConstants::NONE
+                }
+            }
+            Some(ClipShape::Mask(_)) => PixelConstants {
+                mode: CLIP_MASK,
+                ..PixelConstants::NONE
+            },
+        }
+    }
+}
+
+/// How glyphs overlapping the bounds of their section are clipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsClipping {
+    /// Glyph quads are shrunk to the bounds and their texture coordinates adjusted
+    /// accordingly.
+    Vertex,
+    /// Glyph quads are drawn unmodified and pixels outside of the bounds are discarded in the
+    /// pixel shader, cutting edge glyphs cleanly at the cost of some overdraw.
+    Shader,
+}
+
+impl Default for BoundsClipping {
+    #[inline]
+    fn default() -> Self {
+        BoundsClipping::Vertex
+    }
+}
+
+/// The per-instance vertex data of a single glyph quad.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vertex {
-    left_top: [f32; 3],
-    right_bottom: [f32; 2],
-    tex_left_top: [f32; 2],
-    tex_right_bottom: [f32; 2],
-    color: [f32; 4],
+    /// The pixel coordinates `[min_x, max_y]` of the quad and its z value.
+    pub left_top: [f32; 3],
+    /// The pixel coordinates `[max_x, min_y]` of the quad.
+    pub right_bottom: [f32; 2],
+    /// The texture coordinates `[min_u, max_v]` of the glyph in the cache.
+    pub tex_left_top: [f32; 2],
+    /// The texture coordinates `[max_u, min_v]` of the glyph in the cache.
+    pub tex_right_bottom: [f32; 2],
+    pub color: [f32; 4],
+    /// The bounds of the section as `[min_x, min_y, max_x, max_y]`.
+    pub bounds: [f32; 4],
+    /// The offset of the shadow to the glyph in pixels.
+    pub shadow_offset: [f32; 2],
+    /// The color of the shadow, which isn't drawn if fully transparent.
+    pub shadow_color: [f32; 4],
+    /// The width of the outline in pixels.
+    pub outline_width: f32,
+    /// The color of the outline, which isn't drawn if fully transparent.
+    pub outline_color: [f32; 4],
+    /// The color of the glow, which isn't drawn if fully transparent.
+    pub glow_color: [f32; 4],
+    /// The texture coordinates of the fill texture per pixel and at the origin as
+    /// `[scale_u, scale_v, offset_u, offset_v]`, all zero if the glyph isn't textured.
+    pub fill_uv: [f32; 4],
+    /// The clockwise rotation of the quad around its center in radians.
+    pub rotation: f32,
 }
 
-impl<'gv> From<glyph_brush::GlyphVertex<'gv>> for Vertex {
-    fn from(
+impl Vertex {
+    pub fn new<X: GlyphExtra>(
         glyph_brush::GlyphVertex {
             mut tex_coords,
             mut pixel_coords,
             bounds,
             extra,
-        }: glyph_brush::GlyphVertex,
+        }: glyph_brush::GlyphVertex<'_, X>,
+        bounds_clipping: BoundsClipping,
     ) -> Self {
-        // handle overlapping bounds, modify uv_rect to preserve texture aspect
-        if pixel_coords.max.x > bounds.max.x {
-            let old_width = pixel_coords.width();
-            pixel_coords.max.x = bounds.max.x;
-            tex_coords.max.x =
-                tex_coords.min.x + tex_coords.width() * pixel_coords.width() / old_width;
-        }
+        // the quad of the glyph before it is clipped, which per glyph fills are mapped onto
+        let glyph_rect = pixel_coords;
+        let rotation = extra.rotation();
+        // clipping a rotated quad to the bounds would cut off the wrong part of the glyph
+        if bounds_clipping == BoundsClipping::Vertex && rotation == 0.0 {
+            // handle overlapping bounds, modify uv_rect to preserve texture aspect
+            if pixel_coords.max.x > bounds.max.x {
+                let old_width = pixel_coords.width();
+                pixel_coords.max.x = bounds.max.x;
+                tex_coords.max.x =
+                    tex_coords.min.x + tex_coords.width() * pixel_coords.width() / old_width;
+            }
 
-        if pixel_coords.min.x < bounds.min.x {
-            let old_width = pixel_coords.width();
-            pixel_coords.min.x = bounds.min.x;
-            tex_coords.min.x =
-                tex_coords.max.x - tex_coords.width() * pixel_coords.width() / old_width;
-        }
+            if pixel_coords.min.x < bounds.min.x {
+                let old_width = pixel_coords.width();
+                pixel_coords.min.x = bounds.min.x;
+                tex_coords.min.x =
+                    tex_coords.max.x - tex_coords.width() * pixel_coords.width() / old_width;
+            }
 
-        if pixel_coords.max.y > bounds.max.y {
-            let old_height = pixel_coords.height();
-            pixel_coords.max.y = bounds.max.y;
-            tex_coords.max.y =
-                tex_coords.min.y + tex_coords.height() * pixel_coords.height() / old_height;
-        }
+            if pixel_coords.max.y > bounds.max.y {
+                let old_height = pixel_coords.height();
+                pixel_coords.max.y = bounds.max.y;
+                tex_coords.max.y =
+                    tex_coords.min.y + tex_coords.height() * pixel_coords.height() / old_height;
+            }
 
-        if pixel_coords.min.y < bounds.min.y {
-            let old_height = pixel_coords.height();
-            pixel_coords.min.y = bounds.min.y;
-            tex_coords.min.y =
-                tex_coords.max.y - tex_coords.height() * pixel_coords.height() / old_height;
+            if pixel_coords.min.y < bounds.min.y {
+                let old_height = pixel_coords.height();
+                pixel_coords.min.y = bounds.min.y;
+                tex_coords.min.y =
+                    tex_coords.max.y - tex_coords.height() * pixel_coords.height() / old_height;
+            }
         }
 
+        let shadow = extra.shadow();
+        let outline = extra.outline();
+        let fill = extra.fill();
         Vertex {
-            left_top: [pixel_coords.min.x, pixel_coords.max.y, extra.z],
+            left_top: [pixel_coords.min.x, pixel_coords.max.y, extra.z()],
             right_bottom: [pixel_coords.max.x, pixel_coords.min.y],
             tex_left_top: [tex_coords.min.x, tex_coords.max.y],
             tex_right_bottom: [tex_coords.max.x, tex_coords.min.y],
-            color: extra.color,
+            color: extra.color(),
+            bounds: [bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y],
+            shadow_offset: shadow.map_or([0.0; 2], |shadow| shadow.offset),
+            shadow_color: shadow.map_or([0.0; 4], |shadow| shadow.color),
+            outline_width: outline.map_or(0.0, |outline| outline.width),
+            outline_color: outline.map_or([0.0; 4], |outline| outline.color),
+            glow_color: extra.glow().unwrap_or([0.0; 4]),
+            fill_uv: fill.map_or([0.0; 4], |fill| {
+                let [scale_u, scale_v] = fill.uv_scale;
+                let [offset_u, offset_v] = fill.uv_offset;
+                if fill.per_glyph {
+                    let scale_u = scale_u / glyph_rect.width();
+                    let scale_v = scale_v / glyph_rect.height();
+                    [
+                        scale_u,
+                        scale_v,
+                        offset_u - glyph_rect.min.x * scale_u,
+                        offset_v - glyph_rect.min.y * scale_v,
+                    ]
+                } else {
+                    [scale_u, scale_v, offset_u, offset_v]
+                }
+            }),
+            rotation,
         }
     }
 }
@@ -0,0 +1,254 @@
+use std::borrow::Cow;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use glyph_brush::{Extra, OwnedSection};
+
+use crate::{Region, Section};
+
+/// A queue of sections that can be filled concurrently from multiple threads and is later
+/// consumed by a brush using
+/// [`GlyphBrush::queue_from`](struct.GlyphBrush.html#method.queue_from).
+#[derive(Debug)]
+pub struct TextQueue<X = Extra> {
+    sections: Mutex<Vec<OwnedSection<X>>>,
+}
+
+impl<X> Default for TextQueue<X> {
+    fn default() -> Self {
+        TextQueue {
+            sections: Mutex::default(),
+        }
+    }
+}
+
+impl<X: Clone> TextQueue<X> {
+    pub fn new() -> Self {
+        TextQueue::default()
+    }
+
+    /// Queues a section, copying its text.
+    pub fn push<'a, S>(&self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        let section = Section::to_owned(&section.into());
+        self.lock().push(section);
+    }
+
+    /// Returns the number of queued sections.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Appends the sections of a batch, emptying it.
+    pub fn push_batch(&self, batch: &mut TextBatch<X>) {
+        self.lock().append(&mut batch.sections);
+    }
+
+    /// Removes and returns all queued sections.
+    pub fn take(&self) -> Vec<OwnedSection<X>> {
+        mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<OwnedSection<X>>> {
+        // the queue is left in a consistent state even if a pushing thread panicked
+        self.sections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Sections collected by a single thread without locking, e.g. by a job building part of a UI,
+/// which are later handed to the [`TextRenderer`](type.TextRenderer.html) owning the device
+/// resources and the glyph cache.
+///
+/// Batches are laid out and drawn into vertex buffers of their own with
+/// [`process_batches`](struct.GlyphBrush.html#method.process_batches) and
+/// [`draw_batch`](struct.GlyphBrush.html#method.draw_batch), sharing the pipeline and the glyph
+/// cache with the other batches. They can also be added to the queue of the renderer with
+/// [`submit`](struct.GlyphBrush.html#method.submit) or merged into a
+/// [`TextQueue`](struct.TextQueue.html).
+///
+/// Processing a batch empties it but keeps its allocation, so batches can be reused across
+/// frames. Each batch, including clones, has a vertex buffer of its own.
+#[derive(Debug)]
+pub struct TextBatch<X = Extra> {
+    /// Identifies the vertex buffer of the batch in the renderer.
+    id: u64,
+    sections: Vec<OwnedSection<X>>,
+}
+
+impl<X> Default for TextBatch<X> {
+    fn default() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TextBatch {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            sections: Vec::new(),
+        }
+    }
+}
+
+impl<X: Clone> Clone for TextBatch<X> {
+    fn clone(&self) -> Self {
+        TextBatch {
+            sections: self.sections.clone(),
+            ..TextBatch::default()
+        }
+    }
+}
+
+impl<X: Clone> TextBatch<X> {
+    pub fn new() -> Self {
+        TextBatch::default()
+    }
+
+    /// Queues a section, copying its text.
+    pub fn push<'a, S>(&mut self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        self.sections.push(Section::to_owned(&section.into()));
+    }
+
+    /// Appends the sections of another batch after the ones of this batch, emptying it.
+    pub fn append(&mut self, other: &mut TextBatch<X>) {
+        self.sections.append(&mut other.sections);
+    }
+
+    /// Returns the number of queued sections.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sections.clear();
+    }
+
+    /// Removes and returns all queued sections, keeping the allocation of the batch.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, OwnedSection<X>> {
+        self.sections.drain(..)
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// Sections queued and moved together, e.g. the text of a UI widget, see
+/// [`GlyphBrush::queue_group`](struct.GlyphBrush.html#method.queue_group).
+///
+/// Unlike a [`TextBatch`](struct.TextBatch.html), a group keeps its sections when it is queued,
+/// so it is built once and queued every frame. Its offset is applied after layout, so moving
+/// the group reuses the cached layouts of its sections, and hiding it keeps them cached.
+#[derive(Debug, Clone)]
+pub struct TextGroup<X = Extra> {
+    sections: Vec<OwnedSection<X>>,
+    offset: (f32, f32),
+    clip: Option<Region>,
+    visible: bool,
+}
+
+impl<X> Default for TextGroup<X> {
+    fn default() -> Self {
+        TextGroup {
+            sections: Vec::new(),
+            offset: (0.0, 0.0),
+            clip: None,
+            visible: true,
+        }
+    }
+}
+
+impl<X: Clone> TextGroup<X> {
+    pub fn new() -> Self {
+        TextGroup::default()
+    }
+
+    /// Adds a section to the group, copying its text.
+    pub fn push<'a, S>(&mut self, section: S)
+    where
+        X: 'a,
+        S: Into<Cow<'a, Section<'a, X>>>,
+    {
+        self.sections.push(Section::to_owned(&section.into()));
+    }
+
+    #[inline]
+    pub fn sections(&self) -> &[OwnedSection<X>] {
+        &self.sections
+    }
+
+    /// The sections of the group, e.g. to change the text of a single section.
+    #[inline]
+    pub fn sections_mut(&mut self) -> &mut Vec<OwnedSection<X>> {
+        &mut self.sections
+    }
+
+    /// Returns the number of sections in the group.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sections.clear();
+    }
+
+    /// The offset in pixels the sections are moved by after layout.
+    #[inline]
+    pub fn offset(&self) -> (f32, f32) {
+        self.offset
+    }
+
+    #[inline]
+    pub fn set_offset(&mut self, offset: (f32, f32)) {
+        self.offset = offset;
+    }
+
+    /// Moves the group by `(dx, dy)` pixels.
+    #[inline]
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.offset = (self.offset.0 + dx, self.offset.1 + dy);
+    }
+
+    /// The region the moved sections are clipped to, intersected with the clip region of the
+    /// brush when the group is queued.
+    #[inline]
+    pub fn clip(&self) -> Option<Region> {
+        self.clip
+    }
+
+    #[inline]
+    pub fn set_clip(&mut self, clip: Option<Region>) {
+        self.clip = clip;
+    }
+
+    /// Returns `false` if the group is hidden. Defaults to `true`.
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the group. Queuing a hidden group only keeps the layouts of its sections
+    /// cached, so showing it again doesn't lay them out anew.
+    #[inline]
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+}
@@ -0,0 +1,330 @@
+//! Rasterizing the glyphs of a font with a custom rasterizer, e.g. a hinting one.
+use std::fmt;
+use std::sync::Arc;
+
+use glyph_brush::ab_glyph::{
+    point, v2, Font, Glyph, GlyphId, GlyphSvg, Outline, OutlineCurve, OutlinedGlyph, PxScaleFactor,
+    Rect,
+};
+
+/// The coverage of a glyph rasterized by a [`GlyphRasterizer`](trait.GlyphRasterizer.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphBitmap {
+    /// The offset of the left column from the glyph position rounded down, in pixels.
+    pub left: i32,
+    /// The offset of the top row from the glyph position rounded down, in pixels pointing
+    /// down, so glyphs above the baseline have a negative offset.
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+    /// One coverage byte per pixel in row-major order.
+    pub coverage: Vec<u8>,
+}
+
+/// Rasterizes the glyphs of a [`RasterizedFont`](struct.RasterizedFont.html) into the glyph
+/// cache in place of ab_glyph, e.g. a FreeType face rasterizing small UI text with hinting.
+pub trait GlyphRasterizer {
+    /// Rasterizes `glyph` at its scale and position, or returns `None` to rasterize its
+    /// outline as usual, e.g. for sizes the rasterizer isn't meant for.
+    ///
+    /// glyph_brush caches the bitmap for positions within its
+    /// [position tolerance](struct.GlyphBrushBuilder.html#method.draw_cache_position_tolerance),
+    /// so rasterizers aligning glyphs to whole pixels may ignore the fractional part of the
+    /// position.
+    fn rasterize(&self, glyph: &Glyph) -> Option<GlyphBitmap>;
+}
+
+/// A font whose glyphs are rasterized by a [`GlyphRasterizer`](trait.GlyphRasterizer.html),
+/// which is selected per font.
+///
+/// The rasterized glyphs are drawn from the glyph cache like any other glyph. Only their
+/// coverage changes, they are laid out with the metrics of the wrapped font.
+///
+/// The rasterizer is only used if this is the font type of the brush, e.g.
+/// `GlyphBrush<RasterizedFont<FontArc>>` with fonts added by
+/// [`RasterizedFont::new`](#method.new) using the default rasterization, as `FontArc` and other
+/// wrappers rasterize the outlines of the fonts they wrap.
+pub struct RasterizedFont<F> {
+    font: F,
+    rasterizer: Option<Arc<dyn GlyphRasterizer + Send + Sync>>,
+}
+
+impl<F: Font> RasterizedFont<F> {
+    /// Wraps a font rasterized by ab_glyph, for brushes which rasterize only some of their
+    /// fonts themselves.
+    #[inline]
+    pub fn new(font: F) -> Self {
+        RasterizedFont {
+            font,
+            rasterizer: None,
+        }
+    }
+
+    /// Wraps a font whose glyphs are rasterized by `rasterizer`.
+    #[inline]
+    pub fn with_rasterizer<R>(font: F, rasterizer: R) -> Self
+    where
+        R: GlyphRasterizer + Send + Sync + 'static,
+    {
+        RasterizedFont {
+            font,
+            rasterizer: Some(Arc::new(rasterizer)),
+        }
+    }
+
+    /// The wrapped font.
+    #[inline]
+    pub fn font(&self) -> &F {
+        &self.font
+    }
+}
+
+impl<F: Clone> Clone for RasterizedFont<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        RasterizedFont {
+            font: self.font.clone(),
+            rasterizer: self.rasterizer.clone(),
+        }
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for RasterizedFont<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RasterizedFont")
+            .field("font", &self.font)
+            .field("rasterizer", &self.rasterizer.is_some())
+            .finish()
+    }
+}
+
+/// Outlines the pixels of `bitmap` as rectangles covering the same fraction of their pixel as
+/// their coverage, which the rasterizer of ab_glyph turns back into the coverage.
+///
+/// The outline is in pixels relative to the glyph position with y pointing up, to be drawn
+/// with a scale factor of one.
+fn bitmap_outline(bitmap: &GlyphBitmap, glyph: &Glyph) -> Option<Outline> {
+    if bitmap.width == 0 || bitmap.coverage.len() < bitmap.width as usize * bitmap.height as usize {
+        return None;
+    }
+    // rounding errors of the positions mustn't leave fully covered pixels at 254, so the
+    // rectangles overlap their neighbours by less than half a coverage step. They mustn't
+    // cross the left and right edge, the rasterizer drops the edges left of the first pixel.
+    const OVERLAP: f32 = 1.0 / 1024.0;
+    let position = glyph.position;
+    let left = position.x.floor() - position.x + bitmap.left as f32;
+    let right = left + bitmap.width as f32;
+    let top = position.y - position.y.floor() - bitmap.top as f32;
+    let mut curves = Vec::new();
+    let mut rectangle = |x0: f32, x1: f32, y0: f32, y1: f32| {
+        curves.push(OutlineCurve::Line(point(x0, y0), point(x1, y0)));
+        curves.push(OutlineCurve::Line(point(x1, y0), point(x1, y1)));
+        curves.push(OutlineCurve::Line(point(x1, y1), point(x0, y1)));
+        curves.push(OutlineCurve::Line(point(x0, y1), point(x0, y0)));
+    };
+    let rows = bitmap.coverage.chunks(bitmap.width as usize);
+    for (y, row) in rows.take(bitmap.height as usize).enumerate() {
+        let (y0, y1) = (top - y as f32 + OVERLAP, top - (y + 1) as f32 - OVERLAP);
+        let mut x = 0;
+        while x < row.len() {
+            // runs of fully covered pixels are merged into a single rectangle
+            let run = row[x..].iter().take_while(|&&texel| texel == 255).count();
+            if run > 0 {
+                let x0 = (left + x as f32 - OVERLAP).max(left);
+                let x1 = (left + (x + run) as f32 + OVERLAP).min(right);
+                rectangle(x0, x1, y0, y1);
+                x += run;
+                continue;
+            }
+            if row[x] > 0 {
+                // glyph_brush truncates the coverage, so half a step is added to round it
+                let width = (row[x] as f32 + 0.5) / 255.0;
+                let x0 = left + x as f32;
+                rectangle(x0, x0 + width, y0, y1);
+            }
+            x += 1;
+        }
+    }
+    if curves.is_empty() {
+        return None;
+    }
+    Some(Outline {
+        bounds: Rect {
+            min: point(left, top),
+            max: point(right, top - bitmap.height as f32),
+        },
+        curves,
+    })
+}
+
+impl<F: Font> Font for RasterizedFont<F> {
+    #[inline]
+    fn units_per_em(&self) -> Option<f32> {
+        self.font.units_per_em()
+    }
+
+    #[inline]
+    fn ascent_unscaled(&self) -> f32 {
+        self.font.ascent_unscaled()
+    }
+
+    #[inline]
+    fn descent_unscaled(&self) -> f32 {
+        self.font.descent_unscaled()
+    }
+
+    #[inline]
+    fn line_gap_unscaled(&self) -> f32 {
+        self.font.line_gap_unscaled()
+    }
+
+    #[inline]
+    fn italic_angle(&self) -> f32 {
+        self.font.italic_angle()
+    }
+
+    #[inline]
+    fn glyph_id(&self, c: char) -> GlyphId {
+        self.font.glyph_id(c)
+    }
+
+    #[inline]
+    fn h_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.h_advance_unscaled(id)
+    }
+
+    #[inline]
+    fn h_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.h_side_bearing_unscaled(id)
+    }
+
+    #[inline]
+    fn v_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.v_advance_unscaled(id)
+    }
+
+    #[inline]
+    fn v_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+        self.font.v_side_bearing_unscaled(id)
+    }
+
+    #[inline]
+    fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> f32 {
+        self.font.kern_unscaled(first, second)
+    }
+
+    #[inline]
+    fn outline(&self, id: GlyphId) -> Option<Outline> {
+        self.font.outline(id)
+    }
+
+    #[inline]
+    fn glyph_count(&self) -> usize {
+        self.font.glyph_count()
+    }
+
+    #[inline]
+    fn codepoint_ids(&self) -> glyph_brush::ab_glyph::CodepointIdIter<'_> {
+        self.font.codepoint_ids()
+    }
+
+    #[inline]
+    fn glyph_raster_image2(&self, id: GlyphId, pixel_size: u16) -> Option<v2::GlyphImage<'_>> {
+        self.font.glyph_raster_image2(id, pixel_size)
+    }
+
+    #[inline]
+    fn glyph_svg_image(&self, id: GlyphId) -> Option<GlyphSvg<'_>> {
+        self.font.glyph_svg_image(id)
+    }
+
+    #[inline]
+    fn font_data(&self) -> &[u8] {
+        self.font.font_data()
+    }
+
+    /// Outlines the glyph as rasterized by the rasterizer of the font, which glyph_brush
+    /// rasterizes into the glyph cache.
+    fn outline_glyph(&self, glyph: Glyph) -> Option<OutlinedGlyph> {
+        let bitmap = match &self.rasterizer {
+            Some(rasterizer) => rasterizer.rasterize(&glyph),
+            None => None,
+        };
+        match bitmap {
+            Some(bitmap) => {
+                let outline = bitmap_outline(&bitmap, &glyph)?;
+                let scale_factor = PxScaleFactor {
+                    horizontal: 1.0,
+                    vertical: 1.0,
+                };
+                Some(OutlinedGlyph::new(glyph, outline, scale_factor))
+            }
+            None => self.font.outline_glyph(glyph),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glyph_brush::ab_glyph::{FontRef, PxScale};
+
+    /// Rasterizes every glyph as the same bitmap.
+    struct Stamp(GlyphBitmap);
+
+    impl GlyphRasterizer for Stamp {
+        fn rasterize(&self, _glyph: &Glyph) -> Option<GlyphBitmap> {
+            Some(self.0.clone())
+        }
+    }
+
+    fn inconsolata() -> FontRef<'static> {
+        FontRef::try_from_slice(include_bytes!("../examples/Inconsolata-Regular.ttf")).unwrap()
+    }
+
+    /// Draws the glyph like glyph_brush does, returning its pixel bounds and coverage.
+    fn draw(outlined: &OutlinedGlyph) -> (Rect, Vec<u8>) {
+        let bounds = outlined.px_bounds();
+        let width = bounds.width() as usize;
+        let mut pixels = vec![0; width * bounds.height() as usize];
+        outlined.draw(|x, y, coverage| {
+            pixels[y as usize * width + x as usize] = (coverage * 255.0) as u8
+        });
+        (bounds, pixels)
+    }
+
+    #[test]
+    fn draws_the_rasterized_coverage() {
+        let bitmap = GlyphBitmap {
+            left: 1,
+            top: -3,
+            width: 3,
+            height: 2,
+            coverage: vec![255, 255, 0, 17, 128, 255],
+        };
+        let font = RasterizedFont::with_rasterizer(inconsolata(), Stamp(bitmap.clone()));
+        let glyph_id = font.glyph_id('a');
+        for &(x, y) in &[(10.0, 20.0), (10.3, 20.7), (-5.6, -2.2), (1234.9, 567.1)] {
+            let glyph = glyph_id.with_scale_and_position(PxScale::from(12.0), point(x, y));
+            let (bounds, pixels) = draw(&font.outline_glyph(glyph).unwrap());
+            let min = point(x.floor() + 1.0, y.floor() - 3.0);
+            assert_eq!(bounds.min, min, "at {:?}", (x, y));
+            assert_eq!(
+                bounds.max,
+                point(min.x + 3.0, min.y + 2.0),
+                "at {:?}",
+                (x, y)
+            );
+            assert_eq!(pixels, bitmap.coverage, "at {:?}", (x, y));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_outline() {
+        let font = RasterizedFont::new(inconsolata());
+        let glyph = (font.glyph_id('a')).with_scale_and_position(12.0, point(10.3, 20.0));
+        let expected = inconsolata().outline_glyph(glyph.clone()).unwrap();
+        let outlined = font.outline_glyph(glyph).unwrap();
+        assert_eq!(draw(&outlined), draw(&expected));
+    }
+}
@@ -0,0 +1,312 @@
+use std::hash::{Hash, Hasher};
+
+use glyph_brush::ab_glyph::{point, Font, Rect};
+use glyph_brush::{
+    GlyphChange, GlyphPositioner, Section, SectionGeometry, SectionGlyph, ToSectionText,
+};
+use winapi::um::d3d11::{ID3D11ShaderResourceView, D3D11_RECT};
+use wio::com::ComPtr;
+
+use crate::Transform;
+
+/// A rectangular region of the render target in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// Returns the overlapping part of both regions. Regions that don't overlap result in an
+    /// empty region.
+    pub fn intersect(self, other: Region) -> Region {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right()).max(x);
+        let bottom = self.bottom().min(other.bottom()).max(y);
+        Region {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Returns the pixels of a `target_width` x `target_height` target covered by the bounds of
+    /// `section` drawn with `transform`, e.g. the scissor rect of
+    /// [`draw_with_scissoring`](struct.GlyphBrush.html#method.draw_with_scissoring) or a clip
+    /// region for the section.
+    ///
+    /// The bounds are placed by the alignment of the section's layout. Partially covered pixels
+    /// are included and the region is clamped to the target, so unbounded sections cover the
+    /// target up to the edges they extend to.
+    pub fn from_section_bounds<X>(
+        section: &Section<'_, X>,
+        transform: &Transform,
+        target_width: u32,
+        target_height: u32,
+    ) -> Region {
+        let rect = section.layout.bounds_rect(&SectionGeometry::from(section));
+        // infinite bounds are limited to stay finite when multiplied by zeroes of the transform
+        let limit = |value: f32| value.clamp(-1e9, 1e9);
+        let corners = [
+            [rect.min.x, rect.min.y],
+            [rect.max.x, rect.min.y],
+            [rect.min.x, rect.max.y],
+            [rect.max.x, rect.max.y],
+        ]
+        .map(|[x, y]| {
+            (transform.project_to_screen([limit(x), limit(y), 0.0], target_width, target_height))
+                .position
+        });
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for (x, y) in &corners[1..] {
+            min = (min.0.min(*x), min.1.min(*y));
+            max = (max.0.max(*x), max.1.max(*y));
+        }
+        // the rounding errors of the projection mustn't spill bounds on pixel edges over into
+        // the neighbouring pixels
+        let snap = |value: f32| {
+            let rounded = value.round();
+            if (value - rounded).abs() < 1.0 / 256.0 {
+                rounded
+            } else {
+                value
+            }
+        };
+        let (width, height) = (target_width as f32, target_height as f32);
+        let left = snap(min.0).floor().clamp(0.0, width) as u32;
+        let top = snap(min.1).floor().clamp(0.0, height) as u32;
+        let right = (snap(max.0).ceil().clamp(0.0, width) as u32).max(left);
+        let bottom = (snap(max.1).ceil().clamp(0.0, height) as u32).max(top);
+        Region {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    pub(crate) fn to_rect(self) -> Rect {
+        Rect {
+            min: point(self.x as f32, self.y as f32),
+            max: point(self.right() as f32, self.bottom() as f32),
+        }
+    }
+
+    /// The right edge, saturating for regions reaching past `u32::MAX`.
+    fn right(self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    fn bottom(self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+}
+
+impl From<Region> for D3D11_RECT {
+    fn from(region: Region) -> Self {
+        let clamp = |value: u32| value.min(i32::MAX as u32) as i32;
+        D3D11_RECT {
+            left: clamp(region.x),
+            top: clamp(region.y),
+            right: clamp(region.right()),
+            bottom: clamp(region.bottom()),
+        }
+    }
+}
+
+/// A shape to clip drawn text against, in render target pixels.
+///
+/// Clip shapes are ignored on devices below feature level 10.0.
+#[derive(Clone, Copy)]
+pub enum ClipShape<'a> {
+    /// Clips against a rectangle with rounded corners, anti-aliasing the edges.
+    ///
+    /// The radius is limited to half the smaller side of the rectangle.
+    RoundedRect { rect: Region, radius: f32 },
+    /// Multiplies the coverage of every pixel with the red channel of the mask texture at the
+    /// same pixel position. The mask should have the size of the render target.
+    Mask(&'a ComPtr<ID3D11ShaderResourceView>),
+}
+
+/// Intersects two rects, collapsing the result to an empty rect if they don't overlap.
+pub(crate) fn intersect_rect(a: Rect, b: Rect) -> Rect {
+    let min = point(a.min.x.max(b.min.x), a.min.y.max(b.min.y));
+    let max = point(
+        a.max.x.min(b.max.x).max(min.x),
+        a.max.y.min(b.max.y).max(min.y),
+    );
+    Rect { min, max }
+}
+
+/// Wraps a layout, restricting its bounds to a clip region.
+///
+/// Glyphs are laid out as usual but clipped to the region during vertex generation.
+pub(crate) struct ClippedLayout<'a, L> {
+    pub layout: &'a L,
+    pub clip: Region,
+}
+
+impl<L: Hash> Hash for ClippedLayout<'_, L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.layout.hash(state);
+        self.clip.hash(state);
+    }
+}
+
+impl<L: GlyphPositioner> GlyphPositioner for ClippedLayout<'_, L> {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        self.layout.calculate_glyphs(fonts, geometry, sections)
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect {
+        intersect_rect(self.layout.bounds_rect(geometry), self.clip.to_rect())
+    }
+
+    fn recalculate_glyphs<F, S, P>(
+        &self,
+        previous: P,
+        change: GlyphChange,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+        P: IntoIterator<Item = SectionGlyph>,
+    {
+        self.layout
+            .recalculate_glyphs(previous, change, fonts, geometry, sections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect_saturates() {
+        let unbounded = Region {
+            x: 10,
+            y: 20,
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        let target = Region {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 50,
+        };
+        let expected = Region {
+            x: 10,
+            y: 20,
+            width: 90,
+            height: 30,
+        };
+        assert_eq!(unbounded.intersect(target), expected);
+        assert_eq!(target.intersect(unbounded), expected);
+        // the right and bottom edges saturate at `u32::MAX`
+        let itself = unbounded.intersect(unbounded);
+        assert_eq!(
+            (itself.width, itself.height),
+            (u32::MAX - 10, u32::MAX - 20)
+        );
+    }
+
+    #[test]
+    fn rect_saturates() {
+        let rect = D3D11_RECT::from(Region {
+            x: 10,
+            y: u32::MAX - 1,
+            width: u32::MAX,
+            height: 5,
+        });
+        assert_eq!(
+            (rect.left, rect.top, rect.right, rect.bottom),
+            (10, i32::MAX, i32::MAX, i32::MAX)
+        );
+    }
+
+    fn section_bounds(position: (f32, f32), bounds: (f32, f32)) -> Region {
+        let section: Section<'_> = Section::default()
+            .with_screen_position(position)
+            .with_bounds(bounds);
+        Region::from_section_bounds(&section, &Transform::orthographic(100, 50), 100, 50)
+    }
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn whole_pixel_bounds() {
+        assert_eq!(
+            section_bounds((10.0, 20.0), (30.0, 10.0)),
+            region(10, 20, 30, 10)
+        );
+        assert_eq!(
+            section_bounds((0.0, 0.0), (100.0, 50.0)),
+            region(0, 0, 100, 50)
+        );
+    }
+
+    #[test]
+    fn fractional_bounds() {
+        // partially covered pixels are included
+        assert_eq!(
+            section_bounds((10.5, 20.25), (30.0, 10.5)),
+            region(10, 20, 31, 11)
+        );
+        assert_eq!(
+            section_bounds((10.25, 20.0), (0.5, 0.25)),
+            region(10, 20, 1, 1)
+        );
+    }
+
+    #[test]
+    fn negative_bounds() {
+        assert_eq!(
+            section_bounds((-5.5, -3.0), (20.0, 10.0)),
+            region(0, 0, 15, 7)
+        );
+        assert_eq!(
+            section_bounds((-30.0, -20.0), (20.0, 10.0)),
+            region(0, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn bounds_past_the_target() {
+        assert_eq!(
+            section_bounds((90.0, 45.0), (30.0, 30.0)),
+            region(90, 45, 10, 5)
+        );
+        assert_eq!(
+            section_bounds((10.0, 20.0), (f32::INFINITY, f32::INFINITY)),
+            region(10, 20, 90, 30)
+        );
+        assert_eq!(
+            section_bounds((120.0, 60.0), (10.0, 10.0)),
+            region(100, 50, 0, 0)
+        );
+    }
+}
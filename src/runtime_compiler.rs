@@ -0,0 +1,129 @@
+//! Compiles the built-in shaders at runtime if the build script had no shader compiler
+//! available, e.g. when cross-compiling.
+use std::borrow::Cow;
+use std::num::NonZeroI32;
+use std::sync::OnceLock;
+use std::{mem, ptr, slice};
+
+use winapi::shared::minwindef::{FARPROC, UINT};
+use winapi::shared::ntdef::LPCSTR;
+use winapi::shared::winerror::{E_FAIL, HRESULT};
+use winapi::um::d3dcommon::{ID3DBlob, ID3DInclude, D3D_SHADER_MACRO};
+use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryA};
+use wio::com::ComPtr;
+
+use crate::util::{hresult, HResult};
+use crate::Error;
+
+type D3DCompileFn = unsafe extern "system" fn(
+    src_data: *const u8,
+    src_data_size: usize,
+    source_name: LPCSTR,
+    defines: *const D3D_SHADER_MACRO,
+    include: *mut ID3DInclude,
+    entry_point: LPCSTR,
+    target: LPCSTR,
+    flags1: UINT,
+    flags2: UINT,
+    code: *mut *mut ID3DBlob,
+    error_msgs: *mut *mut ID3DBlob,
+) -> HRESULT;
+
+/// Returns `bytecode` unless it is empty, in which case the built-in shader `source` is compiled
+/// for the `"vs"` or `"ps"` stage of the given shader model.
+///
+/// `define` selects a variant of the shader, shader model `4_0_level_9_3` always defines
+/// `LEVEL_9_3`.
+pub fn or_compile(
+    bytecode: &'static [u8],
+    source: &str,
+    stage: &str,
+    model: &str,
+    define: Option<&str>,
+) -> HResult<Cow<'static, [u8]>> {
+    if !bytecode.is_empty() {
+        return Ok(Cow::Borrowed(bytecode));
+    }
+    let define = match define {
+        Some(define) => Some(define),
+        None if model.contains("level_9") => Some("LEVEL_9_3"),
+        None => None,
+    };
+    unsafe { compile(source, &format!("{}_{}\0", stage, model), define) }.map(Cow::Owned)
+}
+
+/// Returns `D3DCompile` of d3dcompiler_47.dll, which is loaded once and stays loaded for the
+/// lifetime of the process.
+fn d3d_compile() -> Option<D3DCompileFn> {
+    static D3D_COMPILE: OnceLock<Option<D3DCompileFn>> = OnceLock::new();
+    *D3D_COMPILE.get_or_init(|| unsafe {
+        let library = LoadLibraryA("d3dcompiler_47.dll\0".as_ptr().cast());
+        if library.is_null() {
+            return None;
+        }
+        let d3d_compile = GetProcAddress(library, "D3DCompile\0".as_ptr().cast());
+        if d3d_compile.is_null() {
+            return None;
+        }
+        Some(mem::transmute::<FARPROC, D3DCompileFn>(d3d_compile))
+    })
+}
+
+unsafe fn compile(source: &str, target: &str, define: Option<&str>) -> HResult<Vec<u8>> {
+    let d3d_compile = match d3d_compile() {
+        Some(d3d_compile) => d3d_compile,
+        None => {
+            log::error!("Unable to load D3DCompile from d3dcompiler_47.dll");
+            return Err(Error::HResult {
+                call: "D3DCompile",
+                code: NonZeroI32::new(E_FAIL).unwrap(),
+            });
+        }
+    };
+
+    let define = define.map(|define| format!("{}\0", define));
+    let defines = [
+        D3D_SHADER_MACRO {
+            Name: define
+                .as_ref()
+                .map_or(ptr::null(), |define| define.as_ptr().cast()),
+            Definition: "1\0".as_ptr().cast(),
+        },
+        D3D_SHADER_MACRO {
+            Name: ptr::null(),
+            Definition: ptr::null(),
+        },
+    ];
+    let mut code = ptr::null_mut();
+    let mut err = ptr::null_mut();
+    let hr = d3d_compile(
+        source.as_ptr(),
+        source.len(),
+        ptr::null(),
+        defines.as_ptr(),
+        ptr::null_mut(),
+        "main\0".as_ptr().cast(),
+        target.as_ptr().cast(),
+        0,
+        0,
+        &mut code,
+        &mut err,
+    );
+    let err = (!err.is_null()).then(|| ComPtr::from_raw(err));
+    if hr < 0 {
+        log::error!(
+            "Failed to compile {} shader: {}",
+            target.trim_end_matches('\0'),
+            err.as_ref().map_or(Cow::Borrowed("Unknown error"), |err| {
+                String::from_utf8_lossy(blob_bytes(err))
+            })
+        );
+    }
+    hresult("D3DCompile", hr)?;
+    let code = ComPtr::from_raw(code);
+    Ok(blob_bytes(&code).to_vec())
+}
+
+unsafe fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    slice::from_raw_parts(blob.GetBufferPointer().cast::<u8>(), blob.GetBufferSize())
+}
@@ -0,0 +1,28 @@
+cbuffer blurBuffer: register(b0) {
+    // the offset between two taps in pixels along the blur direction
+    int2 tap_step;
+    float sigma;
+    uint radius;
+};
+
+struct PS_INPUT {
+    float4 pos: SV_POSITION;
+    float2 tex_pos: TEXCOORD0;
+};
+
+Texture2D source: register(t0);
+
+// one direction of a separable gaussian blur. The source has the size of the whole render
+// target, so it's read at the pixel position rather than the texture coordinate, which only
+// spans the bound viewport. Taps outside of the source read zero.
+float4 main(PS_INPUT input): SV_Target {
+    int2 pixel = int2(input.pos.xy);
+    float4 sum = 0.0f;
+    float weights = 0.0f;
+    for (int i = -(int)radius; i <= (int)radius; i++) {
+        float weight = exp(-(i * i) / (2.0f * sigma * sigma));
+        sum += source.Load(int3(pixel + tap_step * i, 0)) * weight;
+        weights += weight;
+    }
+    return sum / weights;
+}
@@ -0,0 +1,12 @@
+struct VS_OUTPUT {
+    float4 pos: SV_POSITION;
+    float2 tex_pos: TEXCOORD0;
+};
+
+// draws a triangle covering the whole target without any vertex buffer
+VS_OUTPUT main(uint vertex_id: SV_VertexID) {
+    VS_OUTPUT o;
+    o.tex_pos = float2((vertex_id << 1) & 2, vertex_id & 2);
+    o.pos = float4(o.tex_pos * float2(2.0f, -2.0f) + float2(-1.0f, 1.0f), 0.0f, 1.0f);
+    return o;
+}
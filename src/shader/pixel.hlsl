@@ -1,18 +1,204 @@
+cbuffer pixelBuffer: register(b0) {
+    float4 clip_rect;
+    float clip_radius;
+    uint clip_mode;
+    // 0: glyphs, 1: outlines drawn before the glyphs, 2: glow mask, 3: glyphs over their
+    // outlines
+    uint draw_pass;
+    // 0: alpha, 1: additive, 2: multiply
+    uint blend_mode;
+    // drawn around glyphs without an outline of their own, disabled if transparent
+    float4 halo_color;
+    float halo_width;
+    // seconds set with `GlyphBrush::set_time`, for animated effects of custom shaders
+    float time;
+    // only used by the vertex shader
+    uint instance_offset;
+    uint instance_stride;
+    // coverage is raised to the inverse of this, thickening glyphs above 1
+    float coverage_gamma;
+    // the distance in texels up to which the green channel of the cache holds the distance
+    // to the glyph, only stored for single pass outlines
+    float outline_radius;
+    float2 cache_size;
+    // the spread of the signed distance fields of an SDF cache, zero if it holds coverage
+    float sdf_spread;
+    // the distance of the glyph edge and the width it is smoothed over, zero to smooth it
+    // over a pixel
+    float sdf_edge;
+    float sdf_width;
+    // only used by the vertex shader
+    float cache_height_scale;
+    // the height of the texture the cache is copied from over several frames after growing,
+    // whose rows are sampled from it until then, zero if it isn't migrating
+    float migration_height;
+};
+
 struct PS_INPUT {
     float4 pos: SV_POSITION;
     float4 color: COLOR0;
-    float2 tex_pos: TEXCOORD0;
+    float4 shadow_color: COLOR1;
+    float4 outline_color: COLOR2;
+    float4 glow_color: COLOR3;
+    // xy: glyph, zw: shadow
+    float4 tex_pos: TEXCOORD0;
+    // xy: position, zw: outline width in texels
+    float4 section_pos: TEXCOORD1;
+    float4 bounds: TEXCOORD2;
+    float4 tex_bounds: TEXCOORD3;
+    // xy: texture coordinates per pixel, zw: at the origin, zero if not textured
+    float4 fill_uv: TEXCOORD4;
 };
 
-sampler sampler0;
-Texture2D texture0;
+sampler sampler0: register(s0);
+sampler fill_sampler: register(s1);
+Texture2D texture0: register(t0);
+Texture2D clip_mask: register(t1);
+Texture2D fill_texture: register(t2);
+Texture2D migration_texture: register(t3);
+
+// the width the edge of the distance fields is smoothed over at the current pixel
+static float sdf_smoothing;
+
+// signed distance of the pixel to the clip rect, negative inside
+float rounded_rect_distance(float2 pos) {
+    float2 half_size = (clip_rect.zw - clip_rect.xy) * 0.5f;
+    float2 q = abs(pos - (clip_rect.xy + half_size)) - half_size + clip_radius;
+    return length(max(q, 0.0f)) + min(max(q.x, q.y), 0.0f) - clip_radius;
+}
+
+// samples the glyph cache, reading the rows of a grown cache that are still being copied from
+// the texture it grew from
+float4 sample_cache(float2 tex_pos) {
+    float4 texel = texture0.Sample(sampler0, tex_pos);
+#ifndef LEVEL_9_3
+    float row = tex_pos.y * cache_size.y;
+    // the row above the new rows is copied at once, so filtering across the seam reads the
+    // grown texture
+    if (migration_height > 0.0f && row < migration_height - 0.5f) {
+        float2 old_pos = float2(tex_pos.x, row / migration_height);
+        texel = migration_texture.SampleLevel(sampler0, old_pos, 0.0f);
+    }
+#endif
+    return texel;
+}
+
+// samples the glyph coverage, which is zero outside of the glyph's region of the cache
+float coverage(float2 tex_pos, float4 tex_bounds) {
+    float2 inside = step(tex_bounds.xy, tex_pos) * step(tex_pos, tex_bounds.zw);
+    float alpha = sample_cache(tex_pos).r;
+    if (sdf_spread > 0.0f) {
+        alpha = smoothstep(sdf_edge - sdf_smoothing, sdf_edge + sdf_smoothing, alpha);
+    }
+    alpha *= inside.x * inside.y;
+    return pow(alpha, 1.0f / coverage_gamma);
+}
+
+// the maximum coverage within the outline width around the pixel, for outlines drawn in a pass
+// of their own
+float dilated_coverage(float2 tex_pos, float2 width, float4 tex_bounds) {
+    float alpha = coverage(tex_pos, tex_bounds);
+    [unroll] for (int i = 0; i < 8; i++) {
+        float2 dir;
+        sincos(i * 0.785398f, dir.y, dir.x);
+        alpha = max(alpha, coverage(tex_pos + dir * width, tex_bounds));
+        alpha = max(alpha, coverage(tex_pos + dir * width * 0.5f, tex_bounds));
+    }
+    return alpha;
+}
+
+// the coverage of the outline of the given width for single pass outlines, read from the
+// distance to the glyph in the green channel of the cache and continued beyond the glyph's
+// region by the distance to it
+float outline_coverage(float2 tex_pos, float2 width, float4 tex_bounds) {
+    float2 half_texel = 0.5f / cache_size;
+    float2 inside = clamp(tex_pos, tex_bounds.xy + half_texel, tex_bounds.zw - half_texel);
+    float glyph_distance = (1.0f - sample_cache(inside).g) * outline_radius
+        + length((tex_pos - inside) * cache_size);
+    float2 texels = width * cache_size;
+    return saturate(min(max(texels.x, texels.y), outline_radius) + 0.5f - glyph_distance);
+}
+
+// composites `top` over `bottom`, neither premultiplied
+float4 over(float4 top, float4 bottom) {
+    float alpha = top.a + bottom.a * (1.0f - top.a);
+    top.rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0f - top.a)) / max(alpha, 1e-5f);
+    top.a = alpha;
+    return top;
+}
+
+// composites `color` over the shadow of the glyph
+float4 over_shadow(float4 color, PS_INPUT input) {
+    float4 shadow = input.shadow_color;
+    shadow.a *= coverage(input.tex_pos.zw, input.tex_bounds);
+    return over(color, shadow);
+}
 
 float4 main(PS_INPUT input): SV_Target {
-    float alpha = texture0.Sample(sampler0, input.tex_pos).r;
+    // discard everything outside of the section bounds
+    clip(float4(
+        input.section_pos.xy - input.bounds.xy,
+        input.bounds.zw - input.section_pos.xy
+    ));
+
+    // the distance field changes by half a spread per texel, so the derived width keeps the
+    // edge a pixel wide at any scale
+    float2 texels_per_pixel = fwidth(input.tex_pos.xy * cache_size);
+    sdf_smoothing = 0.5f * (sdf_width > 0.0f
+        ? sdf_width
+        : 0.5f * max(texels_per_pixel.x, texels_per_pixel.y) / max(sdf_spread, 1.0f));
+
+    if (draw_pass == 2) {
+        float4 glow = input.glow_color;
+        glow.a *= coverage(input.tex_pos.xy, input.tex_bounds);
+        if (glow.a <= 0.0f) { discard; }
+        return glow;
+    }
+
+    float4 outline_color = input.outline_color.a > 0.0f ? input.outline_color : halo_color;
+    bool outlined = outline_color.a > 0.0f;
+    bool outline_pass = draw_pass == 1;
+    if (outline_pass && !outlined) { discard; }
+
+    float4 target0;
+    if (outline_pass) {
+        target0 = outline_color;
+        target0.a *= dilated_coverage(input.tex_pos.xy, input.section_pos.zw, input.tex_bounds);
+    } else {
+        target0 = input.color;
+        if (any(input.fill_uv.xy != 0.0f)) {
+            float2 fill_pos = input.section_pos.xy * input.fill_uv.xy + input.fill_uv.zw;
+            target0 *= fill_texture.Sample(fill_sampler, fill_pos);
+        }
+        target0.a *= coverage(input.tex_pos.xy, input.tex_bounds);
+        if (draw_pass == 3 && outlined) {
+            outline_color.a *=
+                outline_coverage(input.tex_pos.xy, input.section_pos.zw, input.tex_bounds);
+            target0 = over(target0, outline_color);
+        }
+    }
+    // the shadow is drawn below the outline if there is one
+    bool below_outline = outline_pass || draw_pass == 3;
+    if (input.shadow_color.a > 0.0f && (below_outline || !outlined)) {
+        target0 = over_shadow(target0, input);
+    }
+    float alpha = target0.a;
+
+#ifndef LEVEL_9_3
+    // feature level 9.3 can't read the pixel position, so clip shapes are ignored there
+    if (clip_mode == 1) {
+        alpha *= saturate(0.5f - rounded_rect_distance(input.pos.xy));
+    } else if (clip_mode == 2) {
+        alpha *= clip_mask.Load(int3(input.pos.xy, 0)).r;
+    }
+#endif
 
     if (alpha <= 0.0f) { discard; }
 
-    float4 target0 = input.color;
-    target0.a *= alpha;
+    target0.a = alpha;
+    if (blend_mode == 2) {
+        // the render target is multiplied with the output color
+        target0.rgb = lerp(1.0f, target0.rgb, alpha);
+    }
     return target0;
 }
@@ -1,54 +1,167 @@
+// column-major, transforming positions as column vectors, see `Transform`
 cbuffer vertexBuffer: register(b0) {
     float4x4 ProjectionMatrix;
 };
 
-struct VS_INPUT {
-    uint vertex_id: SV_VertexID;
+// the constants of the pixel shader, of which only the halo, the instances and the cache height
+// scale are used here
+cbuffer pixelBuffer: register(b1) {
+    float4 clip_rect;
+    float clip_radius;
+    uint clip_mode;
+    uint draw_pass;
+    uint blend_mode;
+    float4 halo_color;
+    float halo_width;
+    float time;
+    // the index of the first instance of the draw call and the size of an instance in bytes
+    uint instance_offset;
+    uint instance_stride;
+    float coverage_gamma;
+    float outline_radius;
+    float2 cache_size;
+    float sdf_spread;
+    float sdf_edge;
+    float sdf_width;
+    // the height of the draw cache over the height of the texture while it grows into a
+    // reserved height, scaling the vertical texture coordinates of the glyphs
+    float cache_height_scale;
+    float migration_height;
+};
+
+// a glyph quad, read from the instance stream or from the raw instance buffer
+struct INSTANCE {
     float3 left_top: POSITION0;
     float2 right_bottom: POSITION1;
     float2 tex_left_top: TEXCOORD0;
     float2 tex_right_bottom: TEXCOORD1;
     float4 col: COLOR0;
+    float4 bounds: TEXCOORD2;
+    float2 shadow_offset: TEXCOORD3;
+    float4 shadow_color: COLOR1;
+    float outline_width: TEXCOORD4;
+    float4 outline_color: COLOR2;
+    float4 glow_color: COLOR3;
+    // xy: texture coordinates per pixel, zw: at the origin
+    float4 fill_uv: TEXCOORD5;
+    // clockwise around the center of the quad, in radians
+    float rotation: TEXCOORD6;
+};
+
+struct VS_INPUT {
+#ifdef LEVEL_9_3
+    // feature level 9.3 lacks SV_VertexID, so the quad corners come from a second stream
+    float2 corner: CORNER;
+#else
+    uint vertex_id: SV_VertexID;
+#endif
+#ifdef RAW_INSTANCES
+    uint instance_id: SV_InstanceID;
+#else
+    INSTANCE instance;
+#endif
 };
 
+#ifdef RAW_INSTANCES
+// the instance buffer viewed as raw bytes, replacing the input layout on feature level 11.0
+ByteAddressBuffer instances: register(t0);
+
+INSTANCE load_instance(uint instance_id) {
+    uint offset = (instance_offset + instance_id) * instance_stride;
+    INSTANCE instance;
+    instance.left_top = asfloat(instances.Load3(offset));
+    instance.right_bottom = asfloat(instances.Load2(offset + 12));
+    instance.tex_left_top = asfloat(instances.Load2(offset + 20));
+    instance.tex_right_bottom = asfloat(instances.Load2(offset + 28));
+    instance.col = asfloat(instances.Load4(offset + 36));
+    instance.bounds = asfloat(instances.Load4(offset + 52));
+    instance.shadow_offset = asfloat(instances.Load2(offset + 68));
+    instance.shadow_color = asfloat(instances.Load4(offset + 76));
+    instance.outline_width = asfloat(instances.Load(offset + 92));
+    instance.outline_color = asfloat(instances.Load4(offset + 96));
+    instance.glow_color = asfloat(instances.Load4(offset + 112));
+    instance.fill_uv = asfloat(instances.Load4(offset + 128));
+    instance.rotation = asfloat(instances.Load(offset + 144));
+    return instance;
+}
+#endif
+
 struct PS_INPUT {
     float4 pos: SV_POSITION;
     float4 color: COLOR0;
-    float2 tex_pos: TEXCOORD0;
+    float4 shadow_color: COLOR1;
+    float4 outline_color: COLOR2;
+    float4 glow_color: COLOR3;
+    // xy: glyph, zw: shadow
+    float4 tex_pos: TEXCOORD0;
+    // xy: position, zw: outline width in texels
+    float4 section_pos: TEXCOORD1;
+    float4 bounds: TEXCOORD2;
+    float4 tex_bounds: TEXCOORD3;
+    float4 fill_uv: TEXCOORD4;
 };
 
-PS_INPUT main(VS_INPUT input) {
+PS_INPUT main(VS_INPUT vs_input) {
     PS_INPUT o;
 
-    float left = input.left_top.x;
-    float right = input.right_bottom.x;
-    float top = input.left_top.y;
-    float bottom = input.right_bottom.y;
-    float2 pos = float2(0.0f, 0.0f);
-    
-    switch (input.vertex_id) {
-        case 0:
-            pos = float2(left, top);
-            o.tex_pos = input.tex_left_top;
-            break;
-
-        case 1:
-            pos = float2(right, top);
-            o.tex_pos = float2(input.tex_right_bottom.x, input.tex_left_top.y);
-            break;
-
-        case 2:
-            pos = float2(left, bottom);
-            o.tex_pos = float2(input.tex_left_top.x, input.tex_right_bottom.y);
-            break;
-
-        case 3:
-            pos = float2(right, bottom);
-            o.tex_pos = input.tex_right_bottom;
-            break;
+#ifdef LEVEL_9_3
+    float2 corner = vs_input.corner;
+#else
+    // 0: left top, 1: right top, 2: left bottom, 3: right bottom
+    float2 corner = float2(vs_input.vertex_id & 1, vs_input.vertex_id >> 1);
+#endif
+#ifdef RAW_INSTANCES
+    INSTANCE input = load_instance(vs_input.instance_id);
+#else
+    INSTANCE input = vs_input.instance;
+#endif
+    input.tex_left_top.y *= cache_height_scale;
+    input.tex_right_bottom.y *= cache_height_scale;
+
+    float2 size = input.right_bottom - input.left_top.xy;
+    float2 tex_size = input.tex_right_bottom - input.tex_left_top;
+    float2 pos = lerp(input.left_top.xy, input.right_bottom, corner);
+    float2 tex_pos = lerp(input.tex_left_top, input.tex_right_bottom, corner);
+    float2 shadow_tex_pos = tex_pos;
+    float outline_width = input.outline_color.a > 0.0f ? input.outline_width
+        : halo_color.a > 0.0f ? halo_width : 0.0f;
+
+    if (input.shadow_color.a > 0.0f || outline_width > 0.0f) {
+        // grow the quad to cover the glyph, its outline and its shadow, extrapolating the
+        // texture coordinates
+        float2 shadow_offset = input.shadow_color.a > 0.0f ? input.shadow_offset : 0.0f;
+        float2 glyph_min = min(input.left_top.xy, input.right_bottom) - outline_width;
+        float2 glyph_max = max(input.left_top.xy, input.right_bottom) + outline_width;
+        pos = lerp(
+            glyph_min + min(shadow_offset, 0.0f),
+            glyph_max + max(shadow_offset, 0.0f),
+            corner
+        );
+        tex_pos = input.tex_left_top + tex_size * (pos - input.left_top.xy) / size;
+        shadow_tex_pos = input.tex_left_top
+            + tex_size * (pos - shadow_offset - input.left_top.xy) / size;
+    }
+
+    if (input.rotation != 0.0f) {
+        float2 center = (input.left_top.xy + input.right_bottom) * 0.5f;
+        float2 offset = pos - center;
+        float s, c;
+        sincos(input.rotation, s, c);
+        pos = center + float2(c * offset.x - s * offset.y, s * offset.x + c * offset.y);
     }
 
     o.pos = mul(ProjectionMatrix, float4(pos, input.left_top.z, 1.0f));
     o.color = input.col;
+    o.shadow_color = input.shadow_color;
+    o.outline_color = input.outline_color;
+    o.glow_color = input.glow_color;
+    o.tex_pos = float4(tex_pos, shadow_tex_pos);
+    o.section_pos = float4(pos, abs(tex_size / size) * outline_width);
+    o.bounds = input.bounds;
+    o.fill_uv = input.fill_uv;
+    o.tex_bounds = float4(
+        min(input.tex_left_top, input.tex_right_bottom),
+        max(input.tex_left_top, input.tex_right_bottom)
+    );
     return o;
 }
@@ -0,0 +1,982 @@
+//! Shaping of complex scripts, enabled with the `shaping` feature.
+//!
+//! Arabic text is shaped through the presentation forms of the font. Indic syllables are shaped
+//! with the `GSUB` table of the font, which forms their conjuncts, half forms and reph, and their
+//! pre-base vowel signs are reordered before the syllable.
+use std::ops::Range;
+
+use glyph_brush::ab_glyph::{Font, ScaleFont};
+use glyph_brush::{SectionGlyph, ToSectionText};
+use ttf_parser::gsub::{SingleSubstitution, SubstitutionSubtable};
+use ttf_parser::opentype_layout::{LanguageSystem, LayoutTable, Lookup};
+use ttf_parser::{GlyphId, Tag};
+
+use crate::layout;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Joining {
+    /// Doesn't join its neighbours, e.g. hamza.
+    None,
+    /// Only joins the preceding letter, e.g. alef.
+    Right,
+    /// Joins both neighbours.
+    Dual,
+}
+
+/// The isolated presentation form and joining type of the letters from U+0621 to U+064A. The
+/// final, initial and medial forms follow the isolated one, initial and medial forms only exist
+/// for dual-joining letters.
+const FORMS: [(u16, Joining); 42] = [
+    (0xFE80, Joining::None),  // hamza
+    (0xFE81, Joining::Right), // alef with madda above
+    (0xFE83, Joining::Right), // alef with hamza above
+    (0xFE85, Joining::Right), // waw with hamza above
+    (0xFE87, Joining::Right), // alef with hamza below
+    (0xFE89, Joining::Dual),  // yeh with hamza above
+    (0xFE8D, Joining::Right), // alef
+    (0xFE8F, Joining::Dual),  // beh
+    (0xFE93, Joining::Right), // teh marbuta
+    (0xFE95, Joining::Dual),  // teh
+    (0xFE99, Joining::Dual),  // theh
+    (0xFE9D, Joining::Dual),  // jeem
+    (0xFEA1, Joining::Dual),  // hah
+    (0xFEA5, Joining::Dual),  // khah
+    (0xFEA9, Joining::Right), // dal
+    (0xFEAB, Joining::Right), // thal
+    (0xFEAD, Joining::Right), // reh
+    (0xFEAF, Joining::Right), // zain
+    (0xFEB1, Joining::Dual),  // seen
+    (0xFEB5, Joining::Dual),  // sheen
+    (0xFEB9, Joining::Dual),  // sad
+    (0xFEBD, Joining::Dual),  // dad
+    (0xFEC1, Joining::Dual),  // tah
+    (0xFEC5, Joining::Dual),  // zah
+    (0xFEC9, Joining::Dual),  // ain
+    (0xFECD, Joining::Dual),  // ghain
+    (0, Joining::None),
+    (0, Joining::None),
+    (0, Joining::None),
+    (0, Joining::None),
+    (0, Joining::None),
+    (0, Joining::Dual),       // tatweel, which has no presentation forms
+    (0xFED1, Joining::Dual),  // feh
+    (0xFED5, Joining::Dual),  // qaf
+    (0xFED9, Joining::Dual),  // kaf
+    (0xFEDD, Joining::Dual),  // lam
+    (0xFEE1, Joining::Dual),  // meem
+    (0xFEE5, Joining::Dual),  // noon
+    (0xFEE9, Joining::Dual),  // heh
+    (0xFEED, Joining::Right), // waw
+    (0xFEEF, Joining::Right), // alef maksura
+    (0xFEF1, Joining::Dual),  // yeh
+];
+
+const LAM: char = '\u{644}';
+
+/// Returns `true` if the text contains Arabic letters or Indic text that need shaping.
+pub(crate) fn needs_shaping(text: &str) -> bool {
+    text.chars()
+        .any(|character| forms(character).is_some() || block_of(character).is_some())
+}
+
+/// Shapes the Arabic and Indic text of the laid out `glyphs`, see `shape_arabic` and
+/// `shape_indic`.
+pub(crate) fn shape<F, S>(
+    fonts: &[F],
+    sections: &[S],
+    glyphs: &mut Vec<SectionGlyph>,
+    realign: impl Fn(&mut [SectionGlyph], f32),
+) where
+    F: Font,
+    S: ToSectionText,
+{
+    shape_arabic(fonts, sections, glyphs, &realign);
+    shape_indic(fonts, sections, glyphs, &realign);
+}
+
+/// Replaces the Arabic letters of the laid out `glyphs` with the presentation forms joining
+/// their neighbours, including lam-alef ligatures, and lays out runs of Arabic text from right
+/// to left. `realign` is called with every line that changed in width.
+///
+/// Letters are only replaced if the font has a glyph for their presentation form.
+pub(crate) fn shape_arabic<F, S>(
+    fonts: &[F],
+    sections: &[S],
+    glyphs: &mut Vec<SectionGlyph>,
+    realign: impl Fn(&mut [SectionGlyph], f32),
+) where
+    F: Font,
+    S: ToSectionText,
+{
+    let character = |glyph: &SectionGlyph| {
+        sections[glyph.section_index].to_section_text().text[glyph.byte_index..]
+            .chars()
+            .next()
+            .unwrap_or_default()
+    };
+    // the alefs merged into a lam-alef ligature, which are removed afterwards
+    let mut merged = Vec::new();
+    for line in layout::lines(glyphs) {
+        let characters: Vec<char> = line.iter().map(character).collect();
+        let joining: Vec<Option<Joining>> = characters
+            .iter()
+            .map(|&character| match forms(character) {
+                _ if is_transparent(character) => None,
+                Some((_, joining)) => Some(joining),
+                None => Some(Joining::None),
+            })
+            .collect();
+        // the nearest neighbours that aren't transparent marks
+        let len = line.len();
+        let previous = |index: usize| (0..index).rev().find(|&i| joining[i].is_some());
+        let next = |index: usize| (index + 1..len).find(|&i| joining[i].is_some());
+
+        let mut offset = 0.0;
+        // the alef merged into the next ligature, with the gap between it and its lam
+        let mut skip = None;
+        let mut removed = vec![false; len];
+        for index in 0..len {
+            let advance = |glyph: &SectionGlyph| advance_of(fonts, glyph);
+            let x = line[index].glyph.position.x;
+            line[index].glyph.position.x += offset;
+            if let Some((_, gap)) = skip.filter(|&(alef, _)| alef == index) {
+                // the letter spacing and kerning between lam and alef go with the alef
+                offset -= advance(&line[index]) + gap;
+                removed[index] = true;
+                merged.push((line[index].section_index, line[index].byte_index));
+                continue;
+            }
+            let (base, joins) = match forms(characters[index]) {
+                Some((base, joins)) if base != 0 && !is_transparent(characters[index]) => {
+                    (base, joins)
+                }
+                _ => continue,
+            };
+            let joins_previous = joins != Joining::None
+                && previous(index).is_some_and(|i| joining[i] == Some(Joining::Dual));
+            let following = next(index);
+            let ligature = following.and_then(|i| lam_alef(characters[index], characters[i]));
+            let joins_next = joins == Joining::Dual
+                && ligature.is_none()
+                && following.is_some_and(|i| joining[i] != Some(Joining::None));
+
+            let form = match ligature {
+                Some(ligature) => ligature + joins_previous as u32,
+                None => {
+                    base as u32
+                        + match (joins_previous, joins_next) {
+                            (false, false) => 0,
+                            (true, false) => 1,
+                            (false, true) => 2,
+                            (true, true) => 3,
+                        }
+                }
+            };
+            let font = &fonts[line[index].font_id.0];
+            let glyph_id = match std::char::from_u32(form).map(|form| font.glyph_id(form)) {
+                Some(glyph_id) if glyph_id.0 != 0 => glyph_id,
+                _ => continue,
+            };
+            let previous_advance = advance(&line[index]);
+            line[index].glyph.id = glyph_id;
+            offset += advance(&line[index]) - previous_advance;
+            if let (Some(_), Some(alef)) = (ligature, following) {
+                skip = Some((alef, line[alef].glyph.position.x - x - previous_advance));
+            }
+        }
+        reverse_runs(line, &characters, &removed, |glyph| {
+            advance_of(fonts, glyph)
+        });
+        if offset != 0.0 {
+            realign(line, offset);
+        }
+    }
+    if !merged.is_empty() {
+        glyphs.retain(|glyph| !merged.contains(&(glyph.section_index, glyph.byte_index)));
+    }
+}
+
+/// The `GSUB` script tags of the Indic blocks from Devanagari to Malayalam, the tag of the
+/// current shaping model followed by the one of the old model.
+const SCRIPTS: [(&[u8; 4], &[u8; 4]); 9] = [
+    (b"dev2", b"deva"),
+    (b"bng2", b"beng"),
+    (b"gur2", b"guru"),
+    (b"gjr2", b"gujr"),
+    (b"ory2", b"orya"),
+    (b"tml2", b"taml"),
+    (b"tel2", b"telu"),
+    (b"knd2", b"knda"),
+    (b"mlm2", b"mlym"),
+];
+
+/// Shapes the Indic syllables of the laid out `glyphs` with the `GSUB` table of their font,
+/// forming conjuncts, half forms, reph and the other substitutions of the script, and moves
+/// pre-base vowel signs before and reph after their syllable. `realign` is called with every
+/// line that changed in width.
+///
+/// Syllables drawn with a font without a `GSUB` table for their script only have their vowel
+/// signs reordered.
+pub(crate) fn shape_indic<F, S>(
+    fonts: &[F],
+    sections: &[S],
+    glyphs: &mut Vec<SectionGlyph>,
+    realign: impl Fn(&mut [SectionGlyph], f32),
+) where
+    F: Font,
+    S: ToSectionText,
+{
+    let character = |glyph: &SectionGlyph| {
+        sections[glyph.section_index].to_section_text().text[glyph.byte_index..]
+            .chars()
+            .next()
+            .unwrap_or_default()
+    };
+    // the `GSUB` table of every font, parsed when a syllable is drawn with it
+    let mut tables = vec![None; fonts.len()];
+    let mut shaped = Vec::with_capacity(glyphs.len());
+    for line in layout::lines(glyphs) {
+        let characters: Vec<char> = line.iter().map(character).collect();
+        let line_start = shaped.len();
+        let mut offset = 0.0;
+        let mut index = 0;
+        while index < line.len() {
+            let end = match syllable_end(&characters, line, index) {
+                Some(end) => end,
+                None => {
+                    line[index].glyph.position.x += offset;
+                    shaped.push(line[index].clone());
+                    index += 1;
+                    continue;
+                }
+            };
+            let syllable = &mut line[index..end];
+            for glyph in syllable.iter_mut() {
+                glyph.glyph.position.x += offset;
+            }
+            let font_id = syllable[0].font_id.0;
+            let table = *tables[font_id].get_or_insert_with(|| {
+                let face = ttf_parser::Face::parse(fonts[font_id].font_data(), 0).ok()?;
+                face.tables().gsub
+            });
+            let syllable_glyphs = shape_syllable(fonts, table, &characters[index..end], syllable);
+            offset += right_of(fonts, &syllable_glyphs) - right_of(fonts, syllable);
+            shaped.extend(syllable_glyphs);
+            index = end;
+        }
+        if offset != 0.0 {
+            realign(&mut shaped[line_start..], offset);
+        }
+    }
+    *glyphs = shaped;
+}
+
+/// Returns the end of the syllable starting at `start`, a consonant followed by any number of
+/// consonants joined with a virama and the signs of the last one, or `None` if no syllable
+/// starts there. The characters of a syllable belong to the same block and are drawn with the
+/// same font.
+fn syllable_end(characters: &[char], line: &[SectionGlyph], start: usize) -> Option<usize> {
+    if !is_consonant(characters[start]) {
+        return None;
+    }
+    let block = block_of(characters[start]);
+    let joins = |index: usize| {
+        index < characters.len()
+            && block_of(characters[index]) == block
+            && line[index].font_id == line[start].font_id
+    };
+    let offset = |index: usize| {
+        Some(index)
+            .filter(|&i| joins(i))
+            .and_then(|i| indic_offset(characters[i]))
+    };
+    let mut end = start + 1;
+    loop {
+        if offset(end) == Some(NUKTA) {
+            end += 1;
+        }
+        if offset(end) == Some(VIRAMA) && joins(end + 1) && is_consonant(characters[end + 1]) {
+            end += 2;
+        } else {
+            break;
+        }
+    }
+    while matches!(
+        offset(end),
+        Some(0x01..=0x03) | Some(NUKTA) | Some(0x3E..=0x4D) | Some(0x51..=0x57) | Some(0x62..=0x63)
+    ) {
+        end += 1;
+    }
+    Some(end)
+}
+
+/// Substitutes the glyphs of the Indic `syllable` with the lookups of its script in the `GSUB`
+/// `table` of its font and lays them out in visual order from the position of its first
+/// glyph.
+///
+/// The glyphs are returned in the order of their characters, glyphs merged into a ligature are
+/// dropped.
+fn shape_syllable<F: Font>(
+    fonts: &[F],
+    table: Option<LayoutTable<'_>>,
+    characters: &[char],
+    syllable: &[SectionGlyph],
+) -> Vec<SectionGlyph> {
+    // every glyph with the index of the character it was substituted for
+    let mut run: Vec<(GlyphId, usize)> = (syllable.iter().enumerate())
+        .map(|(source, glyph)| (GlyphId(glyph.glyph.id.0), source))
+        .collect();
+    let len = characters.len();
+    // the base consonant is the last one, unless it is a ra drawn below the one before it
+    let consonants: Vec<usize> = (0..len).filter(|&i| is_consonant(characters[i])).collect();
+    let mut base = consonants[consonants.len() - 1];
+    if consonants.len() > 1 && indic_offset(characters[base]) == Some(RA) {
+        base = consonants[consonants.len() - 2];
+    }
+    // a ra joined by a virama to the following consonant may be drawn as a reph above the end
+    // of the syllable
+    let mut reph = false;
+    if let Some(substitutions) = table.and_then(|table| Substitutions::new(table, characters[0])) {
+        let apply = |feature: &[u8; 4], run: &mut Vec<(GlyphId, usize)>, range: Range<usize>| {
+            substitutions.apply(feature, run, range)
+        };
+        apply(b"locl", &mut run, 0..len);
+        apply(b"ccmp", &mut run, 0..len);
+        apply(b"nukt", &mut run, 0..len);
+        apply(b"akhn", &mut run, 0..len);
+        if base >= 2
+            && indic_offset(characters[0]) == Some(RA)
+            && indic_offset(characters[1]) == Some(VIRAMA)
+        {
+            apply(b"rphf", &mut run, 0..2);
+            reph = !run.iter().any(|&(_, source)| source == 1);
+        }
+        let pre_base = if reph { 2..base } else { 0..base };
+        apply(b"rkrf", &mut run, 0..len);
+        apply(b"pref", &mut run, base + 1..len);
+        apply(b"blwf", &mut run, base + 1..len);
+        apply(b"abvf", &mut run, base + 1..len);
+        apply(b"half", &mut run, pre_base);
+        apply(b"pstf", &mut run, base + 1..len);
+        apply(b"vatu", &mut run, 0..len);
+        apply(b"cjct", &mut run, 0..len);
+        for feature in &[b"pres", b"abvs", b"blws", b"psts", b"haln"] {
+            apply(feature, &mut run, 0..len);
+        }
+    }
+    if reph {
+        // reph is drawn after the glyphs of the syllable, before the marks of its nasalization
+        let reph = run.remove(0);
+        let marks = (run.iter().rev())
+            .take_while(|&&(_, source)| {
+                matches!(indic_offset(characters[source]), Some(0x01..=0x03))
+            })
+            .count();
+        run.insert(run.len() - marks, reph);
+    }
+    if let Some(index) = run
+        .iter()
+        .position(|&(_, source)| is_pre_base(characters[source]))
+    {
+        let sign = run.remove(index);
+        run.insert(0, sign);
+    }
+    let mut x = syllable[0].glyph.position.x;
+    let mut glyphs: Vec<SectionGlyph> = (run.into_iter())
+        .map(|(id, source)| {
+            let mut glyph = syllable[source].clone();
+            glyph.glyph.id.0 = id.0;
+            glyph.glyph.position.x = x;
+            x += advance_of(fonts, &glyph);
+            glyph
+        })
+        .collect();
+    glyphs.sort_by_key(|glyph| (glyph.section_index, glyph.byte_index));
+    glyphs
+}
+
+/// The lookups of the default language system of an Indic script in a `GSUB` table.
+struct Substitutions<'a> {
+    table: LayoutTable<'a>,
+    system: LanguageSystem<'a>,
+}
+
+impl<'a> Substitutions<'a> {
+    /// Returns the substitutions of the script of `character`, preferring the current shaping
+    /// model.
+    fn new(table: LayoutTable<'a>, character: char) -> Option<Self> {
+        let (tag, old_tag) = SCRIPTS[block_of(character)?];
+        let script = (table.scripts.find(Tag::from_bytes(tag)))
+            .or_else(|| table.scripts.find(Tag::from_bytes(old_tag)))?;
+        Some(Substitutions {
+            table,
+            system: script.default_language?,
+        })
+    }
+
+    /// Applies the lookups of `feature` to the glyphs of `run` substituted for the characters
+    /// in `range`.
+    fn apply(&self, feature: &[u8; 4], run: &mut Vec<(GlyphId, usize)>, range: Range<usize>) {
+        let tag = Tag::from_bytes(feature);
+        let features = (self.system.feature_indices.into_iter())
+            .chain(self.system.required_feature)
+            .filter_map(|index| self.table.features.get(index))
+            .filter(|feature| feature.tag == tag);
+        for feature in features {
+            for index in feature.lookup_indices {
+                if let Some(lookup) = self.table.lookups.get(index) {
+                    apply_lookup(&lookup, run, &range);
+                }
+            }
+        }
+    }
+}
+
+/// Applies the single, multiple and ligature substitutions of `lookup` to the glyphs of `run`
+/// substituted for the characters in `range`.
+fn apply_lookup(lookup: &Lookup<'_>, run: &mut Vec<(GlyphId, usize)>, range: &Range<usize>) {
+    let mut index = 0;
+    while index < run.len() {
+        let substituted = if range.contains(&run[index].1) {
+            (lookup.subtables.into_iter::<SubstitutionSubtable<'_>>())
+                .find_map(|subtable| substitute(&subtable, run, index, range))
+        } else {
+            None
+        };
+        // the substituted glyphs aren't substituted again by the same lookup
+        index += substituted.unwrap_or(1);
+    }
+}
+
+/// Substitutes the glyph of `run` at `index` with `subtable`, returning the number of glyphs it
+/// was replaced by, or `None` if the subtable doesn't apply. The components of a ligature have
+/// to be substituted for characters in `range`.
+fn substitute(
+    subtable: &SubstitutionSubtable<'_>,
+    run: &mut Vec<(GlyphId, usize)>,
+    index: usize,
+    range: &Range<usize>,
+) -> Option<usize> {
+    let (glyph, source) = run[index];
+    match subtable {
+        SubstitutionSubtable::Single(SingleSubstitution::Format1 { coverage, delta }) => {
+            coverage.get(glyph)?;
+            run[index].0 = GlyphId((glyph.0 as i32 + *delta as i32) as u16);
+            Some(1)
+        }
+        SubstitutionSubtable::Single(SingleSubstitution::Format2 {
+            coverage,
+            substitutes,
+        }) => {
+            run[index].0 = substitutes.get(coverage.get(glyph)?)?;
+            Some(1)
+        }
+        SubstitutionSubtable::Multiple(multiple) => {
+            let sequence = multiple.sequences.get(multiple.coverage.get(glyph)?)?;
+            let substitutes = sequence.substitutes.into_iter().map(|id| (id, source));
+            run.splice(index..=index, substitutes);
+            Some(sequence.substitutes.len() as usize)
+        }
+        SubstitutionSubtable::Ligature(ligatures) => {
+            let set = ligatures
+                .ligature_sets
+                .get(ligatures.coverage.get(glyph)?)?;
+            let components = &run[index + 1..];
+            let ligature = set.into_iter().find(|ligature| {
+                ligature.components.len() as usize <= components.len()
+                    && (ligature.components.into_iter().zip(components))
+                        .all(|(id, &(glyph, source))| id == glyph && range.contains(&source))
+            })?;
+            let end = index + ligature.components.len() as usize;
+            run.splice(index..=end, std::iter::once((ligature.glyph, source)));
+            Some(1)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the right edge of the last glyph.
+fn right_of<F: Font>(fonts: &[F], glyphs: &[SectionGlyph]) -> f32 {
+    (glyphs.iter())
+        .map(|glyph| glyph.glyph.position.x + advance_of(fonts, glyph))
+        .fold(f32::MIN, f32::max)
+}
+
+const NUKTA: u32 = 0x3C;
+const VIRAMA: u32 = 0x4D;
+const RA: u32 = 0x30;
+
+/// Returns the index of the Indic block of a character, from Devanagari to Malayalam.
+fn block_of(character: char) -> Option<usize> {
+    match character as u32 {
+        code @ 0x0900..=0x0D7F => Some((code - 0x0900) as usize / 0x80),
+        _ => None,
+    }
+}
+
+/// Returns the offset of a character within its Indic block, the blocks from Devanagari to
+/// Malayalam share the layout of ISCII.
+fn indic_offset(character: char) -> Option<u32> {
+    match character as u32 {
+        code @ 0x0900..=0x0D7F => Some(code % 0x80),
+        _ => None,
+    }
+}
+
+fn is_consonant(character: char) -> bool {
+    matches!(
+        indic_offset(character),
+        Some(0x15..=0x39) | Some(0x58..=0x5F)
+    )
+}
+
+/// Returns `true` for the vowel signs drawn to the left of the consonant cluster they follow.
+fn is_pre_base(character: char) -> bool {
+    matches!(
+        character as u32,
+        0x093F // devanagari i
+            | 0x094E // devanagari prishthamatra e
+            | 0x09BF | 0x09C7 | 0x09C8 // bengali i, e, ai
+            | 0x0A3F // gurmukhi i
+            | 0x0ABF // gujarati i
+            | 0x0B47 // oriya e
+            | 0x0BC6..=0x0BC8 // tamil e, ee, ai
+            | 0x0D46..=0x0D48 // malayalam e, ee, ai
+    )
+}
+
+fn advance_of<F: Font>(fonts: &[F], glyph: &SectionGlyph) -> f32 {
+    fonts[glyph.font_id.0]
+        .as_scaled(glyph.glyph.scale)
+        .h_advance(glyph.glyph.id)
+}
+
+/// Mirrors the positions of every run of Arabic glyphs within the space the run occupies, so
+/// it reads from right to left. Spaces and marks between Arabic letters belong to the run,
+/// `removed` glyphs don't take up space.
+fn reverse_runs(
+    line: &mut [SectionGlyph],
+    characters: &[char],
+    removed: &[bool],
+    advance: impl Fn(&SectionGlyph) -> f32,
+) {
+    let is_arabic = |character: char| matches!(character as u32, 0x0600..=0x06FF);
+    let mut index = 0;
+    while index < line.len() {
+        if !is_arabic(characters[index]) {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        let mut end = index + 1;
+        // extends the run over spaces followed by more Arabic text
+        while end < line.len() {
+            if is_arabic(characters[end]) {
+                end += 1;
+            } else if characters[end].is_whitespace() {
+                match (end..line.len()).find(|&i| !characters[i].is_whitespace()) {
+                    Some(i) if is_arabic(characters[i]) => end = i,
+                    _ => break,
+                }
+            } else {
+                break;
+            }
+        }
+        let run = &mut line[start..end];
+        let left = run[0].glyph.position.x;
+        let right = (run.iter().zip(&removed[start..end]))
+            .filter(|(_, &removed)| !removed)
+            .map(|(glyph, _)| glyph.glyph.position.x + advance(glyph))
+            .fold(left, f32::max);
+        for glyph in run.iter_mut() {
+            let width = advance(glyph);
+            glyph.glyph.position.x = left + right - glyph.glyph.position.x - width;
+        }
+        index = end;
+    }
+}
+
+fn forms(character: char) -> Option<(u16, Joining)> {
+    match character as u32 {
+        code @ 0x0621..=0x064A => Some(FORMS[(code - 0x0621) as usize]),
+        _ => None,
+    }
+}
+
+/// Returns the isolated form of the ligature of a lam followed by an alef.
+fn lam_alef(lam: char, alef: char) -> Option<u32> {
+    if lam != LAM {
+        return None;
+    }
+    match alef {
+        '\u{622}' => Some(0xFEF5),
+        '\u{623}' => Some(0xFEF7),
+        '\u{625}' => Some(0xFEF9),
+        '\u{627}' => Some(0xFEFB),
+        _ => None,
+    }
+}
+
+/// Returns `true` for the marks that are skipped when finding the letters a letter joins.
+fn is_transparent(character: char) -> bool {
+    matches!(
+        character as u32,
+        0x0610..=0x061A
+            | 0x064B..=0x065F
+            | 0x0670
+            | 0x06D6..=0x06DC
+            | 0x06DF..=0x06E4
+            | 0x06E7..=0x06E8
+            | 0x06EA..=0x06ED
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use glyph_brush::ab_glyph::{point, v2, CodepointIdIter, GlyphId, Outline};
+    use glyph_brush::{FontId, SectionText};
+
+    use super::*;
+
+    const SCALE: f32 = 20.0;
+    /// The advance of every glyph at `SCALE`.
+    const ADVANCE: f32 = 10.0;
+
+    /// A font with a glyph for every Arabic and Indic character and presentation form, whose id
+    /// is its code point, and an advance of half the height. The data of its tables is only
+    /// read for the `GSUB` table.
+    #[derive(Default)]
+    struct TestFont(Vec<u8>);
+
+    impl Font for TestFont {
+        fn units_per_em(&self) -> Option<f32> {
+            Some(1000.0)
+        }
+
+        fn ascent_unscaled(&self) -> f32 {
+            800.0
+        }
+
+        fn descent_unscaled(&self) -> f32 {
+            -200.0
+        }
+
+        fn line_gap_unscaled(&self) -> f32 {
+            0.0
+        }
+
+        fn glyph_id(&self, character: char) -> GlyphId {
+            match character as u32 {
+                code @ 0x0600..=0x0DFF | code @ 0xFE70..=0xFEFF => GlyphId(code as u16),
+                _ => GlyphId(character as u16 & 0xFF),
+            }
+        }
+
+        fn h_advance_unscaled(&self, _: GlyphId) -> f32 {
+            500.0
+        }
+
+        fn h_side_bearing_unscaled(&self, _: GlyphId) -> f32 {
+            0.0
+        }
+
+        fn v_advance_unscaled(&self, _: GlyphId) -> f32 {
+            1000.0
+        }
+
+        fn v_side_bearing_unscaled(&self, _: GlyphId) -> f32 {
+            0.0
+        }
+
+        fn kern_unscaled(&self, _: GlyphId, _: GlyphId) -> f32 {
+            0.0
+        }
+
+        fn outline(&self, _: GlyphId) -> Option<Outline> {
+            None
+        }
+
+        fn glyph_count(&self) -> usize {
+            0x10000
+        }
+
+        fn codepoint_ids(&self) -> CodepointIdIter<'_> {
+            unimplemented!("not used by shaping")
+        }
+
+        fn glyph_raster_image2(&self, _: GlyphId, _: u16) -> Option<v2::GlyphImage<'_>> {
+            None
+        }
+
+        fn font_data(&self) -> &[u8] {
+            &self.0
+        }
+    }
+
+    /// Lays out `text` on a single line starting at the origin, a glyph per character.
+    fn lay_out(text: &str) -> Vec<SectionGlyph> {
+        lay_out_spaced(text, 0.0)
+    }
+
+    /// Lays out `text` like `lay_out` with `spacing` pixels of letter spacing after every glyph.
+    fn lay_out_spaced(text: &str, spacing: f32) -> Vec<SectionGlyph> {
+        (text.char_indices().enumerate())
+            .map(|(index, (byte_index, character))| SectionGlyph {
+                section_index: 0,
+                byte_index,
+                glyph: TestFont::default()
+                    .glyph_id(character)
+                    .with_scale_and_position(SCALE, point(index as f32 * (ADVANCE + spacing), 0.0)),
+                font_id: FontId(0),
+            })
+            .collect()
+    }
+
+    /// Shapes the Arabic `text`, returning the glyph ids and positions and the widths the line
+    /// was realigned by.
+    fn shape_text(text: &str) -> (Vec<u32>, Vec<f32>, Vec<f32>) {
+        shape_spaced(text, 0.0)
+    }
+
+    /// Shapes the Arabic `text` laid out with `spacing` pixels of letter spacing.
+    fn shape_spaced(text: &str, spacing: f32) -> (Vec<u32>, Vec<f32>, Vec<f32>) {
+        let sections = [SectionText {
+            text,
+            ..SectionText::default()
+        }];
+        let mut glyphs = lay_out_spaced(text, spacing);
+        let realigned = RefCell::new(Vec::new());
+        shape_arabic(
+            &[TestFont::default()],
+            &sections,
+            &mut glyphs,
+            |_, width| realigned.borrow_mut().push(width),
+        );
+        let ids = glyphs.iter().map(|glyph| glyph.glyph.id.0 as u32).collect();
+        let positions = (glyphs.iter())
+            .map(|glyph| glyph.glyph.position.x)
+            .collect();
+        (ids, positions, realigned.into_inner())
+    }
+
+    #[test]
+    fn joining_forms() {
+        // beh joins both neighbours
+        let (ids, ..) = shape_text("\u{628}\u{628}\u{628}");
+        assert_eq!(ids, [0xFE91, 0xFE92, 0xFE90]);
+        // dal only joins the preceding letter, hamza doesn't join at all
+        let (ids, ..) = shape_text("\u{628}\u{62F}\u{628}\u{621}\u{628}");
+        assert_eq!(ids, [0xFE91, 0xFEAA, 0xFE8F, 0xFE80, 0xFE8F]);
+        // marks are skipped when finding the letters a letter joins
+        let (ids, ..) = shape_text("\u{628}\u{64E}\u{628}");
+        assert_eq!(ids, [0xFE91, 0x64E, 0xFE90]);
+    }
+
+    #[test]
+    fn lam_alef_ligature() {
+        let (ids, positions, realigned) = shape_text("\u{644}\u{627}");
+        assert_eq!(ids, [0xFEFB]);
+        assert_eq!(positions, [0.0]);
+        assert_eq!(realigned, [-ADVANCE]);
+
+        let (ids, positions, realigned) = shape_text("\u{628}\u{644}\u{627}");
+        assert_eq!(ids, [0xFE91, 0xFEFC]);
+        assert_eq!(positions, [ADVANCE, 0.0]);
+        assert_eq!(realigned, [-ADVANCE]);
+    }
+
+    #[test]
+    fn reversed_runs() {
+        // the Arabic run includes the space between its letters but not the Latin text
+        let (ids, positions, realigned) = shape_text("a\u{628} \u{62F}b");
+        assert_eq!(ids[1..4], [0xFE8F, ' ' as u32, 0xFEA9]);
+        assert_eq!(positions, [0.0, 30.0, 20.0, 10.0, 40.0]);
+        assert!(realigned.is_empty());
+    }
+
+    #[test]
+    fn letter_spacing() {
+        // the mirrored letters keep the spacing between them instead of overlapping
+        let (_, positions, realigned) = shape_spaced("\u{628}\u{628}\u{628}", 4.0);
+        assert_eq!(positions, [28.0, 14.0, 0.0]);
+        assert!(realigned.is_empty());
+        // the alef merged into a ligature takes the spacing between it and the lam along
+        let (ids, positions, realigned) = shape_spaced("\u{628}\u{644}\u{627}", 4.0);
+        assert_eq!(ids, [0xFE91, 0xFEFC]);
+        assert_eq!(positions, [14.0, 0.0]);
+        assert_eq!(realigned, [-14.0]);
+        let (_, positions, _) = shape_spaced("a\u{628}\u{628}b", 4.0);
+        assert_eq!(positions, [0.0, 28.0, 14.0, 42.0]);
+    }
+
+    #[test]
+    fn reversed_runs_skip_removed_glyphs() {
+        let characters = ['\u{628}', '\u{627}', '\u{628}'];
+        let mut line = lay_out("\u{628}\u{627}\u{628}");
+        // the removed glyph doesn't take up space, so the run is moved back into place
+        line[2].glyph.position.x -= ADVANCE;
+        reverse_runs(&mut line, &characters, &[false, true, false], |_| ADVANCE);
+        let positions: Vec<_> = line.iter().map(|glyph| glyph.glyph.position.x).collect();
+        assert_eq!(positions, [10.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn syllables() {
+        let (ka, ssa, virama, nukta) = ('\u{915}', '\u{937}', '\u{94D}', '\u{93C}');
+        let end = |characters: &[char], start: usize| {
+            let text: String = characters.iter().collect();
+            syllable_end(characters, &lay_out(&text), start)
+        };
+        assert_eq!(end(&[ka], 0), Some(1));
+        assert_eq!(end(&['a', ka, virama, ssa, 'b'], 1), Some(4));
+        assert_eq!(end(&[ka, nukta, virama, ssa, '\u{93F}'], 0), Some(5));
+        // a trailing virama belongs to the syllable, a following consonant doesn't
+        assert_eq!(end(&[ka, virama], 0), Some(2));
+        assert_eq!(end(&[ka, '\u{93F}', ka], 0), Some(2));
+        // the signs of another script don't
+        assert_eq!(end(&[ka, '\u{9BF}'], 0), Some(1));
+        assert_eq!(end(&['a'], 0), None);
+        assert_eq!(end(&[virama, ka], 0), None);
+    }
+
+    /// Shapes the Indic `text` drawn with `font`, returning the glyph ids and positions and the
+    /// widths the line was realigned by.
+    fn shape_indic_text(font: TestFont, text: &str) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
+        let sections = [SectionText {
+            text,
+            ..SectionText::default()
+        }];
+        let mut glyphs = lay_out(text);
+        let realigned = RefCell::new(Vec::new());
+        shape_indic(&[font], &sections, &mut glyphs, |_, width| {
+            realigned.borrow_mut().push(width)
+        });
+        let ids = glyphs.iter().map(|glyph| glyph.glyph.id.0).collect();
+        let positions = (glyphs.iter())
+            .map(|glyph| glyph.glyph.position.x)
+            .collect();
+        (ids, positions, realigned.into_inner())
+    }
+
+    #[test]
+    fn pre_base_vowel_signs() {
+        assert!(needs_shaping("\u{915}\u{93F}"));
+        assert!(needs_shaping("\u{915}\u{94D}\u{937}"));
+        assert!(!needs_shaping("ka"));
+
+        // the vowel sign i moves before the conjunct ka-ssa, which the font doesn't form
+        let text = "a\u{915}\u{94D}\u{937}\u{93F}";
+        let (ids, positions, realigned) = shape_indic_text(TestFont::default(), text);
+        assert_eq!(ids, [0x61, 0x915, 0x94D, 0x937, 0x93F]);
+        assert_eq!(positions, [0.0, 20.0, 30.0, 40.0, 10.0]);
+        assert!(realigned.is_empty());
+    }
+
+    /// Builds a font with only the tables ttf-parser requires and a `GSUB` table for Devanagari
+    /// with a feature for each of `ligatures`, which substitutes the characters with a ligature
+    /// glyph.
+    fn font_with_ligatures(ligatures: &[(&[u8; 4], &[char], u16)]) -> Vec<u8> {
+        fn push(data: &mut Vec<u8>, words: &[u16]) {
+            for word in words {
+                data.extend_from_slice(&word.to_be_bytes());
+            }
+        }
+        let count = ligatures.len() as u16;
+        let script_list = 10;
+        let feature_list = script_list + 18 + 2 * count;
+        let lookup_list = feature_list + 2 + 12 * count;
+        let mut gsub = Vec::new();
+        push(&mut gsub, &[1, 0, script_list, feature_list, lookup_list]);
+        // the script with a default language system containing every feature
+        push(&mut gsub, &[1]);
+        gsub.extend_from_slice(b"dev2");
+        push(&mut gsub, &[8, 4, 0, 0, 0xFFFF, count]);
+        push(&mut gsub, &(0..count).collect::<Vec<_>>());
+        // the features, each with the lookup of the same index
+        push(&mut gsub, &[count]);
+        for (index, (tag, ..)) in ligatures.iter().enumerate() {
+            gsub.extend_from_slice(*tag);
+            push(&mut gsub, &[2 + 6 * count + 6 * index as u16]);
+        }
+        for index in 0..count {
+            push(&mut gsub, &[0, 1, index]);
+        }
+        // the lookups, each with a ligature subtable of one ligature
+        let lookups: Vec<Vec<u16>> = (ligatures.iter())
+            .map(|&(_, characters, ligature)| {
+                let mut lookup = vec![4, 0, 1, 8, 1, 8, 1, 14, 1, 1];
+                lookup.extend(&[characters[0] as u16, 1, 4, ligature]);
+                lookup.push(characters.len() as u16);
+                lookup.extend(characters[1..].iter().map(|&character| character as u16));
+                lookup
+            })
+            .collect();
+        push(&mut gsub, &[count]);
+        let mut offset = 2 + 2 * count;
+        for lookup in &lookups {
+            push(&mut gsub, &[offset]);
+            offset += 2 * lookup.len() as u16;
+        }
+        for lookup in &lookups {
+            push(&mut gsub, lookup);
+        }
+
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        let tables = [
+            (b"GSUB", gsub),
+            (b"head", head),
+            (b"hhea", vec![0; 36]),
+            (b"maxp", vec![0, 0, 0x50, 0, 0xFF, 0xFF]),
+        ];
+        let mut font = Vec::new();
+        push(&mut font, &[1, 0, tables.len() as u16, 0, 0, 0]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in &tables {
+            font.extend_from_slice(*tag);
+            font.extend_from_slice(&[0; 4]);
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in &tables {
+            font.extend_from_slice(table);
+        }
+        font
+    }
+
+    #[test]
+    fn conjuncts() {
+        let (ka, ta, ra, ssa, virama) = ('\u{915}', '\u{924}', '\u{930}', '\u{937}', '\u{94D}');
+        let (kssa, reph, half_ka) = (0xE000, 0xE001, 0xE002);
+        let font = || {
+            TestFont(font_with_ligatures(&[
+                (b"akhn", &[ka, virama, ssa], kssa),
+                (b"rphf", &[ra, virama], reph),
+                (b"half", &[ka, virama], half_ka),
+            ]))
+        };
+
+        // the conjunct replaces the cluster, moving the following text back
+        let (ids, positions, realigned) = shape_indic_text(font(), "a\u{915}\u{94D}\u{937}b");
+        assert_eq!(ids, [0x61, kssa, 0x62]);
+        assert_eq!(positions, [0.0, 10.0, 20.0]);
+        assert_eq!(realigned, [-20.0]);
+
+        // consonants before the base take their half form
+        let (ids, positions, _) = shape_indic_text(font(), "\u{915}\u{94D}\u{924}");
+        assert_eq!(ids, [half_ka, ta as u16]);
+        assert_eq!(positions, [0.0, 10.0]);
+        // but not after it
+        let (ids, ..) = shape_indic_text(font(), "\u{924}\u{94D}\u{915}\u{94D}");
+        assert_eq!(ids, [ta as u16, virama as u16, ka as u16, virama as u16]);
+
+        // reph moves after the base and the vowel sign i before it
+        let (ids, positions, realigned) = shape_indic_text(font(), "\u{930}\u{94D}\u{915}\u{93F}");
+        assert_eq!(ids, [reph, ka as u16, 0x93F]);
+        assert_eq!(positions, [20.0, 10.0, 0.0]);
+        assert_eq!(realigned, [-10.0]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use glyph_brush::ab_glyph::Rect;
+use glyph_brush::{Section, SectionGlyph};
+
+use crate::layout::LayoutOptions;
+
+/// A cache of laid out sections shared by several brushes, e.g. one per window showing the
+/// same HUD, so identical sections are laid out once for all of them, see
+/// [`GlyphBrush::set_shared_layout_cache`](struct.GlyphBrush.html#method.set_shared_layout_cache).
+///
+/// Clones share the cache. The brushes sharing it have to use the same fonts, added in the same
+/// order, as sections are identified by their font ids.
+///
+/// Each brush keeps the layouts it queues in its own cache and only looks up the layouts
+/// missing from it in the shared cache, so a reused section costs hashing its text once per
+/// frame, as it does without sharing. A layout is forgotten by the shared cache once the
+/// brushes have processed as many frames without looking it up as there are clones of the
+/// cache, by then the brushes queueing it have it in their own caches.
+#[derive(Debug, Clone, Default)]
+pub struct SharedLayoutCache(Arc<Mutex<Layouts>>);
+
+#[derive(Debug, Default)]
+struct Layouts {
+    layouts: HashMap<u64, SharedLayout>,
+    /// The number of frames processed by the sharing brushes.
+    frame: u64,
+}
+
+#[derive(Debug)]
+struct SharedLayout {
+    glyphs: Arc<[SectionGlyph]>,
+    bounds: Rect,
+    /// The frame the layout was last looked up in.
+    used: u64,
+}
+
+impl SharedLayoutCache {
+    pub fn new() -> Self {
+        SharedLayoutCache::default()
+    }
+
+    /// Returns the number of cached layouts.
+    pub fn len(&self) -> usize {
+        self.lock().layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().layouts.is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.lock().layouts.clear();
+    }
+
+    /// Looks up the glyphs and bounds of the layout `key`, keeping it for the current frame.
+    pub(crate) fn get(&self, key: u64) -> Option<(Arc<[SectionGlyph]>, Rect)> {
+        let mut layouts = self.lock();
+        let frame = layouts.frame;
+        let layout = layouts.layouts.get_mut(&key)?;
+        layout.used = frame;
+        Some((Arc::clone(&layout.glyphs), layout.bounds))
+    }
+
+    pub(crate) fn insert(&self, key: u64, glyphs: Arc<[SectionGlyph]>, bounds: Rect) {
+        let mut layouts = self.lock();
+        let used = layouts.frame;
+        layouts.layouts.insert(
+            key,
+            SharedLayout {
+                glyphs,
+                bounds,
+                used,
+            },
+        );
+    }
+
+    /// Ends a frame of one of the sharing brushes, forgetting the layouts unused since each
+    /// brush has processed a frame.
+    pub(crate) fn finish_frame(&self) {
+        let sharing = Arc::strong_count(&self.0) as u64;
+        let mut layouts = self.lock();
+        layouts.frame += 1;
+        let frame = layouts.frame;
+        (layouts.layouts).retain(|_, layout| frame - layout.used <= sharing);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Layouts> {
+        // the layouts stay valid even if a sharing thread panicked
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Identifies the layout of `section` by `layout` with the layout options of a brush.
+///
+/// The extra data of the texts isn't part of the key, as it only affects the glyphs through
+/// the styling `layout` has been created with, so e.g. fading text keeps its layout.
+pub(crate) fn key<X, L: Hash>(
+    section: &Section<'_, X>,
+    layout: &L,
+    options: &LayoutOptions,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let (x, y) = section.screen_position;
+    let (width, height) = section.bounds;
+    for value in [x, y, width, height] {
+        value.to_bits().hash(&mut hasher);
+    }
+    for text in &section.text {
+        text.text.hash(&mut hasher);
+        text.scale.x.to_bits().hash(&mut hasher);
+        text.scale.y.to_bits().hash(&mut hasher);
+        text.font_id.hash(&mut hasher);
+    }
+    layout.hash(&mut hasher);
+    options.hash(&mut hasher);
+    hasher.finish()
+}
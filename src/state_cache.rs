@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use winapi::um::d3d11::{D3D11_DEPTH_STENCILOP_DESC, D3D11_DEPTH_STENCIL_DESC};
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use crate::util::HResult;
+
+/// Upper bound of states kept alive per cache, the cache is flushed once exceeded.
+const MAX_CACHED_STATES: usize = 64;
+
+/// A small cache of D3D11 state objects keyed by their description.
+pub struct StateCache<K, T: Interface> {
+    states: HashMap<K, ComPtr<T>>,
+}
+
+impl<K: Hash + Eq, T: Interface> StateCache<K, T> {
+    pub fn new() -> Self {
+        StateCache {
+            states: HashMap::new(),
+        }
+    }
+
+    /// Returns the state for `key`, creating it with `create` if it isn't cached yet.
+    pub fn get_or_create<F>(&mut self, key: K, create: F) -> HResult<ComPtr<T>>
+    where
+        F: FnOnce() -> HResult<ComPtr<T>>,
+    {
+        if let Some(state) = self.states.get(&key) {
+            return Ok(state.clone());
+        }
+        if self.states.len() >= MAX_CACHED_STATES {
+            self.states.clear();
+        }
+        let state = create()?;
+        self.states.insert(key, state.clone());
+        Ok(state)
+    }
+}
+
+pub type DepthStencilKey = [u32; 14];
+
+pub fn depth_stencil_key(desc: &D3D11_DEPTH_STENCIL_DESC) -> DepthStencilKey {
+    let op = |op: &D3D11_DEPTH_STENCILOP_DESC| {
+        [
+            op.StencilFailOp,
+            op.StencilDepthFailOp,
+            op.StencilPassOp,
+            op.StencilFunc,
+        ]
+    };
+    let [front0, front1, front2, front3] = op(&desc.FrontFace);
+    let [back0, back1, back2, back3] = op(&desc.BackFace);
+    [
+        desc.DepthEnable as u32,
+        desc.DepthWriteMask,
+        desc.DepthFunc,
+        desc.StencilEnable as u32,
+        desc.StencilReadMask.into(),
+        desc.StencilWriteMask.into(),
+        front0,
+        front1,
+        front2,
+        front3,
+        back0,
+        back1,
+        back2,
+        back3,
+    ]
+}
@@ -0,0 +1,237 @@
+//! Tessellation of glyph outlines into triangle meshes, enabled by the `tessellation` feature.
+use glyph_brush::ab_glyph::{Font, Glyph, OutlineCurve, Point, ScaleFont};
+use glyph_brush::SectionGlyph;
+use lyon_tessellation::math::point;
+use lyon_tessellation::path::iterator::PathIterator;
+use lyon_tessellation::path::{self, Path, PathEvent};
+use lyon_tessellation::{
+    BuffersBuilder, FillOptions, FillRule, FillTessellator, FillVertex, VertexBuffers,
+};
+
+pub use lyon_tessellation::TessellationError;
+
+/// An indexed triangle list of filled glyph outlines.
+///
+/// Vertex positions are in the same coordinate space as the glyphs they have been generated
+/// from, so screen space for sections laid out with the built-in layouts.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+/// Tessellates the outlines of positioned glyphs into a triangle mesh.
+///
+/// `tolerance` is the maximum distance in pixels between the curves of the outline and the
+/// line segments approximating them.
+pub fn tessellate_glyphs<F: Font>(
+    fonts: &[F],
+    glyphs: &[SectionGlyph],
+    tolerance: f32,
+) -> Result<Mesh, TessellationError> {
+    let path = glyph_path(fonts, glyphs);
+
+    let mut buffers: VertexBuffers<[f32; 2], u32> = VertexBuffers::new();
+    FillTessellator::new().tessellate_path(
+        &path,
+        &FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero),
+        &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| {
+            vertex.position().to_array()
+        }),
+    )?;
+
+    Ok(Mesh {
+        vertices: buffers.vertices,
+        indices: buffers.indices,
+    })
+}
+
+/// Builds a path out of the scaled and positioned outlines of the glyphs.
+fn glyph_path<F: Font>(fonts: &[F], glyphs: &[SectionGlyph]) -> Path {
+    let mut builder = Path::builder();
+    for SectionGlyph { glyph, font_id, .. } in glyphs {
+        append_glyph_outline(&mut builder, &fonts[font_id.0], glyph);
+    }
+    builder.build()
+}
+
+/// Appends the scaled and positioned outline of `glyph` to `builder`, returns `false` if the
+/// glyph has no outline.
+fn append_glyph_outline<F: Font>(builder: &mut path::Builder, font: &F, glyph: &Glyph) -> bool {
+    let outline = match font.outline(glyph.id) {
+        Some(outline) => outline,
+        None => return false,
+    };
+    let scaled = font.as_scaled(glyph.scale);
+    let (h_factor, v_factor) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+    // outlines are in unscaled font units with the y axis pointing up
+    let to_screen = |p: Point| {
+        point(
+            glyph.position.x + p.x * h_factor,
+            glyph.position.y - p.y * v_factor,
+        )
+    };
+
+    let mut contour_end = None;
+    for curve in &outline.curves {
+        let start = match *curve {
+            OutlineCurve::Line(p0, _)
+            | OutlineCurve::Quad(p0, _, _)
+            | OutlineCurve::Cubic(p0, _, _, _) => p0,
+        };
+        if contour_end != Some(start) {
+            if contour_end.is_some() {
+                builder.end(true);
+            }
+            builder.begin(to_screen(start));
+        }
+        contour_end = Some(match *curve {
+            OutlineCurve::Line(_, p1) => {
+                builder.line_to(to_screen(p1));
+                p1
+            }
+            OutlineCurve::Quad(_, p1, p2) => {
+                builder.quadratic_bezier_to(to_screen(p1), to_screen(p2));
+                p2
+            }
+            OutlineCurve::Cubic(_, p1, p2, p3) => {
+                builder.cubic_bezier_to(to_screen(p1), to_screen(p2), to_screen(p3));
+                p3
+            }
+        });
+    }
+    if contour_end.is_some() {
+        builder.end(true);
+    }
+    true
+}
+
+/// A vertex of an [`ExtrudedMesh`](struct.ExtrudedMesh.html).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+/// An indexed triangle list of extruded glyph outlines.
+#[derive(Debug, Clone, Default)]
+pub struct ExtrudedMesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Extrudes the outlines of positioned glyphs into a solid 3D mesh.
+///
+/// The front face lies at `z = 0` facing towards negative z, the back face at `z = depth`
+/// facing towards positive z, connected by side walls with normals pointing away from the
+/// glyph. The triangles of the back face are wound in reverse to the ones of the front face.
+///
+/// `tolerance` is the maximum distance in pixels between the curves of the outline and the
+/// line segments approximating them.
+pub fn extrude_glyphs<F: Font>(
+    fonts: &[F],
+    glyphs: &[SectionGlyph],
+    depth: f32,
+    tolerance: f32,
+) -> Result<ExtrudedMesh, TessellationError> {
+    let mut mesh = ExtrudedMesh::default();
+    let mut tessellator = FillTessellator::new();
+    let options = FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero);
+
+    for SectionGlyph { glyph, font_id, .. } in glyphs {
+        let mut builder = Path::builder();
+        if !append_glyph_outline(&mut builder, &fonts[font_id.0], glyph) {
+            continue;
+        }
+        let path = builder.build();
+
+        let mut face: VertexBuffers<[f32; 2], u32> = VertexBuffers::new();
+        tessellator.tessellate_path(
+            &path,
+            &options,
+            &mut BuffersBuilder::new(&mut face, |vertex: FillVertex| vertex.position().to_array()),
+        )?;
+        for &(z, normal_z) in &[(0.0, -1.0), (depth, 1.0)] {
+            let base = mesh.vertices.len() as u32;
+            mesh.vertices
+                .extend(face.vertices.iter().map(|&[x, y]| MeshVertex {
+                    position: [x, y, z],
+                    normal: [0.0, 0.0, normal_z],
+                }));
+            if normal_z < 0.0 {
+                mesh.indices.extend(face.indices.iter().map(|i| base + i));
+            } else {
+                mesh.indices.extend(
+                    face.indices
+                        .chunks_exact(3)
+                        .flat_map(|tri| [base + tri[0], base + tri[2], base + tri[1]]),
+                );
+            }
+        }
+
+        let mut segments = Vec::new();
+        for event in path.iter().flattened(tolerance) {
+            match event {
+                PathEvent::Line { from, to } if from != to => segments.push((from, to)),
+                PathEvent::End {
+                    last, first, close, ..
+                } if close && last != first => segments.push((last, first)),
+                _ => {}
+            }
+        }
+        // The orientation of outer contours differs between font formats, so determine it
+        // from the signed area of the whole glyph which is dominated by the outer contours.
+        let area: f32 = segments
+            .iter()
+            .map(|(from, to)| from.x * to.y - to.x * from.y)
+            .sum();
+        let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+        for (from, to) in segments {
+            let normal = lyon_tessellation::math::vector(to.y - from.y, from.x - to.x).normalize()
+                * orientation;
+            let normal = [normal.x, normal.y, 0.0];
+            let base = mesh.vertices.len() as u32;
+            mesh.vertices.extend_from_slice(&[
+                MeshVertex {
+                    position: [from.x, from.y, 0.0],
+                    normal,
+                },
+                MeshVertex {
+                    position: [to.x, to.y, 0.0],
+                    normal,
+                },
+                MeshVertex {
+                    position: [to.x, to.y, depth],
+                    normal,
+                },
+                MeshVertex {
+                    position: [from.x, from.y, depth],
+                    normal,
+                },
+            ]);
+            // the winding has to follow the flipped normal to keep facing outwards
+            if orientation > 0.0 {
+                mesh.indices.extend_from_slice(&[
+                    base,
+                    base + 1,
+                    base + 2,
+                    base,
+                    base + 2,
+                    base + 3,
+                ]);
+            } else {
+                mesh.indices.extend_from_slice(&[
+                    base,
+                    base + 2,
+                    base + 1,
+                    base,
+                    base + 3,
+                    base + 2,
+                ]);
+            }
+        }
+    }
+
+    Ok(mesh)
+}
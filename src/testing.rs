@@ -0,0 +1,263 @@
+//! Rendering regression tests against reference images, enabled by the `testing` feature.
+//!
+//! Text is drawn on a WARP device, the software rasterizer shipped with Windows, so the tests
+//! render the same pixels on machines without a GPU, e.g. CI runners:
+//!
+//! ```no_run
+//! use d3d11_glyph::{ab_glyph::FontArc, testing, GlyphBrushBuilder, Section, Text};
+//!
+//! let font = FontArc::try_from_slice(include_bytes!("../examples/Inconsolata-Regular.ttf"))?;
+//! let mut brush = GlyphBrushBuilder::using_font(font).build_warp()?;
+//! let section = Section::default().add_text(Text::new("Hello").with_scale(24.0));
+//! let image = testing::render(&mut brush, Some(section), 128, 32)?;
+//! testing::assert_matches_reference(&image, "tests/references/hello.pam", 8);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Reference images are stored as binary [PAM](https://netpbm.sourceforge.net/doc/pam.html)
+//! files. They are written from the rendered images when the `D3D11_GLYPH_UPDATE_REFERENCES`
+//! environment variable is set, otherwise a missing reference fails the test.
+use std::borrow::Cow;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{mem, ptr};
+
+use glyph_brush::ab_glyph::Font;
+use glyph_brush::Section;
+use winapi::um::d3d11::{
+    ID3D11Texture2D, D3D11_CPU_ACCESS_READ, D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ,
+    D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+};
+use wio::com::ComPtr;
+
+use crate::util::{com_ptr_from_fn, com_ref_cast, hresult, HResult};
+use crate::{BrushVertex, GlyphBrush, GlyphExtra};
+
+/// The environment variable making `assert_matches_reference` overwrite the references.
+const UPDATE_REFERENCES: &str = "D3D11_GLYPH_UPDATE_REFERENCES";
+
+/// An RGBA image with 8 bits per channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels in row-major order, with straight alpha as rendered by the brush.
+    pub pixels: Vec<u8>,
+}
+
+/// How two images of the same size differ, see [`Image::difference`](struct.Image.html#method.difference).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difference {
+    /// The number of pixels with a channel differing by more than the tolerance.
+    pub pixels: usize,
+    /// The largest difference of a channel.
+    pub max_channel: u8,
+}
+
+impl Image {
+    /// Returns how `self` differs from `other` beyond `tolerance` per channel, or `None` if the
+    /// images match. Images of different sizes never match.
+    pub fn difference(&self, other: &Image, tolerance: u8) -> Option<Difference> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Some(Difference {
+                pixels: self.pixels.len().max(other.pixels.len()) / 4,
+                max_channel: u8::MAX,
+            });
+        }
+        let mut difference = Difference {
+            pixels: 0,
+            max_channel: 0,
+        };
+        for (a, b) in self.pixels.chunks(4).zip(other.pixels.chunks(4)) {
+            let max = (a.iter().zip(b))
+                .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0);
+            difference.max_channel = difference.max_channel.max(max);
+            if max > tolerance {
+                difference.pixels += 1;
+            }
+        }
+        Some(difference).filter(|difference| difference.pixels > 0)
+    }
+
+    /// Reads an RGBA image from a binary PAM file.
+    pub fn read_pam(path: impl AsRef<Path>) -> io::Result<Image> {
+        let mut reader = io::BufReader::new(fs::File::open(path)?);
+        let (mut width, mut height, mut depth, mut max) = (None, None, None, None);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.trim_end() != "P7" {
+            return Err(invalid_data("not a PAM file"));
+        }
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("the PAM header isn't terminated"));
+            }
+            let mut fields = line.split_whitespace();
+            let value = |fields: &mut std::str::SplitWhitespace<'_>| {
+                (fields.next().and_then(|value| value.parse::<u32>().ok()))
+                    .ok_or_else(|| invalid_data("invalid PAM header value"))
+            };
+            match fields.next() {
+                Some("WIDTH") => width = Some(value(&mut fields)?),
+                Some("HEIGHT") => height = Some(value(&mut fields)?),
+                Some("DEPTH") => depth = Some(value(&mut fields)?),
+                Some("MAXVAL") => max = Some(value(&mut fields)?),
+                Some("ENDHDR") => break,
+                _ => {}
+            }
+        }
+        let (width, height) = match (width, height, depth, max) {
+            (Some(width), Some(height), Some(4), Some(255)) => (width, height),
+            _ => return Err(invalid_data("only 8 bit RGBA PAM files are supported")),
+        };
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        reader.read_exact(&mut pixels)?;
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Writes the image as a binary PAM file.
+    pub fn write_pam(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        write!(
+            file,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height
+        )?;
+        file.write_all(&self.pixels)?;
+        file.flush()
+    }
+}
+
+/// Draws `sections` into a transparent `width` x `height` texture, like
+/// [`GlyphBrush::render_to_texture`](../struct.GlyphBrush.html#method.render_to_texture), and
+/// reads it back.
+pub fn render<'a, F, H, X, V, I, S>(
+    brush: &mut GlyphBrush<F, H, X, V>,
+    sections: I,
+    width: u32,
+    height: u32,
+) -> HResult<Image>
+where
+    F: Font + Sync,
+    H: BuildHasher,
+    X: GlyphExtra + 'a,
+    V: BrushVertex<X>,
+    I: IntoIterator<Item = S>,
+    S: Into<Cow<'a, Section<'a, X>>>,
+{
+    let texture = brush.render_to_texture(sections, width, height)?;
+    unsafe { read_back(brush, &texture) }
+}
+
+unsafe fn read_back<F, H, X, V: Copy>(
+    brush: &GlyphBrush<F, H, X, V>,
+    texture: &ComPtr<ID3D11Texture2D>,
+) -> HResult<Image> {
+    let mut desc = mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+    texture.GetDesc(&mut desc);
+    desc.Usage = D3D11_USAGE_STAGING;
+    desc.BindFlags = 0;
+    desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ;
+    let staging = com_ptr_from_fn("CreateTexture2D", |staging| {
+        brush
+            .pipeline
+            .device()
+            .CreateTexture2D(&desc, ptr::null(), staging)
+    })?;
+
+    let ctx = brush.pipeline.context();
+    let _lock = brush.pipeline.lock_context();
+    ctx.CopyResource(
+        com_ref_cast(&staging).as_raw(),
+        com_ref_cast(texture).as_raw(),
+    );
+    let mut mapped = mem::zeroed::<D3D11_MAPPED_SUBRESOURCE>();
+    hresult(
+        "Map",
+        ctx.Map(
+            com_ref_cast(&staging).as_raw(),
+            0,
+            D3D11_MAP_READ,
+            0,
+            &mut mapped,
+        ),
+    )?;
+    let row = desc.Width as usize * 4;
+    let mut pixels = Vec::with_capacity(row * desc.Height as usize);
+    for y in 0..desc.Height as usize {
+        let start = mapped.pData.cast::<u8>().add(y * mapped.RowPitch as usize);
+        pixels.extend_from_slice(std::slice::from_raw_parts(start, row));
+    }
+    ctx.Unmap(com_ref_cast(&staging).as_raw(), 0);
+    Ok(Image {
+        width: desc.Width,
+        height: desc.Height,
+        pixels,
+    })
+}
+
+/// Panics if `image` differs from the reference image at `path` by more than `tolerance` per
+/// channel or the reference doesn't exist, writing the image next to the reference with an
+/// `actual.pam` extension for inspection.
+///
+/// The reference is written instead if the `D3D11_GLYPH_UPDATE_REFERENCES` environment variable
+/// is set.
+pub fn assert_matches_reference(image: &Image, path: impl AsRef<Path>, tolerance: u8) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_REFERENCES).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("failed to create the reference directory");
+        }
+        image
+            .write_pam(path)
+            .unwrap_or_else(|error| panic!("failed to write {}: {}", path.display(), error));
+        return;
+    }
+    let actual = PathBuf::from(path).with_extension("actual.pam");
+    if !path.exists() {
+        let written = image.write_pam(&actual).is_ok();
+        panic!(
+            "the reference {} doesn't exist, set {} to write it{}",
+            path.display(),
+            UPDATE_REFERENCES,
+            if written {
+                format!(", see {} for the rendered image", actual.display())
+            } else {
+                String::new()
+            },
+        );
+    }
+    let reference = Image::read_pam(path)
+        .unwrap_or_else(|error| panic!("failed to read {}: {}", path.display(), error));
+    if let Some(difference) = image.difference(&reference, tolerance) {
+        let written = image.write_pam(&actual).is_ok();
+        panic!(
+            "{}x{} image differs from the {}x{} reference {} in {} pixels by up to {}{}",
+            image.width,
+            image.height,
+            reference.width,
+            reference.height,
+            path.display(),
+            difference.pixels,
+            difference.max_channel,
+            if written {
+                format!(", see {}", actual.display())
+            } else {
+                String::new()
+            },
+        );
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,165 @@
+/// A 4x4 matrix transforming glyph positions into clip space.
+///
+/// Positions are treated as column vectors, i.e. a position `p` is transformed as `M * p` and
+/// the translation lives in the last column. This is the convention of `glam`, `nalgebra` and
+/// `cgmath`, whose column-major arrays can be passed to
+/// [`from_column_major`](#method.from_column_major) as is. Matrices in the row vector convention
+/// of DirectXMath, where `p` is transformed as `p * M`, are the transpose and can be passed to
+/// [`from_row_major`](#method.from_row_major) as laid out in memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform([f32; 16]);
+
+impl Transform {
+    #[rustfmt::skip]
+    pub const IDENTITY: Transform = Transform([
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ]);
+
+    /// Maps pixel coordinates of a `width` x `height` target, with the origin in the top left
+    /// corner, to clip space.
+    ///
+    /// The projection of a zero-sized target isn't finite, see
+    /// [`GlyphBrush::draw_queued`](struct.GlyphBrush.html#method.draw_queued).
+    #[rustfmt::skip]
+    pub fn orthographic(width: u32, height: u32) -> Transform {
+        let width = width as f32;
+        let height = height as f32;
+        Transform([
+             2.0 / width, 0.0,           0.0, 0.0,
+             0.0,         -2.0 / height, 0.0, 0.0,
+             0.0,         0.0,           1.0, 0.0,
+            -1.0,         1.0,           0.0, 1.0,
+        ])
+    }
+
+    /// Maps the pixel coordinates of the rectangle from `left` to `right` and `top` to `bottom`
+    /// to clip space, e.g. the visible part of a scrolled viewport.
+    ///
+    /// `top` has to be smaller than `bottom`, as text is laid out with y pointing down and would
+    /// be drawn mirrored otherwise.
+    #[rustfmt::skip]
+    pub fn orthographic_off_center(left: f32, right: f32, top: f32, bottom: f32) -> Transform {
+        let width = right - left;
+        let height = top - bottom;
+        Transform([
+            2.0 / width,                   0.0,                            0.0, 0.0,
+            0.0,                           2.0 / height,                   0.0, 0.0,
+            0.0,                           0.0,                            1.0, 0.0,
+            -(right + left) / width,       -(top + bottom) / height,       0.0, 1.0,
+        ])
+    }
+
+    /// Places text at `position` in world space, facing the camera of the `view` matrix and
+    /// projected with `projection`, e.g. for labels over objects in a 3D scene.
+    ///
+    /// One pixel of the text is `scale` world units large, so the text shrinks with distance.
+    /// The origin of the text lies at `position`, with x pointing to the right of the camera and
+    /// y pointing down. The z values of the glyphs are ignored, the text is depth tested at the
+    /// depth of `position`.
+    #[rustfmt::skip]
+    pub fn billboard(
+        view: Transform,
+        projection: Transform,
+        position: [f32; 3],
+        scale: f32,
+    ) -> Transform {
+        let v = view.0;
+        // the rows of the rotation part of the view matrix are the camera axes in world space
+        let right = [v[0] * scale, v[4] * scale, v[8] * scale];
+        let down = [-v[1] * scale, -v[5] * scale, -v[9] * scale];
+        let [x, y, z] = position;
+        let model = Transform([
+            right[0], right[1], right[2], 0.0,
+            down[0],  down[1],  down[2],  0.0,
+            0.0,      0.0,      0.0,      0.0,
+            x,        y,        z,        1.0,
+        ]);
+        projection * view * model
+    }
+
+    /// Projects a point in world space to the pixel coordinates of a `width` x `height`
+    /// viewport, treating the transform as a view-projection matrix.
+    ///
+    /// The position can be used as the `screen_position` of a section to place labels like
+    /// nameplates or damage numbers over objects in a 3D scene, drawn with the default
+    /// [`orthographic`](#method.orthographic) transform.
+    pub fn project_to_screen(&self, point: [f32; 3], width: u32, height: u32) -> ScreenPosition {
+        let m = self.0;
+        let [x, y, z] = point;
+        let clip = |row: usize| m[row] * x + m[4 + row] * y + m[8 + row] * z + m[12 + row];
+        let w = clip(3);
+        // points behind the camera are mirrored by the perspective divide, `w.abs()` keeps them
+        // on the side they lie on
+        let divisor = w.abs().max(f32::EPSILON);
+        let (ndc_x, ndc_y) = (clip(0) / divisor, clip(1) / divisor);
+        ScreenPosition {
+            position: (
+                (ndc_x + 1.0) * 0.5 * width as f32,
+                (1.0 - ndc_y) * 0.5 * height as f32,
+            ),
+            depth: clip(2) / divisor,
+            behind_camera: w <= 0.0,
+        }
+    }
+
+    /// Creates the transform from a matrix stored column by column.
+    #[inline]
+    pub const fn from_column_major(matrix: [f32; 16]) -> Transform {
+        Transform(matrix)
+    }
+
+    /// Creates the transform from a matrix stored row by row.
+    pub fn from_row_major(matrix: [f32; 16]) -> Transform {
+        let mut columns = [0.0; 16];
+        for (i, value) in matrix.iter().enumerate() {
+            columns[i % 4 * 4 + i / 4] = *value;
+        }
+        Transform(columns)
+    }
+
+    /// Returns the matrix stored column by column, the layout of the shader's constant buffer.
+    #[inline]
+    pub const fn to_column_major(self) -> [f32; 16] {
+        self.0
+    }
+}
+
+/// A point projected to screen space, see
+/// [`Transform::project_to_screen`](struct.Transform.html#method.project_to_screen).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenPosition {
+    /// The pixel coordinates of the point, with the origin in the top left corner.
+    pub position: (f32, f32),
+    /// The depth of the point in normalized device coordinates, `0.0..=1.0` between the near
+    /// and the far plane.
+    pub depth: f32,
+    /// `true` if the point lies behind the camera, in which case labels are usually hidden.
+    pub behind_camera: bool,
+}
+
+impl Default for Transform {
+    #[inline]
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+/// Combines two transforms, `a * b` applies `b` first and then `a`.
+impl std::ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        let (a, b) = (self.0, rhs.0);
+        let mut product = [0.0; 16];
+        for column in 0..4 {
+            for row in 0..4 {
+                product[column * 4 + row] =
+                    (0..4).map(|i| a[i * 4 + row] * b[column * 4 + i]).sum();
+            }
+        }
+        Transform(product)
+    }
+}
@@ -2,26 +2,60 @@ use std::num::NonZeroI32;
 use std::ptr;
 
 use winapi::shared::winerror::HRESULT;
+use winapi::um::d3d11::{ID3D11Device, D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION};
+use winapi::um::d3dcommon::{
+    D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_11_0, D3D_FEATURE_LEVEL_9_3,
+};
 use winapi::Interface;
 use wio::com::ComPtr;
 
-pub type HResult<T> = std::result::Result<T, NonZeroI32>;
+use crate::Error;
 
-pub fn hresult(code: HRESULT) -> HResult<()> {
+pub type HResult<T> = std::result::Result<T, Error>;
+
+/// Turns the `HRESULT` returned by `call` into an error if it isn't `S_OK`.
+pub fn hresult(call: &'static str, code: HRESULT) -> HResult<()> {
     match NonZeroI32::new(code) {
-        Some(err) => Err(err),
+        Some(code) => Err(Error::HResult { call, code }),
         None => Ok(()),
     }
 }
 
-pub unsafe fn com_ptr_from_fn<T, F>(fun: F) -> HResult<ComPtr<T>>
+/// Returns the maximum width and height of 2D textures supported by the device's feature level.
+pub fn max_texture_dimension(device: &ID3D11Device) -> u32 {
+    let feature_level = unsafe { device.GetFeatureLevel() };
+    if feature_level >= D3D_FEATURE_LEVEL_11_0 {
+        D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    } else if feature_level >= D3D_FEATURE_LEVEL_10_0 {
+        8192
+    } else if feature_level >= D3D_FEATURE_LEVEL_9_3 {
+        4096
+    } else {
+        2048
+    }
+}
+
+/// Checks that a glyph cache of the given size can be created on the device.
+pub fn validate_cache_size(device: &ID3D11Device, width: u32, height: u32) -> HResult<()> {
+    let max_dimension = max_texture_dimension(device);
+    if width > max_dimension || height > max_dimension {
+        Err(Error::CacheTooLarge {
+            requested: (width, height),
+            max_dimension,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub unsafe fn com_ptr_from_fn<T, F>(call: &'static str, fun: F) -> HResult<ComPtr<T>>
 where
     T: Interface,
     F: FnOnce(&mut *mut T) -> HRESULT,
 {
     let mut ptr = ptr::null_mut();
     let res = fun(&mut ptr);
-    hresult(res).map(|()| ComPtr::from_raw(ptr))
+    hresult(call, res).map(|()| ComPtr::from_raw(ptr))
 }
 
 pub unsafe fn com_ref_cast<T, U>(com_ptr: &ComPtr<T>) -> &ComPtr<U>
@@ -0,0 +1,100 @@
+//! Reporting the messages of the D3D11 debug layer caused by the brush, enabled by the
+//! `validation` feature.
+use std::ffi::CStr;
+use std::ptr;
+
+use winapi::um::d3d11::ID3D11Device;
+use winapi::um::d3d11sdklayers::{
+    ID3D11InfoQueue, D3D11_MESSAGE, D3D11_MESSAGE_SEVERITY_CORRUPTION,
+    D3D11_MESSAGE_SEVERITY_ERROR, D3D11_MESSAGE_SEVERITY_WARNING,
+};
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use crate::util::com_ptr_from_fn;
+
+/// The info queue of a device created with the debug layer.
+///
+/// Messages stored by operations of the brush are logged, errors and corruption as errors and
+/// warnings as warnings. The queue itself is left untouched, so the messages are still
+/// available to the application and debuggers.
+pub struct Validation(ComPtr<ID3D11InfoQueue>);
+
+impl Validation {
+    /// Returns `None` if `device` hasn't been created with `D3D11_CREATE_DEVICE_DEBUG`.
+    pub fn new(device: &ComPtr<ID3D11Device>) -> Option<Self> {
+        let queue = unsafe {
+            com_ptr_from_fn("QueryInterface", |queue: &mut *mut ID3D11InfoQueue| {
+                device.QueryInterface(
+                    &ID3D11InfoQueue::uuidof(),
+                    (queue as *mut *mut ID3D11InfoQueue).cast(),
+                )
+            })
+        };
+        queue.ok().map(Validation)
+    }
+
+    /// Logs the messages stored during `operation` once the returned scope is dropped.
+    #[inline]
+    pub fn scope(&self, operation: &'static str) -> ValidationScope {
+        ValidationScope {
+            queue: self.0.clone(),
+            operation,
+            start: message_count(&self.0),
+        }
+    }
+}
+
+pub struct ValidationScope {
+    queue: ComPtr<ID3D11InfoQueue>,
+    operation: &'static str,
+    /// The number of messages ever stored when the scope was entered.
+    start: u64,
+}
+
+impl Drop for ValidationScope {
+    fn drop(&mut self) {
+        let new = message_count(&self.queue).saturating_sub(self.start);
+        if new == 0 {
+            return;
+        }
+        // messages beyond the count limit have pushed the oldest ones out of the queue
+        let stored = unsafe { self.queue.GetNumStoredMessages() };
+        for index in stored.saturating_sub(new)..stored {
+            unsafe { log_message(&self.queue, index, self.operation) };
+        }
+    }
+}
+
+/// The number of messages stored in `queue` so far, including the ones discarded since.
+#[inline]
+fn message_count(queue: &ID3D11InfoQueue) -> u64 {
+    unsafe { queue.GetNumStoredMessages() + queue.GetNumMessagesDiscardedByMessageCountLimit() }
+}
+
+unsafe fn log_message(queue: &ID3D11InfoQueue, index: u64, operation: &str) {
+    let mut len = 0;
+    if queue.GetMessage(index, ptr::null_mut(), &mut len) < 0 || len == 0 {
+        return;
+    }
+    // the description is stored behind the message, aligned like the message itself
+    let mut storage = vec![0u64; len.div_ceil(8)];
+    let message = storage.as_mut_ptr().cast::<D3D11_MESSAGE>();
+    if queue.GetMessage(index, message, &mut len) < 0 {
+        return;
+    }
+    let message = &*message;
+    if message.pDescription.is_null() {
+        return;
+    }
+    let description = CStr::from_ptr(message.pDescription).to_string_lossy();
+    match message.Severity {
+        D3D11_MESSAGE_SEVERITY_CORRUPTION | D3D11_MESSAGE_SEVERITY_ERROR => {
+            log::error!("D3D11 error during {}: {}", operation, description)
+        }
+        D3D11_MESSAGE_SEVERITY_WARNING => {
+            log::warn!("D3D11 warning during {}: {}", operation, description)
+        }
+        _ => {}
+    }
+}
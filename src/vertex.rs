@@ -0,0 +1,195 @@
+use glyph_brush::ab_glyph::{point, Rect};
+use glyph_brush::GlyphVertex;
+use winapi::shared::dxgiformat::{
+    DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R32G32B32_FLOAT, DXGI_FORMAT_R32G32_FLOAT,
+    DXGI_FORMAT_R32_FLOAT,
+};
+use winapi::um::d3d11::{D3D11_INPUT_ELEMENT_DESC, D3D11_INPUT_PER_INSTANCE_DATA};
+
+use crate::{BoundsClipping, GlyphExtra, Vertex};
+
+/// A per-instance vertex describing a single glyph quad.
+///
+/// Implemented by [`Vertex`](struct.Vertex.html), the vertex of the built-in shaders. Custom
+/// vertex types allow packing additional per-instance data into the instance stream, e.g. pick
+/// IDs for GPU picking. Their input layout is matched against the vertex shader of the brush,
+/// so data not consumed by the built-in shader requires a custom
+/// [`vertex_shader`](struct.GlyphBrushBuilder.html#method.vertex_shader).
+///
+/// The vertex only holds what is uploaded to the GPU. The blend mode, texture filter and fill
+/// texture selecting the draw call of a quad are taken from the extra data of its glyph.
+pub trait BrushVertex<X>: Copy + 'static {
+    /// Converts the data of a single glyph into a vertex.
+    fn from_glyph(vertex: GlyphVertex<'_, X>, bounds_clipping: BoundsClipping) -> Self;
+
+    /// Describes the layout of the vertex in the instance stream, bound to input slot `0`.
+    ///
+    /// Input slot `1` and the `CORNER` semantic are reserved for the quad corners.
+    fn input_layout() -> Vec<D3D11_INPUT_ELEMENT_DESC>;
+
+    /// Returns the pixel and texture coordinates of the quad as drawn, which
+    /// [`GlyphBrush::glyph_quads`](struct.GlyphBrush.html#method.glyph_quads) reports.
+    ///
+    /// Vertex types returning `None` don't report any quads.
+    #[inline]
+    fn coords(&self) -> Option<(Rect, Rect)> {
+        None
+    }
+}
+
+/// The screen-space quad of a glyph as drawn by the brush, see
+/// [`GlyphBrush::glyph_quads`](struct.GlyphBrush.html#method.glyph_quads).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+    /// The index of the text of the section containing the glyph.
+    pub section_index: usize,
+    /// The byte index of the glyph's character in its text.
+    pub byte_index: usize,
+    /// The pixel coordinates of the quad.
+    pub pixel_coords: Rect,
+    /// The texture coordinates of the quad in the glyph cache, normalized to `0..1`. With a
+    /// [reserved cache height](struct.GlyphBrushBuilder.html#method.reserve_cache_height) they
+    /// are relative to the reserved height rather than to the texture.
+    pub tex_coords: Rect,
+}
+
+impl<X: GlyphExtra> BrushVertex<X> for Vertex {
+    #[inline]
+    fn from_glyph(vertex: GlyphVertex<'_, X>, bounds_clipping: BoundsClipping) -> Self {
+        Vertex::new(vertex, bounds_clipping)
+    }
+
+    fn coords(&self) -> Option<(Rect, Rect)> {
+        let pixel_coords = Rect {
+            min: point(self.left_top[0], self.right_bottom[1]),
+            max: point(self.right_bottom[0], self.left_top[1]),
+        };
+        let tex_coords = Rect {
+            min: point(self.tex_left_top[0], self.tex_right_bottom[1]),
+            max: point(self.tex_right_bottom[0], self.tex_left_top[1]),
+        };
+        Some((pixel_coords, tex_coords))
+    }
+
+    fn input_layout() -> Vec<D3D11_INPUT_ELEMENT_DESC> {
+        vec![
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "POSITION\0".as_ptr().cast(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32B32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 0,
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "POSITION\0".as_ptr().cast(),
+                SemanticIndex: 1,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * 3,
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 1,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "COLOR\0".as_ptr().cast(),
+                SemanticIndex: 0,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 2,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 3,
+                Format: DXGI_FORMAT_R32G32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "COLOR\0".as_ptr().cast(),
+                SemanticIndex: 1,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 4,
+                Format: DXGI_FORMAT_R32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "COLOR\0".as_ptr().cast(),
+                SemanticIndex: 2,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2 + 4 + 1),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "COLOR\0".as_ptr().cast(),
+                SemanticIndex: 3,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2 + 4 + 1 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 5,
+                Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2 + 4 + 1 + 4 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+            D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: "TEXCOORD\0".as_ptr().cast(),
+                SemanticIndex: 6,
+                Format: DXGI_FORMAT_R32_FLOAT,
+                InputSlot: 0,
+                AlignedByteOffset: 4 * (3 + 2 + 2 + 2 + 4 + 4 + 2 + 4 + 1 + 4 + 4 + 4),
+                InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+                InstanceDataStepRate: 1,
+            },
+        ]
+    }
+}
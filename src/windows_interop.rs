@@ -0,0 +1,126 @@
+//! Conversions between the `winapi` types used by this crate and their equivalents in the
+//! `windows` crate, enabled by the `windows` feature.
+//!
+//! This allows codebases built on `windows` to pass their device and views to the brush and
+//! to use the raw resources it exposes without transmuting between the two.
+//!
+//! COM interfaces are converted by adding a reference, so the returned pointer and the
+//! converted one both keep the object alive.
+use winapi::shared::dxgi::IDXGISwapChain;
+use winapi::um::d3d11::{
+    ID3D11Buffer, ID3D11DepthStencilView, ID3D11Device, ID3D11DeviceContext,
+    ID3D11RenderTargetView, ID3D11ShaderResourceView, ID3D11Texture2D, D3D11_DEPTH_STENCILOP_DESC,
+    D3D11_DEPTH_STENCIL_DESC, D3D11_FILTER, D3D11_RECT,
+};
+use windows::core::Interface;
+use windows::Win32::Foundation;
+use windows::Win32::Graphics::{Direct3D11, Dxgi};
+use wio::com::ComPtr;
+
+macro_rules! interface_conversions {
+    ($($interface:ident: $from_windows:ident, $to_windows:ident;)*) => {$(
+        #[doc = concat!("Converts an `", stringify!($interface), "` of the `windows` crate.")]
+        #[inline]
+        pub fn $from_windows(interface: &interface_type!($interface)) -> ComPtr<$interface> {
+            unsafe { ComPtr::from_raw(interface.clone().into_raw().cast()) }
+        }
+
+        #[doc = concat!("Converts an `", stringify!($interface), "` into the `windows` crate.")]
+        #[inline]
+        pub fn $to_windows(interface: &ComPtr<$interface>) -> interface_type!($interface) {
+            unsafe { Interface::from_raw(interface.clone().into_raw().cast()) }
+        }
+    )*};
+}
+
+macro_rules! interface_type {
+    (IDXGISwapChain) => {
+        Dxgi::IDXGISwapChain
+    };
+    ($interface:ident) => {
+        Direct3D11::$interface
+    };
+}
+
+interface_conversions! {
+    ID3D11Device: device_from_windows, device_to_windows;
+    ID3D11DeviceContext: context_from_windows, context_to_windows;
+    ID3D11RenderTargetView: render_target_view_from_windows, render_target_view_to_windows;
+    ID3D11DepthStencilView: depth_stencil_view_from_windows, depth_stencil_view_to_windows;
+    ID3D11ShaderResourceView: shader_resource_view_from_windows, shader_resource_view_to_windows;
+    ID3D11Texture2D: texture_from_windows, texture_to_windows;
+    ID3D11Buffer: buffer_from_windows, buffer_to_windows;
+    IDXGISwapChain: swapchain_from_windows, swapchain_to_windows;
+}
+
+#[inline]
+pub fn rect_from_windows(rect: Foundation::RECT) -> D3D11_RECT {
+    D3D11_RECT {
+        left: rect.left,
+        top: rect.top,
+        right: rect.right,
+        bottom: rect.bottom,
+    }
+}
+
+#[inline]
+pub fn rect_to_windows(rect: D3D11_RECT) -> Foundation::RECT {
+    Foundation::RECT {
+        left: rect.left,
+        top: rect.top,
+        right: rect.right,
+        bottom: rect.bottom,
+    }
+}
+
+#[inline]
+pub fn filter_from_windows(filter: Direct3D11::D3D11_FILTER) -> D3D11_FILTER {
+    filter.0 as D3D11_FILTER
+}
+
+#[inline]
+pub fn filter_to_windows(filter: D3D11_FILTER) -> Direct3D11::D3D11_FILTER {
+    Direct3D11::D3D11_FILTER(filter as i32)
+}
+
+pub fn depth_stencil_desc_from_windows(
+    desc: &Direct3D11::D3D11_DEPTH_STENCIL_DESC,
+) -> D3D11_DEPTH_STENCIL_DESC {
+    let stencil_op = |desc: &Direct3D11::D3D11_DEPTH_STENCILOP_DESC| D3D11_DEPTH_STENCILOP_DESC {
+        StencilFailOp: desc.StencilFailOp.0 as _,
+        StencilDepthFailOp: desc.StencilDepthFailOp.0 as _,
+        StencilPassOp: desc.StencilPassOp.0 as _,
+        StencilFunc: desc.StencilFunc.0 as _,
+    };
+    D3D11_DEPTH_STENCIL_DESC {
+        DepthEnable: desc.DepthEnable.0,
+        DepthWriteMask: desc.DepthWriteMask.0 as _,
+        DepthFunc: desc.DepthFunc.0 as _,
+        StencilEnable: desc.StencilEnable.0,
+        StencilReadMask: desc.StencilReadMask,
+        StencilWriteMask: desc.StencilWriteMask,
+        FrontFace: stencil_op(&desc.FrontFace),
+        BackFace: stencil_op(&desc.BackFace),
+    }
+}
+
+pub fn depth_stencil_desc_to_windows(
+    desc: &D3D11_DEPTH_STENCIL_DESC,
+) -> Direct3D11::D3D11_DEPTH_STENCIL_DESC {
+    let stencil_op = |desc: &D3D11_DEPTH_STENCILOP_DESC| Direct3D11::D3D11_DEPTH_STENCILOP_DESC {
+        StencilFailOp: Direct3D11::D3D11_STENCIL_OP(desc.StencilFailOp as _),
+        StencilDepthFailOp: Direct3D11::D3D11_STENCIL_OP(desc.StencilDepthFailOp as _),
+        StencilPassOp: Direct3D11::D3D11_STENCIL_OP(desc.StencilPassOp as _),
+        StencilFunc: Direct3D11::D3D11_COMPARISON_FUNC(desc.StencilFunc as _),
+    };
+    Direct3D11::D3D11_DEPTH_STENCIL_DESC {
+        DepthEnable: Foundation::BOOL(desc.DepthEnable),
+        DepthWriteMask: Direct3D11::D3D11_DEPTH_WRITE_MASK(desc.DepthWriteMask as _),
+        DepthFunc: Direct3D11::D3D11_COMPARISON_FUNC(desc.DepthFunc as _),
+        StencilEnable: Foundation::BOOL(desc.StencilEnable),
+        StencilReadMask: desc.StencilReadMask,
+        StencilWriteMask: desc.StencilWriteMask,
+        FrontFace: stencil_op(&desc.FrontFace),
+        BackFace: stencil_op(&desc.BackFace),
+    }
+}
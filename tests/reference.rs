@@ -0,0 +1,87 @@
+//! Tests of the reference image utilities of the `testing` feature.
+#![cfg(feature = "testing")]
+
+use std::env;
+use std::panic;
+use std::path::PathBuf;
+
+use d3d11_glyph::ab_glyph::FontArc;
+use d3d11_glyph::testing::{self, Image};
+use d3d11_glyph::{GlyphBrushBuilder, Section, Text};
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("d3d11-glyph-{}-{}", std::process::id(), name))
+}
+
+fn gradient(width: u32, height: u32) -> Image {
+    let pixels = (0..width * height)
+        .flat_map(|i| {
+            let value = (i % 256) as u8;
+            vec![value, 255 - value, value / 2, 255]
+        })
+        .collect();
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+#[test]
+fn pam_round_trip() {
+    let image = gradient(7, 5);
+    let path = temp_path("round-trip.pam");
+    image.write_pam(&path).unwrap();
+    let read = Image::read_pam(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(read, image);
+}
+
+#[test]
+fn difference_beyond_tolerance() {
+    let image = gradient(4, 4);
+    let mut changed = image.clone();
+    changed.pixels[0] = changed.pixels[0].wrapping_add(3);
+    changed.pixels[5] = changed.pixels[5].wrapping_add(10);
+
+    assert_eq!(image.difference(&image, 0), None);
+    assert_eq!(image.difference(&changed, 10), None);
+    let difference = image.difference(&changed, 3).unwrap();
+    assert_eq!(difference.pixels, 1);
+    assert_eq!(difference.max_channel, 10);
+    assert!(image.difference(&gradient(4, 5), 255).is_some());
+}
+
+#[test]
+fn missing_reference_fails() {
+    // with the variable set the reference would be written instead
+    if env::var_os("D3D11_GLYPH_UPDATE_REFERENCES").is_some() {
+        return;
+    }
+    let path = temp_path("missing.pam");
+    let image = gradient(2, 2);
+    let result = panic::catch_unwind(|| testing::assert_matches_reference(&image, &path, 0));
+    let _ = std::fs::remove_file(path.with_extension("actual.pam"));
+    assert!(result.is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn renders_text_on_warp() {
+    let font =
+        FontArc::try_from_slice(include_bytes!("../examples/Inconsolata-Regular.ttf")).unwrap();
+    let mut brush = GlyphBrushBuilder::using_font(font).build_warp().unwrap();
+    let section = || {
+        Section::default()
+            .with_screen_position((4.0, 4.0))
+            .add_text(Text::new("Hello").with_scale(24.0))
+    };
+
+    let image = testing::render(&mut brush, Some(section()), 96, 32).unwrap();
+    assert_eq!((image.width, image.height), (96, 32));
+    let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/references/hello.pam");
+    testing::assert_matches_reference(&image, reference, 4);
+    // drawing the same text again, now from the glyph cache, renders the same pixels
+    let again = testing::render(&mut brush, Some(section()), 96, 32).unwrap();
+    assert_eq!(image.difference(&again, 0), None);
+}